@@ -7,7 +7,7 @@
 use std::env;
 use std::fs::File;
 use std::io::{self, prelude::*};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
 fn main() -> ExitCode {
@@ -20,6 +20,18 @@ fn main() -> ExitCode {
             .unwrap_or_else(|| panic!("{}: CC_SHIM_OUT_DIR not found", program)),
     );
 
+    // Pretend to be an archiver from the binutils version in `ar-version`,
+    // without recording the query, which most tests don't expect.
+    if Path::new(program).file_stem() == Some("ar".as_ref())
+        && args.len() == 1
+        && args.clone().any(|a| a == "--version")
+    {
+        if let Ok(banner) = std::fs::read_to_string(out_dir.join("ar-version")) {
+            print!("{}", banner);
+        }
+        return ExitCode::SUCCESS;
+    }
+
     // Find the first nonexistent candidate file to which the program's args can be written.
     let candidate = (0..).find_map(|i| {
         let candidate = out_dir.join(format!("out{}", i));
@@ -63,6 +75,144 @@ fn main() -> ExitCode {
         )
     });
 
+    // Pretend that compiling takes a while when the source asks for it.
+    if let Some(src) = args.clone().last() {
+        let contents = std::fs::read_to_string(src).unwrap_or_default();
+        if let Some(ms) = contents.strip_prefix("// shim-sleep-ms=") {
+            let ms = ms.trim().parse().expect("invalid shim-sleep-ms");
+            std::thread::sleep(std::time::Duration::from_millis(ms));
+        }
+    }
+
+    // Pretend that the source doesn't compile if it has an `#error`.
+    if let Some(src) = args.clone().last() {
+        let contents = std::fs::read_to_string(src).unwrap_or_default();
+        if contents.starts_with("#error") {
+            eprintln!("{}:1: error: {}", src, contents.trim());
+            return ExitCode::FAILURE;
+        }
+    }
+
+    // Pretend that LTO crashes on sources asking for it.
+    if let Some(src) = args.clone().last() {
+        let contents = std::fs::read_to_string(src).unwrap_or_default();
+        let is_lto = args.clone().any(|a| a.starts_with("-flto"));
+        if is_lto && contents.starts_with("// shim-lto-fails") {
+            eprintln!("lto1: internal compiler error: in {}", src);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    // Pretend to emit diagnostics when asked to.
+    for arg in args.clone() {
+        if let Some(warning) = arg.strip_prefix("--shim-warning=") {
+            eprintln!("{}", warning);
+        }
+    }
+    // Pretend that the source of the flag checks has an unused function, which
+    // is only reported with the default warnings.
+    let is_flag_check = args.clone().last().map_or(false, |src| {
+        Path::new(src)
+            .file_stem()
+            .map_or(false, |stem| stem == "flag_check")
+    });
+    if is_flag_check && args.clone().any(|a| a == "-Wall") {
+        eprintln!("flag_check.c:1: warning: unused function 'f'");
+    }
+    // Pretend to warn about a header in the include directories, but not in
+    // the system ones.
+    let include_args = args.clone().collect::<Vec<_>>();
+    for pair in include_args.windows(2).filter(|pair| *pair[0] == "-I") {
+        let header = PathBuf::from(pair[1]).join("shim-warning.h");
+        if header.exists() {
+            eprintln!("{}:1: warning: from header", header.display());
+        }
+    }
+
+    // Pretend to write a time trace next to the output, as Clang does.
+    if args.clone().any(|a| a == "-ftime-trace") {
+        if let Some(output) = args.clone().skip_while(|a| *a != "-o").nth(1) {
+            let trace = Path::new(output).with_extension("json");
+            std::fs::write(trace, "{\"traceEvents\": []}\n").unwrap();
+        }
+    }
+
+    // Pretend that the toolchain's files are installed in the output directory.
+    if let Some(name) = args
+        .clone()
+        .find_map(|a| a.strip_prefix("-print-file-name="))
+    {
+        let path = out_dir.join(name);
+        if path.exists() {
+            println!("{}", path.display());
+        } else {
+            println!("{}", name);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    // Pretend to be an archiver that stores the names of its members, one per
+    // line.
+    if Path::new(program).file_stem() == Some("ar".as_ref()) {
+        let args = args.clone().map(String::as_str).collect::<Vec<_>>();
+        if let Some(mode) = args
+            .iter()
+            .position(|a| matches!(*a, "cq" | "r" | "d" | "t" | "s"))
+        {
+            let archive = args[mode + 1];
+            let names = args[mode + 2..]
+                .iter()
+                .map(|path| Path::new(path).file_name().unwrap().to_str().unwrap());
+            let contents = std::fs::read_to_string(archive).unwrap_or_default();
+            let mut members = contents.lines().collect::<Vec<_>>();
+            match args[mode] {
+                "cq" => members.extend(names),
+                "r" => {
+                    for name in names {
+                        if !members.contains(&name) {
+                            members.push(name);
+                        }
+                    }
+                }
+                "d" => members.retain(|member| !args[mode + 2..].contains(member)),
+                "t" => {
+                    for member in &members {
+                        println!("{}", member);
+                    }
+                }
+                _ => {}
+            }
+            let contents = members
+                .iter()
+                .map(|m| format!("{}\n", m))
+                .collect::<String>();
+            std::fs::write(archive, contents).unwrap();
+            return ExitCode::SUCCESS;
+        }
+    }
+
+    // Pretend that each object defines a function named after its source, and
+    // calls `printf`.
+    if Path::new(program).file_stem() == Some("dumpbin".as_ref()) {
+        for obj in args.clone().filter(|a| *a != "/SYMBOLS") {
+            let stem = Path::new(obj).file_stem().unwrap().to_str().unwrap();
+            let name = stem.rsplit('-').next().unwrap();
+            println!("008 00000000 SECT3  notype ()    External     | {}", name);
+            println!("009 00000000 UNDEF  notype ()    External     | printf");
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if program.starts_with("xcrun") {
+        // Pretend that each SDK is installed in a directory named after it.
+        if args.clone().any(|a| a == "--show-sdk-path") {
+            if let Some(sdk) = args.clone().skip_while(|a| *a != "--sdk").nth(1) {
+                println!("{}", out_dir.join(sdk).display());
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
+
     if program.starts_with("clang") {
         // Validate that we got no `-?` without a preceding `--driver-mode=cl`. Compiler family
         // detection depends on this.
@@ -72,7 +222,7 @@ fn main() -> ExitCode {
                 .take(cl_like_help_option_idx)
                 .rev()
                 .find_map(|a| a.strip_prefix("--driver-mode="))
-                .is_some_and(|a| a == "cl");
+                == Some("cl");
             if has_cl_clang_driver_before_cl_like_help_option {
                 return ExitCode::SUCCESS;
             } else {
@@ -84,6 +234,32 @@ fn main() -> ExitCode {
         }
     }
 
+    // Only pretend to accept `-?` when named like `cl.exe`, so that the other
+    // compilers are detected as such even when run with `CC_SHIM_OUT_DIR` set.
+    let is_cl = Path::new(program)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map_or(false, |stem| stem.ends_with("cl"));
+    if !is_cl && args.clone().any(|a| a == "-?") {
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(dep_file) = args.clone().skip_while(|a| *a != "-MF").nth(1) {
+        // Pretend that the source only includes the headers it names in quotes.
+        let src = PathBuf::from(args.clone().last().unwrap());
+        let mut deps = format!("out.o: {}", src.display());
+        for line in std::fs::read_to_string(&src).unwrap_or_default().lines() {
+            if let Some(header) = line.strip_prefix("#include \"") {
+                let header = header.trim_end_matches('"');
+                let header = src.parent().unwrap().join(header);
+                deps.push_str(&format!(" \\\n  {}", header.display()));
+            }
+        }
+        std::fs::write(dep_file, deps + "\n").unwrap_or_else(|e| {
+            panic!("{}: can't write {}: {}", program, dep_file, e);
+        });
+    }
+
     // Create a file used by some tests.
     let path = &out_dir.join("libfoo.a");
     File::create(path).unwrap_or_else(|e| {
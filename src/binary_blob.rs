@@ -0,0 +1,265 @@
+//! Embedding of binary files into the library, see
+//! [`Build::add_binary_blob`](crate::Build::add_binary_blob).
+
+use std::{ffi::OsString, fmt::Write, path::Path, sync::Arc};
+
+use crate::{
+    target::{BinaryFormat, TargetInfo},
+    Error, ErrorKind,
+};
+
+/// A file embedded into the library as the data of a symbol.
+#[derive(Clone, Debug)]
+pub(crate) struct BinaryBlob {
+    pub(crate) symbol: Arc<str>,
+    pub(crate) path: Arc<Path>,
+    pub(crate) section: Arc<str>,
+}
+
+impl BinaryBlob {
+    /// Check that the symbol can be declared in C.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        let mut chars = self.symbol.chars();
+        let is_identifier = chars
+            .next()
+            .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_identifier {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidArgument,
+                format!(
+                    "the symbol `{}` of the binary blob {} is not a C identifier",
+                    self.symbol,
+                    self.path.display()
+                ),
+            ))
+        }
+    }
+
+    /// The arguments to make `objcopy` wrap the blob, which is passed by its
+    /// file name, into the object `output` in the format of the target.
+    pub(crate) fn objcopy_args(
+        &self,
+        (format, arch): (&str, &str),
+        output: &Path,
+    ) -> Result<Vec<OsString>, Error> {
+        let file_name = self.path.file_name().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidArgument,
+                format!("binary blob {} has no file name", self.path.display()),
+            )
+        })?;
+        // `objcopy` names the symbols after the input file.
+        let mangled = file_name
+            .to_string_lossy()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        // 32-bit x86 Windows prefixes the names of C symbols with `_`.
+        let symbol = if format == "pe-i386" {
+            format!("_{}", self.symbol)
+        } else {
+            self.symbol.to_string()
+        };
+        Ok(vec![
+            "-I".into(),
+            "binary".into(),
+            "-O".into(),
+            format.into(),
+            "-B".into(),
+            arch.into(),
+            "--rename-section".into(),
+            format!(".data={},alloc,load,readonly,data,contents", self.section).into(),
+            "--redefine-sym".into(),
+            format!("_binary_{mangled}_start={symbol}").into(),
+            "--strip-symbol".into(),
+            format!("_binary_{mangled}_end").into(),
+            "--strip-symbol".into(),
+            format!("_binary_{mangled}_size").into(),
+            file_name.into(),
+            output.into(),
+        ])
+    }
+
+    /// The C source defining the size of the blob, and its data if it isn't
+    /// wrapped with `objcopy`, in the section the MSVC or GNU way.
+    pub(crate) fn c_source(&self, size: u64, data: Option<&[u8]>, msvc: bool) -> String {
+        let symbol = &self.symbol;
+        let section = &self.section;
+        let mut source =
+            String::from("#include <stddef.h>\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n");
+        if let Some(data) = data {
+            source.push_str(&format!("extern const unsigned char {symbol}[];\n"));
+            if msvc {
+                source.push_str(&format!(
+                    "#pragma section(\"{section}\", read)\n__declspec(allocate(\"{section}\")) "
+                ));
+            } else {
+                source.push_str(&format!("__attribute__((section(\"{section}\"))) "));
+            }
+            // C has no empty arrays.
+            source.push_str(&format!(
+                "const unsigned char {symbol}[{}] = {{",
+                data.len().max(1)
+            ));
+            for (i, byte) in data.iter().enumerate() {
+                if i % 16 == 0 {
+                    source.push_str("\n   ");
+                }
+                let _ = write!(source, " 0x{byte:02x},");
+            }
+            if data.is_empty() {
+                source.push_str(" 0");
+            }
+            source.push_str("\n};\n");
+        }
+        source.push_str(&format!(
+            "extern const size_t {symbol}_size;\nconst size_t {symbol}_size = {size};\n"
+        ));
+        source.push_str("#ifdef __cplusplus\n}\n#endif\n");
+        source
+    }
+}
+
+impl TargetInfo<'_> {
+    /// The BFD output format and architecture `objcopy` wraps binary blobs
+    /// into for the target, if it's known.
+    pub(crate) fn objcopy_format(&self) -> Option<(&'static str, &'static str)> {
+        let little_endian = !(self.full_arch.ends_with("_be")
+            || self.full_arch.starts_with("armeb")
+            || self.full_arch == "powerpc64");
+        if self.binary_format() == BinaryFormat::Coff && self.env == "gnu" {
+            return match self.arch {
+                "x86_64" => Some(("pe-x86-64", "i386:x86-64")),
+                "x86" => Some(("pe-i386", "i386")),
+                _ => None,
+            };
+        }
+        if !self.is_elf() {
+            return None;
+        }
+        Some(match self.arch {
+            "x86_64" if self.abi == "x32" => ("elf32-x86-64", "i386:x64-32"),
+            "x86_64" => ("elf64-x86-64", "i386:x86-64"),
+            "x86" => ("elf32-i386", "i386"),
+            "aarch64" if little_endian => ("elf64-littleaarch64", "aarch64"),
+            "aarch64" => ("elf64-bigaarch64", "aarch64"),
+            "arm" if little_endian => ("elf32-littlearm", "arm"),
+            "arm" => ("elf32-bigarm", "arm"),
+            "riscv64" => ("elf64-littleriscv", "riscv"),
+            "riscv32" => ("elf32-littleriscv", "riscv"),
+            "powerpc64" if little_endian => ("elf64-powerpcle", "powerpc:common64"),
+            "powerpc64" => ("elf64-powerpc", "powerpc:common64"),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::BinaryBlob;
+    use crate::TargetInfo;
+
+    fn blob(symbol: &str) -> BinaryBlob {
+        BinaryBlob {
+            symbol: symbol.into(),
+            path: Path::new("assets/font.ttf").into(),
+            section: ".rodata.font".into(),
+        }
+    }
+
+    #[test]
+    fn symbols() {
+        assert!(blob("FONT_2").check().is_ok());
+        assert!(blob("_font").check().is_ok());
+        assert!(blob("2font").check().is_err());
+        assert!(blob("font-data").check().is_err());
+        assert!(blob("").check().is_err());
+    }
+
+    #[test]
+    fn objcopy() {
+        let args = blob("font")
+            .objcopy_args(("elf64-x86-64", "i386:x86-64"), Path::new("out/font.o"))
+            .unwrap();
+        let args = args.iter().map(|a| a.to_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(
+            args,
+            [
+                "-I",
+                "binary",
+                "-O",
+                "elf64-x86-64",
+                "-B",
+                "i386:x86-64",
+                "--rename-section",
+                ".data=.rodata.font,alloc,load,readonly,data,contents",
+                "--redefine-sym",
+                "_binary_font_ttf_start=font",
+                "--strip-symbol",
+                "_binary_font_ttf_end",
+                "--strip-symbol",
+                "_binary_font_ttf_size",
+                "font.ttf",
+                "out/font.o",
+            ]
+        );
+
+        let args = blob("font")
+            .objcopy_args(("pe-i386", "i386"), Path::new("out/font.o"))
+            .unwrap();
+        assert!(args.iter().any(|a| a == "_binary_font_ttf_start=_font"));
+    }
+
+    #[test]
+    fn c_sources() {
+        let source = blob("font").c_source(2, Some(b"ab"), true);
+        assert!(source.contains(
+            "#pragma section(\".rodata.font\", read)\n\
+             __declspec(allocate(\".rodata.font\")) const unsigned char font[2] = {\n    0x61, 0x62,\n};\n"
+        ));
+        assert!(source.contains("const size_t font_size = 2;\n"));
+
+        let source = blob("font").c_source(0, Some(b""), false);
+        assert!(source.contains(
+            "__attribute__((section(\".rodata.font\"))) const unsigned char font[1] = { 0\n};\n"
+        ));
+
+        let source = blob("font").c_source(3, None, false);
+        assert!(!source.contains("unsigned char"));
+        assert!(source.contains("const size_t font_size = 3;\n"));
+    }
+
+    #[test]
+    fn objcopy_formats() {
+        let format = |target| {
+            TargetInfo::from_rustc_target(target)
+                .unwrap()
+                .objcopy_format()
+        };
+        assert_eq!(
+            format("x86_64-unknown-linux-gnu"),
+            Some(("elf64-x86-64", "i386:x86-64"))
+        );
+        assert_eq!(
+            format("aarch64_be-unknown-linux-gnu"),
+            Some(("elf64-bigaarch64", "aarch64"))
+        );
+        assert_eq!(
+            format("thumbv7em-none-eabihf"),
+            Some(("elf32-littlearm", "arm"))
+        );
+        assert_eq!(format("armebv7r-none-eabi"), Some(("elf32-bigarm", "arm")));
+        assert_eq!(
+            format("x86_64-pc-windows-gnu"),
+            Some(("pe-x86-64", "i386:x86-64"))
+        );
+        assert_eq!(format("x86_64-pc-windows-msvc"), None);
+        assert_eq!(format("aarch64-apple-darwin"), None);
+        assert_eq!(format("wasm32-unknown-unknown"), None);
+    }
+}
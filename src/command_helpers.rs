@@ -4,19 +4,19 @@ use std::{
     borrow::Cow,
     collections::hash_map,
     ffi::OsString,
-    fmt::Display,
+    fmt::{self, Display},
     fs,
     hash::Hasher,
     io::{self, Read, Write},
-    path::Path,
+    path::{Component, Path},
     process::{Child, ChildStderr, Command, Stdio},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
 };
 
-use crate::{Error, ErrorKind, Object};
+use crate::{Error, ErrorKind, Object, ObjectNaming};
 
 #[derive(Clone, Debug)]
 pub(crate) struct CargoOutput {
@@ -24,9 +24,65 @@ pub(crate) struct CargoOutput {
     pub(crate) warnings: bool,
     pub(crate) debug: bool,
     pub(crate) output: OutputKind,
+    pub(crate) warning_writer: Option<WarningWriter>,
     checked_dbg_var: Arc<AtomicBool>,
 }
 
+/// A sink for compiler diagnostics, shared between clones of a `Build` and
+/// the compilers running in parallel.
+#[derive(Clone)]
+pub(crate) struct WarningWriter(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl WarningWriter {
+    pub(crate) fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self(Arc::new(Mutex::new(writer)))
+    }
+}
+
+/// A sink keeping a copy of the diagnostics it forwards to the sink of a
+/// `CargoOutput`, if any.
+struct CapturingWriter {
+    captured: Arc<Mutex<Vec<u8>>>,
+    inner: Option<WarningWriter>,
+    forward: bool,
+    /// The incomplete line forwarded to Cargo's warnings.
+    line: Vec<u8>,
+}
+
+impl Write for CapturingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.captured
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend_from_slice(buf);
+        match &self.inner {
+            Some(WarningWriter(inner)) => inner
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .write_all(buf)?,
+            None if self.forward => {
+                self.line.extend_from_slice(buf);
+                while let Some(end) = self.line.iter().position(|&b| b == b'\n') {
+                    let line = self.line.drain(..=end).collect::<Vec<_>>();
+                    write_warning(None, &line[..end]);
+                }
+            }
+            None => {}
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for WarningWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WarningWriter(..)")
+    }
+}
+
 /// Different strategies for handling compiler output (to stdout)
 #[derive(Clone, Debug)]
 pub(crate) enum OutputKind {
@@ -45,6 +101,7 @@ impl CargoOutput {
             metadata: true,
             warnings: true,
             output: OutputKind::Forward,
+            warning_writer: None,
             debug: match std::env::var_os("CC_ENABLE_DEBUG_OUTPUT") {
                 Some(v) => v != "0" && v != "false" && !v.is_empty(),
                 None => false,
@@ -53,6 +110,21 @@ impl CargoOutput {
         }
     }
 
+    /// A copy of this output that also keeps the diagnostics of the commands
+    /// run with it, which are still forwarded as usual.
+    pub(crate) fn capturing_diagnostics(&self) -> (CargoOutput, Arc<Mutex<Vec<u8>>>) {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let writer = CapturingWriter {
+            captured: captured.clone(),
+            inner: self.warning_writer.clone(),
+            forward: self.warnings,
+            line: Vec::new(),
+        };
+        let mut output = self.clone();
+        output.warning_writer = Some(WarningWriter::new(Box::new(writer)));
+        (output, captured)
+    }
+
     pub(crate) fn print_metadata(&self, s: &dyn Display) {
         if self.metadata {
             println!("{}", s);
@@ -62,6 +134,7 @@ impl CargoOutput {
     pub(crate) fn print_warning(&self, arg: &dyn Display) {
         if self.warnings {
             println!("cargo:warning={}", arg);
+            log_record!(warn, "{}", arg);
         }
     }
 
@@ -76,7 +149,7 @@ impl CargoOutput {
     }
 
     fn stdio_for_warnings(&self) -> Stdio {
-        if self.warnings {
+        if self.warnings || self.warning_writer.is_some() {
             Stdio::piped()
         } else {
             Stdio::null()
@@ -94,6 +167,7 @@ impl CargoOutput {
 
 pub(crate) struct StderrForwarder {
     inner: Option<(ChildStderr, Vec<u8>)>,
+    writer: Option<WarningWriter>,
     #[cfg(feature = "parallel")]
     is_non_blocking: bool,
     #[cfg(feature = "parallel")]
@@ -105,12 +179,13 @@ pub(crate) struct StderrForwarder {
 const MIN_BUFFER_CAPACITY: usize = 100;
 
 impl StderrForwarder {
-    pub(crate) fn new(child: &mut Child) -> Self {
+    pub(crate) fn new(child: &mut Child, cargo_output: &CargoOutput) -> Self {
         Self {
             inner: child
                 .stderr
                 .take()
                 .map(|stderr| (stderr, Vec::with_capacity(MIN_BUFFER_CAPACITY))),
+            writer: cargo_output.warning_writer.clone(),
             bytes_buffered: 0,
             #[cfg(feature = "parallel")]
             is_non_blocking: false,
@@ -143,7 +218,7 @@ impl StderrForwarder {
                             // On Windows, if we get an error then the pipe is broken, so flush
                             // the buffer and bail.
                             if !buffer.is_empty() {
-                                write_warning(&buffer[..]);
+                                write_warning(self.writer.as_ref(), &buffer[..]);
                             }
                             self.inner = None;
                             break true;
@@ -183,7 +258,7 @@ impl StderrForwarder {
                             // Only forward complete lines, leave the rest in the buffer.
                             if let Some((b'\n', line)) = line.split_last() {
                                 consumed += line.len() + 1;
-                                write_warning(line);
+                                write_warning(self.writer.as_ref(), line);
                             }
                         }
                         if consumed > 0 && consumed < self.bytes_buffered {
@@ -195,10 +270,11 @@ impl StderrForwarder {
                     res => {
                         // End of stream: flush remaining data and bail.
                         if self.bytes_buffered > 0 {
-                            write_warning(&buffer[..self.bytes_buffered]);
+                            write_warning(self.writer.as_ref(), &buffer[..self.bytes_buffered]);
                         }
                         if let Err(err) = res {
                             write_warning(
+                                self.writer.as_ref(),
                                 format!("Failed to read from child stderr: {err}").as_bytes(),
                             );
                         }
@@ -237,7 +313,18 @@ impl StderrForwarder {
     }
 }
 
-fn write_warning(line: &[u8]) {
+fn write_warning(writer: Option<&WarningWriter>, line: &[u8]) {
+    if let Some(WarningWriter(writer)) = writer {
+        // A poisoned lock only means that another thread panicked while
+        // writing, so keep writing to the sink. Failing to write diagnostics
+        // shouldn't fail the build either.
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writer
+            .write_all(line)
+            .and_then(|()| writer.write_all(b"\n"));
+        return;
+    }
+
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
     stdout.write_all(b"cargo:warning=").unwrap();
@@ -250,7 +337,7 @@ fn wait_on_child(
     child: &mut Child,
     cargo_output: &CargoOutput,
 ) -> Result<(), Error> {
-    StderrForwarder::new(child).forward_all();
+    StderrForwarder::new(child, cargo_output).forward_all();
 
     let status = match child.wait() {
         Ok(s) => s,
@@ -258,11 +345,13 @@ fn wait_on_child(
             return Err(Error::new(
                 ErrorKind::ToolExecError,
                 format!("failed to wait on spawned child process `{cmd:?}`: {e}"),
-            ));
+            )
+            .with_source(e));
         }
     };
 
     cargo_output.print_debug(&status);
+    log_record!(debug, "{:?} exited with {}", cmd, status);
 
     if status.success() {
         Ok(())
@@ -276,8 +365,12 @@ fn wait_on_child(
 
 /// Find the destination object path for each file in the input source files,
 /// and store them in the output Object.
-pub(crate) fn objects_from_files(files: &[Arc<Path>], dst: &Path) -> Result<Vec<Object>, Error> {
-    let mut objects = Vec::with_capacity(files.len());
+pub(crate) fn objects_from_files(
+    files: &[Arc<Path>],
+    dst: &Path,
+    naming: ObjectNaming,
+) -> Result<Vec<Object>, Error> {
+    let mut objects: Vec<Object> = Vec::with_capacity(files.len());
     for file in files {
         let basename = file
             .file_name()
@@ -298,10 +391,6 @@ pub(crate) fn objects_from_files(files: &[Arc<Path>], dst: &Path) -> Result<Vec<
             })?
             .to_string_lossy();
 
-        // Hash the dirname. This should prevent conflicts if we have multiple
-        // object files with the same filename in different subfolders.
-        let mut hasher = hash_map::DefaultHasher::new();
-
         // Make the dirname relative (if possible) to avoid full system paths influencing the sha
         // and making the output system-dependent
         //
@@ -315,13 +404,47 @@ pub(crate) fn objects_from_files(files: &[Arc<Path>], dst: &Path) -> Result<Vec<
             dirname
         };
 
-        hasher.write(dirname.as_bytes());
-        if let Some(extension) = file.extension() {
-            hasher.write(extension.to_string_lossy().as_bytes());
+        let obj = match naming {
+            ObjectNaming::Hashed => {
+                // Hash the dirname. This should prevent conflicts if we have multiple
+                // object files with the same filename in different subfolders.
+                let mut hasher = hash_map::DefaultHasher::new();
+                hasher.write(dirname.as_bytes());
+                if let Some(extension) = file.extension() {
+                    hasher.write(extension.to_string_lossy().as_bytes());
+                }
+                dst.join(format!("{:016x}-{}", hasher.finish(), basename))
+            }
+            ObjectNaming::Flat => dst.join(&*basename),
+            ObjectNaming::Mirrored => {
+                let mut obj = dst.to_path_buf();
+                for component in Path::new(&*dirname).components() {
+                    match component {
+                        Component::Normal(component) => obj.push(component),
+                        // Keep objects for sources outside the manifest directory inside `dst`.
+                        Component::ParentDir => obj.push("__"),
+                        Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+                    }
+                }
+                obj.join(&*basename)
+            }
+        }
+        .with_extension("o");
+
+        // Only the hashed scheme guarantees unique names (for distinct files).
+        if naming != ObjectNaming::Hashed {
+            if let Some(other) = objects.iter().find(|other| other.dst == obj) {
+                return Err(Error::new(
+                    ErrorKind::InvalidArgument,
+                    format!(
+                        "{} and {} would both be compiled to {}",
+                        other.src.display(),
+                        file.display(),
+                        obj.display()
+                    ),
+                ));
+            }
         }
-        let obj = dst
-            .join(format!("{:016x}-{}", hasher.finish(), basename))
-            .with_extension("o");
 
         match obj.parent() {
             Some(s) => fs::create_dir_all(s)?,
@@ -376,6 +499,7 @@ pub(crate) fn spawn(cmd: &mut Command, cargo_output: &CargoOutput) -> Result<Chi
     }
 
     cargo_output.print_debug(&format_args!("running: {:?}", cmd));
+    log_record!(info, "running: {:?}", cmd);
 
     let cmd = ResetStderr(cmd);
     let child = cmd
@@ -385,7 +509,7 @@ pub(crate) fn spawn(cmd: &mut Command, cargo_output: &CargoOutput) -> Result<Chi
         .spawn();
     match child {
         Ok(child) => Ok(child),
-        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
             let extra = if cfg!(windows) {
                 " (see https://docs.rs/cc/latest/cc/#compile-time-requirements for help)"
             } else {
@@ -394,12 +518,14 @@ pub(crate) fn spawn(cmd: &mut Command, cargo_output: &CargoOutput) -> Result<Chi
             Err(Error::new(
                 ErrorKind::ToolNotFound,
                 format!("failed to find tool {:?}: {e}{extra}", cmd.0.get_program()),
-            ))
+            )
+            .with_source(e))
         }
         Err(e) => Err(Error::new(
             ErrorKind::ToolExecError,
             format!("command `{:?}` failed to start: {e}", cmd.0),
-        )),
+        )
+        .with_source(e)),
     }
 }
 
@@ -455,7 +581,8 @@ pub(crate) fn try_wait_on_child(
             Err(Error::new(
                 ErrorKind::ToolExecError,
                 format!("failed to wait on spawned child process `{cmd:?}`: {e}"),
-            ))
+            )
+            .with_source(e))
         }
     }
 }
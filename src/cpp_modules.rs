@@ -0,0 +1,206 @@
+//! Experimental support for C++20 modules, see
+//! [`Build::cpp_module_interface`](crate::Build::cpp_module_interface).
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{json, tool::ToolFamily, Error, ErrorKind};
+
+/// Find the name of the module declared with `export module name;` by an
+/// interface unit.
+pub(crate) fn parse_module_name(contents: &str) -> Option<&str> {
+    contents.lines().find_map(|line| {
+        let rest = line.trim_start().strip_prefix("export")?;
+        let rest = rest.trim_start().strip_prefix("module")?;
+        if !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let name = rest.split(';').next()?.trim();
+        (!name.is_empty()).then_some(name)
+    })
+}
+
+/// Read the name of the module declared by the interface unit `src`.
+pub(crate) fn read_module_name(src: &Path) -> Result<String, Error> {
+    let contents = fs::read_to_string(src)?;
+    match parse_module_name(&contents) {
+        Some(name) => Ok(name.to_string()),
+        None => Err(Error::new(
+            ErrorKind::InvalidArgument,
+            format!(
+                "C++ module interface unit {} has no `export module` declaration",
+                src.display()
+            ),
+        )),
+    }
+}
+
+/// The `std` module shipped with libc++.
+#[derive(Debug, PartialEq)]
+pub(crate) struct StdModule {
+    /// The interface unit of the module.
+    pub(crate) source: PathBuf,
+    /// The directories the interface unit includes the libc++ sources from.
+    pub(crate) system_include_directories: Vec<PathBuf>,
+}
+
+/// Find the `std` module in the `libc++.modules.json` metadata at `path`,
+/// whose paths are relative to its directory.
+pub(crate) fn parse_libcxx_modules(path: &Path, contents: &str) -> Result<StdModule, String> {
+    let metadata = json::parse(contents)?;
+    let module = metadata
+        .get("modules")
+        .and_then(json::JsonValue::as_array)
+        .unwrap_or_default()
+        .iter()
+        .find(|module| module.get("logical-name").and_then(json::JsonValue::as_str) == Some("std"))
+        .ok_or("no `std` module")?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let source = module
+        .get("source-path")
+        .and_then(json::JsonValue::as_str)
+        .ok_or("missing `source-path` of the `std` module")?;
+    let system_include_directories = module
+        .get("local-arguments")
+        .and_then(|args| args.get("system-include-directories"))
+        .and_then(json::JsonValue::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(json::JsonValue::as_str)
+        .map(|include| dir.join(include))
+        .collect();
+    Ok(StdModule {
+        source: dir.join(source),
+        system_include_directories,
+    })
+}
+
+/// Where the built module interface (BMI) of a module is written in `dir`.
+fn bmi_path(family: ToolFamily, dir: &Path, name: &str) -> PathBuf {
+    let extension = match family {
+        ToolFamily::Gnu => "gcm",
+        ToolFamily::Clang { .. } => "pcm",
+        ToolFamily::Msvc { .. } => "ifc",
+    };
+    // Clang names the BMIs of partitions `module-partition.pcm`.
+    dir.join(format!("{}.{}", name.replace(':', "-"), extension))
+}
+
+/// Write the module mapper telling GCC where to find the BMI of each
+/// module, as it otherwise uses `gcm.cache` in the working directory.
+pub(crate) fn write_module_mapper(dir: &Path, names: &[String]) -> Result<(), Error> {
+    let mut mapper = String::new();
+    for name in names {
+        let bmi = bmi_path(ToolFamily::Gnu, dir, name);
+        mapper.push_str(&format!("{} {}\n", name, bmi.display()));
+    }
+    fs::write(dir.join("module-mapper.txt"), mapper)?;
+    Ok(())
+}
+
+/// The flags to compile a C++ translation unit that may import the modules
+/// whose BMIs are in `dir`, and which is the interface unit of the module
+/// `interface` if given.
+pub(crate) fn module_flags(
+    family: ToolFamily,
+    dir: &Path,
+    interface: Option<&str>,
+) -> Result<Vec<OsString>, Error> {
+    let mut flags: Vec<OsString> = Vec::new();
+    match family {
+        ToolFamily::Gnu => {
+            flags.push("-fmodules-ts".into());
+            let mut mapper = OsString::from("-fmodule-mapper=");
+            mapper.push(dir.join("module-mapper.txt"));
+            flags.push(mapper);
+            if interface.is_some() {
+                // GCC doesn't recognize extensions like `.cppm`.
+                flags.extend(["-x".into(), "c++".into()]);
+            }
+        }
+        ToolFamily::Clang { .. } => {
+            let mut path = OsString::from("-fprebuilt-module-path=");
+            path.push(dir);
+            flags.push(path);
+            if let Some(name) = interface {
+                flags.extend(["-x".into(), "c++-module".into()]);
+                let mut output = OsString::from("-fmodule-output=");
+                output.push(bmi_path(family, dir, name));
+                flags.push(output);
+            }
+        }
+        ToolFamily::Msvc { clang_cl: false } => {
+            flags.extend(["-ifcSearchDir".into(), dir.into()]);
+            if let Some(name) = interface {
+                flags.extend(["-interface".into(), "-TP".into(), "-ifcOutput".into()]);
+                flags.push(bmi_path(family, dir, name).into());
+            }
+        }
+        ToolFamily::Msvc { clang_cl: true } => {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFeature,
+                "C++ modules are not supported with clang-cl",
+            ))
+        }
+    }
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{parse_libcxx_modules, parse_module_name, StdModule};
+
+    #[test]
+    fn module_names() {
+        let interface = "module;\n#include <vector>\nexport module foo.bar;\nexport int f();\n";
+        assert_eq!(parse_module_name(interface), Some("foo.bar"));
+        assert_eq!(
+            parse_module_name("  export  module foo:part ;"),
+            Some("foo:part")
+        );
+        assert_eq!(parse_module_name("module foo;\nimport bar;"), None);
+        assert_eq!(parse_module_name("export modulefoo;"), None);
+        assert_eq!(parse_module_name("export module ;"), None);
+    }
+
+    #[test]
+    fn libcxx_modules() {
+        let metadata = r#"{
+          "version": 1,
+          "revision": 1,
+          "modules": [
+            {
+              "logical-name": "std.compat",
+              "source-path": "../../share/libc++/v1/std.compat.cppm",
+              "is-std-library": true
+            },
+            {
+              "logical-name": "std",
+              "source-path": "../../share/libc++/v1/std.cppm",
+              "is-std-library": true,
+              "local-arguments": {
+                "system-include-directories": ["../../share/libc++/v1"]
+              }
+            }
+          ]
+        }"#;
+        let path = Path::new("/usr/lib/llvm/lib/libc++.modules.json");
+        assert_eq!(
+            parse_libcxx_modules(path, metadata).unwrap(),
+            StdModule {
+                source: PathBuf::from("/usr/lib/llvm/lib/../../share/libc++/v1/std.cppm"),
+                system_include_directories: vec![PathBuf::from(
+                    "/usr/lib/llvm/lib/../../share/libc++/v1"
+                )],
+            }
+        );
+
+        assert!(parse_libcxx_modules(path, r#"{"modules": []}"#).is_err());
+        assert!(parse_libcxx_modules(path, r#"{"modules": [{"logical-name": "std"}]}"#).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! Parsing of the Makefile-like dependency files written by `-MMD`.
+
+use std::path::PathBuf;
+
+/// Parse the prerequisites of the first rule in a dependency file.
+///
+/// The first prerequisite is the source file itself, followed by the headers
+/// it includes. Spaces and `#` escaped with a backslash, `$$`, and line
+/// continuations are handled as written by GCC and Clang.
+pub(crate) fn parse_dep_file(contents: &str) -> Vec<PathBuf> {
+    let mut prerequisites = Vec::new();
+    let mut in_prerequisites = false;
+    let mut word = String::new();
+    let mut chars = contents.chars().peekable();
+
+    let mut end_word = |word: &mut String, in_prerequisites: bool| {
+        if in_prerequisites && !word.is_empty() {
+            prerequisites.push(PathBuf::from(&*word));
+        }
+        word.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some('\n') => {
+                    chars.next();
+                    end_word(&mut word, in_prerequisites);
+                }
+                Some('\r') => {
+                    chars.next();
+                    if chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                    end_word(&mut word, in_prerequisites);
+                }
+                Some(&escaped @ (' ' | '#')) => {
+                    chars.next();
+                    word.push(escaped);
+                }
+                // Backslashes are otherwise literal, e.g. in Windows paths.
+                _ => word.push(c),
+            },
+            '$' if chars.peek() == Some(&'$') => {
+                chars.next();
+                word.push('$');
+            }
+            // A `:` that ends the target, rather than e.g. one in `C:\`.
+            ':' if !in_prerequisites && chars.peek().map_or(true, |next| next.is_whitespace()) => {
+                end_word(&mut word, false);
+                in_prerequisites = true;
+            }
+            '\n' | '\r' if in_prerequisites => break,
+            c if c.is_whitespace() => end_word(&mut word, in_prerequisites),
+            c => word.push(c),
+        }
+    }
+    end_word(&mut word, in_prerequisites);
+
+    prerequisites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_dep_file;
+    use std::path::PathBuf;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn single_line() {
+        assert_eq!(
+            parse_dep_file("foo.o: src/foo.c include/foo.h\n"),
+            paths(&["src/foo.c", "include/foo.h"])
+        );
+    }
+
+    #[test]
+    fn continuations_and_escapes() {
+        let contents = "out/foo.o: src/foo.c \\\n  include/my\\ header.h \\\r\n  we$$ird\\#.h\n";
+        assert_eq!(
+            parse_dep_file(contents),
+            paths(&["src/foo.c", "include/my header.h", "we$ird#.h"])
+        );
+    }
+
+    #[test]
+    fn windows_paths() {
+        assert_eq!(
+            parse_dep_file("C:\\out\\foo.o: C:\\src\\foo.c C:\\include\\foo.h"),
+            paths(&["C:\\src\\foo.c", "C:\\include\\foo.h"])
+        );
+    }
+
+    #[test]
+    fn only_first_rule() {
+        assert_eq!(
+            parse_dep_file("foo.o: foo.c foo.h\nfoo.h:\n"),
+            paths(&["foo.c", "foo.h"])
+        );
+    }
+}
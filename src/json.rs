@@ -0,0 +1,188 @@
+//! Just enough of a JSON parser to read target specifications and toolchain
+//! metadata.
+
+use std::{iter::Peekable, str::Chars};
+
+/// A parsed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum JsonValue {
+    String(String),
+    Array(Vec<JsonValue>),
+    /// The fields of an object, in order.
+    Object(Vec<(String, JsonValue)>),
+    /// Numbers, `true`, `false` and `null`, which nothing needs to read yet.
+    Other,
+}
+
+impl JsonValue {
+    /// The value of the field `key` if this is an object.
+    pub(crate) fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a JSON document.
+pub(crate) fn parse(json: &str) -> Result<JsonValue, String> {
+    let mut parser = JsonParser(json.chars().peekable());
+    let value = parser.parse_value()?;
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected `{c}` after the value")),
+    }
+}
+
+struct JsonParser<'a>(Peekable<Chars<'a>>);
+
+impl JsonParser<'_> {
+    /// Peek at the next character that isn't whitespace.
+    fn peek(&mut self) -> Option<char> {
+        while self.0.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+        self.0.peek().copied()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        self.peek();
+        self.0
+            .next()
+            .ok_or_else(|| "unexpected end of file".to_string())
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(format!("expected `{expected}`, found `{c}`")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        match self.peek().ok_or("unexpected end of file")? {
+            '"' => self.parse_string().map(JsonValue::String),
+            '[' => {
+                self.0.next();
+                let mut values = Vec::new();
+                if self.peek() == Some(']') {
+                    self.0.next();
+                    return Ok(JsonValue::Array(values));
+                }
+                loop {
+                    values.push(self.parse_value()?);
+                    match self.next()? {
+                        ',' => {}
+                        ']' => return Ok(JsonValue::Array(values)),
+                        c => return Err(format!("expected `,` or `]`, found `{c}`")),
+                    }
+                }
+            }
+            '{' => {
+                self.0.next();
+                let mut fields = Vec::new();
+                if self.peek() == Some('}') {
+                    self.0.next();
+                    return Ok(JsonValue::Object(fields));
+                }
+                loop {
+                    let key = self.parse_string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.parse_value()?));
+                    match self.next()? {
+                        ',' => {}
+                        '}' => return Ok(JsonValue::Object(fields)),
+                        c => return Err(format!("expected `,` or `}}`, found `{c}`")),
+                    }
+                }
+            }
+            _ => {
+                let mut any = false;
+                while self
+                    .0
+                    .next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'))
+                    .is_some()
+                {
+                    any = true;
+                }
+                if any {
+                    Ok(JsonValue::Other)
+                } else {
+                    Err("expected a value".to_string())
+                }
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let c = self.0.next().ok_or("unterminated string")?;
+            match c {
+                '"' => return Ok(s),
+                '\\' => s.push(match self.0.next().ok_or("unterminated string")? {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let hex: String = self.0.by_ref().take(4).collect();
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .unwrap_or(char::REPLACEMENT_CHARACTER)
+                    }
+                    c => c, // `"`, `\` and `/`
+                }),
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, JsonValue};
+
+    #[test]
+    fn nested_values() {
+        let value =
+            parse(r#" {"a": [1, "b", {"c": "d!"}], "e": {}, "f": [], "g": null} "#).unwrap();
+        let a = value.get("a").unwrap().as_array().unwrap();
+        assert_eq!(a[0], JsonValue::Other);
+        assert_eq!(a[1].as_str(), Some("b"));
+        assert_eq!(a[2].get("c").and_then(JsonValue::as_str), Some("d!"));
+        assert_eq!(value.get("e"), Some(&JsonValue::Object(Vec::new())));
+        assert_eq!(value.get("f").and_then(JsonValue::as_array), Some(&[][..]));
+        assert_eq!(value.get("g"), Some(&JsonValue::Other));
+        assert_eq!(value.get("h"), None);
+    }
+
+    #[test]
+    fn invalid() {
+        for json in [
+            "",
+            "[1,]",
+            "{\"a\" 1}",
+            "\"unterminated",
+            "[1] 2",
+            "{\"a\": }",
+        ] {
+            assert!(parse(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -69,7 +69,9 @@
 //!
 //! * `CFLAGS` - a series of space separated flags passed to compilers. Note that
 //!   individual flags cannot currently contain spaces, so doing
-//!   something like: `-L=foo\ bar` is not possible.
+//!   something like: `-L=foo\ bar` is not possible. These are passed before
+//!   the flags and defines set on the [`Build`], so that e.g. a `-D` set
+//!   with [`Build::define`] takes precedence over one from `CFLAGS`.
 //! * `CC` - the actual C compiler used. Note that this is used as an exact
 //!   executable name, so (for example) no extra flags can be passed inside
 //!   this variable, and the builder must ensure that there aren't any
@@ -137,6 +139,20 @@
 //! use `-jN` option of `build`, `test` and `run` commands as `$NUM_JOBS`
 //! is supplied by cargo.
 //!
+//! ## Log
+//!
+//! With the `log` feature, cc-rs reports what it does through the [`log`]
+//! facade, for tools that embed it:
+//!
+//! * each command it runs, at the `info` level;
+//! * the compiler families it detects, and the flags it finds supported or
+//!   not with [`Build::flag_if_supported`], at the `debug` level;
+//! * the cargo warnings it prints, at the `warn` level.
+//!
+//! These are logged in addition to the usual output for cargo.
+//!
+//! [`log`]: https://docs.rs/log
+//!
 //! # Compile-time Requirements
 //!
 //! To work properly this crate needs access to a C compiler when the build script
@@ -234,17 +250,26 @@ use std::path::{Component, Path, PathBuf};
 use std::process::Child;
 use std::process::{Command, Stdio};
 use std::sync::{
-    atomic::{AtomicU8, Ordering::Relaxed},
-    Arc, RwLock,
+    atomic::{AtomicBool, AtomicU8, Ordering::Relaxed},
+    Arc, Mutex, RwLock,
 };
+use std::time::{Duration, Instant};
 
 use shlex::Shlex;
 
+/// Emit a record through the `log` facade if the `log` feature is enabled.
+macro_rules! log_record {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        ::log::$level!($($arg)+);
+    };
+}
+
 #[cfg(feature = "parallel")]
 mod parallel;
-mod target;
+pub mod target;
 mod windows;
-use self::target::TargetInfo;
+use self::target::{LibcFlavor, OwnedTargetInfo, RelocationModel, TargetInfo};
 // Regardless of whether this should be in this crate's public API,
 // it has been since 2015, so don't break it.
 pub use windows::find_tools as windows_registry;
@@ -264,14 +289,40 @@ use utilities::*;
 mod flags;
 use flags::*;
 
+mod dep_file;
+
+mod json;
+
+mod binary_blob;
+
+mod cpp_modules;
+
+mod precompiled_header;
+use precompiled_header::Language;
+
+mod rtti;
+
+mod response_file;
+
 #[derive(Debug, Eq, PartialEq, Hash)]
 struct CompilerFlag {
     compiler: Box<Path>,
     flag: Box<OsStr>,
 }
 
+/// A snippet compiled by a compiler with the given arguments.
+#[derive(Debug, Eq, PartialEq, Hash)]
+struct CompilerSnippet {
+    compiler: Box<Path>,
+    args: Box<[OsString]>,
+    snippet: Box<str>,
+}
+
 type Env = Option<Arc<OsStr>>;
 
+/// The binutils version of each archiver, if it's a GNU `ar`.
+type ArchiverVersionCache = HashMap<Box<OsStr>, Option<(u32, u32)>>;
+
 #[derive(Debug, Default)]
 struct BuildCache {
     env_cache: RwLock<HashMap<Box<str>, Env>>,
@@ -279,7 +330,12 @@ struct BuildCache {
     apple_versions_cache: RwLock<HashMap<Box<str>, Arc<str>>>,
     cached_compiler_family: RwLock<CompilerFamilyLookupCache>,
     known_flag_support_status_cache: RwLock<HashMap<CompilerFlag, bool>>,
+    known_snippet_support_status_cache: RwLock<HashMap<CompilerSnippet, bool>>,
+    archiver_version_cache: RwLock<ArchiverVersionCache>,
+    host_cache: OnceLock<Result<Box<str>, Error>>,
     target_info_parser: target::TargetInfoParser,
+    max_errors_unsupported_warned: AtomicBool,
+    x86_stack_realign_unsupported_warned: AtomicBool,
 }
 
 /// A builder for compilation of a native library.
@@ -290,36 +346,77 @@ struct BuildCache {
 #[derive(Clone, Debug)]
 pub struct Build {
     include_directories: Vec<Arc<Path>>,
+    /// The error of an `include_relative` outside of Cargo, returned when
+    /// compiling.
+    include_relative_error: Option<Error>,
+    system_include_directories: Vec<Arc<Path>>,
     definitions: Vec<(Arc<str>, Option<Arc<str>>)>,
+    cargo_cfgs_prefix: Option<Arc<str>>,
+    platform_defines: bool,
     objects: Vec<Arc<Path>>,
     flags: Vec<Arc<OsStr>>,
     flags_supported: Vec<Arc<OsStr>>,
     ar_flags: Vec<Arc<OsStr>>,
+    archive_update: bool,
+    allow_empty: Option<bool>,
     asm_flags: Vec<Arc<OsStr>>,
     no_default_flags: bool,
     files: Vec<Arc<Path>>,
+    cpp_module_interfaces: Vec<Arc<Path>>,
+    cpp_module_impls: Vec<Arc<Path>>,
+    cpp_std_module: bool,
+    precompiled_header: Option<Arc<Path>>,
+    binary_blobs: Vec<binary_blob::BinaryBlob>,
+    unity_build: Option<usize>,
+    unity_excluded: Vec<Arc<Path>>,
     cpp: bool,
     cpp_link_stdlib: Option<Option<Arc<str>>>,
     cpp_set_stdlib: Option<Arc<str>>,
+    cpp_stdlib_static: bool,
+    cpp_rtti: Option<bool>,
+    lint_rtti_usage: bool,
+    x86_stack_realign: bool,
+    reproducible: bool,
+    cpp_inlines_hidden: Option<bool>,
+    default_visibility: Option<Visibility>,
     cuda: bool,
+    cuda_relocatable: Option<bool>,
     cudart: Option<Arc<str>>,
     ccbin: bool,
     std: Option<Arc<str>>,
+    source_charset: Option<Arc<str>>,
+    execution_charset: Option<Arc<str>>,
     target: Option<Arc<str>>,
+    /// The target specification read from the `.json` file given as the
+    /// target, when it's first needed.
+    target_spec: Arc<OnceLock<Result<OwnedTargetInfo, Error>>>,
     /// The host compiler.
     ///
     /// Try to not access this directly, and instead prefer `cfg!(...)`.
     host: Option<Arc<str>>,
+    for_host: bool,
     out_dir: Option<Arc<Path>>,
     opt_level: Option<Arc<str>>,
     debug: Option<bool>,
     force_frame_pointer: Option<bool>,
+    dwarf_version: Option<u8>,
     env: Vec<(Arc<OsStr>, Arc<OsStr>)>,
     compiler: Option<Arc<Path>>,
+    compiler_family: Option<CompilerFamily>,
+    preferred_compilers: Vec<CompilerFamily>,
+    dep_info: bool,
+    report_timings: bool,
+    time_trace: bool,
+    depfile_dir: Option<Arc<Path>>,
+    response_file_encoding: Option<RspEncoding>,
+    command_length_limit: Option<usize>,
     archiver: Option<Arc<Path>>,
     ranlib: Option<Arc<Path>>,
     cargo_output: CargoOutput,
     link_lib_modifiers: Vec<Arc<OsStr>>,
+    link_args: Vec<(&'static str, Arc<OsStr>)>,
+    apple_frameworks: Vec<Arc<str>>,
+    framework_paths: Vec<Arc<Path>>,
     pic: Option<bool>,
     use_plt: Option<bool>,
     static_crt: Option<bool>,
@@ -328,19 +425,44 @@ pub struct Build {
     warnings_into_errors: bool,
     warnings: Option<bool>,
     extra_warnings: Option<bool>,
+    warning_level: Option<WarningLevel>,
+    suppressed_warnings: Vec<Arc<str>>,
+    error_warnings: Vec<Arc<str>>,
     emit_rerun_if_env_changed: bool,
     shell_escaped_flags: Option<bool>,
+    diagnostics_color: ColorChoice,
+    max_errors: Option<usize>,
+    lto: Option<Lto>,
+    lto_fallback: bool,
+    hardening: Option<HardeningLevel>,
+    match_rust_target_features: bool,
+    strip: Option<StripLevel>,
+    #[cfg(feature = "parallel")]
+    jobserver: Option<jobserver::Client>,
+    object_naming: ObjectNaming,
     build_cache: Arc<BuildCache>,
     inherit_rustflags: bool,
+    ignore_environment: bool,
     link_shared_flag: bool,
     shared_lib_out_dir: Option<Arc<Path>>,
+    module_definition: Option<Arc<Path>>,
+    export_all_symbols: bool,
+    linker_flavor: LinkerFlavor,
+    addrsig: Option<bool>,
+    apple_sdk: Option<Arc<str>>,
+    apple_deployment_target: Option<Arc<str>>,
+    apple_embed_bitcode: bool,
+    llvm_target: Option<Arc<str>>,
 }
 
 /// Represents the types of errors that may occur while using cc-rs.
 #[derive(Clone, Debug)]
 enum ErrorKind {
-    /// Error occurred while performing I/O.
-    IOError,
+    /// Error occurred while performing I/O, with the kind of the underlying
+    /// [`io::Error`].
+    // The kind is only surfaced through the `Debug` impl used by `Display`.
+    #[allow(dead_code)]
+    IOError(io::ErrorKind),
     /// Environment variable not found, with the var in question as extra info.
     EnvVarNotFound,
     /// Error occurred while using external tools (ie: invocation of compiler).
@@ -362,6 +484,10 @@ enum ErrorKind {
     JobserverHelpThreadError,
     /// `cc` has been disabled by an environment variable.
     Disabled,
+    /// A requested feature is not supported by the compiler or target.
+    UnsupportedFeature,
+    /// There are no source files or objects to build a library from.
+    NoFiles,
 }
 
 /// Represents an internal error that occurred, with an explanation.
@@ -371,6 +497,8 @@ pub struct Error {
     kind: ErrorKind,
     /// More explanation of error that occurred.
     message: Cow<'static, str>,
+    /// The I/O error that caused this error, if any.
+    source: Option<Arc<io::Error>>,
 }
 
 impl Error {
@@ -378,13 +506,21 @@ impl Error {
         Error {
             kind,
             message: message.into(),
+            source: None,
         }
     }
+
+    /// Attach the I/O error that caused this error, exposed through
+    /// [`std::error::Error::source`].
+    fn with_source(mut self, source: io::Error) -> Error {
+        self.source = Some(Arc::new(source));
+        self
+    }
 }
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Error {
-        Error::new(ErrorKind::IOError, format!("{}", e))
+        Error::new(ErrorKind::IOError(e.kind()), format!("{}", e)).with_source(e)
     }
 }
 
@@ -394,7 +530,195 @@ impl Display for Error {
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// Whether the compiler should use colors in its diagnostics.
+///
+/// See [`Build::diagnostics_color`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Follow `CARGO_TERM_COLOR` if it is set to `always` or `never`, and
+    /// otherwise leave the decision to the compiler.
+    Auto,
+    /// Always emit colored diagnostics.
+    Always,
+    /// Never emit colored diagnostics.
+    Never,
+}
+
+/// The default visibility of symbols.
+///
+/// See [`Build::default_visibility`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// Symbols are exported from shared libraries.
+    Default,
+    /// Symbols are not exported from shared libraries.
+    Hidden,
+}
+
+/// The family of a compiler, i.e. the flavor of flags it accepts.
+///
+/// See [`Build::compiler_family`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompilerFamily {
+    /// GCC, or another compiler accepting the same flags.
+    Gnu,
+    /// Clang, or another compiler accepting the same flags.
+    Clang,
+    /// MSVC's `cl.exe`.
+    Msvc,
+    /// Clang with its MSVC-compatible driver, `clang-cl`.
+    ClangCl,
+}
+
+/// The kind of link-time optimization to compile objects for.
+///
+/// See [`Build::lto`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lto {
+    /// Optimize the whole program as a single unit: `-flto=full` for Clang,
+    /// `-flto -ffat-lto-objects` for GCC and `-GL` for MSVC.
+    Fat,
+    /// Optimize modules separately with a summary of the whole program:
+    /// `-flto=thin`, only supported by Clang.
+    Thin,
+}
+
+/// How much to harden the compiled code against memory corruption.
+///
+/// See [`Build::hardening`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HardeningLevel {
+    /// Don't pass any hardening flags, leaving the compiler's default.
+    None,
+    /// Protect the stack against overflows with `-fstack-protector-strong`,
+    /// and check buffer sizes in libc calls with `-D_FORTIFY_SOURCE=2` when
+    /// optimizing. `-GS` for MSVC.
+    Basic,
+    /// In addition to [`HardeningLevel::Basic`], protect against the stack
+    /// clashing with other memory with `-fstack-clash-protection`, and
+    /// enforce control-flow integrity with `-fcf-protection` on x86. Also
+    /// `-guard:cf` for MSVC.
+    Full,
+}
+
+/// Which symbols to strip from the produced library.
+///
+/// See [`Build::strip`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StripLevel {
+    /// Don't strip anything.
+    None,
+    /// Strip debug information, with `--strip-debug`, or `-S` on Apple
+    /// targets.
+    Debug,
+    /// In addition to [`StripLevel::Debug`], strip the symbols that aren't
+    /// needed to link the library, with `--strip-unneeded`, or `-x` on
+    /// Apple targets.
+    Symbols,
+}
+
+/// The linker the compiler driver uses to link, selected with `-fuse-ld=`.
+///
+/// See [`Build::linker_flavor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkerFlavor {
+    /// The compiler's default linker.
+    Default,
+    /// LLVM's `lld`, i.e. `ld.lld`, `ld64.lld` on Apple targets or
+    /// `lld-link` on MSVC targets.
+    Lld,
+    /// GNU `gold`.
+    Gold,
+    /// GNU `ld`, also known as `ld.bfd`.
+    Bfd,
+    /// `mold`.
+    Mold,
+}
+
+/// How many warnings the compiler should emit.
+///
+/// See [`Build::warning_level`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningLevel {
+    /// Disable all warnings: `-w` for GCC and Clang, `-W0` for MSVC.
+    Off,
+    /// Don't pass any warning flags, leaving the compiler's default.
+    Default,
+    /// `-Wall` for GCC and Clang, `-W3` for MSVC.
+    All,
+    /// `-Wall -Wextra` for GCC and Clang, `-W4` for MSVC.
+    Extra,
+    /// `-Wall -Wextra -Wpedantic` for GCC and Clang, `-Wall` for MSVC.
+    Pedantic,
+}
+
+/// The encoding of response files.
+///
+/// See [`Build::response_file_encoding`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RspEncoding {
+    /// UTF-8 without a byte order mark.
+    Utf8,
+    /// UTF-16 in little endian, starting with a byte order mark.
+    Utf16Le,
+}
+
+/// How object files are named in the output directory.
+///
+/// See [`Build::object_naming`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectNaming {
+    /// Name objects after the source file only, e.g. `foo.c` -> `foo.o`.
+    ///
+    /// Compiling two source files with the same stem is an error.
+    Flat,
+    /// Prefix the object name with a hash of the source file's directory and
+    /// extension, e.g. `src/foo.c` -> `<hash>-foo.o`.
+    ///
+    /// This is the default.
+    Hashed,
+    /// Mirror the directory structure of the sources, relative to
+    /// `CARGO_MANIFEST_DIR`, e.g. `src/foo.c` -> `src/foo.o`.
+    ///
+    /// Compiling two source files with the same path apart from the
+    /// extension is an error.
+    Mirrored,
+}
+
+/// Information about a compilation, as returned by
+/// [`Build::try_compile_with_deps_info`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct CompileArtifacts {
+    /// The headers each source file includes, as listed in the dependency
+    /// file written by the compiler.
+    ///
+    /// Only sources compiled by GCC or Clang have an entry, as other
+    /// compilers don't write dependency files. System headers are omitted.
+    pub dependencies: HashMap<PathBuf, Vec<PathBuf>>,
+    /// How long compiling each source file took, slowest first.
+    ///
+    /// This is only recorded if [`Build::report_timings`] is enabled.
+    pub timings: Vec<(PathBuf, Duration)>,
+    /// The object files in the library: those compiled from the sources,
+    /// followed by those added with [`Build::object`].
+    pub objects: Vec<PathBuf>,
+    /// The `-ftime-trace` traces written by Clang for each object file.
+    ///
+    /// This is only recorded if [`Build::time_trace`] is enabled.
+    pub time_traces: Vec<PathBuf>,
+}
+
+/// How long compiling each source file took.
+type Timings = Vec<(PathBuf, Duration)>;
 
 /// Represents an object.
 ///
@@ -422,47 +746,106 @@ impl Build {
     pub fn new() -> Build {
         Build {
             include_directories: Vec::new(),
+            include_relative_error: None,
+            system_include_directories: Vec::new(),
             definitions: Vec::new(),
+            cargo_cfgs_prefix: None,
+            platform_defines: false,
             objects: Vec::new(),
             flags: Vec::new(),
             flags_supported: Vec::new(),
             ar_flags: Vec::new(),
+            archive_update: false,
+            allow_empty: None,
             asm_flags: Vec::new(),
             no_default_flags: false,
             files: Vec::new(),
+            cpp_module_interfaces: Vec::new(),
+            cpp_module_impls: Vec::new(),
+            cpp_std_module: false,
+            precompiled_header: None,
+            binary_blobs: Vec::new(),
+            unity_build: None,
+            unity_excluded: Vec::new(),
             shared_flag: None,
             static_flag: None,
             cpp: false,
             cpp_link_stdlib: None,
             cpp_set_stdlib: None,
+            cpp_stdlib_static: false,
+            cpp_rtti: None,
+            lint_rtti_usage: false,
+            x86_stack_realign: false,
+            reproducible: false,
+            cpp_inlines_hidden: None,
+            default_visibility: None,
             cuda: false,
+            cuda_relocatable: None,
             cudart: None,
             ccbin: true,
             std: None,
+            source_charset: None,
+            execution_charset: None,
             target: None,
+            target_spec: Arc::default(),
             host: None,
+            for_host: false,
             out_dir: None,
             opt_level: None,
             debug: None,
             force_frame_pointer: None,
+            dwarf_version: None,
             env: Vec::new(),
             compiler: None,
+            compiler_family: None,
+            preferred_compilers: Vec::new(),
+            dep_info: false,
+            report_timings: false,
+            time_trace: false,
+            depfile_dir: None,
+            response_file_encoding: None,
+            command_length_limit: None,
             archiver: None,
             ranlib: None,
             cargo_output: CargoOutput::new(),
             link_lib_modifiers: Vec::new(),
+            link_args: Vec::new(),
+            apple_frameworks: Vec::new(),
+            framework_paths: Vec::new(),
             pic: None,
             use_plt: None,
             static_crt: None,
             warnings: None,
             extra_warnings: None,
+            warning_level: None,
+            suppressed_warnings: Vec::new(),
+            error_warnings: Vec::new(),
             warnings_into_errors: false,
             emit_rerun_if_env_changed: true,
             shell_escaped_flags: None,
+            diagnostics_color: ColorChoice::Auto,
+            max_errors: None,
+            lto: None,
+            lto_fallback: false,
+            hardening: None,
+            match_rust_target_features: false,
+            strip: None,
+            #[cfg(feature = "parallel")]
+            jobserver: None,
+            object_naming: ObjectNaming::Hashed,
             build_cache: Arc::default(),
             inherit_rustflags: true,
+            ignore_environment: false,
             link_shared_flag: false,
             shared_lib_out_dir: None,
+            module_definition: None,
+            export_all_symbols: false,
+            linker_flavor: LinkerFlavor::Default,
+            addrsig: None,
+            apple_sdk: None,
+            apple_deployment_target: None,
+            apple_embed_bitcode: false,
+            llvm_target: None,
         }
     }
 
@@ -486,6 +869,61 @@ impl Build {
         self
     }
 
+    /// Add a directory to the front of the `-I` or include path for headers,
+    /// so that its headers shadow those of the directories added before.
+    ///
+    /// Unlike [`Build::include`], which adds directories in the order it's
+    /// called, each call puts the directory before all the others, including
+    /// the ones from earlier calls to this method.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .include("/usr/include/foo")
+    ///     // Use the vendored `foo.h` over the installed one.
+    ///     .include_first("vendor/foo/include")
+    ///     .compile("foo");
+    /// ```
+    pub fn include_first<P: AsRef<Path>>(&mut self, dir: P) -> &mut Build {
+        self.include_directories.insert(0, dir.as_ref().into());
+        self
+    }
+
+    /// Add a directory relative to the package's `CARGO_MANIFEST_DIR` to the
+    /// `-I` or include path for headers.
+    ///
+    /// Compiling fails if `CARGO_MANIFEST_DIR` isn't set, as when not run by
+    /// Cargo.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .include_relative("vendor/include")
+    ///     .compile("foo");
+    /// ```
+    pub fn include_relative<P: AsRef<Path>>(&mut self, relative: P) -> &mut Build {
+        match self.getenv("CARGO_MANIFEST_DIR") {
+            Some(manifest_dir) => {
+                let dir = Path::new(&manifest_dir).join(relative);
+                self.include_directories.push(dir.into());
+            }
+            None => {
+                self.include_relative_error = Some(Error::new(
+                    ErrorKind::EnvVarNotFound,
+                    format!(
+                        "can't include {} relative to CARGO_MANIFEST_DIR, which isn't set",
+                        relative.as_ref().display()
+                    ),
+                ));
+            }
+        }
+        self
+    }
+
     /// Add multiple directories to the `-I` include path.
     ///
     /// # Example
@@ -515,8 +953,58 @@ impl Build {
         self
     }
 
+    /// Add a directory to the system include path.
+    ///
+    /// This passes `-isystem` to GCC and Clang, and `/external:I` to MSVC.
+    /// Unlike with [`include`](Build::include), the compiler doesn't emit
+    /// warnings for headers found there, which is useful e.g. for vendored
+    /// third-party headers, or the C++ standard library headers of a
+    /// freestanding toolchain used with `-nostdinc`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .add_system_include("vendor/include")
+    ///     .compile("foo");
+    /// ```
+    pub fn add_system_include<P: AsRef<Path>>(&mut self, dir: P) -> &mut Build {
+        self.system_include_directories.push(dir.as_ref().into());
+        self
+    }
+
+    /// Add multiple directories to the system include path, see
+    /// [`add_system_include`](Build::add_system_include).
+    ///
+    /// This is meant to be used instead of [`includes`](Build::includes) for
+    /// all the header directories of a third-party library, so that their
+    /// warnings don't show up in the build.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .add_system_includes(["vendor/zlib", "vendor/zlib/contrib"])
+    ///     .compile("foo");
+    /// ```
+    pub fn add_system_includes<P>(&mut self, dirs: P) -> &mut Build
+    where
+        P: IntoIterator,
+        P::Item: AsRef<Path>,
+    {
+        for dir in dirs {
+            self.add_system_include(dir);
+        }
+        self
+    }
+
     /// Specify a `-D` variable with an optional value.
     ///
+    /// Defines are passed after the flags from the environment, such as
+    /// `CFLAGS`, so they take precedence over the same `-D` there.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -532,7 +1020,73 @@ impl Build {
         self
     }
 
+    /// Define a macro for each of the target's Cargo cfgs, so that C code can
+    /// check what a `#[cfg(...)]` would in Rust.
+    ///
+    /// This defines `<prefix>TARGET_ARCH_<arch>`, `<prefix>TARGET_VENDOR_<vendor>`,
+    /// `<prefix>TARGET_OS_<os>`, `<prefix>TARGET_ENV_<env>` and
+    /// `<prefix>TARGET_ABI_<abi>`, using the same values as the
+    /// `CARGO_CFG_TARGET_*` environment variables. Cfgs with an empty value
+    /// (e.g. `target_env` on macOS) are skipped.
+    ///
+    /// The values are converted to uppercase, and every character that is not
+    /// an ASCII letter or digit is replaced with `_`. The prefix is used as-is.
+    /// For instance, with the prefix `"MY_"`, `x86_64-unknown-linux-gnu` defines
+    /// `MY_TARGET_ARCH_X86_64`, `MY_TARGET_VENDOR_UNKNOWN`, `MY_TARGET_OS_LINUX` and
+    /// `MY_TARGET_ENV_GNU`.
+    ///
+    /// No `rerun-if-env-changed` is emitted for the `CARGO_CFG_*` variables, as they
+    /// are controlled by Cargo itself.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .define_cargo_cfgs("FOO_")
+    ///     .compile("foo");
+    /// ```
+    pub fn define_cargo_cfgs(&mut self, prefix: &str) -> &mut Build {
+        self.cargo_cfgs_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Configures whether to define the macros that select the oldest
+    /// platform version the target supports. Defaults to `false`.
+    ///
+    /// On Windows, this defines `_WIN32_WINNT` and `WINVER` to `0x0A00` for
+    /// Windows 10, which `rustc` requires, or to `0x0601` for the Windows 7
+    /// targets like `x86_64-win7-windows-msvc`. Macros set with
+    /// [`Build::define`] take precedence.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .platform_defines(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn platform_defines(&mut self, platform_defines: bool) -> &mut Build {
+        self.platform_defines = platform_defines;
+        self
+    }
+
     /// Add an arbitrary object file to link in
+    ///
+    /// The object file is added to the archive, or shared library, as it is,
+    /// after the objects compiled from [`Build::file`], and is listed in
+    /// [`CompileArtifacts::objects`]. A `cargo:rerun-if-changed` line is
+    /// emitted for it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .object("vendor/prebuilt.o")
+    ///     .compile("foo");
+    /// ```
     pub fn object<P: AsRef<Path>>(&mut self, obj: P) -> &mut Build {
         self.objects.push(obj.as_ref().into());
         self
@@ -552,6 +1106,9 @@ impl Build {
 
     /// Add an arbitrary flag to the invocation of the compiler
     ///
+    /// Flags are passed after the flags from the environment, such as
+    /// `CFLAGS`, so they can override those.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -598,6 +1155,54 @@ impl Build {
         self
     }
 
+    /// Configures whether to update an existing archive in place, rather
+    /// than recreating it. Defaults to `false`.
+    ///
+    /// When enabled, the objects are archived sorted by name, and on later
+    /// builds, the objects written since the archive was last updated are
+    /// replaced with `ar r`, while members whose source was removed are
+    /// deleted with `ar d`. If new objects were added, the archive is still
+    /// recreated to keep the members sorted. This has no effect with MSVC's
+    /// `lib.exe`, and requires the objects to have distinct file names.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .file("src/bar.c")
+    ///     .archive_update(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn archive_update(&mut self, archive_update: bool) -> &mut Build {
+        self.archive_update = archive_update;
+        self
+    }
+
+    /// Configures whether compiling without any source files or objects
+    /// succeeds, e.g. when all sources were excluded by conditions in the
+    /// build script.
+    ///
+    /// By default, such a build writes an empty static archive, emits the
+    /// usual `cargo:rustc-link-lib` metadata for it, and warns. With `true`,
+    /// the empty archive is written without a warning, and neither
+    /// `cargo:rustc-link-lib` nor `cargo:rustc-link-search` is emitted for
+    /// it. With `false`, [`Build::compile`] fails instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let mut build = cc::Build::new();
+    /// if cfg!(feature = "simd") {
+    ///     build.file("src/simd.c");
+    /// }
+    /// build.allow_empty(true).compile("simd");
+    /// ```
+    pub fn allow_empty(&mut self, allow_empty: bool) -> &mut Build {
+        self.allow_empty = Some(allow_empty);
+        self
+    }
+
     /// Add a flag that will only be used with assembly files.
     ///
     /// The flag will be applied to input files with either a `.s` or
@@ -742,6 +1347,56 @@ impl Build {
         self.files.iter().map(AsRef::as_ref)
     }
 
+    /// Configures a unity (or "jumbo") build, where sources are compiled in
+    /// groups of up to `chunk_size` files instead of one at a time.
+    ///
+    /// Each group is compiled from a file generated in the output directory
+    /// that `#include`s the sources of the group, which reduces the number of
+    /// compiler invocations. A group of a single source is compiled directly.
+    /// Only C sources (or C and C++ sources when
+    /// [`cpp`](Build::cpp) is enabled) are grouped, other files such as
+    /// assembly are compiled on their own. Use
+    /// [`unity_exclude`](Build::unity_exclude) for sources that can't share a
+    /// translation unit with others, e.g. because of conflicting `static`
+    /// definitions.
+    ///
+    /// Since Cargo can no longer tell which sources were compiled, a
+    /// `cargo:rerun-if-changed` line is emitted for each grouped source.
+    ///
+    /// A `chunk_size` of 0 is rejected when compiling.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .files(["src/a.c", "src/b.c", "src/c.c", "src/d.c"])
+    ///     .unity_build(2)
+    ///     .compile("foo");
+    /// ```
+    pub fn unity_build(&mut self, chunk_size: usize) -> &mut Build {
+        self.unity_build = Some(chunk_size);
+        self
+    }
+
+    /// Exclude a file from the unity build configured with
+    /// [`unity_build`](Build::unity_build), so that it is compiled on its own.
+    ///
+    /// The path must be the same as the one passed to [`file`](Build::file).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .files(["src/a.c", "src/b.c", "src/conflicting.c"])
+    ///     .unity_build(8)
+    ///     .unity_exclude("src/conflicting.c")
+    ///     .compile("foo");
+    /// ```
+    pub fn unity_exclude<P: AsRef<Path>>(&mut self, p: P) -> &mut Build {
+        self.unity_excluded.push(p.as_ref().into());
+        self
+    }
+
     /// Set C++ support.
     ///
     /// The other `cpp_*` options will only become active if this is set to
@@ -757,56 +1412,206 @@ impl Build {
         self
     }
 
-    /// Set CUDA C++ support.
+    /// **Unstable**: add a C++20 module interface unit, which declares the
+    /// module with `export module name;`.
     ///
-    /// Enabling CUDA will invoke the CUDA compiler, NVCC. While NVCC accepts
-    /// the most common compiler flags, e.g. `-std=c++17`, some project-specific
-    /// flags might have to be prefixed with "-Xcompiler" flag, for example as
-    /// `.flag("-Xcompiler").flag("-fpermissive")`. See the documentation for
-    /// `nvcc`, the CUDA compiler driver, at <https://docs.nvidia.com/cuda/cuda-compiler-driver-nvcc/>
-    /// for more information.
+    /// Support for C++20 modules is experimental, and how modules are built
+    /// may change in any release. It requires [`Build::cpp`], and is only
+    /// implemented for GCC with `-fmodules-ts`, Clang 16 or later and MSVC.
     ///
-    /// If enabled, this also implicitly enables C++ support.
-    pub fn cuda(&mut self, cuda: bool) -> &mut Build {
-        self.cuda = cuda;
-        if cuda {
-            self.cpp = true;
-            self.cudart = Some("static".into());
-        }
+    /// Interface units are compiled before all other files, one at a time in
+    /// the order they were added, so that they can import the modules added
+    /// before them. Their built module interfaces are written to the output
+    /// directory, and all files compiled by this build are then able to
+    /// import the modules. Unless [`Build::std`] is set, `c++20` is used.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .cpp(true)
+    ///     .cpp_module_interface("src/math.cppm")
+    ///     .cpp_module_impl("src/math.cpp")
+    ///     .file("src/main.cpp")
+    ///     .compile("math");
+    /// ```
+    pub fn cpp_module_interface<P: AsRef<Path>>(&mut self, p: P) -> &mut Build {
+        self.cpp_module_interfaces.push(p.as_ref().into());
         self
     }
 
-    /// Link CUDA run-time.
+    /// **Unstable**: add a C++20 module implementation unit, which starts
+    /// with `module name;` to implement a module added with
+    /// [`Build::cpp_module_interface`].
     ///
-    /// This option mimics the `--cudart` NVCC command-line option. Just like
-    /// the original it accepts `{none|shared|static}`, with default being
-    /// `static`. The method has to be invoked after `.cuda(true)`, or not
-    /// at all, if the default is right for the project.
-    pub fn cudart(&mut self, cudart: &str) -> &mut Build {
-        if self.cuda {
-            self.cudart = Some(cudart.into());
-        }
+    /// Implementation units are compiled after all interface units. See
+    /// [`Build::cpp_module_interface`] for the caveats of this experimental
+    /// support.
+    pub fn cpp_module_impl<P: AsRef<Path>>(&mut self, p: P) -> &mut Build {
+        self.cpp_module_impls.push(p.as_ref().into());
         self
     }
 
-    /// Set CUDA host compiler.
+    /// **Unstable**: configures whether to build the C++23 standard library
+    /// module, so that all files can `import std;`. Defaults to `false`.
     ///
-    /// By default, a `-ccbin` flag will be passed to NVCC to specify the
-    /// underlying host compiler. The value of `-ccbin` is the same as the
-    /// chosen C++ compiler. This is not always desired, because NVCC might
-    /// not support that compiler. In this case, you can remove the `-ccbin`
-    /// flag so that NVCC will choose the host compiler by itself.
-    pub fn ccbin(&mut self, ccbin: bool) -> &mut Build {
-        self.ccbin = ccbin;
+    /// This is only supported with Clang and libc++, which describes the
+    /// sources of the module in `libc++.modules.json`. The module is built
+    /// before the interface units added with [`Build::cpp_module_interface`]
+    /// and is archived with them. See there for the caveats of this
+    /// experimental support.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .cpp(true)
+    ///     .compiler("clang++")
+    ///     .cpp_set_stdlib("c++")
+    ///     .std("c++23")
+    ///     .cpp_std_modules_std(true)
+    ///     .cpp_module_interface("src/math.cppm")
+    ///     .compile("math");
+    /// ```
+    pub fn cpp_std_modules_std(&mut self, cpp_std_modules_std: bool) -> &mut Build {
+        self.cpp_std_module = cpp_std_modules_std;
         self
     }
 
-    /// Specify the C or C++ language standard version.
+    /// Add a file to embed into the library as the data of `symbol`, placed
+    /// in the object file section `section`.
     ///
-    /// These values are common to modern versions of GCC, Clang and MSVC:
-    /// - `c11` for ISO/IEC 9899:2011
-    /// - `c17` for ISO/IEC 9899:2018
-    /// - `c++14` for ISO/IEC 14882:2014
+    /// The blob is declared to C as `extern const unsigned char symbol[]`,
+    /// and its length as `extern const size_t symbol_size`, both with C
+    /// linkage. It's wrapped into an object with `objcopy`, which can be
+    /// overridden with the `OBJCOPY` environment variable, on the ELF and
+    /// MinGW targets it knows the format of. With MSVC and on the other
+    /// targets, the data is compiled from a generated C array instead.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/render.c")
+    ///     .add_binary_blob("font_data", "assets/font.ttf", ".rodata.font")
+    ///     .compile("render");
+    /// ```
+    pub fn add_binary_blob<P: AsRef<Path>>(
+        &mut self,
+        symbol: &str,
+        path: P,
+        section: &str,
+    ) -> &mut Build {
+        self.binary_blobs.push(binary_blob::BinaryBlob {
+            symbol: symbol.into(),
+            path: path.as_ref().into(),
+            section: section.into(),
+        });
+        self
+    }
+
+    /// Configures a header to precompile and include in every source of the
+    /// same language, which speeds up builds where all sources include the
+    /// same large headers.
+    ///
+    /// The header is compiled as C++ if its extension is only used by C++
+    /// headers, like `.hpp`, and otherwise as C++ when [`Build::cpp`] is
+    /// enabled, else as C. A precompiled header can't be used across
+    /// languages, so it is only included in the sources of its language,
+    /// and building fails if there are none.
+    ///
+    /// This is only supported with GCC and Clang.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .cpp(true)
+    ///     .precompiled_header("src/common.hpp")
+    ///     .file("src/foo.cpp")
+    ///     .file("src/bar.cpp")
+    ///     .compile("foo");
+    /// ```
+    pub fn precompiled_header<P: AsRef<Path>>(&mut self, header: P) -> &mut Build {
+        self.precompiled_header = Some(header.as_ref().into());
+        self
+    }
+
+    /// Set CUDA C++ support.
+    ///
+    /// Enabling CUDA will invoke the CUDA compiler, NVCC. While NVCC accepts
+    /// the most common compiler flags, e.g. `-std=c++17`, some project-specific
+    /// flags might have to be prefixed with "-Xcompiler" flag, for example as
+    /// `.flag("-Xcompiler").flag("-fpermissive")`. See the documentation for
+    /// `nvcc`, the CUDA compiler driver, at <https://docs.nvidia.com/cuda/cuda-compiler-driver-nvcc/>
+    /// for more information.
+    ///
+    /// If enabled, this also implicitly enables C++ support.
+    pub fn cuda(&mut self, cuda: bool) -> &mut Build {
+        self.cuda = cuda;
+        if cuda {
+            self.cpp = true;
+            self.cudart = Some("static".into());
+        }
+        self
+    }
+
+    /// Configures whether CUDA device code is compiled as relocatable device
+    /// code, with `-rdc=true`, so that it can call device functions defined in
+    /// other translation units.
+    ///
+    /// The device code of the objects is then linked with `nvcc --device-link`,
+    /// and the resulting object added to the library, so that the final link
+    /// doesn't need NVCC.
+    ///
+    /// By default, the device code is only relocatable when there are
+    /// several `.cu` files. Disabling this also skips the device link.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .cuda(true)
+    ///     .cuda_relocatable(true)
+    ///     .file("src/kernel.cu")
+    ///     .compile("kernel");
+    /// ```
+    pub fn cuda_relocatable(&mut self, relocatable: bool) -> &mut Build {
+        self.cuda_relocatable = Some(relocatable);
+        self
+    }
+
+    /// Link CUDA run-time.
+    ///
+    /// This option mimics the `--cudart` NVCC command-line option. Just like
+    /// the original it accepts `{none|shared|static}`, with default being
+    /// `static`. The method has to be invoked after `.cuda(true)`, or not
+    /// at all, if the default is right for the project.
+    pub fn cudart(&mut self, cudart: &str) -> &mut Build {
+        if self.cuda {
+            self.cudart = Some(cudart.into());
+        }
+        self
+    }
+
+    /// Set CUDA host compiler.
+    ///
+    /// By default, a `-ccbin` flag will be passed to NVCC to specify the
+    /// underlying host compiler. The value of `-ccbin` is the same as the
+    /// chosen C++ compiler. This is not always desired, because NVCC might
+    /// not support that compiler. In this case, you can remove the `-ccbin`
+    /// flag so that NVCC will choose the host compiler by itself.
+    pub fn ccbin(&mut self, ccbin: bool) -> &mut Build {
+        self.ccbin = ccbin;
+        self
+    }
+
+    /// Specify the C or C++ language standard version.
+    ///
+    /// These values are common to modern versions of GCC, Clang and MSVC:
+    /// - `c11` for ISO/IEC 9899:2011
+    /// - `c17` for ISO/IEC 9899:2018
+    /// - `c++14` for ISO/IEC 14882:2014
     /// - `c++17` for ISO/IEC 14882:2017
     /// - `c++20` for ISO/IEC 14882:2020
     ///
@@ -832,6 +1637,52 @@ impl Build {
         self
     }
 
+    /// Specify the character set the source files are encoded in, e.g.
+    /// `latin1` for legacy sources which aren't UTF-8.
+    ///
+    /// This is passed with `-finput-charset=` to GCC and Clang, and with
+    /// `/source-charset:` to MSVC, which also accepts code pages like `.1252`.
+    /// Note that Clang only supports `UTF-8`.
+    ///
+    /// The default is that no flag is passed to the compiler, so the
+    /// compiler's default, usually UTF-8 or the system's code page, is used.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/legacy.c")
+    ///     .source_charset("latin1")
+    ///     .compile("legacy");
+    /// ```
+    pub fn source_charset(&mut self, charset: &str) -> &mut Build {
+        self.source_charset = Some(charset.into());
+        self
+    }
+
+    /// Specify the character set that string and character literals are
+    /// encoded in by the compiled code.
+    ///
+    /// This is passed with `-fexec-charset=` to GCC and Clang, and with
+    /// `/execution-charset:` to MSVC. Note that Clang only supports `UTF-8`.
+    ///
+    /// The default is that no flag is passed to the compiler, so the
+    /// compiler's default is used.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/legacy.c")
+    ///     .source_charset("latin1")
+    ///     .execution_charset("latin1")
+    ///     .compile("legacy");
+    /// ```
+    pub fn execution_charset(&mut self, charset: &str) -> &mut Build {
+        self.execution_charset = Some(charset.into());
+        self
+    }
+
     /// Set warnings into errors flag.
     ///
     /// Disabled by default.
@@ -875,6 +1726,7 @@ impl Build {
     pub fn warnings(&mut self, warnings: bool) -> &mut Build {
         self.warnings = Some(warnings);
         self.extra_warnings = Some(warnings);
+        self.warning_level = None;
         self
     }
 
@@ -897,172 +1749,640 @@ impl Build {
     /// ```
     pub fn extra_warnings(&mut self, warnings: bool) -> &mut Build {
         self.extra_warnings = Some(warnings);
+        self.warning_level = None;
         self
     }
 
-    /// Set the standard library to link against when compiling with C++
-    /// support.
+    /// Set the warning flags from a preset level.
     ///
-    /// If the `CXXSTDLIB` environment variable is set, its value will
-    /// override the default value, but not the value explicitly set by calling
-    /// this function.
+    /// See [`WarningLevel`] for the flags passed to each compiler. This
+    /// overrides any earlier call to [`warnings`](Build::warnings) or
+    /// [`extra_warnings`](Build::extra_warnings), and unlike those the flags
+    /// are passed even if `CFLAGS`/`CXXFLAGS` are set.
     ///
-    /// A value of `None` indicates that no automatic linking should happen,
-    /// otherwise cargo will link against the specified library.
+    /// # Example
     ///
-    /// The given library name must not contain the `lib` prefix.
+    /// ```no_run
+    /// use cc::WarningLevel;
     ///
-    /// Common values:
-    /// - `stdc++` for GNU
-    /// - `c++` for Clang
-    /// - `c++_shared` or `c++_static` for Android
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .warning_level(WarningLevel::Pedantic)
+    ///     .compile("libfoo.a");
+    /// ```
+    pub fn warning_level(&mut self, level: WarningLevel) -> &mut Build {
+        self.warning_level = Some(level);
+        self
+    }
+
+    /// Silence a specific warning.
+    ///
+    /// Warnings are given by name for GCC and Clang, e.g. `unused-parameter`
+    /// for `-Wno-unused-parameter`, and by number for MSVC, e.g. `4100` for
+    /// `/wd4100`. Each compiler ignores the form it doesn't understand, so
+    /// both can be given to support several compilers; `clang-cl`
+    /// understands both.
+    ///
+    /// The flags are passed after the ones enabling warnings, so that they
+    /// take effect. Compilation fails if the warning is given with a `-W`,
+    /// `-Wno-` or `/wd` prefix.
     ///
     /// # Example
     ///
     /// ```no_run
     /// cc::Build::new()
     ///     .file("src/foo.c")
-    ///     .shared_flag(true)
-    ///     .cpp_link_stdlib("stdc++")
-    ///     .compile("libfoo.so");
+    ///     .suppress_warning("unused-parameter")
+    ///     .suppress_warning("4100")
+    ///     .compile("foo");
     /// ```
-    pub fn cpp_link_stdlib<'a, V: Into<Option<&'a str>>>(
-        &mut self,
-        cpp_link_stdlib: V,
-    ) -> &mut Build {
-        self.cpp_link_stdlib = Some(cpp_link_stdlib.into().map(Arc::from));
+    pub fn suppress_warning(&mut self, warning: &str) -> &mut Build {
+        self.suppressed_warnings.push(warning.into());
         self
     }
 
-    /// Force the C++ compiler to use the specified standard library.
+    /// Turn a specific warning into an error, without turning all warnings
+    /// into errors like [`Build::warnings_into_errors`].
     ///
-    /// Setting this option will automatically set `cpp_link_stdlib` to the same
-    /// value.
-    ///
-    /// The default value of this option is always `None`.
+    /// Warnings are given by name for GCC and Clang, e.g.
+    /// `implicit-function-declaration` for
+    /// `-Werror=implicit-function-declaration`, and by number for MSVC, e.g.
+    /// `4013` for `/we4013`. As with [`Build::suppress_warning`], each
+    /// compiler ignores the form it doesn't understand, and compilation fails
+    /// if the warning is given with a prefix like `-W` or `/we`.
     ///
-    /// This option has no effect when compiling for a Visual Studio based
-    /// target.
+    /// The flag checks of [`Build::flag_if_supported`] and the snippets of
+    /// [`Build::probe_cpp_snippet`] are compiled without these flags.
     ///
-    /// This option sets the `-stdlib` flag, which is only supported by some
-    /// compilers (clang, icc) but not by others (gcc). The library will not
-    /// detect which compiler is used, as such it is the responsibility of the
-    /// caller to ensure that this option is only used in conjunction with a
-    /// compiler which supports the `-stdlib` flag.
+    /// # Example
     ///
-    /// A value of `None` indicates that no specific C++ standard library should
-    /// be used, otherwise `-stdlib` is added to the compile invocation.
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .warning_as_error("implicit-function-declaration")
+    ///     .warning_as_error("4013")
+    ///     .compile("foo");
+    /// ```
+    pub fn warning_as_error(&mut self, warning: &str) -> &mut Build {
+        self.error_warnings.push(warning.into());
+        self
+    }
+
+    /// Configures whether the compiler should use colors in its diagnostics.
     ///
-    /// The given library name must not contain the `lib` prefix.
+    /// This passes `-fdiagnostics-color=always`/`never` to GCC and
+    /// `-fcolor-diagnostics`/`-fno-color-diagnostics` to Clang (including
+    /// `clang-cl`). MSVC's `cl.exe` has no such option, so nothing is passed.
     ///
-    /// Common values:
-    /// - `stdc++` for GNU
-    /// - `c++` for Clang
+    /// This option defaults to [`ColorChoice::Auto`], which follows
+    /// `CARGO_TERM_COLOR` when it is set to `always` or `never`. Otherwise, no
+    /// flag is passed and the compiler decides by itself, which usually means no
+    /// colors, since cc captures the compiler's output.
     ///
     /// # Example
     ///
     /// ```no_run
     /// cc::Build::new()
     ///     .file("src/foo.c")
-    ///     .cpp_set_stdlib("c++")
-    ///     .compile("libfoo.a");
+    ///     .diagnostics_color(cc::ColorChoice::Never)
+    ///     .compile("foo");
     /// ```
-    pub fn cpp_set_stdlib<'a, V: Into<Option<&'a str>>>(
-        &mut self,
-        cpp_set_stdlib: V,
-    ) -> &mut Build {
-        let cpp_set_stdlib = cpp_set_stdlib.into().map(Arc::from);
-        self.cpp_set_stdlib.clone_from(&cpp_set_stdlib);
-        self.cpp_link_stdlib = Some(cpp_set_stdlib);
+    pub fn diagnostics_color(&mut self, choice: ColorChoice) -> &mut Build {
+        self.diagnostics_color = choice;
         self
     }
 
-    /// Configures the `rustc` target this configuration will be compiling
-    /// for.
+    /// Configures the maximum number of errors the compiler reports before
+    /// giving up, so that e.g. a broken generated header doesn't drown the
+    /// actual problem.
     ///
-    /// This will fail if using a target not in a pre-compiled list taken from
-    /// `rustc +nightly --print target-list`. The list will be updated
-    /// periodically.
+    /// This passes `-fmax-errors=` to GCC and `-ferror-limit=` to Clang
+    /// (including `clang-cl`). MSVC's `cl.exe` has no equivalent, so a warning
+    /// is emitted and nothing is passed.
     ///
-    /// You should avoid setting this in build scripts, target information
-    /// will instead be retrieved from the environment variables `TARGET` and
-    /// `CARGO_CFG_TARGET_*` that Cargo sets.
+    /// By default, no flag is passed and the compiler's default is used.
     ///
     /// # Example
     ///
     /// ```no_run
     /// cc::Build::new()
     ///     .file("src/foo.c")
-    ///     .target("aarch64-linux-android")
+    ///     .max_errors(20)
     ///     .compile("foo");
     /// ```
-    pub fn target(&mut self, target: &str) -> &mut Build {
-        self.target = Some(target.into());
+    pub fn max_errors(&mut self, max_errors: usize) -> &mut Build {
+        self.max_errors = Some(max_errors);
         self
     }
 
-    /// Configures the host assumed by this configuration.
+    /// Configures link-time optimization of the compiled objects.
     ///
-    /// This option is automatically scraped from the `HOST` environment
-    /// variable by build scripts, so it's not required to call this function.
+    /// See [`Lto`] for the flags passed to each compiler. Requesting a kind
+    /// of LTO the compiler doesn't support, such as thin LTO with GCC, makes
+    /// compilation fail with an error, rather than silently producing
+    /// regular objects.
+    ///
+    /// By default, no LTO flags are passed, apart from those inherited from
+    /// `-Clto` in `CARGO_ENCODED_RUSTFLAGS` for Clang.
+    ///
+    /// On Apple targets, `-Wl,-object_path_lto,<path>` is also passed to the
+    /// linker, with a `rustc-link-arg` if `cargo_metadata` is enabled. This
+    /// keeps the objects `ld` generates in the output directory, where
+    /// `dsymutil` and the debugger find the debug info in them.
     ///
     /// # Example
     ///
     /// ```no_run
     /// cc::Build::new()
     ///     .file("src/foo.c")
-    ///     .host("arm-linux-gnueabihf")
+    ///     .compiler("clang")
+    ///     .lto(cc::Lto::Thin)
     ///     .compile("foo");
     /// ```
-    pub fn host(&mut self, host: &str) -> &mut Build {
-        self.host = Some(host.into());
+    pub fn lto(&mut self, lto: Lto) -> &mut Build {
+        self.lto = Some(lto);
         self
     }
 
-    /// Configures the optimization level of the generated object files.
+    /// Configures whether a file that fails to compile because of LTO is
+    /// compiled again without it, rather than failing the build. Defaults to
+    /// `false`.
     ///
-    /// This option is automatically scraped from the `OPT_LEVEL` environment
-    /// variable by build scripts, so it's not required to call this function.
-    pub fn opt_level(&mut self, opt_level: u32) -> &mut Build {
-        self.opt_level = Some(opt_level.to_string().into());
+    /// Some combinations of compilers and linker plugins are known to fail
+    /// only when compiling with LTO. If the diagnostics of a failed compile
+    /// mention LTO, it's retried with the LTO flags replaced by `-fno-lto`,
+    /// or without `/GL` for MSVC, and a warning is emitted. The object is
+    /// then a regular one, which still links with the LTO objects.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .lto(cc::Lto::Fat)
+    ///     .lto_fallback(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn lto_fallback(&mut self, lto_fallback: bool) -> &mut Build {
+        self.lto_fallback = lto_fallback;
         self
     }
 
-    /// Configures the optimization level of the generated object files.
+    /// Configures hardening of the compiled code against memory corruption.
     ///
-    /// This option is automatically scraped from the `OPT_LEVEL` environment
-    /// variable by build scripts, so it's not required to call this function.
-    pub fn opt_level_str(&mut self, opt_level: &str) -> &mut Build {
-        self.opt_level = Some(opt_level.into());
+    /// See [`HardeningLevel`] for the flags passed at each level. Each flag
+    /// is only passed when the target supports it, e.g. the stack protector
+    /// is left out on bare-metal targets without a C library to provide it.
+    ///
+    /// By default, no hardening flags are passed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .hardening(cc::HardeningLevel::Full)
+    ///     .compile("foo");
+    /// ```
+    pub fn hardening(&mut self, level: HardeningLevel) -> &mut Build {
+        self.hardening = Some(level);
         self
     }
 
-    /// Configures whether the compiler will emit debug information when
-    /// generating object files.
+    /// Configures whether to compile C code with the same ISA extensions as
+    /// the Rust code, by translating the target features in
+    /// `CARGO_CFG_TARGET_FEATURE` into compiler flags. Defaults to `false`.
     ///
-    /// This option is automatically scraped from the `DEBUG` environment
-    /// variable by build scripts, so it's not required to call this function.
-    pub fn debug(&mut self, debug: bool) -> &mut Build {
-        self.debug = Some(debug);
+    /// On x86, features like `sse4.2` or `avx2` become `-msse4.2` and
+    /// `-mavx2`, or the highest matching `-arch:` level with MSVC. On
+    /// `aarch64`, features like `crc` or `sve` and the architecture version are
+    /// combined into e.g. `-march=armv8.2-a+crc+sve`, using at least the
+    /// version of the target's architecture, like `armv8.3-a` for `arm64e`.
+    /// Other architectures aren't supported yet, and get no flags.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .match_rust_target_features(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn match_rust_target_features(&mut self, match_rust_target_features: bool) -> &mut Build {
+        self.match_rust_target_features = match_rust_target_features;
         self
     }
 
-    /// Configures whether the compiler will emit instructions to store
-    /// frame pointers during codegen.
+    /// Configures stripping symbols from the library produced by
+    /// [`Build::compile`], to make it smaller.
     ///
-    /// This option is automatically enabled when debug information is emitted.
-    /// Otherwise the target platform compiler's default will be used.
-    /// You can use this option to force a specific setting.
-    pub fn force_frame_pointer(&mut self, force: bool) -> &mut Build {
-        self.force_frame_pointer = Some(force);
+    /// The library is stripped with the `strip` of the target, which can be
+    /// overridden with the `STRIP` environment variable. On MSVC targets this
+    /// does nothing, as debug information is kept in separate PDB files there.
+    ///
+    /// By default, nothing is stripped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .strip(cc::StripLevel::Debug)
+    ///     .compile("foo");
+    /// ```
+    pub fn strip(&mut self, level: StripLevel) -> &mut Build {
+        self.strip = Some(level);
         self
     }
 
-    /// Configures the output directory where all object files and static
-    /// libraries will be located.
+    /// Configures the jobserver used to limit the number of compilers run in
+    /// parallel.
     ///
-    /// This option is automatically scraped from the `OUT_DIR` environment
-    /// variable by build scripts, so it's not required to call this function.
+    /// By default, the jobserver configured by Cargo for build scripts is
+    /// used, falling back to an in-process limit of `$NUM_JOBS` (or 4) when
+    /// there is none. Use this to share a jobserver with e.g. other tools
+    /// spawned by the build script.
+    ///
+    /// As with a jobserver inherited from Cargo, the calling process is
+    /// assumed to already hold one token, so one more compiler than the
+    /// number of tokens available in `client` may run.
+    ///
+    /// This is only available with the `parallel` feature.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let client = jobserver::Client::new(4).unwrap();
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .file("src/bar.c")
+    ///     .jobserver(client)
+    ///     .compile("foo");
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn jobserver(&mut self, client: jobserver::Client) -> &mut Build {
+        self.jobserver = Some(client);
+        self
+    }
+
+    /// Configures how object files are named in the output directory.
+    ///
+    /// This option defaults to [`ObjectNaming::Hashed`], which avoids collisions
+    /// between source files that share a name but live in different directories
+    /// (e.g. two `utils.c`).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .object_naming(cc::ObjectNaming::Mirrored)
+    ///     .compile("foo");
+    /// ```
+    pub fn object_naming(&mut self, naming: ObjectNaming) -> &mut Build {
+        self.object_naming = naming;
+        self
+    }
+
+    /// Set the standard library to link against when compiling with C++
+    /// support.
+    ///
+    /// If the `CXXSTDLIB` environment variable is set, its value will
+    /// override the default value, but not the value explicitly set by calling
+    /// this function.
+    ///
+    /// A value of `None` indicates that no automatic linking should happen,
+    /// otherwise cargo will link against the specified library.
+    ///
+    /// Either way, this replaces all of the C++ runtime link metadata that
+    /// would otherwise be emitted for the target, including the `libc++` and
+    /// `libc++abi` from the sysroot on WebAssembly.
+    ///
+    /// The given library name must not contain the `lib` prefix.
+    ///
+    /// Common values:
+    /// - `stdc++` for GNU
+    /// - `c++` for Clang
+    /// - `c++_shared` or `c++_static` for Android
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .shared_flag(true)
+    ///     .cpp_link_stdlib("stdc++")
+    ///     .compile("libfoo.so");
+    /// ```
+    pub fn cpp_link_stdlib<'a, V: Into<Option<&'a str>>>(
+        &mut self,
+        cpp_link_stdlib: V,
+    ) -> &mut Build {
+        self.cpp_link_stdlib = Some(cpp_link_stdlib.into().map(Arc::from));
+        self
+    }
+
+    /// Force the C++ compiler to use the specified standard library.
+    ///
+    /// Setting this option will automatically set `cpp_link_stdlib` to the same
+    /// value.
+    ///
+    /// The default value of this option is always `None`.
+    ///
+    /// This option has no effect when compiling for a Visual Studio based
+    /// target.
+    ///
+    /// This option sets the `-stdlib` flag, which is only supported by some
+    /// compilers (clang, icc) but not by others (gcc). The library will not
+    /// detect which compiler is used, as such it is the responsibility of the
+    /// caller to ensure that this option is only used in conjunction with a
+    /// compiler which supports the `-stdlib` flag.
+    ///
+    /// A value of `None` indicates that no specific C++ standard library should
+    /// be used, otherwise `-stdlib` is added to the compile invocation.
+    ///
+    /// The given library name must not contain the `lib` prefix.
+    ///
+    /// Common values:
+    /// - `stdc++` for GNU
+    /// - `c++` for Clang
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .cpp_set_stdlib("c++")
+    ///     .compile("libfoo.a");
+    /// ```
+    pub fn cpp_set_stdlib<'a, V: Into<Option<&'a str>>>(
+        &mut self,
+        cpp_set_stdlib: V,
+    ) -> &mut Build {
+        let cpp_set_stdlib = cpp_set_stdlib.into().map(Arc::from);
+        self.cpp_set_stdlib.clone_from(&cpp_set_stdlib);
+        self.cpp_link_stdlib = Some(cpp_set_stdlib);
+        self
+    }
+
+    /// Configures whether the C++ standard library is linked statically.
+    ///
+    /// When enabled, the library from [`Build::cpp_link_stdlib`] is linked
+    /// with `cargo:rustc-link-lib=static:-bundle=`, along with `c++abi` for
+    /// `c++`, and a shared library built with [`Build::link_shared_flag`]
+    /// is linked with `-static-libstdc++`, which Clang also understands for
+    /// libc++. The directory of the static library is found by asking the
+    /// compiler for it, and passed to cargo as a link search path.
+    ///
+    /// This has no effect on MSVC, where the C++ standard library is part of
+    /// the CRT, which is linked statically with [`Build::static_crt`], nor
+    /// on Apple targets, which don't ship a static libc++.
+    ///
+    /// The default value of this option is `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.cpp")
+    ///     .cpp(true)
+    ///     .cpp_stdlib_static(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn cpp_stdlib_static(&mut self, cpp_stdlib_static: bool) -> &mut Build {
+        self.cpp_stdlib_static = cpp_stdlib_static;
+        self
+    }
+
+    /// Configures whether C++ run-time type information is enabled, by
+    /// passing `-frtti`/`-fno-rtti`, or `/GR`/`/GR-` to MSVC.
+    ///
+    /// Without RTTI, `dynamic_cast` and `typeid` can't be used, see
+    /// [`Build::lint_rtti_usage`] to find them.
+    ///
+    /// The default is the compiler's, which enables RTTI.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.cpp")
+    ///     .cpp(true)
+    ///     .cpp_rtti(false)
+    ///     .compile("foo");
+    /// ```
+    pub fn cpp_rtti(&mut self, cpp_rtti: bool) -> &mut Build {
+        self.cpp_rtti = Some(cpp_rtti);
+        self
+    }
+
+    /// Configures whether the sources are checked for `dynamic_cast` and
+    /// `typeid` when RTTI is disabled with [`Build::cpp_rtti`].
+    ///
+    /// The compiler errors about them don't always say that RTTI is needed,
+    /// so when enabled, the first use in each source is reported as a cargo
+    /// warning before compiling it. This is only a textual search, which
+    /// may also find the operators in strings and block comments.
+    ///
+    /// This option defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.cpp")
+    ///     .cpp(true)
+    ///     .cpp_rtti(false)
+    ///     .lint_rtti_usage(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn lint_rtti_usage(&mut self, lint_rtti_usage: bool) -> &mut Build {
+        self.lint_rtti_usage = lint_rtti_usage;
+        self
+    }
+
+    /// Configures whether inline C++ functions (including member functions
+    /// defined in class bodies and template instantiations) get hidden
+    /// visibility, by passing `-fvisibility-inlines-hidden`.
+    ///
+    /// This shrinks the exported symbol table of C++ shared libraries. It
+    /// only applies in C++ mode on ELF and Mach-O targets, and has no effect
+    /// with MSVC.
+    ///
+    /// This option defaults to `true` if [`Build::default_visibility`] is
+    /// set to [`Visibility::Hidden`], and to `false` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.cpp")
+    ///     .cpp(true)
+    ///     .cpp_inlines_hidden(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn cpp_inlines_hidden(&mut self, cpp_inlines_hidden: bool) -> &mut Build {
+        self.cpp_inlines_hidden = Some(cpp_inlines_hidden);
+        self
+    }
+
+    /// Configures the `rustc` target this configuration will be compiling
+    /// for.
+    ///
+    /// This will fail if using a target not in a pre-compiled list taken from
+    /// `rustc +nightly --print target-list`. The list will be updated
+    /// periodically.
+    ///
+    /// A path to a custom target specification JSON file, i.e. one ending in
+    /// `.json`, can be given instead, like with `rustc --target`. The target
+    /// information and LLVM triple are then read from the specification.
+    ///
+    /// You should avoid setting this in build scripts, target information
+    /// will instead be retrieved from the environment variables `TARGET` and
+    /// `CARGO_CFG_TARGET_*` that Cargo sets.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .target("aarch64-linux-android")
+    ///     .compile("foo");
+    /// ```
+    pub fn target(&mut self, target: &str) -> &mut Build {
+        self.target = Some(target.into());
+        self.target_spec = Arc::default();
+        self
+    }
+
+    /// Configures the host assumed by this configuration.
+    ///
+    /// This option is automatically scraped from the `HOST` environment
+    /// variable by build scripts, so it's not required to call this function.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .host("arm-linux-gnueabihf")
+    ///     .compile("foo");
+    /// ```
+    pub fn host(&mut self, host: &str) -> &mut Build {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Configures whether to compile for the host instead of the target, e.g.
+    /// for a code generator that the build script runs while cross-compiling.
+    ///
+    /// This uses the host both as the target and for the compiler and flags
+    /// picked from the environment, like `HOST_CC` and `CFLAGS_<host>`. The
+    /// target information Cargo sets, like `CARGO_CFG_TARGET_FEATURE` and
+    /// `CARGO_ENCODED_RUSTFLAGS`, and a target specification `.json` file
+    /// given to [`Build::target`] are ignored, as they're for the target.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/codegen.c")
+    ///     .for_host(true)
+    ///     .compile("codegen");
+    /// ```
+    pub fn for_host(&mut self, for_host: bool) -> &mut Build {
+        self.for_host = for_host;
+        self
+    }
+
+    /// Configures the optimization level of the generated object files.
+    ///
+    /// This option is automatically scraped from the `OPT_LEVEL` environment
+    /// variable by build scripts, so it's not required to call this function.
+    pub fn opt_level(&mut self, opt_level: u32) -> &mut Build {
+        self.opt_level = Some(opt_level.to_string().into());
+        self
+    }
+
+    /// Configures the optimization level of the generated object files.
+    ///
+    /// In addition to the numeric levels accepted by [`Build::opt_level`],
+    /// this accepts `"s"` and `"z"` to optimize for size. `"s"` is passed as
+    /// `-Os` to GCC-like compilers, while `"z"` becomes `-Oz` for Clang and
+    /// falls back to `-Os` for GCC, which has no `-Oz`. MSVC maps both to
+    /// `-O1`.
+    ///
+    /// This option is automatically scraped from the `OPT_LEVEL` environment
+    /// variable by build scripts, so it's not required to call this function.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .opt_level_str("z")
+    ///     .compile("foo");
+    /// ```
+    pub fn opt_level_str(&mut self, opt_level: &str) -> &mut Build {
+        self.opt_level = Some(opt_level.into());
+        self
+    }
+
+    /// Configures whether the compiler will emit debug information when
+    /// generating object files.
+    ///
+    /// This option is automatically scraped from the `DEBUG` environment
+    /// variable by build scripts, so it's not required to call this function.
+    pub fn debug(&mut self, debug: bool) -> &mut Build {
+        self.debug = Some(debug);
+        self
+    }
+
+    /// Configures the DWARF version of the debug information emitted by the
+    /// compiler, by passing e.g. `-gdwarf-5` instead of the target's default.
+    ///
+    /// This only has an effect when debug information is emitted (see
+    /// [`debug`](Build::debug)), and is ignored by MSVC, which doesn't emit
+    /// DWARF. Versions outside of 2 to 5 are rejected when compiling.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .debug(true)
+    ///     .dwarf_version(4)
+    ///     .compile("foo");
+    /// ```
+    pub fn dwarf_version(&mut self, version: u8) -> &mut Build {
+        self.dwarf_version = Some(version);
+        self
+    }
+
+    /// Configures whether the compiler will emit instructions to store
+    /// frame pointers during codegen.
+    ///
+    /// This option is automatically enabled when debug information is emitted.
+    /// Otherwise the target platform compiler's default will be used.
+    /// You can use this option to force a specific setting.
+    pub fn force_frame_pointer(&mut self, force: bool) -> &mut Build {
+        self.force_frame_pointer = Some(force);
+        self
+    }
+
+    /// Configures the output directory where all object files and static
+    /// libraries will be located.
+    ///
+    /// This option is automatically scraped from the `OUT_DIR` environment
+    /// variable by build scripts, so it's not required to call this function.
+    ///
+    /// The intermediate files, like the dependency files, response files,
+    /// unity build sources and the sources of the compiler checks, are also
+    /// written to this directory, which must exist. Only the directories
+    /// configured with [`Build::depfile_dir`] and
+    /// [`Build::shared_lib_out_dir`] are written to as well.
     pub fn out_dir<P: AsRef<Path>>(&mut self, out_dir: P) -> &mut Build {
         self.out_dir = Some(out_dir.as_ref().into());
         self
@@ -1078,6 +2398,55 @@ impl Build {
         self
     }
 
+    /// Configures the family of the compiler set with
+    /// [`compiler`](Build::compiler), instead of detecting it.
+    ///
+    /// Detecting the family runs the compiler, which may fail, or even hang,
+    /// in some sandboxed environments, and can guess wrong for compilers
+    /// with unusual names. With both the compiler and its family set, the
+    /// compiler is used as-is without any detection. The caller is then
+    /// responsible for the flags passed to it matching the family.
+    ///
+    /// This has no effect if the compiler isn't set explicitly.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .compiler("/opt/toolchain/bin/xcc")
+    ///     .compiler_family(cc::CompilerFamily::Clang)
+    ///     .compile("foo");
+    /// ```
+    pub fn compiler_family(&mut self, family: CompilerFamily) -> &mut Build {
+        self.compiler_family = Some(family);
+        self
+    }
+
+    /// Configures which compilers to prefer, in order, over the platform's
+    /// default `cc` (or `c++`) when it isn't clear which to use.
+    ///
+    /// The first family whose compiler, e.g. `clang` or `gcc`, is found in
+    /// `PATH` is used. This only affects the default compiler, so a compiler
+    /// set with [`compiler`](Build::compiler) or through the `CC`/`CXX`
+    /// environment variables still takes precedence, as does the compiler
+    /// used for targets that require a specific one.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cc::CompilerFamily;
+    ///
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .prefer_compiler(&[CompilerFamily::Clang, CompilerFamily::Gnu])
+    ///     .compile("foo");
+    /// ```
+    pub fn prefer_compiler(&mut self, families: &[CompilerFamily]) -> &mut Build {
+        self.preferred_compilers = families.to_vec();
+        self
+    }
+
     /// Configures the tool used to assemble archives.
     ///
     /// This option is automatically determined from the target platform or a
@@ -1090,37 +2459,180 @@ impl Build {
 
     /// Configures the tool used to index archives.
     ///
-    /// This option is automatically determined from the target platform or a
-    /// number of environment variables, so it's not required to call this
-    /// function.
-    pub fn ranlib<P: AsRef<Path>>(&mut self, ranlib: P) -> &mut Build {
-        self.ranlib = Some(ranlib.as_ref().into());
+    /// This option is automatically determined from the target platform or a
+    /// number of environment variables, so it's not required to call this
+    /// function.
+    pub fn ranlib<P: AsRef<Path>>(&mut self, ranlib: P) -> &mut Build {
+        self.ranlib = Some(ranlib.as_ref().into());
+        self
+    }
+
+    /// Define whether metadata should be emitted for cargo allowing it to
+    /// automatically link the binary. Defaults to `true`.
+    ///
+    /// The emitted metadata is:
+    ///
+    ///  - `rustc-link-lib=static=`*compiled lib*
+    ///  - `rustc-link-search=native=`*target folder*
+    ///  - When target is MSVC, the ATL-MFC libs are added via `rustc-link-search=native=`
+    ///  - When C++ is enabled, the C++ stdlib is added via `rustc-link-lib`
+    ///  - Arguments added with [`Build::link_arg`] and friends, via `rustc-link-arg*`
+    ///  - If `emit_rerun_if_env_changed` is not `false`, `rerun-if-env-changed=`*env*
+    ///
+    pub fn cargo_metadata(&mut self, cargo_metadata: bool) -> &mut Build {
+        self.cargo_output.metadata = cargo_metadata;
+        self
+    }
+
+    /// Define whether compile warnings should be emitted for cargo. Defaults to
+    /// `true`.
+    ///
+    /// If disabled, compiler messages will not be printed.
+    /// Issues unrelated to the compilation will always produce cargo warnings regardless of this setting.
+    pub fn cargo_warnings(&mut self, cargo_warnings: bool) -> &mut Build {
+        self.cargo_output.warnings = cargo_warnings;
+        self
+    }
+
+    /// Configures whether to measure how long compiling each source file
+    /// takes. Defaults to `false`.
+    ///
+    /// If enabled, the slowest files are reported with cargo warnings, and
+    /// all timings are returned in [`CompileArtifacts::timings`] by
+    /// [`Build::try_compile_with_deps_info`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .file("src/bar.c")
+    ///     .report_timings(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn report_timings(&mut self, report_timings: bool) -> &mut Build {
+        self.report_timings = report_timings;
+        self
+    }
+
+    /// Configures whether Clang writes a trace of where the time compiling
+    /// each source file goes, with `-ftime-trace`. Defaults to `false`.
+    ///
+    /// The traces are written next to the object files in the output
+    /// directory, and returned in [`CompileArtifacts::time_traces`] by
+    /// [`Build::try_compile_with_deps_info`]. They can be viewed with e.g.
+    /// `chrome://tracing` or <https://ui.perfetto.dev>.
+    ///
+    /// Other compilers don't support this, so a warning is emitted and the
+    /// option is ignored.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.cpp")
+    ///     .cpp(true)
+    ///     .compiler("clang++")
+    ///     .time_trace(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn time_trace(&mut self, time_trace: bool) -> &mut Build {
+        self.time_trace = time_trace;
+        self
+    }
+
+    /// Configures the directory where the dependency (`.d`) files written by
+    /// [`Build::try_compile_with_deps_info`] are put.
+    ///
+    /// By default, each dependency file is written next to its object file
+    /// in the output directory. In the configured directory, the files keep
+    /// the names and subdirectories the object files have in the output
+    /// directory, so sources with the same name don't collide.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let artifacts = cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .depfile_dir("target/deps")
+    ///     .compile_with_deps_info("foo");
+    /// ```
+    pub fn depfile_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Build {
+        self.depfile_dir = Some(dir.as_ref().into());
+        self
+    }
+
+    /// Configures the encoding of the response files that pass the arguments
+    /// of a compiler invocation to the compiler in a file named with `@path`.
+    ///
+    /// Response files are used when a command line gets longer than about
+    /// 32000 characters on Windows, and elsewhere when it's longer than
+    /// 512 KiB together with the environment, or has an argument over
+    /// 128 KiB, see [`Build::command_length_limit`].
+    ///
+    /// The wrong encoding corrupts non-ASCII paths, so by default, MSVC's
+    /// response files are UTF-16LE, since it reads files without a byte order
+    /// mark in the ANSI code page, and all other compilers' are UTF-8 without
+    /// a byte order mark, as GCC doesn't understand one.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .compiler("clang-cl")
+    ///     .response_file_encoding(cc::RspEncoding::Utf16Le)
+    ///     .compile("foo");
+    /// ```
+    pub fn response_file_encoding(&mut self, encoding: RspEncoding) -> &mut Build {
+        self.response_file_encoding = Some(encoding);
         self
     }
 
-    /// Define whether metadata should be emitted for cargo allowing it to
-    /// automatically link the binary. Defaults to `true`.
+    /// Configures the length of the compiler command lines above which their
+    /// arguments are passed in a response file, see
+    /// [`response_file_encoding`](Build::response_file_encoding).
     ///
-    /// The emitted metadata is:
+    /// On Unix, the environment of the compiler counts towards the length,
+    /// as it shares the space the kernel reserves for the arguments. Lowering
+    /// the limit helps when running the compiler fails with a huge
+    /// environment, or through wrappers that add arguments of their own.
     ///
-    ///  - `rustc-link-lib=static=`*compiled lib*
-    ///  - `rustc-link-search=native=`*target folder*
-    ///  - When target is MSVC, the ATL-MFC libs are added via `rustc-link-search=native=`
-    ///  - When C++ is enabled, the C++ stdlib is added via `rustc-link-lib`
-    ///  - If `emit_rerun_if_env_changed` is not `false`, `rerun-if-env-changed=`*env*
+    /// Defaults to about 32000 characters, the limit on Windows, and to
+    /// 512 KiB elsewhere, where arguments over 128 KiB, which Linux rejects,
+    /// also use a response file.
     ///
-    pub fn cargo_metadata(&mut self, cargo_metadata: bool) -> &mut Build {
-        self.cargo_output.metadata = cargo_metadata;
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .command_length_limit(8000)
+    ///     .compile("foo");
+    /// ```
+    pub fn command_length_limit(&mut self, limit: usize) -> &mut Build {
+        self.command_length_limit = Some(limit);
         self
     }
 
-    /// Define whether compile warnings should be emitted for cargo. Defaults to
-    /// `true`.
+    /// Write compiler messages to `writer` instead of emitting them as cargo
+    /// warnings, one line at a time.
     ///
-    /// If disabled, compiler messages will not be printed.
-    /// Issues unrelated to the compilation will always produce cargo warnings regardless of this setting.
-    pub fn cargo_warnings(&mut self, cargo_warnings: bool) -> &mut Build {
-        self.cargo_output.warnings = cargo_warnings;
+    /// This is useful when using `cc` outside of a build script. The messages
+    /// are written even if [`cargo_warnings`](Build::cargo_warnings) is
+    /// disabled, and the writer is shared by all compilers running in
+    /// parallel, as well as by clones of this `Build`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .warning_writer(Box::new(std::io::stderr()))
+    ///     .compile("foo");
+    /// ```
+    pub fn warning_writer(&mut self, writer: Box<dyn Write + Send>) -> &mut Build {
+        self.cargo_output.warning_writer = Some(WarningWriter::new(writer));
         self
     }
 
@@ -1148,6 +2660,50 @@ impl Build {
         self
     }
 
+    /// Links an Apple framework, like `Foundation`, to the final artifacts of
+    /// the crate being built.
+    ///
+    /// This emits a `rustc-link-lib=framework=NAME` metadata line for cargo
+    /// if `cargo_metadata` is enabled, and passes `-framework NAME` when
+    /// linking a shared library with [`Build::link_shared_flag`]. Building
+    /// fails for targets other than Apple's.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.m")
+    ///     .apple_framework("Foundation")
+    ///     .apple_framework("Security")
+    ///     .compile("foo");
+    /// ```
+    pub fn apple_framework(&mut self, name: &str) -> &mut Build {
+        self.apple_frameworks.push(name.into());
+        self
+    }
+
+    /// Adds a directory to search for the frameworks added with
+    /// [`Build::apple_framework`], and for the headers of frameworks.
+    ///
+    /// This passes `-F` to the compiler, and emits a
+    /// `rustc-link-search=framework=PATH` metadata line for cargo if
+    /// `cargo_metadata` is enabled. Building fails for targets other than
+    /// Apple's.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.m")
+    ///     .framework_path("vendor/Frameworks")
+    ///     .apple_framework("Sparkle")
+    ///     .compile("foo");
+    /// ```
+    pub fn framework_path<P: AsRef<Path>>(&mut self, path: P) -> &mut Build {
+        self.framework_paths.push(path.as_ref().into());
+        self
+    }
+
     /// Adds a native library modifier that will be added to the
     /// `rustc-link-lib=static:MODIFIERS=LIBRARY_NAME` metadata line
     /// emitted for cargo if `cargo_metadata` is enabled.
@@ -1159,15 +2715,78 @@ impl Build {
         self
     }
 
+    /// Adds an argument that will be passed to the linker when linking the
+    /// final artifacts of the crate being built, such as `-Wl,--no-as-needed`.
+    ///
+    /// This emits a `rustc-link-arg=ARG` metadata line for cargo if
+    /// `cargo_metadata` is enabled, which applies to all of the package's
+    /// benchmarks, binaries, `cdylib` crates, examples and tests.
+    /// See <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-link-arg>.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .link_arg("-Wl,--no-as-needed")
+    ///     .compile("foo");
+    /// ```
+    pub fn link_arg(&mut self, link_arg: impl AsRef<OsStr>) -> &mut Build {
+        self.link_args
+            .push(("rustc-link-arg", link_arg.as_ref().into()));
+        self
+    }
+
+    /// Like [`Build::link_arg`], but only applies to the package's binaries,
+    /// by emitting a `rustc-link-arg-bins=ARG` metadata line.
+    pub fn link_arg_bins(&mut self, link_arg: impl AsRef<OsStr>) -> &mut Build {
+        self.link_args
+            .push(("rustc-link-arg-bins", link_arg.as_ref().into()));
+        self
+    }
+
+    /// Like [`Build::link_arg`], but only applies to the package's tests,
+    /// by emitting a `rustc-link-arg-tests=ARG` metadata line.
+    pub fn link_arg_tests(&mut self, link_arg: impl AsRef<OsStr>) -> &mut Build {
+        self.link_args
+            .push(("rustc-link-arg-tests", link_arg.as_ref().into()));
+        self
+    }
+
     /// Configures whether the compiler will emit position independent code.
     ///
-    /// This option defaults to `false` for `windows-gnu` and bare metal targets and
-    /// to `true` for all other targets.
+    /// This option defaults to `false` for `windows-gnu`, ESP-IDF and bare metal targets
+    /// and to `true` for all other targets.
+    ///
+    /// Without this option, the relocation model can also be forced with the
+    /// `CC_RELOCATION_MODEL_<target>` environment variable, or one of its
+    /// variants like the other compiler environment variables, set to
+    /// `static`, `pic` or `pie`. This takes precedence over the target's
+    /// default, and is meant for debugging link errors.
     pub fn pic(&mut self, pic: bool) -> &mut Build {
         self.pic = Some(pic);
         self
     }
 
+    /// Configures the default visibility of symbols, by passing
+    /// `-fvisibility=`.
+    ///
+    /// This only applies to ELF and Mach-O targets, and has no effect with
+    /// MSVC. By default, no flag is passed, which leaves symbols visible.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .default_visibility(cc::Visibility::Hidden)
+    ///     .compile("foo");
+    /// ```
+    pub fn default_visibility(&mut self, visibility: Visibility) -> &mut Build {
+        self.default_visibility = Some(visibility);
+        self
+    }
+
     /// Configures whether the Procedure Linkage Table is used for indirect
     /// calls into shared libraries.
     ///
@@ -1183,6 +2802,30 @@ impl Build {
         self
     }
 
+    /// Configures whether to pass `-fno-plt`, the inverse of
+    /// [`use_plt`](Build::use_plt).
+    ///
+    /// Calls into shared libraries then go through the GOT directly, which
+    /// avoids the indirection through the PLT on dynamically linked ELF
+    /// targets such as `x86_64` Linux.
+    ///
+    /// The flag is only passed when position independent code is emitted, so
+    /// this has no effect on targets that default to the static relocation
+    /// model, such as bare metal ones, or when [`pic`](Build::pic) is set to
+    /// `false`. It is also ignored on non-ELF targets.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .no_plt(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn no_plt(&mut self, no_plt: bool) -> &mut Build {
+        self.use_plt(!no_plt)
+    }
+
     /// Define whether metadata should be emitted for cargo to detect environment
     /// changes that should trigger a rebuild.
     ///
@@ -1231,6 +2874,103 @@ impl Build {
         self
     }
 
+    /// Configure whether cc ignores the environment variables that configure
+    /// the build, and only uses the values configured on this `Build`.
+    ///
+    /// This makes builds hermetic, as flags or tools leaking from the
+    /// environment can't change the compiler invocation. When enabled, these
+    /// variables are ignored, including their target-specific variants like
+    /// `CFLAGS_x86_64-unknown-linux-gnu`, `CFLAGS_x86_64_unknown_linux_gnu`
+    /// and `TARGET_CFLAGS`:
+    ///
+    /// - `CC`, `CXX`, `AR`, `RANLIB` and `NVCC`
+    /// - `CFLAGS`, `CXXFLAGS`, `ARFLAGS` and `RANLIBFLAGS`, and the variables
+    ///   read by [`Build::try_flags_from_environment`] after this is set
+    /// - `CXXSTDLIB` and `CC_RELOCATION_MODEL`
+    /// - `CRATE_CC_NO_DEFAULTS` and `CC_SHELL_ESCAPED_FLAGS`
+    /// - `CROSS_COMPILE` and `RUSTC_LINKER`, which pick the compiler prefix
+    /// - `RUSTC_WRAPPER` and `CC_KNOWN_WRAPPER_CUSTOM`
+    /// - `CARGO_ENCODED_RUSTFLAGS`, as with [`Build::inherit_rustflags`]
+    ///
+    /// The variables Cargo sets to identify the target and profile, like
+    /// `TARGET`, `HOST`, `OPT_LEVEL`, `DEBUG`, `OUT_DIR` and
+    /// `CARGO_CFG_TARGET_FEATURE`, are still used unless configured on this
+    /// `Build`, as are the ones locating the toolchain, like `PATH`,
+    /// `SDKROOT` and `WASI_SYSROOT`.
+    ///
+    /// This option defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .compiler("gcc")
+    ///     .ignore_environment(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn ignore_environment(&mut self, ignore_environment: bool) -> &mut Build {
+        self.ignore_environment = ignore_environment;
+        self
+    }
+
+    /// Configures whether to pass `-mstackrealign` on 32-bit x86 targets
+    /// whose code may be called with a misaligned stack.
+    ///
+    /// Rust aligns the stack to 16 bytes on `i686` targets, but code
+    /// following the original i386 ABI, like that of old libraries or
+    /// callbacks from them, only aligns it to 4 bytes, which breaks the SSE
+    /// code of functions assuming more. With this flag, such functions
+    /// realign the stack themselves. It's not passed for `i586` targets,
+    /// which don't use SSE, nor for Apple ones, whose ABI aligns the stack to
+    /// 16 bytes, and is ignored with a warning by MSVC.
+    ///
+    /// This option defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .x86_stack_realign(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn x86_stack_realign(&mut self, x86_stack_realign: bool) -> &mut Build {
+        self.x86_stack_realign = x86_stack_realign;
+        self
+    }
+
+    /// Configures whether the compiler runs set up for reproducible builds,
+    /// so that building the same sources gives the same objects.
+    ///
+    /// When enabled, the compiler is run with:
+    ///
+    /// - `SOURCE_DATE_EPOCH` set to `0` unless it's already set, so that
+    ///   `__DATE__` and `__TIME__` expand to a fixed date, along with
+    ///   `-Wdate-time` to warn about their use.
+    /// - `LC_ALL=C` and `TZ=UTC`, so that its output doesn't depend on the
+    ///   locale or time zone.
+    /// - `-ffile-prefix-map` mapping `CARGO_MANIFEST_DIR` to `.` and the
+    ///   output directory to `out`, so that the paths in debug information
+    ///   and `__FILE__` don't depend on where the package is built.
+    ///
+    /// MSVC gets `/Brepro` instead of the flags.
+    ///
+    /// This option defaults to `false`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .reproducible(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn reproducible(&mut self, reproducible: bool) -> &mut Build {
+        self.reproducible = reproducible;
+        self
+    }
+
     /// Configure whether cc should build dynamic library and link with `rustc-link-lib=dylib`
     ///
     /// This option defaults to `false`.
@@ -1241,9 +2981,208 @@ impl Build {
 
     /// Configures the output directory where shared library will be located.
     ///
-    /// This option defaults to `out_dir`.
-    pub fn shared_lib_out_dir<P: AsRef<Path>>(&mut self, out_dir: P) -> &mut Build {
-        self.shared_lib_out_dir = Some(out_dir.as_ref().into());
+    /// This option defaults to `out_dir`.
+    pub fn shared_lib_out_dir<P: AsRef<Path>>(&mut self, out_dir: P) -> &mut Build {
+        self.shared_lib_out_dir = Some(out_dir.as_ref().into());
+        self
+    }
+
+    /// Configures a module-definition (`.def`) file listing the exports of
+    /// a DLL built for a Windows target.
+    ///
+    /// With [`Build::link_shared_flag`], the file is passed to the linker of
+    /// the DLL, with `/DEF:` for MSVC and as an input for MinGW. Otherwise,
+    /// it is passed to the final link by rustc with a `rustc-link-arg`.
+    ///
+    /// The file must exist. It is ignored with a warning for other targets.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .link_shared_flag(true)
+    ///     .module_definition("src/foo.def")
+    ///     .compile("foo");
+    /// ```
+    pub fn module_definition<P: AsRef<Path>>(&mut self, path: P) -> &mut Build {
+        self.module_definition = Some(path.as_ref().into());
+        self
+    }
+
+    /// Configures whether a DLL built with [`Build::link_shared_flag`] for a
+    /// Windows target exports all of its non-static functions, without
+    /// listing them in a [module-definition file](Build::module_definition).
+    ///
+    /// MinGW's linker is passed `--export-all-symbols`. For MSVC, the
+    /// functions defined by the objects are listed with `dumpbin /SYMBOLS`,
+    /// and written to a `.def` file passed with `/DEF:`.
+    ///
+    /// This is disabled by default, can't be combined with
+    /// [`Build::module_definition`], and is ignored with a warning for
+    /// other targets, whose shared libraries export all symbols anyway.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .link_shared_flag(true)
+    ///     .export_all_symbols(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn export_all_symbols(&mut self, export_all_symbols: bool) -> &mut Build {
+        self.export_all_symbols = export_all_symbols;
+        self
+    }
+
+    /// Configures the linker used to link, which can be much faster than the
+    /// default one. Defaults to [`LinkerFlavor::Default`].
+    ///
+    /// The linker is selected with `-fuse-ld=`. With
+    /// [`Build::link_shared_flag`], it is passed to the link of the shared
+    /// library. Otherwise, it is passed to the final link by rustc with a
+    /// `rustc-link-arg`.
+    ///
+    /// If the linker isn't found in `PATH`, or isn't supported for the
+    /// target or by MSVC, a warning is emitted and the default linker is used.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .link_shared_flag(true)
+    ///     .linker_flavor(cc::LinkerFlavor::Lld)
+    ///     .compile("foo");
+    /// ```
+    pub fn linker_flavor(&mut self, linker_flavor: LinkerFlavor) -> &mut Build {
+        self.linker_flavor = linker_flavor;
+        self
+    }
+
+    /// Configures whether Clang emits address-significance tables, which let
+    /// linkers fold more identical functions, with `-faddrsig` or
+    /// `-fno-addrsig`.
+    ///
+    /// With `None`, the default, Clang's default is used, except that
+    /// `-fno-addrsig` is passed for ELF targets when the archiver is a GNU
+    /// `ar` older than binutils 2.36, as these old binutils can't handle the
+    /// tables. This runs the archiver once to find its version.
+    ///
+    /// This has no effect with other compilers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .addrsig(Some(false))
+    ///     .compile("foo");
+    /// ```
+    pub fn addrsig(&mut self, addrsig: Option<bool>) -> &mut Build {
+        self.addrsig = addrsig;
+        self
+    }
+
+    /// Configures the Apple SDK to compile against when targeting an Apple
+    /// platform, e.g. `"macosx14.5"` or `"iphonesimulator"`.
+    ///
+    /// The path of the SDK is discovered with `xcrun --show-sdk-path --sdk
+    /// <sdk>` and passed to the compiler via `-isysroot`, unless `SDKROOT`
+    /// points to an SDK for the same platform.
+    ///
+    /// This option defaults to `None`, which uses the SDK matching the target
+    /// (`macosx`, `iphoneos`, `iphonesimulator`, `appletvos`, etc.).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .target("aarch64-apple-ios")
+    ///     .apple_sdk(Some("iphoneos17.5"))
+    ///     .compile("foo");
+    /// ```
+    pub fn apple_sdk(&mut self, sdk: Option<&str>) -> &mut Build {
+        self.apple_sdk = sdk.map(Into::into);
+        self
+    }
+
+    /// Configures the minimum OS version (deployment target) to compile for
+    /// when targeting an Apple platform, e.g. `"11.0"`.
+    ///
+    /// The deployment target is determined with the following precedence:
+    ///
+    /// 1. The version set with this method.
+    /// 2. The platform's environment variable, e.g. `MACOSX_DEPLOYMENT_TARGET`
+    ///    or `IPHONEOS_DEPLOYMENT_TARGET`.
+    /// 3. The default deployment target of the SDK, falling back to a
+    ///    per-OS default.
+    ///
+    /// The version is passed to the compiler exactly once, either with a
+    /// `-m*-version-min=` flag, or in the `--target` triple on platforms
+    /// that have no such flag (visionOS and Mac Catalyst).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .target("aarch64-apple-ios")
+    ///     .apple_deployment_target("16.0")
+    ///     .compile("foo");
+    /// ```
+    pub fn apple_deployment_target(&mut self, version: &str) -> &mut Build {
+        self.apple_deployment_target = Some(version.into());
+        self
+    }
+
+    /// Configures whether to embed LLVM bitcode in the objects for Apple
+    /// devices. Defaults to `false`.
+    ///
+    /// Bitcode is deprecated since Xcode 14, but some older App Store
+    /// submission flows still require it. When enabled, `-fembed-bitcode` is
+    /// passed to the compiler, or `-fembed-bitcode-marker` in debug builds,
+    /// which only reserves the section like Xcode does.
+    ///
+    /// This only has an effect when targeting iOS, tvOS, watchOS or visionOS
+    /// devices, and is ignored for their simulators, Mac Catalyst, macOS and
+    /// non-Apple targets, where bitcode was never accepted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .target("aarch64-apple-ios")
+    ///     .apple_embed_bitcode(true)
+    ///     .compile("foo");
+    /// ```
+    pub fn apple_embed_bitcode(&mut self, apple_embed_bitcode: bool) -> &mut Build {
+        self.apple_embed_bitcode = apple_embed_bitcode;
+        self
+    }
+
+    /// Configures the LLVM target triple passed to Clang with `--target=`.
+    ///
+    /// By default, the triple is derived from the Rust target, or read from
+    /// its specification for custom targets given as a JSON file, which may
+    /// be wrong for exotic targets. The triple set here is used as-is, so it must include
+    /// the deployment target on platforms that need it there (visionOS and
+    /// Mac Catalyst). All other flags are still derived from the Rust target.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .target("riscv64gc-custom-none-elf")
+    ///     .llvm_target("riscv64-unknown-elf")
+    ///     .compile("foo");
+    /// ```
+    pub fn llvm_target(&mut self, llvm_target: &str) -> &mut Build {
+        self.llvm_target = Some(llvm_target.into());
         self
     }
 
@@ -1279,6 +3218,145 @@ impl Build {
         )
     }
 
+    /// Get the flags checked by `is_flag_supported` and `flag_if_supported`
+    /// so far, and whether each of them is supported, sorted by flag.
+    ///
+    /// This helps to find out why a flag added with `flag_if_supported` is
+    /// missing from the compiler invocations. The checks are shared with the
+    /// clones of this `Build`, and a flag checked with several compilers is
+    /// listed once per compiler.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let mut build = cc::Build::new();
+    /// build.file("src/foo.c").flag_if_supported("-Wall").compile("foo");
+    /// for (flag, supported) in build.supported_flags() {
+    ///     println!("{}: {}", flag, supported);
+    /// }
+    /// ```
+    pub fn supported_flags(&self) -> Vec<(String, bool)> {
+        let mut flags = self
+            .build_cache
+            .known_flag_support_status_cache
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(compiler_flag, &is_supported)| {
+                (
+                    compiler_flag.flag.to_string_lossy().into_owned(),
+                    is_supported,
+                )
+            })
+            .collect::<Vec<_>>();
+        flags.sort();
+        flags
+    }
+
+    /// Check whether the C++ compiler can compile `snippet`, e.g. to find out
+    /// whether the standard library has `<filesystem>`, or the compiler
+    /// supports concepts.
+    ///
+    /// The snippet is compiled to an object file in `out_dir` with the
+    /// compiler and flags used for C++ sources, so it can't rely on being
+    /// linked. Like [`is_flag_supported`](Build::is_flag_supported), the
+    /// result is cached, also for the clones of this `Build`.
+    ///
+    /// It may return error if it's unable to run the compiler (e.g. the
+    /// compiler is missing or a write to the `out_dir` failed).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let mut build = cc::Build::new();
+    /// build.cpp(true).std("c++17").file("src/foo.cpp");
+    /// if build.probe_cpp_snippet("#include <filesystem>").unwrap() {
+    ///     build.define("HAVE_FILESYSTEM", None);
+    /// }
+    /// build.compile("foo");
+    /// ```
+    pub fn probe_cpp_snippet(&self, snippet: &str) -> Result<bool, Error> {
+        use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+
+        let mut cfg = self.clone();
+        cfg.cpp(true).cuda(false);
+        // The snippet isn't the user's code, so its warnings don't matter.
+        cfg.error_warnings.clear();
+        let compiler = cfg.try_get_compiler()?;
+        let compiler_snippet = CompilerSnippet {
+            compiler: compiler.path().into(),
+            args: compiler.args().into(),
+            snippet: snippet.into(),
+        };
+
+        if let Some(is_supported) = self
+            .build_cache
+            .known_snippet_support_status_cache
+            .read()
+            .unwrap()
+            .get(&compiler_snippet)
+            .cloned()
+        {
+            return Ok(is_supported);
+        }
+
+        // Each snippet gets its own files, as different snippets may be
+        // checked at the same time.
+        let mut hasher = DefaultHasher::new();
+        hasher.write(snippet.as_bytes());
+        let name = format!("snippet_check-{:016x}", hasher.finish());
+        let out_dir = self.get_out_dir()?;
+        let src = out_dir.join(format!("{}.cpp", name));
+        fs::write(&src, format!("{}\n", snippet))?;
+        let obj = out_dir.join(name);
+
+        let mut cmd = compiler.to_command();
+        for (a, b) in self.env.iter() {
+            cmd.env(a, b);
+        }
+        let target = self.get_target()?;
+        command_add_output_file(
+            &mut cmd,
+            &obj,
+            CmdAddOutputFileArgs {
+                cuda: false,
+                is_assembler_msvc: false,
+                msvc: compiler.is_like_msvc(),
+                clang: compiler.is_like_clang(),
+                gnu: compiler.is_like_gnu(),
+                is_asm: false,
+                is_arm: matches!(target.arch, "aarch64" | "arm"),
+            },
+        );
+        cmd.arg("-c");
+        if compiler.supports_path_delimiter() {
+            cmd.arg("--");
+        }
+        cmd.arg(&src);
+
+        let output = cmd.current_dir(&*out_dir).output()?;
+        let is_supported = output.status.success();
+        log_record!(
+            debug,
+            "snippet {:?} is {} by {}",
+            snippet,
+            if is_supported {
+                "supported"
+            } else {
+                "not supported"
+            },
+            compiler.path().display()
+        );
+
+        self.build_cache
+            .known_snippet_support_status_cache
+            .write()
+            .unwrap()
+            .insert(compiler_snippet, is_supported);
+
+        Ok(is_supported)
+    }
+
     fn ensure_check_file(&self) -> Result<PathBuf, Error> {
         let out_dir = self.get_out_dir()?;
         let src = if self.cuda {
@@ -1324,13 +3402,23 @@ impl Build {
         let src = self.ensure_check_file()?;
         let obj = out_dir.join("flag_check");
 
+        // Any diagnostic means that the flag isn't supported, so the check is
+        // compiled without the default warnings, which could otherwise warn
+        // about the check itself. Warnings can't be disabled with `-w`, as
+        // compilers report some unsupported flags as warnings, like Clang
+        // does for unknown `-W` options, or GCC for `-std=c++11` in C.
         let mut compiler = {
             let mut cfg = Build::new();
+            // The compiler family is detected again in the output directory,
+            // rather than in the system's temporary directory.
             cfg.flag(flag)
                 .compiler(tool.path())
+                .out_dir(&*out_dir)
                 .cargo_metadata(self.cargo_output.metadata)
                 .opt_level(0)
                 .debug(false)
+                .warnings(false)
+                .extra_warnings(false)
                 .cpp(self.cpp)
                 .cuda(self.cuda)
                 .inherit_rustflags(false)
@@ -1341,6 +3429,7 @@ impl Build {
             if let Some(host) = &self.host {
                 cfg.host(host);
             }
+            cfg.for_host(self.for_host);
             cfg.try_get_compiler()?
         };
 
@@ -1390,6 +3479,17 @@ impl Build {
 
         let output = cmd.current_dir(out_dir).output()?;
         let is_supported = output.status.success() && output.stderr.is_empty();
+        log_record!(
+            debug,
+            "flag {:?} is {} by {}",
+            flag,
+            if is_supported {
+                "supported"
+            } else {
+                "not supported"
+            },
+            tool.path().display()
+        );
 
         self.build_cache
             .known_flag_support_status_cache
@@ -1400,6 +3500,214 @@ impl Build {
         Ok(is_supported)
     }
 
+    /// Get the path of the dependency file written when compiling `obj`.
+    fn get_dep_file(&self, obj: &Object) -> Result<PathBuf, Error> {
+        let dep_file = obj.dst.with_extension("d");
+        let dir = match &self.depfile_dir {
+            Some(dir) => dir,
+            None => return Ok(dep_file),
+        };
+        // Keep the path relative to the output directory, which is unique
+        // for each object.
+        let out_dir = self.get_out_dir()?;
+        Ok(match dep_file.strip_prefix(&out_dir) {
+            Ok(relative) => dir.join(relative),
+            Err(_) => dir.join(dep_file.file_name().unwrap_or_default()),
+        })
+    }
+
+    /// Get the linker argument passing the module-definition file, if one is
+    /// configured and the target supports it.
+    fn get_module_definition_arg(&self) -> Result<Option<OsString>, Error> {
+        let def = match &self.module_definition {
+            Some(def) => def,
+            None => return Ok(None),
+        };
+        if !def.is_file() {
+            return Err(Error::new(
+                ErrorKind::InvalidArgument,
+                format!("module definition file {} does not exist", def.display()),
+            ));
+        }
+        let target = self.get_target()?;
+        if target.os != "windows" {
+            self.cargo_output.print_warning(&format_args!(
+                "module_definition is specified, but {} is not a Windows target, ignored",
+                self.get_raw_target()?
+            ));
+            return Ok(None);
+        }
+        Ok(Some(if target.env == "msvc" {
+            let mut arg = OsString::from("/DEF:");
+            arg.push(&**def);
+            arg
+        } else {
+            // GNU ld reads `.def` files given as inputs.
+            def.as_os_str().to_owned()
+        }))
+    }
+
+    /// Get the linker argument exporting all the functions defined by
+    /// `objects` from the DLL named `lib_name`, if
+    /// [`Build::export_all_symbols`] is enabled and the target supports it.
+    fn get_export_all_symbols_arg(
+        &self,
+        lib_name: &str,
+        objects: &[&Path],
+    ) -> Result<Option<OsString>, Error> {
+        if !self.export_all_symbols {
+            return Ok(None);
+        }
+        if self.module_definition.is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidArgument,
+                "export_all_symbols can't be combined with module_definition",
+            ));
+        }
+        let target = self.get_target()?;
+        if target.os != "windows" {
+            self.cargo_output.print_warning(&format_args!(
+                "export_all_symbols is specified, but {} is not a Windows target, ignored",
+                self.get_raw_target()?
+            ));
+            return Ok(None);
+        }
+        if target.env != "msvc" {
+            return Ok(Some("--export-all-symbols".into()));
+        }
+
+        let mut dumpbin = self
+            .windows_registry_find(&target, "dumpbin.exe")
+            .unwrap_or_else(|| self.cmd("dumpbin.exe"));
+        dumpbin.arg("/SYMBOLS").args(objects);
+        let symbols = run_output(&mut dumpbin, &self.cargo_output)?;
+        let symbols = String::from_utf8_lossy(&symbols);
+        // 32-bit x86 prefixes the names of C functions with `_`, which isn't
+        // part of their exported name.
+        let strip_underscore = target.arch == "x86";
+        let mut def = String::from("EXPORTS\n");
+        for name in parse_dumpbin_functions(&symbols) {
+            let name = match name.strip_prefix('_') {
+                Some(stripped) if strip_underscore => stripped,
+                _ => name,
+            };
+            def.push_str(&format!("    {}\n", name));
+        }
+        let path = self.get_out_dir()?.join(format!("{}.def", lib_name));
+        fs::write(&path, def)?;
+        let mut arg = OsString::from("/DEF:");
+        arg.push(path);
+        Ok(Some(arg))
+    }
+
+    /// The `-fuse-ld=` flag selecting the linker configured with
+    /// [`Build::linker_flavor`], if it's available.
+    fn get_fuse_ld_arg(&self) -> Result<Option<String>, Error> {
+        let target = self.get_target()?;
+        let apple = target.vendor == "apple";
+        let msvc = target.env == "msvc";
+        let (name, linker) = match self.linker_flavor {
+            LinkerFlavor::Default => return Ok(None),
+            LinkerFlavor::Lld if apple => ("lld", "ld64.lld"),
+            LinkerFlavor::Lld if msvc => ("lld", "lld-link"),
+            LinkerFlavor::Lld => ("lld", "ld.lld"),
+            LinkerFlavor::Gold if !apple && !msvc => ("gold", "ld.gold"),
+            LinkerFlavor::Bfd if !apple && !msvc => ("bfd", "ld.bfd"),
+            LinkerFlavor::Mold if !apple && !msvc => ("mold", "ld.mold"),
+            flavor => {
+                self.cargo_output.print_warning(&format_args!(
+                    "linker_flavor {:?} is not supported for {}, using the default linker",
+                    flavor,
+                    self.get_raw_target()?
+                ));
+                return Ok(None);
+            }
+        };
+        // `rustc` doesn't link through the compiler driver on MSVC targets.
+        if self.get_base_compiler()?.is_like_msvc() || (msvc && !self.link_shared_flag) {
+            self.cargo_output
+                .print_warning(&"linker_flavor is not supported by MSVC, using the default linker");
+            return Ok(None);
+        }
+        // The compiler runs with the environment configured on the builder,
+        // where the last setting wins.
+        let path = self
+            .env
+            .iter()
+            .rev()
+            .find(|(key, _)| **key == *OsStr::new("PATH"))
+            .map(|(_, value)| &**value);
+        let found = match path {
+            // Only that `PATH` is searched, as `which` would fall back to the
+            // one of the build script.
+            Some(path) => {
+                env::split_paths(path).any(|dir| self.which(&dir.join(linker), None).is_some())
+            }
+            None => self.which(Path::new(linker), None).is_some(),
+        };
+        if !found {
+            self.cargo_output.print_warning(&format_args!(
+                "linker_flavor is {:?}, but `{}` was not found in PATH, using the default linker",
+                self.linker_flavor, linker
+            ));
+            return Ok(None);
+        }
+        Ok(Some(format!("-fuse-ld={}", name)))
+    }
+
+    /// Get the argument making Apple's `ld` keep the objects it generates with
+    /// LTO in `dst`, instead of deleting them after linking, as the debug
+    /// info of the linked binary refers to them.
+    fn get_object_path_lto_arg(&self, dst: &Path) -> Result<Option<String>, Error> {
+        let lto = match self.lto {
+            Some(lto) if self.get_target()?.vendor == "apple" => lto,
+            _ => return Ok(None),
+        };
+        let path = match lto {
+            Lto::Fat => dst.join("lto.o"),
+            // With thin LTO, an object is written for each module in the
+            // directory.
+            Lto::Thin => {
+                let dir = dst.join("lto");
+                fs::create_dir_all(&dir)?;
+                dir
+            }
+        };
+        Ok(Some(format!("-Wl,-object_path_lto,{}", path.display())))
+    }
+
+    /// Check that frameworks are only linked on Apple targets.
+    fn check_apple_frameworks(&self) -> Result<(), Error> {
+        if (self.apple_frameworks.is_empty() && self.framework_paths.is_empty())
+            || self.get_target()?.vendor == "apple"
+        {
+            return Ok(());
+        }
+        Err(Error::new(
+            ErrorKind::UnsupportedFeature,
+            format!(
+                "frameworks can only be linked on Apple targets, not {}",
+                self.get_raw_target()?
+            ),
+        ))
+    }
+
+    /// Strip the library at `path` as configured with [`Build::strip`].
+    fn strip_library(&self, path: &Path) -> Result<(), Error> {
+        let target = self.get_target()?;
+        let flags: &[&str] = match self.strip {
+            None | Some(StripLevel::None) => return Ok(()),
+            _ if target.env == "msvc" => return Ok(()),
+            Some(StripLevel::Debug) if target.vendor == "apple" => &["-S"],
+            Some(StripLevel::Symbols) if target.vendor == "apple" => &["-S", "-x"],
+            Some(StripLevel::Debug) => &["--strip-debug"],
+            Some(StripLevel::Symbols) => &["--strip-unneeded"],
+        };
+        let (mut cmd, _) = self.get_base_archiver_variant("STRIP", "strip")?;
+        cmd.args(flags).arg(path);
+        run(&mut cmd, &self.cargo_output)
+    }
+
     /// Get canonical library names for `output`
     fn get_canonical_library_names<'a>(
         &self,
@@ -1427,6 +3735,78 @@ impl Build {
     /// This will return a result instead of panicking; see [`Self::compile()`] for
     /// the complete description.
     pub fn try_compile(&self, output: &str) -> Result<(), Error> {
+        self.compile_library(output).map(|_| ())
+    }
+
+    /// Run the compiler, generating the file `output`, and return the
+    /// headers each source file depends on.
+    ///
+    /// This behaves like [`Build::try_compile`], but additionally has GCC and
+    /// Clang write dependency files with `-MMD`, which are parsed into
+    /// [`CompileArtifacts::dependencies`]. This complements the
+    /// `cargo:rerun-if-changed` metadata for tools that do their own caching.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let artifacts = cc::Build::new()
+    ///     .file("src/foo.c")
+    ///     .try_compile_with_deps_info("foo")
+    ///     .unwrap();
+    /// for header in artifacts.dependencies.values().flatten() {
+    ///     println!("{}", header.display());
+    /// }
+    /// ```
+    pub fn try_compile_with_deps_info(&self, output: &str) -> Result<CompileArtifacts, Error> {
+        let mut build = self.clone();
+        build.dep_info = true;
+        let (objects, timings) = build.compile_library(output)?;
+
+        let mut artifacts = CompileArtifacts {
+            timings,
+            objects: objects
+                .iter()
+                .map(|obj| obj.dst.clone())
+                .chain(self.objects.iter().map(|obj| obj.to_path_buf()))
+                .collect(),
+            ..CompileArtifacts::default()
+        };
+        if self.time_trace {
+            artifacts.time_traces = objects
+                .iter()
+                .map(|obj| obj.dst.with_extension("json"))
+                .filter(|trace| trace.exists())
+                .collect();
+        }
+        for obj in objects {
+            let contents = match fs::read_to_string(self.get_dep_file(&obj)?) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            // The first prerequisite is the source file itself.
+            let headers = dep_file::parse_dep_file(&contents)
+                .into_iter()
+                .skip(1)
+                .collect();
+            artifacts.dependencies.insert(obj.src, headers);
+        }
+        Ok(artifacts)
+    }
+
+    /// Run the compiler, generating the file `output`, and return the
+    /// headers each source file depends on.
+    ///
+    /// This will panic on errors; see [`Self::try_compile_with_deps_info()`]
+    /// for the complete description.
+    pub fn compile_with_deps_info(&self, output: &str) -> CompileArtifacts {
+        match self.try_compile_with_deps_info(output) {
+            Ok(artifacts) => artifacts,
+            Err(e) => fail(&e.message),
+        }
+    }
+
+    fn compile_library(&self, output: &str) -> Result<(Vec<Object>, Timings), Error> {
         let mut output_components = Path::new(output).components();
         match (output_components.next(), output_components.next()) {
             (Some(Component::Normal(_)), None) => {}
@@ -1440,23 +3820,105 @@ impl Build {
 
         let (lib_name, static_name, dynlib_name) = self.get_canonical_library_names(output)?;
         let dst = self.get_out_dir()?;
+        let module_definition = self.get_module_definition_arg()?;
+        let fuse_ld = self.get_fuse_ld_arg()?;
+        let object_path_lto = self.get_object_path_lto_arg(&dst)?;
+        self.check_apple_frameworks()?;
+
+        let empty = self.files.is_empty()
+            && self.objects.is_empty()
+            && self.binary_blobs.is_empty()
+            && !self.uses_cpp_modules();
+        if empty {
+            match self.allow_empty {
+                Some(true) => {}
+                Some(false) => {
+                    return Err(Error::new(
+                        ErrorKind::NoFiles,
+                        format!(
+                            "no source files or objects were added to build `{}`",
+                            output
+                        ),
+                    ));
+                }
+                None => self.cargo_output.print_warning(&format_args!(
+                    "no source files or objects were added to build `{}`, so it's \
+                     empty; see `Build::allow_empty`",
+                    output
+                )),
+            }
+        }
 
-        let objects = objects_from_files(&self.files, &dst)?;
+        let (objects, timings) = self.compile_sources(&dst)?;
+        let static_cpp_stdlib = self.links_cpp_stdlib_statically()?;
 
-        self.compile_objects(&objects)?;
+        for obj in &self.objects {
+            self.cargo_output
+                .print_metadata(&format_args!("cargo:rerun-if-changed={}", obj.display()));
+        }
 
         if self.link_shared_flag {
-            let objects = objects.iter().map(|o| o.dst.clone()).collect::<Vec<_>>();
+            let objects = objects
+                .iter()
+                .map(|o| o.dst.as_path())
+                .chain(self.objects.iter().map(std::ops::Deref::deref))
+                .collect::<Vec<_>>();
 
-            let mut cmd = self.try_get_compiler()?.to_command();
+            let compiler = self.try_get_compiler()?;
+            let mut cmd = compiler.to_command();
+            for (a, b) in self.env.iter() {
+                cmd.env(a, b);
+            }
             let dynlib_path = dst.join(&dynlib_name);
             cmd.args(["-shared", "-o"]).arg(&dynlib_path).args(&objects);
+            if let Some(fuse_ld) = &fuse_ld {
+                cmd.arg(fuse_ld);
+            }
+            if let Some(object_path_lto) = &object_path_lto {
+                cmd.arg(object_path_lto);
+            }
+            if static_cpp_stdlib {
+                cmd.arg("-static-libstdc++");
+            }
+            for path in &self.framework_paths {
+                let mut arg = OsString::from("-F");
+                arg.push(&**path);
+                cmd.arg(arg);
+            }
+            for framework in &self.apple_frameworks {
+                cmd.arg("-framework").arg(&**framework);
+            }
+            if let Some(def) = &module_definition {
+                if compiler.is_like_msvc() {
+                    // Linker options come last with `cl`.
+                    cmd.arg("/link").arg(def);
+                } else if self.get_target()?.env == "msvc" {
+                    // Clang passes `/DEF:` on to `link.exe` or `lld-link`.
+                    let mut arg = OsString::from("-Wl,");
+                    arg.push(def);
+                    cmd.arg(arg);
+                } else {
+                    cmd.arg(def);
+                }
+            }
+            if let Some(arg) = self.get_export_all_symbols_arg(lib_name, &objects)? {
+                if compiler.is_like_msvc() {
+                    cmd.arg("/link").arg(arg);
+                } else {
+                    let mut linker_arg = OsString::from("-Wl,");
+                    linker_arg.push(arg);
+                    cmd.arg(linker_arg);
+                }
+            }
             run(&mut cmd, &self.cargo_output)?;
+            self.strip_library(&dynlib_path)?;
             if let Some(out_dir) = &self.shared_lib_out_dir {
                 fs::copy(dynlib_path, out_dir.join(&dynlib_name))?;
             }
         } else {
-            self.assemble(lib_name, &dst.join(static_name), &objects)?;
+            let static_path = dst.join(static_name);
+            self.assemble(lib_name, &static_path, &objects)?;
+            self.strip_library(&static_path)?;
         }
 
         let target = self.get_target()?;
@@ -1481,37 +3943,81 @@ impl Build {
             }
         }
 
-        if self.link_lib_modifiers.is_empty() {
-            if self.link_shared_flag {
-                self.cargo_output
-                    .print_metadata(&format_args!("cargo:rustc-link-lib=dylib={}", lib_name));
+        // An empty library that's explicitly allowed isn't linked at all.
+        if !(empty && self.allow_empty == Some(true)) {
+            if self.link_lib_modifiers.is_empty() {
+                if self.link_shared_flag {
+                    self.cargo_output
+                        .print_metadata(&format_args!("cargo:rustc-link-lib=dylib={}", lib_name));
+                } else {
+                    self.cargo_output
+                        .print_metadata(&format_args!("cargo:rustc-link-lib=static={}", lib_name));
+                }
             } else {
+                self.cargo_output.print_metadata(&format_args!(
+                    "cargo:rustc-link-lib=static:{}={}",
+                    JoinOsStrs {
+                        slice: &self.link_lib_modifiers,
+                        delimiter: ','
+                    },
+                    lib_name
+                ));
+            }
+            self.cargo_output.print_metadata(&format_args!(
+                "cargo:rustc-link-search=native={}",
+                dst.display()
+            ));
+        }
+
+        if !self.link_shared_flag {
+            if let Some(def) = &module_definition {
+                self.cargo_output.print_metadata(&format_args!(
+                    "cargo:rustc-link-arg={}",
+                    Path::new(def).display()
+                ));
+            }
+            if let Some(fuse_ld) = &fuse_ld {
                 self.cargo_output
-                    .print_metadata(&format_args!("cargo:rustc-link-lib=static={}", lib_name));
+                    .print_metadata(&format_args!("cargo:rustc-link-arg={}", fuse_ld));
             }
-        } else {
+            if let Some(object_path_lto) = &object_path_lto {
+                self.cargo_output
+                    .print_metadata(&format_args!("cargo:rustc-link-arg={}", object_path_lto));
+            }
+        }
+        for (directive, link_arg) in &self.link_args {
             self.cargo_output.print_metadata(&format_args!(
-                "cargo:rustc-link-lib=static:{}={}",
-                JoinOsStrs {
-                    slice: &self.link_lib_modifiers,
-                    delimiter: ','
-                },
-                lib_name
+                "cargo:{}={}",
+                directive,
+                Path::new(link_arg).display()
+            ));
+        }
+        for path in &self.framework_paths {
+            self.cargo_output.print_metadata(&format_args!(
+                "cargo:rustc-link-search=framework={}",
+                path.display()
+            ));
+        }
+        for framework in &self.apple_frameworks {
+            self.cargo_output.print_metadata(&format_args!(
+                "cargo:rustc-link-lib=framework={}",
+                framework
             ));
         }
-        self.cargo_output.print_metadata(&format_args!(
-            "cargo:rustc-link-search=native={}",
-            dst.display()
-        ));
 
         // Add specific C++ libraries, if enabled.
         if self.cpp {
             if let Some(stdlib) = self.get_cpp_link_stdlib()? {
-                self.cargo_output
-                    .print_metadata(&format_args!("cargo:rustc-link-lib={}", stdlib.display()));
+                if static_cpp_stdlib {
+                    self.print_static_cpp_stdlib(&stdlib)?;
+                } else {
+                    self.cargo_output
+                        .print_metadata(&format_args!("cargo:rustc-link-lib={}", stdlib.display()));
+                }
             }
-            // Link c++ lib from WASI sysroot
-            if target.arch == "wasm32" {
+            // Link c++ lib from WASI sysroot, unless the C++ runtime was
+            // configured explicitly.
+            if target.arch == "wasm32" && self.cpp_link_stdlib.is_none() {
                 if target.os == "wasi" {
                     if let Ok(wasi_sysroot) = self.wasi_sysroot() {
                         self.cargo_output.print_metadata(&format_args!(
@@ -1580,7 +4086,7 @@ impl Build {
             }
         }
 
-        Ok(())
+        Ok((objects, timings))
     }
 
     /// Run the compiler, generating the file `output`
@@ -1626,33 +4132,281 @@ impl Build {
         }
     }
 
-    /// Run the compiler, generating intermediate files, but without linking
-    /// them into an archive file.
-    ///
-    /// This will return a list of compiled object files, in the same order
-    /// as they were passed in as `file`/`files` methods.
-    pub fn compile_intermediates(&self) -> Vec<PathBuf> {
-        match self.try_compile_intermediates() {
-            Ok(v) => v,
-            Err(e) => fail(&e.message),
+    /// Run the compiler, generating intermediate files, but without linking
+    /// them into an archive file.
+    ///
+    /// This will return a list of compiled object files, in the same order
+    /// as they were passed in as `file`/`files` methods.
+    ///
+    /// The files are compiled the same way as with [`Build::compile`]
+    /// (including in parallel, if the `parallel` feature is enabled), but
+    /// neither the archiver nor any `rustc-link-*` metadata is emitted, which
+    /// is useful if you link the objects yourself.
+    pub fn compile_intermediates(&self) -> Vec<PathBuf> {
+        match self.try_compile_intermediates() {
+            Ok(v) => v,
+            Err(e) => fail(&e.message),
+        }
+    }
+
+    /// Run the compiler, generating intermediate files, but without linking
+    /// them into an archive file.
+    ///
+    /// This will return a result instead of panicking; see `compile_intermediates()` for the complete description.
+    pub fn try_compile_intermediates(&self) -> Result<Vec<PathBuf>, Error> {
+        let dst = self.get_out_dir()?;
+        let (objects, _) = self.compile_sources(&dst)?;
+
+        Ok(objects.into_iter().map(|v| v.dst).collect())
+    }
+
+    /// Compile all source files in `dst`.
+    ///
+    /// C++ module interface units are compiled first, one at a time as they
+    /// may import each other, followed by all other files.
+    fn compile_sources(&self, dst: &Path) -> Result<(Vec<Object>, Timings), Error> {
+        if self.time_trace {
+            let compiler = self.try_get_compiler()?;
+            if !compiler.is_like_clang() {
+                self.cargo_output.print_warning(&format_args!(
+                    "time_trace is enabled, but the {:?} compiler does not support -ftime-trace, ignored",
+                    compiler.family
+                ));
+            }
+        }
+        let mut files = self.get_unity_files(dst)?;
+        let (blob_sources, blob_objects) = self.prepare_binary_blobs(dst)?;
+        if !blob_sources.is_empty() {
+            files.to_mut().extend(blob_sources);
+        }
+        self.compile_precompiled_header(dst, &files)?;
+        self.warn_about_rtti_usage();
+        if !self.uses_cpp_modules() {
+            let mut objects = objects_from_files(&files, dst, self.object_naming)?;
+            let timings = self.compile_objects(&objects)?;
+            objects.extend(blob_objects);
+            return Ok((objects, self.finish_timings(timings)));
+        }
+
+        if !self.cpp {
+            return Err(Error::new(
+                ErrorKind::InvalidArgument,
+                "C++ modules can only be compiled with `Build::cpp(true)`",
+            ));
+        }
+        let bmi_dir = dst.join("cpp-modules");
+        fs::create_dir_all(&bmi_dir)?;
+        if self.try_get_compiler()?.family == ToolFamily::Gnu {
+            let names = self
+                .cpp_module_interfaces
+                .iter()
+                .map(|src| cpp_modules::read_module_name(src))
+                .collect::<Result<Vec<_>, _>>()?;
+            cpp_modules::write_module_mapper(&bmi_dir, &names)?;
+        }
+
+        let mut timings = Vec::new();
+        let std_module = if self.cpp_std_module {
+            Some(self.compile_std_module(dst, &mut timings)?)
+        } else {
+            None
+        };
+
+        let files = self
+            .cpp_module_interfaces
+            .iter()
+            .chain(files.iter())
+            .chain(&self.cpp_module_impls)
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut objects = objects_from_files(&files, dst, self.object_naming)?;
+        let (interfaces, others) = objects.split_at(self.cpp_module_interfaces.len());
+
+        for interface in interfaces {
+            timings.extend(self.compile_objects(std::slice::from_ref(interface))?);
+        }
+        timings.extend(self.compile_objects(others)?);
+
+        objects.splice(0..0, std_module);
+        objects.extend(blob_objects);
+        Ok((objects, self.finish_timings(timings)))
+    }
+
+    /// Write the C sources of the binary blobs into `dst`, and wrap their
+    /// data into objects with `objcopy` where it knows the target's format.
+    fn prepare_binary_blobs(&self, dst: &Path) -> Result<(Vec<Arc<Path>>, Vec<Object>), Error> {
+        let mut sources = Vec::new();
+        let mut objects = Vec::new();
+        if self.binary_blobs.is_empty() {
+            return Ok((sources, objects));
+        }
+        let target = self.get_target()?;
+        let msvc = self.try_get_compiler()?.is_like_msvc();
+        // MSVC has no `objcopy`, so the data is always compiled from C there.
+        let format = if msvc { None } else { target.objcopy_format() };
+        let cwd = env::current_dir()?;
+
+        for blob in &self.binary_blobs {
+            blob.check()?;
+            self.cargo_output.print_metadata(&format_args!(
+                "cargo:rerun-if-changed={}",
+                blob.path.display()
+            ));
+            let path = cwd.join(&blob.path);
+            let source = match format {
+                Some(format) => {
+                    let obj = dst.join(format!("blob-{}-data.o", blob.symbol));
+                    let (mut cmd, _) = self.get_base_archiver_variant("OBJCOPY", "objcopy")?;
+                    // `objcopy` names the symbols after the path of the
+                    // blob, so it's run next to it.
+                    cmd.args(blob.objcopy_args(format, &obj)?)
+                        .current_dir(path.parent().unwrap_or(&cwd));
+                    run(&mut cmd, &self.cargo_output)?;
+                    objects.push(Object::new(path.clone(), obj));
+                    blob.c_source(fs::metadata(&path)?.len(), None, msvc)
+                }
+                None => {
+                    let data = fs::read(&path)?;
+                    blob.c_source(data.len() as u64, Some(&data), msvc)
+                }
+            };
+            let src = dst.join(format!("blob-{}.c", blob.symbol));
+            // Keep the source untouched when it's unchanged, for the
+            // incremental builds.
+            if fs::read_to_string(&src).ok().as_deref() != Some(&*source) {
+                fs::write(&src, source)?;
+            }
+            sources.push(src.into());
         }
+        Ok((sources, objects))
     }
 
-    /// Run the compiler, generating intermediate files, but without linking
-    /// them into an archive file.
-    ///
-    /// This will return a result instead of panicking; see `compile_intermediates()` for the complete description.
-    pub fn try_compile_intermediates(&self) -> Result<Vec<PathBuf>, Error> {
-        let dst = self.get_out_dir()?;
-        let objects = objects_from_files(&self.files, &dst)?;
+    /// Warn about the first use of RTTI in each source, if it's disabled and
+    /// [`Build::lint_rtti_usage`] is enabled.
+    fn warn_about_rtti_usage(&self) {
+        if !(self.cpp && self.cpp_rtti == Some(false) && self.lint_rtti_usage) {
+            return;
+        }
+        // The sources as given, rather than the unity sources including them.
+        let sources = self.files.iter().chain(&self.cpp_module_interfaces);
+        for file in sources.chain(&self.cpp_module_impls) {
+            // Unreadable sources are left to the compiler to report.
+            let source = match fs::read_to_string(file) {
+                Ok(source) => source,
+                Err(_) => continue,
+            };
+            if let Some((line, operator)) = rtti::find_rtti_usage(&source) {
+                self.cargo_output.print_warning(&format_args!(
+                    "{}:{}: `{}` needs RTTI, which is disabled with `Build::cpp_rtti(false)`",
+                    file.display(),
+                    line,
+                    operator
+                ));
+            }
+        }
+    }
 
-        self.compile_objects(&objects)?;
+    /// Compile libc++'s `std` module into `dst`, as found through the
+    /// `libc++.modules.json` metadata next to the library.
+    fn compile_std_module(&self, dst: &Path, timings: &mut Timings) -> Result<Object, Error> {
+        let compiler = self.try_get_compiler()?;
+        if !matches!(compiler.family, ToolFamily::Clang { .. }) {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFeature,
+                "the C++ `std` module can only be built with Clang and libc++",
+            ));
+        }
 
-        Ok(objects.into_iter().map(|v| v.dst).collect())
+        let mut cmd = compiler.to_command();
+        for (a, b) in self.env.iter() {
+            cmd.env(a, b);
+        }
+        cmd.arg("-print-file-name=libc++.modules.json");
+        let output = run_output(&mut cmd, &self.cargo_output)?;
+        let metadata = PathBuf::from(String::from_utf8_lossy(&output).trim());
+        // Clang prints the name as it is if it can't find the file.
+        if !metadata.is_absolute() {
+            return Err(Error::new(
+                ErrorKind::ToolNotFound,
+                "failed to find `libc++.modules.json` for the C++ `std` module; \
+                 is libc++ used with `Build::cpp_set_stdlib(\"c++\")`?",
+            ));
+        }
+        let std_module =
+            cpp_modules::parse_libcxx_modules(&metadata, &fs::read_to_string(&metadata)?).map_err(
+                |e| {
+                    Error::new(
+                        ErrorKind::InvalidArgument,
+                        format!("invalid {}: {}", metadata.display(), e),
+                    )
+                },
+            )?;
+
+        // The module's sources include the libc++ sources, and use a name
+        // that is reserved for it.
+        let mut build = self.clone();
+        build.cpp_std_module = false;
+        build.cpp_module_interfaces = vec![std_module.source.as_path().into()];
+        build.system_include_directories.extend(
+            std_module
+                .system_include_directories
+                .iter()
+                .map(|d| d.as_path().into()),
+        );
+        build
+            .flags
+            .push(OsStr::new("-Wno-reserved-module-identifier").into());
+
+        let object =
+            objects_from_files(&build.cpp_module_interfaces, dst, self.object_naming)?.remove(0);
+        timings.extend(build.compile_objects(std::slice::from_ref(&object))?);
+        Ok(object)
+    }
+
+    /// Compile the header configured with [`Build::precompiled_header`], if
+    /// any, for the sources `files`.
+    fn compile_precompiled_header(&self, dst: &Path, files: &[Arc<Path>]) -> Result<(), Error> {
+        let header = match &self.precompiled_header {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        let language = Language::of_header(header, self.cpp);
+        let sources = files
+            .iter()
+            .chain(&self.cpp_module_interfaces)
+            .chain(&self.cpp_module_impls)
+            .map(|src| &**src)
+            .collect::<Vec<_>>();
+        precompiled_header::check_sources(language, &sources)?;
+
+        let compiler = self.try_get_compiler()?;
+        if self.cuda {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFeature,
+                "precompiled headers are not supported with CUDA",
+            ));
+        }
+        let pch = precompiled_header::pch_path(compiler.family, &dst.join("pch"), header)?;
+        fs::create_dir_all(dst.join("pch"))?;
+        let mut cmd = compiler.to_command();
+        for (a, b) in self.env.iter() {
+            cmd.env(a, b);
+        }
+        cmd.args(precompiled_header::compile_flags(language, header, &pch));
+        run(&mut cmd, &self.cargo_output)?;
+        self.cargo_output
+            .print_metadata(&format_args!("cargo:rerun-if-changed={}", header.display()));
+        Ok(())
+    }
+
+    fn uses_cpp_modules(&self) -> bool {
+        !self.cpp_module_interfaces.is_empty()
+            || !self.cpp_module_impls.is_empty()
+            || self.cpp_std_module
     }
 
     #[cfg(feature = "parallel")]
-    fn compile_objects(&self, objs: &[Object]) -> Result<(), Error> {
+    fn compile_objects(&self, objs: &[Object]) -> Result<Timings, Error> {
         use std::cell::Cell;
 
         use parallel::async_executor::{block_on, YieldOnce};
@@ -1660,16 +4414,31 @@ impl Build {
         check_disabled()?;
 
         if objs.len() <= 1 {
+            let mut timings = Vec::new();
             for obj in objs {
                 let mut cmd = self.create_compile_object_cmd(obj)?;
-                run(&mut cmd, &self.cargo_output)?;
+                let start = Instant::now();
+                let (output, diagnostics) = self.compile_output();
+                run(&mut cmd, &output)
+                    .or_else(|e| self.retry_without_lto(obj, &cmd, diagnostics.as_deref(), e))?;
+                self.record_timing(&mut timings, obj, start);
             }
 
-            return Ok(());
+            return Ok(timings);
         }
 
-        // Limit our parallelism globally with a jobserver.
-        let mut tokens = parallel::job_token::ActiveJobTokenServer::new();
+        // Limit our parallelism globally with a jobserver, unless one has
+        // been configured explicitly.
+        let explicit_jobserver;
+        let jobserver = match &self.jobserver {
+            Some(client) => {
+                explicit_jobserver =
+                    parallel::job_token::JobTokenServer::from_client(client.clone());
+                &explicit_jobserver
+            }
+            None => parallel::job_token::JobTokenServer::global(),
+        };
+        let mut tokens = parallel::job_token::ActiveJobTokenServer::new(jobserver);
 
         // When compiling objects in parallel we do a few dirty tricks to speed
         // things up:
@@ -1680,8 +4449,8 @@ impl Build {
         //   coordinated across C compilations and Rust compilations. Before we
         //   compile anything we make sure to wait until we acquire a token.
         //
-        //   Note that this jobserver is cached globally so we only used one per
-        //   process and only worry about creating it once.
+        //   Note that the jobserver from the environment is cached globally so
+        //   we only used one per process and only worry about creating it once.
         //
         // * Next we use spawn the process to actually compile objects in
         //   parallel after we've acquired a token to perform some work
@@ -1690,8 +4459,15 @@ impl Build {
         // acquire the appropriate tokens, Once all objects have been compiled
         // we wait on all the processes and propagate the results of compilation.
 
-        let pendings =
-            Cell::new(Vec::<(Command, KillOnDrop, parallel::job_token::JobToken)>::new());
+        let pendings = Cell::new(Vec::<(
+            Command,
+            KillOnDrop,
+            parallel::job_token::JobToken,
+            &Object,
+            Instant,
+            Option<Arc<Mutex<Vec<u8>>>>,
+        )>::new());
+        let timings = Cell::new(Vec::new());
         let is_disconnected = Cell::new(false);
         let has_made_progress = Cell::new(false);
 
@@ -1712,11 +4488,21 @@ impl Build {
 
                 cell_update(&pendings, |mut pendings| {
                     // Try waiting on them.
-                    pendings.retain_mut(|(cmd, child, _token)| {
-                        match try_wait_on_child(cmd, &mut child.0, &mut stdout, &mut child.1) {
+                    pendings.retain_mut(|(cmd, child, _token, obj, start, diagnostics)| {
+                        let result =
+                            try_wait_on_child(cmd, &mut child.0, &mut stdout, &mut child.1)
+                                .or_else(|e| {
+                                    self.retry_without_lto(obj, cmd, diagnostics.as_deref(), e)
+                                        .map(Some)
+                                });
+                        match result {
                             Ok(Some(())) => {
                                 // Task done, remove the entry
                                 has_made_progress.set(true);
+                                cell_update(&timings, |mut timings| {
+                                    self.record_timing(&mut timings, obj, *start);
+                                    timings
+                                });
                                 false
                             }
                             Ok(None) => true, // Task still not finished, keep the entry
@@ -1754,12 +4540,21 @@ impl Build {
             for obj in objs {
                 let mut cmd = self.create_compile_object_cmd(obj)?;
                 let token = tokens.acquire().await?;
-                let mut child = spawn(&mut cmd, &self.cargo_output)?;
-                let mut stderr_forwarder = StderrForwarder::new(&mut child);
+                let (output, diagnostics) = self.compile_output();
+                let mut child = spawn(&mut cmd, &output)?;
+                let mut stderr_forwarder = StderrForwarder::new(&mut child, &output);
                 stderr_forwarder.set_non_blocking()?;
 
+                let start = Instant::now();
                 cell_update(&pendings, |mut pendings| {
-                    pendings.push((cmd, KillOnDrop(child, stderr_forwarder), token));
+                    pendings.push((
+                        cmd,
+                        KillOnDrop(child, stderr_forwarder),
+                        token,
+                        obj,
+                        start,
+                        diagnostics,
+                    ));
                     pendings
                 });
 
@@ -1770,7 +4565,8 @@ impl Build {
             Ok::<_, Error>(())
         };
 
-        return block_on(wait_future, spawn_future, &has_made_progress);
+        block_on(wait_future, spawn_future, &has_made_progress)?;
+        return Ok(timings.take());
 
         struct KillOnDrop(Child, StderrForwarder);
 
@@ -1794,15 +4590,77 @@ impl Build {
     }
 
     #[cfg(not(feature = "parallel"))]
-    fn compile_objects(&self, objs: &[Object]) -> Result<(), Error> {
+    fn compile_objects(&self, objs: &[Object]) -> Result<Timings, Error> {
         check_disabled()?;
 
+        let mut timings = Vec::new();
         for obj in objs {
             let mut cmd = self.create_compile_object_cmd(obj)?;
-            run(&mut cmd, &self.cargo_output)?;
+            let start = Instant::now();
+            let (output, diagnostics) = self.compile_output();
+            run(&mut cmd, &output)
+                .or_else(|e| self.retry_without_lto(obj, &cmd, diagnostics.as_deref(), e))?;
+            self.record_timing(&mut timings, obj, start);
         }
 
-        Ok(())
+        Ok(timings)
+    }
+
+    /// How to run the compiler for an object, which keeps a copy of its
+    /// diagnostics to recognize LTO failures if `lto_fallback` is enabled.
+    fn compile_output(&self) -> (CargoOutput, Option<Arc<Mutex<Vec<u8>>>>) {
+        if self.lto_fallback {
+            let (output, diagnostics) = self.cargo_output.capturing_diagnostics();
+            (output, Some(diagnostics))
+        } else {
+            (self.cargo_output.clone(), None)
+        }
+    }
+
+    /// Compile `obj` again without LTO if compiling it with `cmd` failed
+    /// with `error` because of LTO, according to the `diagnostics`, and
+    /// otherwise return the error.
+    fn retry_without_lto(
+        &self,
+        obj: &Object,
+        cmd: &Command,
+        diagnostics: Option<&Mutex<Vec<u8>>>,
+        error: Error,
+    ) -> Result<(), Error> {
+        let is_lto_failure = diagnostics.map_or(false, |diagnostics| {
+            let diagnostics = diagnostics.lock().unwrap_or_else(|e| e.into_inner());
+            is_lto_failure(&String::from_utf8_lossy(&diagnostics))
+        });
+        let mut retry = match command_without_lto(cmd) {
+            Some(retry) if is_lto_failure && matches!(error.kind, ErrorKind::ToolExecError) => {
+                retry
+            }
+            _ => return Err(error),
+        };
+        self.cargo_output.print_warning(&format_args!(
+            "compiling {} failed with LTO, compiling it again without LTO",
+            obj.src.display()
+        ));
+        run(&mut retry, &self.cargo_output)
+    }
+
+    fn record_timing(&self, timings: &mut Timings, obj: &Object, start: Instant) {
+        if self.report_timings {
+            timings.push((obj.src.clone(), start.elapsed()));
+        }
+    }
+
+    /// Sort the timings, slowest first, and report the slowest files.
+    fn finish_timings(&self, mut timings: Timings) -> Timings {
+        timings.sort_by(|(_, a), (_, b)| b.cmp(a));
+        for (src, duration) in timings.iter().take(5) {
+            self.cargo_output.print_warning(&format_args!(
+                "compiling {} took {:.2}s",
+                src.display(),
+                duration.as_secs_f64()
+            ));
+        }
+        timings
     }
 
     fn create_compile_object_cmd(&self, obj: &Object) -> Result<Command, Error> {
@@ -1840,11 +4698,56 @@ impl Build {
         if !is_assembler_msvc || !is_arm {
             cmd.arg("-c");
         }
-        if self.cuda && self.cuda_file_count() > 1 {
-            cmd.arg("--device-c");
+        if self.dep_info && !self.cuda && (compiler.is_like_gnu() || compiler.is_like_clang()) {
+            let dep_file = self.get_dep_file(obj)?;
+            if let Some(parent) = dep_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            cmd.arg("-MMD").arg("-MF").arg(dep_file);
+        }
+        // Clang names the trace after the object file.
+        if self.time_trace && !self.cuda && compiler.is_like_clang() {
+            cmd.arg("-ftime-trace");
+        }
+        if self.cuda {
+            match self.cuda_relocatable {
+                Some(true) => {
+                    cmd.arg("-rdc=true");
+                }
+                Some(false) => {}
+                None if self.cuda_file_count() > 1 => {
+                    cmd.arg("--device-c");
+                }
+                None => {}
+            }
         }
         if is_asm {
             cmd.args(self.asm_flags.iter().map(std::ops::Deref::deref));
+            if !self.cuda && !compiler.is_like_msvc() {
+                if let Some(language) = asm_ext.and_then(AsmFileExt::gnu_language) {
+                    cmd.arg("-x").arg(language);
+                }
+            }
+        } else if self.uses_cpp_modules() && !self.cuda {
+            let interface = if self.cpp_module_interfaces.iter().any(|i| **i == *obj.src) {
+                Some(cpp_modules::read_module_name(&obj.src)?)
+            } else {
+                None
+            };
+            let bmi_dir = self.get_out_dir()?.join("cpp-modules");
+            cmd.args(cpp_modules::module_flags(
+                compiler.family,
+                &bmi_dir,
+                interface.as_deref(),
+            )?);
+        }
+
+        if let Some(header) = &self.precompiled_header {
+            if Language::of_source(&obj.src) == Some(Language::of_header(header, self.cpp)) {
+                let dir = self.get_out_dir()?.join("pch");
+                let pch = precompiled_header::pch_path(compiler.family, &dir, header)?;
+                cmd.args(precompiled_header::use_flags(compiler.family, &pch));
+            }
         }
 
         if compiler.supports_path_delimiter() && !is_assembler_msvc {
@@ -1860,6 +4763,26 @@ impl Build {
             self.fix_env_for_apple_os(&mut cmd)?;
         }
 
+        // NVCC and the MSVC assemblers don't read `@path`.
+        if !is_assembler_msvc
+            && !self.cuda
+            && response_file::is_too_long(&cmd, self.command_length_limit)
+        {
+            let encoding = self
+                .response_file_encoding
+                .unwrap_or_else(|| response_file::default_encoding(compiler.family));
+            // The compiler path passed to a wrapper stays on the command line.
+            let wrapper_args =
+                compiler.cc_wrapper_args.len() + usize::from(compiler.cc_wrapper_path.is_some());
+            cmd = response_file::use_response_file(
+                cmd,
+                wrapper_args,
+                &obj.dst.with_extension("rsp"),
+                compiler.family,
+                encoding,
+            )?;
+        }
+
         Ok(cmd)
     }
 
@@ -1917,6 +4840,116 @@ impl Build {
         }
     }
 
+    /// Get the complete command line that would be used to compile `file`,
+    /// starting with the compiler itself, without running it.
+    ///
+    /// Unlike [`get_compiler`](Self::get_compiler), this includes everything
+    /// added for the specific file, such as the `-c` flag, the output object
+    /// in the output directory and the file itself, which is e.g. useful to
+    /// write a `compile_commands.json`. Arguments that are not valid UTF-8 are
+    /// converted lossily.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// let argv = cc::Build::new()
+    ///     .include("include")
+    ///     .define("FOO", "1")
+    ///     .get_compiler_command(Path::new("src/foo.c"))
+    ///     .unwrap();
+    /// println!("{}", argv.join(" "));
+    /// ```
+    pub fn get_compiler_command(&self, file: &Path) -> Result<Vec<String>, Error> {
+        let dst = self.get_out_dir()?;
+        let objects = objects_from_files(&[file.into()], &dst, self.object_naming)?;
+        let cmd = self.create_compile_object_cmd(&objects[0])?;
+
+        Ok(std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    /// Get the architecture of the target as LLVM spells it, e.g. `arm64`
+    /// for `aarch64-apple-darwin` or `riscv64` for
+    /// `riscv64gc-unknown-linux-gnu`.
+    ///
+    /// This is the arch that starts the `--target` triple passed to Clang, and
+    /// is useful to build other Clang commands for the same target.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let arch = cc::Build::new()
+    ///     .target("aarch64-apple-darwin")
+    ///     .try_get_llvm_arch()
+    ///     .unwrap();
+    /// assert_eq!(arch, "arm64");
+    /// ```
+    pub fn try_get_llvm_arch(&self) -> Result<String, Error> {
+        Ok(self.get_target()?.llvm_arch().to_string())
+    }
+
+    /// Check whether the compiler in use is of the given family and at least
+    /// the given version.
+    ///
+    /// The version is read from the banner printed by the compiler, e.g. by
+    /// `gcc --version`, so this runs the compiler once. Note that the
+    /// versions of Apple's Clang differ from those of upstream Clang.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cc::CompilerFamily;
+    ///
+    /// let mut build = cc::Build::new();
+    /// build.file("src/foo.c");
+    /// if build.compiler_at_least(CompilerFamily::Clang, 14, 0).unwrap() {
+    ///     build.flag("-flto=thin");
+    /// }
+    /// build.compile("foo");
+    /// ```
+    pub fn compiler_at_least(
+        &self,
+        family: CompilerFamily,
+        major: u32,
+        minor: u32,
+    ) -> Result<bool, Error> {
+        let compiler = self.get_base_compiler()?;
+        if compiler.family.compiler_family() != family {
+            return Ok(false);
+        }
+
+        let mut cmd = Command::new(&compiler.path);
+        for (a, b) in compiler.env.iter() {
+            cmd.env(a, b);
+        }
+        for (a, b) in self.env.iter() {
+            cmd.env(a, b);
+        }
+        let banner = if compiler.family == (ToolFamily::Msvc { clang_cl: false }) {
+            // `cl.exe` prints its banner to stderr when run without arguments.
+            let output = cmd.stdin(Stdio::null()).output()?;
+            String::from_utf8_lossy(&output.stderr).into_owned()
+        } else {
+            let stdout = run_output(cmd.arg("--version"), &self.cargo_output)?;
+            String::from_utf8_lossy(&stdout).into_owned()
+        };
+        match tool::parse_version_banner(compiler.family, &banner) {
+            Some(version) => Ok(version >= (major, minor)),
+            None => Err(Error::new(
+                ErrorKind::ToolExecError,
+                format!(
+                    "failed to find the version of {} in its banner: {:?}",
+                    compiler.path.display(),
+                    banner
+                ),
+            )),
+        }
+    }
+
     /// Get the compiler that's in use for this configuration.
     ///
     /// This function will return a `Tool` which represents the culmination
@@ -1947,6 +4980,9 @@ impl Build {
     /// This will return a result instead of panicking; see
     /// [`get_compiler()`](Self::get_compiler) for the complete description.
     pub fn try_get_compiler(&self) -> Result<Tool, Error> {
+        if let Some(e) = &self.include_relative_error {
+            return Err(e.clone());
+        }
         let opt_level = self.get_opt_level()?;
         let target = self.get_target()?;
 
@@ -1957,12 +4993,13 @@ impl Build {
         //   - Controlled by `cc-rs`.
         // 2. `rustc`-inherited flags
         //   - Controlled by `rustc`.
-        // 3. Builder flags
-        //   - Controlled by the developer using `cc-rs` in e.g. their `build.rs`.
-        // 4. Environment flags
+        // 3. Environment flags
         //   - Controlled by the end user.
+        // 4. Builder flags and defines
+        //   - Controlled by the developer using `cc-rs` in e.g. their `build.rs`.
         //
-        // This is important to allow later flags to override previous ones.
+        // This is important to allow later flags to override previous ones, and
+        // explicit configuration to override the environment.
 
         // Copied from <https://github.com/rust-lang/rust/blob/5db81020006d2920fc9c62ffc0f4322f90bffa04/compiler/rustc_codegen_ssa/src/back/linker.rs#L27-L38>
         //
@@ -1977,6 +5014,10 @@ impl Build {
             cmd.env.push(("LC_ALL".into(), "C".into()));
         }
 
+        if self.reproducible {
+            self.add_reproducible_flags(&mut cmd)?;
+        }
+
         // Disable default flag generation via `no_default_flags` or environment variable
         let no_defaults = self.no_default_flags || self.getenv_boolean("CRATE_CC_NO_DEFAULTS");
         if !no_defaults {
@@ -1985,17 +5026,93 @@ impl Build {
 
         // Specify various flags that are not considered part of the default flags above.
         // FIXME(madsmtm): Should these be considered part of the defaults? If no, why not?
-        if let Some(ref std) = self.std {
+        let default_std = if self.uses_cpp_modules() {
+            Some("c++20")
+        } else {
+            None
+        };
+        if let Some(std) = self.std.as_deref().or(default_std) {
             let separator = match cmd.family {
                 ToolFamily::Msvc { .. } => ':',
                 ToolFamily::Gnu | ToolFamily::Clang { .. } => '=',
             };
             cmd.push_cc_arg(format!("-std{}{}", separator, std).into());
         }
+        if let Some(charset) = &self.source_charset {
+            let flag = match cmd.family {
+                ToolFamily::Msvc { .. } => "-source-charset:",
+                ToolFamily::Gnu | ToolFamily::Clang { .. } => "-finput-charset=",
+            };
+            cmd.push_cc_arg(format!("{}{}", flag, charset).into());
+        }
+        if let (true, Some(rtti)) = (self.cpp, self.cpp_rtti) {
+            let flag = match (cmd.family, rtti) {
+                (ToolFamily::Msvc { .. }, true) => "-GR",
+                (ToolFamily::Msvc { .. }, false) => "-GR-",
+                (ToolFamily::Gnu | ToolFamily::Clang { .. }, true) => "-frtti",
+                (ToolFamily::Gnu | ToolFamily::Clang { .. }, false) => "-fno-rtti",
+            };
+            cmd.push_cc_arg(flag.into());
+        }
+        if self.x86_stack_realign && target.needs_stack_realign() {
+            if cmd.is_like_msvc() {
+                if !self
+                    .build_cache
+                    .x86_stack_realign_unsupported_warned
+                    .swap(true, Relaxed)
+                {
+                    self.cargo_output.print_warning(&format_args!(
+                        "x86_stack_realign is specified, but the {:?} compiler does not support -mstackrealign, ignored",
+                        cmd.family
+                    ));
+                }
+            } else {
+                cmd.push_cc_arg("-mstackrealign".into());
+            }
+        }
+        if let Some(charset) = &self.execution_charset {
+            let flag = match cmd.family {
+                ToolFamily::Msvc { .. } => "-execution-charset:",
+                ToolFamily::Gnu | ToolFamily::Clang { .. } => "-fexec-charset=",
+            };
+            cmd.push_cc_arg(format!("{}{}", flag, charset).into());
+        }
+        if let ToolFamily::Clang { .. } = cmd.family {
+            let addrsig = match self.addrsig {
+                None if !no_defaults && self.has_old_gnu_archiver(&target) => Some(false),
+                addrsig => addrsig,
+            };
+            match addrsig {
+                Some(true) => cmd.push_cc_arg("-faddrsig".into()),
+                Some(false) => cmd.push_cc_arg("-fno-addrsig".into()),
+                None => {}
+            }
+        }
         for directory in self.include_directories.iter() {
             cmd.args.push("-I".into());
             cmd.args.push(directory.as_os_str().into());
         }
+        if target.vendor == "apple" {
+            for path in self.framework_paths.iter() {
+                let mut arg = OsString::from("-F");
+                arg.push(&**path);
+                cmd.args.push(arg);
+            }
+        }
+        if !self.system_include_directories.is_empty() {
+            let flag = if cmd.is_like_msvc() {
+                // Headers from `/external:I` only have their warnings
+                // suppressed with an explicit `/external:W` level.
+                cmd.args.push("-external:W0".into());
+                "-external:I"
+            } else {
+                "-isystem"
+            };
+            for directory in self.system_include_directories.iter() {
+                cmd.args.push(flag.into());
+                cmd.args.push(directory.as_os_str().into());
+            }
+        }
         if self.warnings_into_errors {
             let warnings_to_errors_flag = cmd.family.warnings_to_errors_flag().into();
             cmd.push_cc_arg(warnings_to_errors_flag);
@@ -2006,23 +5123,124 @@ impl Build {
         // CFLAGS/CXXFLAGS, since those variables presumably already contain
         // the desired set of warnings flags.
         let envflags = self.envflags(if self.cpp { "CXXFLAGS" } else { "CFLAGS" })?;
-        if self.warnings.unwrap_or(envflags.is_none()) {
-            let wflags = cmd.family.warnings_flags().into();
-            cmd.push_cc_arg(wflags);
+        if let Some(level) = self.warning_level {
+            for wflags in cmd.family.warning_level_flags(level) {
+                cmd.push_cc_arg((*wflags).into());
+            }
+        } else {
+            if self.warnings.unwrap_or(envflags.is_none()) {
+                let wflags = cmd.family.warnings_flags().into();
+                cmd.push_cc_arg(wflags);
+            }
+            if self.extra_warnings.unwrap_or(envflags.is_none()) {
+                if let Some(wflags) = cmd.family.extra_warnings_flags() {
+                    cmd.push_cc_arg(wflags.into());
+                }
+            }
+        }
+        for warning in self.suppressed_warnings.iter() {
+            check_warning_name(warning, "suppress")?;
+            if let Some(flag) = cmd.family.suppress_warning_flag(warning) {
+                cmd.push_cc_arg(flag.into());
+            }
+        }
+        for warning in self.error_warnings.iter() {
+            check_warning_name(warning, "turn into an error")?;
+            if let Some(flag) = cmd.family.warning_as_error_flag(warning) {
+                cmd.push_cc_arg(flag.into());
+            }
+        }
+
+        // Symbol visibility only applies to ELF and Mach-O.
+        if !cmd.is_like_msvc() && target.os != "windows" && !target.is_uefi() {
+            if let Some(visibility) = self.default_visibility {
+                cmd.push_cc_arg(
+                    match visibility {
+                        Visibility::Default => "-fvisibility=default",
+                        Visibility::Hidden => "-fvisibility=hidden",
+                    }
+                    .into(),
+                );
+            }
+            let inlines_hidden = self
+                .cpp_inlines_hidden
+                .unwrap_or(self.default_visibility == Some(Visibility::Hidden));
+            if self.cpp && inlines_hidden {
+                cmd.push_cc_arg("-fvisibility-inlines-hidden".into());
+            }
+        }
+
+        if let Some(max_errors) = self.max_errors {
+            match cmd.family.max_errors_flag() {
+                Some(flag) => cmd.push_cc_arg(format!("{}{}", flag, max_errors).into()),
+                None => {
+                    if !self
+                        .build_cache
+                        .max_errors_unsupported_warned
+                        .swap(true, Relaxed)
+                    {
+                        self.cargo_output.print_warning(
+                            &"`max_errors` is not supported by MSVC and will be ignored",
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(lto) = self.lto {
+            let flags = match cmd.family.lto_flags(lto) {
+                Some(flags) => flags,
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::UnsupportedFeature,
+                        format!(
+                            "{:?} LTO is not supported by {} when compiling for {}",
+                            lto,
+                            cmd.path.display(),
+                            self.get_raw_target()?
+                        ),
+                    ))
+                }
+            };
+            for flag in flags {
+                cmd.push_cc_arg((*flag).into());
+            }
+        }
+
+        if let Some(level) = self.hardening {
+            for flag in target.hardening_flags(cmd.family, level, &*opt_level != "0") {
+                cmd.push_cc_arg(flag.into());
+            }
         }
-        if self.extra_warnings.unwrap_or(envflags.is_none()) {
-            if let Some(wflags) = cmd.family.extra_warnings_flags() {
-                cmd.push_cc_arg(wflags.into());
+
+        if self.match_rust_target_features && !self.for_host {
+            let features = self.getenv("CARGO_CFG_TARGET_FEATURE");
+            let features = features.as_deref().unwrap_or_default().to_string_lossy();
+            for flag in target.target_feature_flags(cmd.family, &features) {
+                cmd.push_cc_arg(flag.into());
+            }
+        }
+
+        if let Some(color) = self.get_diagnostics_color() {
+            if let Some(flag) = cmd.family.diagnostics_color_flag(color) {
+                cmd.push_cc_arg(flag.into());
             }
         }
 
         // Add cc flags inherited from matching rustc flags.
-        if self.inherit_rustflags {
+        if self.inherit_rustflags && !self.for_host {
             self.add_inherited_rustflags(&mut cmd, &target)?;
         }
 
-        // Set flags configured in the builder (do this second-to-last, to allow these to override
-        // everything above).
+        // Set flags from the environment.
+        if let Some(flags) = &envflags {
+            for arg in flags {
+                cmd.push_cc_arg(arg.into());
+            }
+        }
+
+        // Set flags configured in the builder (do this last, to allow these to override
+        // everything above, including the environment).
         for flag in self.flags.iter() {
             cmd.args.push((**flag).into());
         }
@@ -2041,13 +5259,19 @@ impl Build {
                 cmd.args.push(format!("-D{}", key).into());
             }
         }
-
-        // Set flags from the environment (do this last, to allow these to override everything else).
-        if let Some(flags) = &envflags {
-            for arg in flags {
-                cmd.push_cc_arg(arg.into());
+        if let Some(version) = target.win32_winnt().filter(|_| self.platform_defines) {
+            for key in ["_WIN32_WINNT", "WINVER"].iter() {
+                if !self.definitions.iter().any(|(k, _)| **k == **key) {
+                    cmd.args.push(format!("-D{}={}", key, version).into());
+                }
+            }
+        }
+        if let Some(prefix) = &self.cargo_cfgs_prefix {
+            for key in cargo_cfg_definitions(prefix, &target) {
+                cmd.args.push(format!("-D{}", key).into());
             }
         }
+        cmd.dedup_warning_args();
 
         Ok(cmd)
     }
@@ -2058,7 +5282,6 @@ impl Build {
         target: &TargetInfo<'_>,
         opt_level: &str,
     ) -> Result<(), Error> {
-        let raw_target = self.get_raw_target()?;
         // Non-target flags
         // If the flag is not conditioned on target variable, it belongs here :)
         match cmd.family {
@@ -2069,9 +5292,7 @@ impl Build {
                     Some(true) => "-MT",
                     Some(false) => "-MD",
                     None => {
-                        let features = self.getenv("CARGO_CFG_TARGET_FEATURE");
-                        let features = features.as_deref().unwrap_or_default();
-                        if features.to_string_lossy().contains("crt-static") {
+                        if self.is_crt_static(target) {
                             "-MT"
                         } else {
                             "-MD"
@@ -2106,44 +5327,47 @@ impl Build {
                     cmd.push_opt_unless_duplicate("-DANDROID".into());
                 }
 
+                // LLVM always puts each function and data item in its own
+                // section on WASM, so the flags are redundant there.
                 if target.os != "ios"
                     && target.os != "watchos"
                     && target.os != "tvos"
                     && target.os != "visionos"
+                    && !target.is_wasm_family()
                 {
                     cmd.push_cc_arg("-ffunction-sections".into());
                     cmd.push_cc_arg("-fdata-sections".into());
                 }
-                // Disable generation of PIC on bare-metal for now: rust-lld doesn't support this yet
-                //
-                // `rustc` also defaults to disable PIC on WASM:
-                // <https://github.com/rust-lang/rust/blob/1.82.0/compiler/rustc_target/src/spec/base/wasm.rs#L101-L108>
-                if self.pic.unwrap_or(
-                    target.os != "windows"
-                        && target.os != "none"
-                        && target.os != "uefi"
-                        && target.arch != "wasm32"
-                        && target.arch != "wasm64",
-                ) {
-                    cmd.push_cc_arg("-fPIC".into());
+                let relocation_model = self.get_relocation_model(target)?;
+                if relocation_model != RelocationModel::Static {
+                    let flag = if relocation_model == RelocationModel::Pie {
+                        "-fPIE"
+                    } else {
+                        "-fPIC"
+                    };
+                    cmd.push_cc_arg(flag.into());
                     // PLT only applies if code is compiled with PIC support,
-                    // and only for ELF targets.
-                    if (target.os == "linux" || target.os == "android")
-                        && !self.use_plt.unwrap_or(true)
-                    {
+                    // and only for dynamically linked ELF targets.
+                    if target.is_dynamic_elf() && !self.use_plt.unwrap_or(true) {
                         cmd.push_cc_arg("-fno-plt".into());
                     }
                 }
-                if target.arch == "wasm32" || target.arch == "wasm64" {
+                if target.is_wasm_family() && target.os != "emscripten" {
                     // WASI does not support exceptions yet.
                     // https://github.com/WebAssembly/exception-handling
                     //
                     // `rustc` also defaults to (currently) disable exceptions
-                    // on all WASM targets:
+                    // on WASM targets, except for Emscripten, which unwinds:
                     // <https://github.com/rust-lang/rust/blob/1.82.0/compiler/rustc_target/src/spec/base/wasm.rs#L72-L77>
                     cmd.push_cc_arg("-fno-exceptions".into());
                 }
 
+                // UEFI applications have no C library, which the Windows
+                // triples used to compile them would otherwise assume.
+                if target.is_uefi() {
+                    cmd.push_cc_arg("-ffreestanding".into());
+                }
+
                 if target.os == "wasi" {
                     // Link clang sysroot
                     if let Ok(wasi_sysroot) = self.wasi_sysroot() {
@@ -2152,12 +5376,24 @@ impl Build {
                         );
                     }
 
-                    // FIXME(madsmtm): Read from `target_features` instead?
-                    if raw_target.contains("threads") {
+                    // `rustc` enables the same features for the threads
+                    // targets, which `-pthread` also implies in Clang.
+                    if target.wasm_threads() {
+                        for x in &["atomics", "bulk-memory", "mutable-globals"] {
+                            cmd.push_cc_arg(format!("-m{x}").into());
+                        }
                         cmd.push_cc_arg("-pthread".into());
                     }
                 }
 
+                // The `wasm32v1-none` target only uses the features of the
+                // first version of WebAssembly, plus mutable globals, while
+                // Clang's default CPU has more.
+                if target.full_arch == "wasm32v1" {
+                    cmd.push_cc_arg("-mcpu=mvp".into());
+                    cmd.push_cc_arg("-mmutable-globals".into());
+                }
+
                 if target.os == "nto" {
                     // Select the target with `-V`, see qcc documentation:
                     // QNX 7.1: https://www.qnx.com/developers/docs/7.1/index.html#com.qnx.doc.neutrino.utilities/topic/q/qcc.html
@@ -2165,13 +5401,17 @@ impl Build {
                     // This assumes qcc/q++ as compiler, which is currently the only supported compiler for QNX.
                     // See for details: https://github.com/rust-lang/cc-rs/pull/1319
                     let arg = match target.arch {
-                        "i586" => "-Vgcc_ntox86_cxx",
+                        "x86" => "-Vgcc_ntox86_cxx",
                         "aarch64" => "-Vgcc_ntoaarch64le_cxx",
                         "x86_64" => "-Vgcc_ntox86_64_cxx",
                         _ => {
                             return Err(Error::new(
                                 ErrorKind::InvalidTarget,
-                                format!("Unknown architecture for Neutrino QNX: {}", target.arch),
+                                format!(
+                                    "Unknown architecture for Neutrino QNX {}: {}",
+                                    target.qnx_version().unwrap_or("(unknown version)"),
+                                    target.arch
+                                ),
                             ))
                         }
                     };
@@ -2180,13 +5420,14 @@ impl Build {
             }
         }
 
+        let dwarf_version = self.get_dwarf_version()?;
         if self.get_debug() {
             if self.cuda {
                 // NVCC debug flag
                 cmd.args.push("-G".into());
             }
             let family = cmd.family;
-            family.add_debug_flags(cmd, self.get_dwarf_version());
+            family.add_debug_flags(cmd, dwarf_version);
         }
 
         if self.get_force_frame_pointer() {
@@ -2215,7 +5456,8 @@ impl Build {
             ToolFamily::Clang { .. } => {
                 if !(cmd.has_internal_target_arg
                     || (target.os == "android"
-                        && android_clang_compiler_uses_target_arg_internally(&cmd.path)))
+                        && android_clang_compiler_uses_target_arg_internally(&cmd.path))
+                    || (target.os == "emscripten" && is_emscripten_driver(cmd)))
                 {
                     if target.os == "freebsd" {
                         // FreeBSD only supports C++11 and above when compiling against libc++
@@ -2279,14 +5521,13 @@ impl Build {
                     // So instead, we pass the deployment target with `-m*-version-min=`, and only
                     // pass it here on visionOS and Mac Catalyst where that option does not exist:
                     // https://github.com/rust-lang/cc-rs/issues/1383
-                    let version = if target.os == "visionos" || target.abi == "macabi" {
-                        Some(self.apple_deployment_target(target))
+                    let version = if apple_version_in_llvm_target(cmd, target) {
+                        Some(self.get_apple_deployment_target(target))
                     } else {
                         None
                     };
 
-                    let clang_target =
-                        target.llvm_target(&self.get_raw_target()?, version.as_deref());
+                    let clang_target = self.get_llvm_target(target, version.as_deref())?;
                     cmd.push_cc_arg(format!("--target={clang_target}").into());
                 }
             }
@@ -2313,11 +5554,7 @@ impl Build {
                         cmd.push_cc_arg("-arch:SSE2".into());
                     } else {
                         cmd.push_cc_arg(
-                            format!(
-                                "--target={}",
-                                target.llvm_target(&self.get_raw_target()?, None)
-                            )
-                            .into(),
+                            format!("--target={}", self.get_llvm_target(target, None)?).into(),
                         );
                     }
                 } else if target.full_arch == "i586" {
@@ -2344,66 +5581,34 @@ impl Build {
                     cmd.args.push("-finput-charset=utf-8".into());
                 }
 
-                if self.static_flag.is_none() {
-                    let features = self.getenv("CARGO_CFG_TARGET_FEATURE");
-                    let features = features.as_deref().unwrap_or_default();
-                    if features.to_string_lossy().contains("crt-static") {
-                        cmd.args.push("-static".into());
-                    }
-                }
-
                 // armv7 targets get to use armv7 instructions
                 if (target.full_arch.starts_with("armv7")
                     || target.full_arch.starts_with("thumbv7"))
                     && (target.os == "linux" || target.vendor == "kmc")
                 {
                     cmd.args.push("-march=armv7-a".into());
-
-                    if target.abi == "eabihf" {
-                        // lowest common denominator FPU
-                        cmd.args.push("-mfpu=vfpv3-d16".into());
-                        cmd.args.push("-mfloat-abi=hard".into());
-                    }
                 }
 
                 // (x86 Android doesn't say "eabi")
                 if target.os == "android" && target.full_arch.contains("v7") {
                     cmd.args.push("-march=armv7-a".into());
                     cmd.args.push("-mthumb".into());
-                    if !target.full_arch.contains("neon") {
-                        // On android we can guarantee some extra float instructions
-                        // (specified in the android spec online)
-                        // NEON guarantees even more; see below.
-                        cmd.args.push("-mfpu=vfpv3-d16".into());
-                    }
-                    cmd.args.push("-mfloat-abi=softfp".into());
-                }
-
-                if target.full_arch.contains("neon") {
-                    cmd.args.push("-mfpu=neon-vfpv4".into());
                 }
 
                 if target.full_arch == "armv4t" && target.os == "linux" {
                     cmd.args.push("-march=armv4t".into());
                     cmd.args.push("-marm".into());
-                    cmd.args.push("-mfloat-abi=soft".into());
                 }
 
                 if target.full_arch == "armv5te" && target.os == "linux" {
                     cmd.args.push("-march=armv5te".into());
                     cmd.args.push("-marm".into());
-                    cmd.args.push("-mfloat-abi=soft".into());
                 }
 
                 // For us arm == armv6 by default
                 if target.full_arch == "arm" && target.os == "linux" {
                     cmd.args.push("-march=armv6".into());
-                    cmd.args.push("-marm".into());
-                    if target.abi == "eabihf" {
-                        cmd.args.push("-mfpu=vfp".into());
-                    } else {
-                        cmd.args.push("-mfloat-abi=soft".into());
-                    }
+                    cmd.args.push("-marm".into());
                 }
 
                 // Turn codegen down on i586 to avoid some instructions.
@@ -2421,13 +5626,10 @@ impl Build {
                 // linker that we're generating 32-bit executables as well. This'll
                 // typically only be used for build scripts which transitively use
                 // these flags that try to compile executables.
-                if target.arch == "x86" && target.env == "musl" {
+                if target.arch == "x86" && target.libc_flavor() == LibcFlavor::Musl {
                     cmd.args.push("-Wl,-melf_i386".into());
                 }
 
-                if target.arch == "arm" && target.os == "none" && target.abi == "eabihf" {
-                    cmd.args.push("-mfloat-abi=hard".into())
-                }
                 if target.full_arch.starts_with("thumb") {
                     cmd.args.push("-mthumb".into());
                 }
@@ -2436,10 +5638,6 @@ impl Build {
                 }
                 if target.full_arch.starts_with("thumbv7em") {
                     cmd.args.push("-march=armv7e-m".into());
-
-                    if target.abi == "eabihf" {
-                        cmd.args.push("-mfpu=fpv4-sp-d16".into())
-                    }
                 }
                 if target.full_arch.starts_with("thumbv7m") {
                     cmd.args.push("-march=armv7-m".into());
@@ -2449,10 +5647,6 @@ impl Build {
                 }
                 if target.full_arch.starts_with("thumbv8m.main") {
                     cmd.args.push("-march=armv8-m.main".into());
-
-                    if target.abi == "eabihf" {
-                        cmd.args.push("-mfpu=fpv5-sp-d16".into())
-                    }
                 }
                 if target.full_arch.starts_with("armebv7r") | target.full_arch.starts_with("armv7r")
                 {
@@ -2467,20 +5661,17 @@ impl Build {
 
                     // R Profile
                     cmd.args.push("-march=armv7-r".into());
-
-                    if target.abi == "eabihf" {
-                        // lowest common denominator FPU
-                        // (see Cortex-R4 technical reference manual)
-                        cmd.args.push("-mfpu=vfpv3-d16".into())
-                    }
                 }
                 if target.full_arch.starts_with("armv7a") {
                     cmd.args.push("-march=armv7-a".into());
-
-                    if target.abi == "eabihf" {
-                        // lowest common denominator FPU
-                        cmd.args.push("-mfpu=vfpv3-d16".into());
-                    }
+                }
+                // Flags set in the builder or the environment come later, and
+                // thus override these.
+                if let Some(fpu) = target.arm_fpu() {
+                    cmd.args.push(format!("-mfpu={}", fpu).into());
+                }
+                if let Some(float_abi) = target.arm_float_abi() {
+                    cmd.args.push(format!("-mfloat-abi={}", float_abi).into());
                 }
                 if target.arch == "riscv32" || target.arch == "riscv64" {
                     // get the 32i/32imac/32imc/64gc/64imac/... part
@@ -2488,18 +5679,14 @@ impl Build {
                     if arch.starts_with("64") {
                         if matches!(target.os, "linux" | "freebsd" | "netbsd") {
                             cmd.args.push(("-march=rv64gc").into());
-                            cmd.args.push("-mabi=lp64d".into());
                         } else {
                             cmd.args.push(("-march=rv".to_owned() + arch).into());
-                            cmd.args.push("-mabi=lp64".into());
                         }
                     } else if arch.starts_with("32") {
                         if target.os == "linux" {
                             cmd.args.push(("-march=rv32gc").into());
-                            cmd.args.push("-mabi=ilp32d".into());
                         } else {
                             cmd.args.push(("-march=rv".to_owned() + arch).into());
-                            cmd.args.push("-mabi=ilp32".into());
                         }
                     } else {
                         cmd.args.push("-mcmodel=medany".into());
@@ -2508,11 +5695,22 @@ impl Build {
             }
         }
 
+        // The compilers' default ABI doesn't always match the one `rustc`
+        // uses, e.g. for `riscv64imac` or the MIPS `-march`es.
+        if !cmd.is_like_msvc() {
+            if let Some(mabi) = target.mabi() {
+                cmd.args.push(format!("-mabi={}", mabi).into());
+            }
+        }
+
         if target.vendor == "apple" {
             self.apple_flags(cmd)?;
         }
 
-        if self.static_flag.unwrap_or(false) {
+        // Link the C runtime statically when the Rust code does, with both
+        // GCC and Clang, e.g. on the musl targets where that's the default.
+        let static_crt = !cmd.is_like_msvc() && target.env != "msvc" && self.is_crt_static(target);
+        if self.static_flag.unwrap_or(static_crt) {
             cmd.args.push("-static".into());
         }
         if self.shared_flag.unwrap_or(false) {
@@ -2534,6 +5732,40 @@ impl Build {
         Ok(())
     }
 
+    /// Set up `cmd` for [`Build::reproducible`].
+    fn add_reproducible_flags(&self, cmd: &mut Tool) -> Result<(), Error> {
+        // Keep the timestamp of the whole build when one is given.
+        let epoch = self
+            .getenv("SOURCE_DATE_EPOCH")
+            .map_or_else(|| "0".into(), |epoch| epoch.to_os_string());
+        cmd.env.push(("SOURCE_DATE_EPOCH".into(), epoch));
+        cmd.env.push(("TZ".into(), "UTC".into()));
+        if !cmd.env.iter().any(|(var, _)| var == "LC_ALL") {
+            cmd.env.push(("LC_ALL".into(), "C".into()));
+        }
+
+        if cmd.is_like_msvc() {
+            cmd.push_cc_arg("-Brepro".into());
+            return Ok(());
+        }
+        cmd.push_cc_arg("-Wdate-time".into());
+        // Clang uses the longest matching prefix, and GCC the last one given,
+        // which is the output directory when it's in the package.
+        let mut prefixes = Vec::new();
+        if let Some(manifest_dir) = self.getenv("CARGO_MANIFEST_DIR") {
+            prefixes.push((PathBuf::from(&*manifest_dir), "."));
+        }
+        prefixes.push((self.get_out_dir()?.into_owned(), "out"));
+        for (prefix, replacement) in prefixes {
+            let mut arg = OsString::from("-ffile-prefix-map=");
+            arg.push(prefix);
+            arg.push("=");
+            arg.push(replacement);
+            cmd.push_cc_arg(arg);
+        }
+        Ok(())
+    }
+
     fn add_inherited_rustflags(
         &self,
         cmd: &mut Tool,
@@ -2610,35 +5842,49 @@ impl Build {
     }
 
     fn assemble(&self, lib_name: &str, dst: &Path, objs: &[Object]) -> Result<(), Error> {
-        // Delete the destination if it exists as we want to
-        // create on the first iteration instead of appending.
-        let _ = fs::remove_file(dst);
-
-        // Add objects to the archive in limited-length batches. This helps keep
-        // the length of the command line within a reasonable length to avoid
-        // blowing system limits on limiting platforms like Windows.
-        let objs: Vec<_> = objs
+        let mut objs: Vec<_> = objs
             .iter()
             .map(|o| o.dst.as_path())
             .chain(self.objects.iter().map(std::ops::Deref::deref))
             .collect();
-        for chunk in objs.chunks(100) {
-            self.assemble_progressive(dst, chunk)?;
+
+        let target = self.get_target()?;
+        let update = self.archive_update && target.env != "msvc" && !self.cuda;
+        if update {
+            objs.sort_by_key(|obj| obj.file_name());
+        }
+        if objs.is_empty() {
+            // Archivers don't all create empty archives, so write the global
+            // header of the `ar` format, which `lib.exe` also uses.
+            fs::write(dst, b"!<arch>\n")?;
+        } else if !(update && self.update_archive(dst, &objs)?) {
+            // Delete the destination if it exists as we want to
+            // create on the first iteration instead of appending.
+            let _ = fs::remove_file(dst);
+
+            // Add objects to the archive in limited-length batches. This helps keep
+            // the length of the command line within a reasonable length to avoid
+            // blowing system limits on limiting platforms like Windows.
+            for chunk in objs.chunks(100) {
+                self.assemble_progressive(dst, chunk)?;
+            }
         }
 
-        if self.cuda && self.cuda_file_count() > 0 {
+        if self.cuda && self.cuda_file_count() > 0 && self.cuda_relocatable != Some(false) {
             // Link the device-side code and add it to the target library,
             // so that non-CUDA linker can link the final binary.
 
             let out_dir = self.get_out_dir()?;
             let dlink = out_dir.join(lib_name.to_owned() + "_dlink.o");
             let mut nvcc = self.get_compiler().to_command();
+            for (a, b) in self.env.iter() {
+                nvcc.env(a, b);
+            }
             nvcc.arg("--device-link").arg("-o").arg(&dlink).arg(dst);
             run(&mut nvcc, &self.cargo_output)?;
             self.assemble_progressive(dst, &[dlink.as_path()])?;
         }
 
-        let target = self.get_target()?;
         if target.env == "msvc" {
             // The Rust compiler will look for libfoo.a and foo.lib, but the
             // MSVC linker will also be passed foo.lib, so be sure that both
@@ -2651,11 +5897,12 @@ impl Build {
                 fs::copy(dst, &lib_dst).map(|_| ())
             }) {
                 Ok(_) => (),
-                Err(_) => {
+                Err(e) => {
                     return Err(Error::new(
-                        ErrorKind::IOError,
+                        ErrorKind::IOError(e.kind()),
                         "Could not copy or create a hard-link to the generated lib file.",
-                    ));
+                    )
+                    .with_source(e));
                 }
             };
         } else {
@@ -2673,6 +5920,55 @@ impl Build {
         Ok(())
     }
 
+    /// Update the existing archive `dst` to contain exactly `objs`, returning
+    /// `false` if it has to be recreated instead.
+    fn update_archive(&self, dst: &Path, objs: &[&Path]) -> Result<bool, Error> {
+        let archive_modified = match fs::metadata(dst).and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return Ok(false),
+        };
+
+        let mut ar = self.try_get_archiver()?;
+        let members = run_output(ar.arg("t").arg(dst), &self.cargo_output)?;
+        let members = String::from_utf8_lossy(&members);
+        let members = members.lines().map(OsStr::new).collect::<Vec<_>>();
+
+        let names = objs
+            .iter()
+            .map(|obj| obj.file_name().unwrap_or_default())
+            .collect::<Vec<_>>();
+        // New members would be appended, out of order.
+        if names.iter().any(|name| !members.contains(name)) {
+            return Ok(false);
+        }
+
+        let stale = members
+            .iter()
+            .filter(|member| !names.contains(member))
+            .collect::<Vec<_>>();
+        for chunk in stale.chunks(100) {
+            let mut ar = self.try_get_archiver()?;
+            run(ar.arg("d").arg(dst).args(chunk), &self.cargo_output)?;
+        }
+
+        let changed = objs
+            .iter()
+            .filter(|obj| {
+                fs::metadata(obj)
+                    .and_then(|m| m.modified())
+                    .map_or(true, |modified| modified >= archive_modified)
+            })
+            .collect::<Vec<_>>();
+        for chunk in changed.chunks(100) {
+            let (mut cmd, _, _) = self.try_get_archiver_and_flags()?;
+            // See `assemble_progressive`.
+            cmd.env("ZERO_AR_DATE", "1");
+            run(cmd.arg("r").arg(dst).args(chunk), &self.cargo_output)?;
+        }
+
+        Ok(true)
+    }
+
     fn assemble_progressive(&self, dst: &Path, objs: &[&Path]) -> Result<(), Error> {
         let target = self.get_target()?;
 
@@ -2746,22 +6042,29 @@ impl Build {
         // similar. Also necessary on GCC, as it forces a compilation error if the compiler is not
         // configured for Darwin: https://gcc.gnu.org/onlinedocs/gcc/Darwin-Options.html
         //
-        // On visionOS and Mac Catalyst, there is no -m*-version-min= flag:
-        // https://github.com/llvm/llvm-project/issues/88271
-        // And the workaround to use `-mtargetos=` cannot be used with the `--target` flag that we
-        // otherwise specify. So we avoid emitting that, and put the version in `--target` instead.
-        if cmd.is_like_gnu() || !(target.os == "visionos" || target.abi == "macabi") {
-            let min_version = self.apple_deployment_target(&target);
+        // Unless the version is already part of the `--target` triple, see
+        // `apple_version_in_llvm_target`.
+        if !apple_version_in_llvm_target(cmd, &target) {
+            let min_version = self.get_apple_deployment_target(&target);
             cmd.args
                 .push(target.apple_version_flag(&min_version).into());
         }
 
+        if self.apple_embed_bitcode && target.is_apple_device() {
+            let flag = if self.get_debug() {
+                "-fembed-bitcode-marker"
+            } else {
+                "-fembed-bitcode"
+            };
+            cmd.args.push(flag.into());
+        }
+
         // AppleClang sometimes requires sysroot even on macOS
         if cmd.is_xctoolchain_clang() || target.os != "macos" {
             self.cargo_output.print_metadata(&format_args!(
                 "Detecting {:?} SDK path for {}",
                 target.os,
-                target.apple_sdk_name(),
+                self.apple_sdk_name(&target),
             ));
             let sdk_path = self.apple_sdk_root(&target)?;
 
@@ -2816,6 +6119,9 @@ impl Build {
         let out_dir = out_dir.as_deref();
 
         if let Some(c) = &self.compiler {
+            if let Some(family) = self.compiler_family {
+                return Ok(Tool::with_family((**c).to_owned(), family.into()));
+            }
             return Ok(Tool::new(
                 (**c).to_owned(),
                 &self.build_cache.cached_compiler_family,
@@ -2840,6 +6146,21 @@ impl Build {
         } else {
             traditional
         };
+        // The first of the preferred compilers that's available is used
+        // instead, if there is one.
+        let preferred_or_default = || {
+            self.preferred_compilers
+                .iter()
+                .map(|family| match family {
+                    CompilerFamily::Gnu => gnu,
+                    CompilerFamily::Clang => clang,
+                    CompilerFamily::Msvc => msvc,
+                    CompilerFamily::ClangCl => "clang-cl",
+                })
+                .find(|compiler| self.which(Path::new(compiler), None).is_some())
+                .unwrap_or(default)
+                .to_string()
+        };
 
         let cl_exe = self.windows_registry_find_tool(&target, "cl.exe");
 
@@ -2914,7 +6235,7 @@ impl Build {
                         "{}-{}-{}-{}",
                         target.full_arch, target.vendor, target.os, traditional
                     )
-                } else if target.arch == "wasm32" || target.arch == "wasm64" {
+                } else if target.is_wasm_family() {
                     // Compiling WASM is not currently supported by GCC, so
                     // let's default to Clang.
                     clang.to_string()
@@ -2942,10 +6263,10 @@ impl Build {
                             let cc = if target.abi == "llvm" { clang } else { gnu };
                             format!("{}-{}", prefix, cc)
                         }
-                        None => default.to_string(),
+                        None => preferred_or_default(),
                     }
                 } else {
-                    default.to_string()
+                    preferred_or_default()
                 };
 
                 let mut t = Tool::new(
@@ -3185,6 +6506,72 @@ impl Build {
         }
     }
 
+    /// Whether [`Build::cpp_stdlib_static`] applies to this configuration.
+    fn links_cpp_stdlib_statically(&self) -> Result<bool, Error> {
+        if !self.cpp || !self.cpp_stdlib_static {
+            return Ok(false);
+        }
+        let target = self.get_target()?;
+        if target.env == "msvc" || self.try_get_compiler()?.is_like_msvc() {
+            return Ok(false);
+        }
+        if target.vendor == "apple" {
+            self.cargo_output.print_warning(
+                &"cpp_stdlib_static is specified, but Apple targets have no static libc++, ignored",
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Print the metadata linking the C++ standard library `stdlib`
+    /// statically, searching for it where the compiler would find it.
+    fn print_static_cpp_stdlib(&self, stdlib: &Path) -> Result<(), Error> {
+        let mut libs = vec![stdlib.to_owned()];
+        // libc++ comes without its ABI library when linked statically.
+        if stdlib == Path::new("c++") {
+            libs.push("c++abi".into());
+        }
+
+        let compiler = self.try_get_compiler()?;
+        let mut search_dirs = Vec::<PathBuf>::new();
+        for lib in &libs {
+            let mut cmd = compiler.to_command();
+            for (a, b) in self.env.iter() {
+                cmd.env(a, b);
+            }
+            let mut arg = OsString::from("-print-file-name=lib");
+            arg.push(lib);
+            arg.push(".a");
+            cmd.arg(arg);
+            let output = run_output(&mut cmd, &self.cargo_output)?;
+            let archive = PathBuf::from(String::from_utf8_lossy(&output).trim());
+            // The compiler prints the name as it is if it can't find the
+            // library, which is then up to the linker's default search path.
+            if let Some(dir) = archive.parent().filter(|_| archive.is_absolute()) {
+                if !search_dirs.iter().any(|d| d == dir) {
+                    search_dirs.push(dir.to_owned());
+                }
+            }
+        }
+
+        for dir in &search_dirs {
+            self.cargo_output.print_metadata(&format_args!(
+                "cargo:rustc-link-search=native={}",
+                dir.display()
+            ));
+        }
+        // Leave the library out of the rlib, so that only the final link
+        // pulls it in once.
+        for lib in &libs {
+            self.cargo_output.print_metadata(&format_args!(
+                "cargo:rustc-link-lib=static:-bundle={}",
+                lib.display()
+            ));
+        }
+        Ok(())
+    }
+
     /// Get the archiver (ar) that's in use for this configuration.
     ///
     /// You can use [`Command::get_program`] to get just the path to the command.
@@ -3237,6 +6624,55 @@ impl Build {
         self.get_base_archiver_variant("AR", "ar")
     }
 
+    /// Whether the target is ELF and the archiver is a GNU `ar` from binutils
+    /// older than 2.36, which can't handle Clang's address-significance
+    /// tables.
+    fn has_old_gnu_archiver(&self, target: &TargetInfo<'_>) -> bool {
+        if !target.is_elf() {
+            return false;
+        }
+        let mut ar = match self.get_base_archiver() {
+            Ok((ar, _)) => ar,
+            Err(_) => return false,
+        };
+        let program: Box<OsStr> = ar.get_program().into();
+        if let Some(version) = self
+            .build_cache
+            .archiver_version_cache
+            .read()
+            .unwrap()
+            .get(&program)
+        {
+            return version.map_or(false, |version| version < (2, 36));
+        }
+
+        // Archivers may not know `--version`, which isn't worth a warning.
+        let mut cargo_output = self.cargo_output.clone();
+        cargo_output.warnings = cargo_output.debug;
+        let version = run_output(ar.arg("--version"), &cargo_output)
+            .ok()
+            .and_then(|stdout| {
+                // `GNU ar (GNU Binutils for Ubuntu) 2.34`, like GCC's banner.
+                let banner = String::from_utf8_lossy(&stdout);
+                if !banner.starts_with("GNU ar") {
+                    return None;
+                }
+                tool::parse_version_banner(ToolFamily::Gnu, &banner)
+            });
+        log_record!(
+            debug,
+            "archiver {} has the binutils version {:?}",
+            Path::new(&program).display(),
+            version
+        );
+        self.build_cache
+            .archiver_version_cache
+            .write()
+            .unwrap()
+            .insert(program, version);
+        version.map_or(false, |version| version < (2, 36))
+    }
+
     /// Get the ranlib that's in use for this configuration.
     ///
     /// You can use [`Command::get_program`] to get just the path to the command.
@@ -3305,7 +6741,7 @@ impl Build {
                         name = format!("em{}", tool).into();
                         Some(self.cmd(&name))
                     }
-                } else if target.arch == "wasm32" || target.arch == "wasm64" {
+                } else if target.is_wasm_family() {
                     // Formally speaking one should be able to use this approach,
                     // parsing -print-search-dirs output, to cover all clang targets,
                     // including Android SDKs and other cross-compilation scenarios...
@@ -3387,38 +6823,44 @@ impl Build {
                     name = format!("g{}", tool).into();
                     self.cmd(&name)
                 } else if self.get_is_cross_compile()? {
-                    match self.prefix_for_target(&self.get_raw_target()?) {
-                        Some(prefix) => {
-                            // GCC uses $target-gcc-ar, whereas binutils uses $target-ar -- try both.
-                            // Prefer -ar if it exists, as builds of `-gcc-ar` have been observed to be
-                            // outright broken (such as when targeting freebsd with `--disable-lto`
-                            // toolchain where the archiver attempts to load the LTO plugin anyway but
-                            // fails to find one).
-                            //
-                            // The same applies to ranlib.
-                            let chosen = ["", "-gcc"]
-                                .iter()
-                                .filter_map(|infix| {
-                                    let target_p = format!("{prefix}{infix}-{tool}");
-                                    let status = Command::new(&target_p)
-                                        .arg("--version")
-                                        .stdin(Stdio::null())
-                                        .stdout(Stdio::null())
-                                        .stderr(Stdio::null())
-                                        .status()
-                                        .ok()?;
-                                    status.success().then_some(target_p)
-                                })
-                                .next()
-                                .unwrap_or_else(|| tool.to_string());
-                            name = chosen.into();
-                            self.cmd(&name)
-                        }
-                        None => {
-                            name = tool.into();
-                            self.cmd(&name)
+                    // Try the well-known prefix (or the one configured through the
+                    // environment) first, then the one derived from the target.
+                    let mut prefixes = Vec::new();
+                    if let Some(prefix) = self.prefix_for_target(&self.get_raw_target()?) {
+                        prefixes.push(prefix.into_owned());
+                    }
+                    if let Some(prefix) = target.gnu_tool_prefix() {
+                        if !prefixes.contains(&prefix) {
+                            prefixes.push(prefix);
                         }
                     }
+
+                    // GCC uses $target-gcc-ar, whereas binutils uses $target-ar -- try both.
+                    // Prefer -ar if it exists, as builds of `-gcc-ar` have been observed to be
+                    // outright broken (such as when targeting freebsd with `--disable-lto`
+                    // toolchain where the archiver attempts to load the LTO plugin anyway but
+                    // fails to find one).
+                    //
+                    // The same applies to ranlib.
+                    let chosen = prefixes
+                        .iter()
+                        .flat_map(|prefix| {
+                            ["", "-gcc"]
+                                .iter()
+                                .map(move |infix| format!("{prefix}{infix}-{tool}"))
+                        })
+                        .find(|target_p| {
+                            Command::new(target_p)
+                                .arg("--version")
+                                .stdin(Stdio::null())
+                                .stdout(Stdio::null())
+                                .stderr(Stdio::null())
+                                .status()
+                                .map_or(false, |status| status.success())
+                        })
+                        .unwrap_or_else(|| tool.to_string());
+                    name = chosen.into();
+                    self.cmd(&name)
                 } else {
                     name = tool.into();
                     self.cmd(&name)
@@ -3453,6 +6895,7 @@ impl Build {
                     "aarch64-unknown-linux-gnu" => Some("aarch64-linux-gnu"),
                     "aarch64-unknown-linux-musl" => Some("aarch64-linux-musl"),
                     "aarch64-unknown-netbsd" => Some("aarch64--netbsd"),
+                    "aarch64-unknown-redox" => Some("aarch64-unknown-redox"),
                     "arm-unknown-linux-gnueabi" => Some("arm-linux-gnueabi"),
                     "armv4t-unknown-linux-gnueabi" => Some("arm-linux-gnueabi"),
                     "armv5te-unknown-linux-gnueabi" => Some("arm-linux-gnueabi"),
@@ -3510,6 +6953,7 @@ impl Build {
                         "riscv64-unknown-elf",
                         "riscv-none-embed",
                     ]),
+                    "riscv32imafc-esp-espidf" => Some("riscv32-esp-elf"),
                     "riscv32imac-unknown-xous-elf" => self.find_working_gnu_prefix(&[
                         "riscv32-unknown-elf",
                         "riscv64-unknown-elf",
@@ -3564,6 +7008,14 @@ impl Build {
                     ]), // explicit None if not found, so caller knows to fall back
                     "x86_64-unknown-linux-musl" => Some("x86_64-linux-musl"),
                     "x86_64-unknown-netbsd" => Some("x86_64--netbsd"),
+                    "x86_64-unknown-redox" => Some("x86_64-unknown-redox"),
+                    "xtensa-esp32-espidf" | "xtensa-esp32-none-elf" => Some("xtensa-esp32-elf"),
+                    "xtensa-esp32s2-espidf" | "xtensa-esp32s2-none-elf" => {
+                        Some("xtensa-esp32s2-elf")
+                    }
+                    "xtensa-esp32s3-espidf" | "xtensa-esp32s3-none-elf" => {
+                        Some("xtensa-esp32s3-elf")
+                    }
                     _ => None,
                 }
                 .map(Cow::Borrowed)
@@ -3602,6 +7054,12 @@ impl Build {
     }
 
     fn get_target(&self) -> Result<TargetInfo<'_>, Error> {
+        if self.for_host {
+            return TargetInfo::from_rustc_target(self.get_raw_host()?);
+        }
+        if let Some(spec) = self.get_target_spec()? {
+            return Ok(spec.target_info());
+        }
         match &self.target {
             Some(t) if Some(&**t) != self.getenv_unwrap_str("TARGET").ok().as_deref() => {
                 TargetInfo::from_rustc_target(t)
@@ -3616,20 +7074,71 @@ impl Build {
         }
     }
 
+    /// The target specification, if the target is a `.json` file.
+    fn get_target_spec(&self) -> Result<Option<&OwnedTargetInfo>, Error> {
+        match &self.target {
+            Some(t) if !self.for_host && t.ends_with(".json") => {
+                match self
+                    .target_spec
+                    .get_or_init(|| TargetInfo::from_json_spec(Path::new(&**t)))
+                {
+                    Ok(spec) => Ok(Some(spec)),
+                    Err(e) => Err(e.clone()),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn get_raw_target(&self) -> Result<Cow<'_, str>, Error> {
+        if self.for_host {
+            return self.get_raw_host().map(Cow::Borrowed);
+        }
         match &self.target {
             Some(t) => Ok(Cow::Borrowed(t)),
-            None => self.getenv_unwrap_str("TARGET").map(Cow::Owned),
+            // The parsed target keeps the `TARGET` it was parsed from.
+            None => match self
+                .build_cache
+                .target_info_parser
+                .parse_from_cargo_environment_variables()
+            {
+                Ok(target) => Ok(Cow::Borrowed(target.triple())),
+                Err(_) => self.getenv_unwrap_str("TARGET").map(Cow::Owned),
+            },
+        }
+    }
+
+    fn get_llvm_target(
+        &self,
+        target: &TargetInfo<'_>,
+        version: Option<&str>,
+    ) -> Result<Cow<'_, str>, Error> {
+        if let Some(llvm_target) = &self.llvm_target {
+            return Ok(Cow::Borrowed(llvm_target));
+        }
+        match self.get_target_spec()? {
+            Some(spec) => Ok(Cow::Borrowed(&spec.llvm_target)),
+            None => Ok(target.llvm_target(&self.get_raw_target()?, version)),
+        }
+    }
+
+    fn get_raw_host(&self) -> Result<&str, Error> {
+        match &self.host {
+            Some(h) => Ok(h),
+            None => match self
+                .build_cache
+                .host_cache
+                .get_or_init(|| self.getenv_unwrap_str("HOST").map(Into::into))
+            {
+                Ok(h) => Ok(h),
+                Err(e) => Err(e.clone()),
+            },
         }
     }
 
     fn get_is_cross_compile(&self) -> Result<bool, Error> {
         let target = self.get_raw_target()?;
-        let host: Cow<'_, str> = match &self.host {
-            Some(h) => Cow::Borrowed(h),
-            None => Cow::Owned(self.getenv_unwrap_str("HOST")?),
-        };
-        Ok(host != target)
+        Ok(self.get_raw_host()? != target)
     }
 
     fn get_opt_level(&self) -> Result<Cow<'_, str>, Error> {
@@ -3639,29 +7148,115 @@ impl Build {
         }
     }
 
+    /// The files to compile, with the sources grouped by
+    /// [`unity_build`](Build::unity_build) replaced by the generated files
+    /// including them.
+    fn get_unity_files(&self, dst: &Path) -> Result<Cow<'_, [Arc<Path>]>, Error> {
+        use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+
+        let chunk_size = match self.unity_build {
+            Some(0) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidArgument,
+                    "the chunk size of a unity build must be at least 1",
+                ))
+            }
+            Some(chunk_size) => chunk_size,
+            None => return Ok(Cow::Borrowed(&self.files)),
+        };
+
+        let (grouped, mut files): (Vec<_>, Vec<_>) = self.files.iter().cloned().partition(|file| {
+            let is_source = match file.extension().and_then(OsStr::to_str) {
+                Some("c") => true,
+                Some("cc" | "cpp" | "cxx" | "c++") => self.cpp,
+                _ => false,
+            };
+            is_source && !self.unity_excluded.contains(file)
+        });
+
+        // The generated files live in `dst`, so make the sources absolute for
+        // `#include` to find them.
+        let cwd = env::current_dir()?;
+        let extension = if self.cpp { "cpp" } else { "c" };
+        for chunk in grouped.chunks(chunk_size) {
+            for file in chunk {
+                self.cargo_output
+                    .print_metadata(&format_args!("cargo:rerun-if-changed={}", file.display()));
+            }
+            if let [file] = chunk {
+                files.push(file.clone());
+                continue;
+            }
+
+            let mut contents = String::new();
+            for file in chunk {
+                contents.push_str(&format!("#include \"{}\"\n", cwd.join(file).display()));
+            }
+
+            // Name the file after its contents, so that separate builds
+            // sharing an output directory don't overwrite each other.
+            let mut hasher = DefaultHasher::new();
+            hasher.write(contents.as_bytes());
+            let path = dst.join(format!("unity-{:016x}.{}", hasher.finish(), extension));
+            // Avoid touching the file, which would needlessly trigger
+            // recompilation in tools tracking its modification time.
+            if fs::read_to_string(&path).ok().as_deref() != Some(&*contents) {
+                fs::write(&path, contents)?;
+            }
+            files.push(path.into());
+        }
+
+        Ok(Cow::Owned(files))
+    }
+
     fn get_debug(&self) -> bool {
         self.debug.unwrap_or_else(|| self.getenv_boolean("DEBUG"))
     }
 
+    fn get_diagnostics_color(&self) -> Option<bool> {
+        match self.diagnostics_color {
+            ColorChoice::Always => Some(true),
+            ColorChoice::Never => Some(false),
+            ColorChoice::Auto => match self.getenv("CARGO_TERM_COLOR").as_deref()?.to_str()? {
+                "always" => Some(true),
+                "never" => Some(false),
+                _ => None,
+            },
+        }
+    }
+
     fn get_shell_escaped_flags(&self) -> bool {
         self.shell_escaped_flags
             .unwrap_or_else(|| self.getenv_boolean("CC_SHELL_ESCAPED_FLAGS"))
     }
 
-    fn get_dwarf_version(&self) -> Option<u32> {
+    fn get_dwarf_version(&self) -> Result<Option<u32>, Error> {
+        if let Some(version) = self.dwarf_version {
+            if !(2..=5).contains(&version) {
+                return Err(Error::new(
+                    ErrorKind::InvalidArgument,
+                    format!("unsupported DWARF version {}, expected 2 to 5", version),
+                ));
+            }
+            return Ok(Some(version.into()));
+        }
+
         // Tentatively matches the DWARF version defaults as of rustc 1.62.
-        let target = self.get_target().ok()?;
+        let target = match self.get_target() {
+            Ok(target) => target,
+            Err(_) => return Ok(None),
+        };
         if matches!(
             target.os,
             "android" | "dragonfly" | "freebsd" | "netbsd" | "openbsd"
         ) || target.vendor == "apple"
             || (target.os == "windows" && target.env == "gnu")
         {
-            Some(2)
+            Ok(Some(2))
         } else if target.os == "linux" {
-            Some(4)
+            Ok(Some(4))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -3702,6 +7297,20 @@ impl Build {
                 _ => false,
             }
         }
+        // The variables without target-specific variants, which are
+        // configuration rather than Cargo's identification of the target.
+        const CONFIGURATION: &[&str] = &[
+            "CRATE_CC_NO_DEFAULTS",
+            "CC_SHELL_ESCAPED_FLAGS",
+            "CROSS_COMPILE",
+            "RUSTC_LINKER",
+            "RUSTC_WRAPPER",
+            "CC_KNOWN_WRAPPER_CUSTOM",
+            "CARGO_ENCODED_RUSTFLAGS",
+        ];
+        if self.ignore_environment && CONFIGURATION.contains(&v) {
+            return None;
+        }
         if let Some(val) = self.build_cache.env_cache.read().unwrap().get(v).cloned() {
             return val;
         }
@@ -3771,14 +7380,45 @@ impl Build {
         ])
     }
 
+    /// The relocation model to compile for: the one set with [`Build::pic`],
+    /// overridden by `CC_RELOCATION_MODEL`, or else the target's default.
+    fn get_relocation_model(&self, target: &TargetInfo<'_>) -> Result<RelocationModel, Error> {
+        let pic = match self.pic {
+            Some(pic) => Some(pic),
+            None => match self.getenv_with_target_prefixes("CC_RELOCATION_MODEL") {
+                Ok(model) => {
+                    let model = model.to_string_lossy();
+                    return RelocationModel::parse(&model).ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidArgument,
+                            format!(
+                                "invalid relocation model `{model}` in CC_RELOCATION_MODEL, \
+                                 expected `static`, `pic` or `pie`"
+                            ),
+                        )
+                    });
+                }
+                Err(_) => self.get_target_spec()?.and_then(OwnedTargetInfo::is_pic),
+            },
+        };
+        Ok(if pic.unwrap_or_else(|| target.is_pic_by_default()) {
+            RelocationModel::Pic
+        } else {
+            RelocationModel::Static
+        })
+    }
+
     /// Get a single-valued environment variable with target variants.
     fn getenv_with_target_prefixes(&self, env: &str) -> Result<Arc<OsStr>, Error> {
         // Take from first environment variable in the environment.
-        let res = self
-            .target_envs(env)?
-            .iter()
-            .filter_map(|env| self.getenv(env))
-            .next();
+        let res = if self.ignore_environment {
+            None
+        } else {
+            self.target_envs(env)?
+                .iter()
+                .filter_map(|env| self.getenv(env))
+                .next()
+        };
 
         match res {
             Some(res) => Ok(res),
@@ -3797,6 +7437,9 @@ impl Build {
         // `CFLAGS`).
         let mut any_set = false;
         let mut res = vec![];
+        if self.ignore_environment {
+            return Ok(None);
+        }
         for env in self.target_envs(env)?.iter().rev() {
             if let Some(var) = self.getenv(env) {
                 any_set = true;
@@ -3857,19 +7500,21 @@ impl Build {
             }
         }
 
-        let sdk_path = run_output(
-            self.cmd("xcrun")
-                .arg("--show-sdk-path")
-                .arg("--sdk")
-                .arg(sdk),
-            &self.cargo_output,
-        )?;
+        let mut cmd = self.cmd("xcrun");
+        cmd.arg("--show-sdk-path").arg("--sdk").arg(sdk);
+        // `xcrun` looks up the SDK in the active developer directory, and is
+        // passed the one read through `getenv`, so that changing it reruns
+        // the build script.
+        if let Some(developer_dir) = self.getenv("DEVELOPER_DIR") {
+            cmd.env("DEVELOPER_DIR", developer_dir);
+        }
+        let sdk_path = run_output(&mut cmd, &self.cargo_output)?;
 
         let sdk_path = match String::from_utf8(sdk_path) {
             Ok(p) => p,
             Err(_) => {
                 return Err(Error::new(
-                    ErrorKind::IOError,
+                    ErrorKind::IOError(io::ErrorKind::InvalidData),
                     "Unable to determine Apple SDK path.",
                 ));
             }
@@ -3877,8 +7522,14 @@ impl Build {
         Ok(Arc::from(OsStr::new(sdk_path.trim())))
     }
 
+    fn apple_sdk_name<'a>(&'a self, target: &TargetInfo<'_>) -> &'a str {
+        self.apple_sdk
+            .as_deref()
+            .unwrap_or_else(|| target.apple_sdk_name())
+    }
+
     fn apple_sdk_root(&self, target: &TargetInfo<'_>) -> Result<Arc<OsStr>, Error> {
-        let sdk = target.apple_sdk_name();
+        let sdk = self.apple_sdk_name(target);
 
         if let Some(ret) = self
             .build_cache
@@ -3899,8 +7550,12 @@ impl Build {
         Ok(sdk_path)
     }
 
-    fn apple_deployment_target(&self, target: &TargetInfo<'_>) -> Arc<str> {
-        let sdk = target.apple_sdk_name();
+    fn get_apple_deployment_target(&self, target: &TargetInfo<'_>) -> Arc<str> {
+        if let Some(version) = &self.apple_deployment_target {
+            return version.clone();
+        }
+
+        let sdk = self.apple_sdk_name(target);
         if let Some(ret) = self
             .build_cache
             .apple_versions_cache
@@ -4038,6 +7693,24 @@ impl Build {
         version
     }
 
+    /// Whether the C runtime is linked statically, as with the `crt-static`
+    /// target feature.
+    ///
+    /// Outside of build scripts, where Cargo doesn't set the target features,
+    /// this falls back to the target's default.
+    fn is_crt_static(&self, target: &TargetInfo<'_>) -> bool {
+        if self.for_host {
+            return target.is_crt_static_by_default();
+        }
+        match self.getenv("CARGO_CFG_TARGET_FEATURE") {
+            Some(features) => features
+                .to_string_lossy()
+                .split(',')
+                .any(|feature| feature == "crt-static"),
+            None => target.is_crt_static_by_default(),
+        }
+    }
+
     fn wasm_musl_sysroot(&self) -> Result<Arc<OsStr>, Error> {
         if let Some(musl_sysroot_path) = self.getenv("WASM_MUSL_SYSROOT") {
             Ok(musl_sysroot_path)
@@ -4176,6 +7849,140 @@ fn android_clang_compiler_uses_target_arg_internally(clang_path: &Path) -> bool
     false
 }
 
+// `emcc` and `em++` configure the target of the Clang they run themselves, so
+// `--target` isn't passed to them. On Windows, they're run as `cmd /c emcc.bat`.
+fn is_emscripten_driver(tool: &Tool) -> bool {
+    let is_driver = |name: &OsStr| {
+        let name = name.to_string_lossy();
+        let stem = name.split('.').next().unwrap_or_default();
+        stem == "emcc" || stem == "em++"
+    };
+    tool.path.file_name().map_or(false, is_driver)
+        || tool.args.first().map_or(false, |arg| arg == "/c")
+            && tool.args.get(1).map_or(false, |arg| is_driver(arg))
+}
+
+/// Find the names of the external functions defined in the symbol tables
+/// printed by `dumpbin /SYMBOLS`, in lines like:
+///
+/// ```text
+/// 008 00000000 SECT3  notype ()    External     | foo
+/// ```
+fn parse_dumpbin_functions(symbols: &str) -> impl Iterator<Item = &str> {
+    symbols.lines().filter_map(|line| {
+        let (fields, name) = line.split_once('|')?;
+        let fields = fields.split_whitespace().collect::<Vec<_>>();
+        let defined = fields.iter().any(|field| field.starts_with("SECT"));
+        if !defined || !fields.contains(&"()") || !fields.contains(&"External") {
+            return None;
+        }
+        // C++ names are followed by their demangled form in parentheses.
+        name.split_whitespace().next()
+    })
+}
+
+// Whether the diagnostics of a failed compile blame LTO, e.g. GCC's
+// `lto-wrapper` or `lto1`, or Clang's ThinLTO or `-flto`.
+fn is_lto_failure(diagnostics: &str) -> bool {
+    diagnostics
+        .to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| {
+            matches!(
+                word,
+                "lto" | "lto1" | "flto" | "thinlto" | "ltrans" | "liblto" | "bitcode"
+            )
+        })
+}
+
+// A copy of the compile command with its LTO flags replaced by `-fno-lto`, or
+// without `/GL` for MSVC, or `None` if it has no LTO flags.
+fn command_without_lto(cmd: &Command) -> Option<Command> {
+    let is_gnu_lto = |arg: &OsStr| {
+        arg == "-flto"
+            || arg == "-ffat-lto-objects"
+            || arg.to_str().map_or(false, |arg| arg.starts_with("-flto="))
+    };
+    let is_msvc_lto = |arg: &OsStr| arg == "-GL" || arg == "/GL";
+    if !cmd
+        .get_args()
+        .any(|arg| is_gnu_lto(arg) || is_msvc_lto(arg))
+    {
+        return None;
+    }
+
+    let mut retry = Command::new(cmd.get_program());
+    let mut replaced = false;
+    for arg in cmd.get_args() {
+        if is_gnu_lto(arg) {
+            // In place of the flags, so that the source stays last.
+            if !replaced {
+                retry.arg("-fno-lto");
+                replaced = true;
+            }
+        } else if !is_msvc_lto(arg) {
+            retry.arg(arg);
+        }
+    }
+    for (key, value) in cmd.get_envs() {
+        match value {
+            Some(value) => retry.env(key, value),
+            None => retry.env_remove(key),
+        };
+    }
+    if let Some(dir) = cmd.get_current_dir() {
+        retry.current_dir(dir);
+    }
+    Some(retry)
+}
+
+// Warnings are given without the prefix of any compiler's flags, which are
+// added for each compiler.
+fn check_warning_name(warning: &str, action: &str) -> Result<(), Error> {
+    if warning.is_empty()
+        || warning.starts_with(['-', '/'])
+        || warning.starts_with("no-")
+        || warning.contains(|c: char| c == '=' || c.is_whitespace())
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidArgument,
+            format!(
+                "invalid warning to {} `{}`, expected a name like \
+                 `unused-parameter` or a number like `4100`",
+                action, warning
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The macros defined by [`Build::define_cargo_cfgs`] for the given target.
+fn cargo_cfg_definitions(prefix: &str, target: &TargetInfo<'_>) -> Vec<String> {
+    [
+        ("TARGET_ARCH", target.arch),
+        ("TARGET_VENDOR", target.vendor),
+        ("TARGET_OS", target.os),
+        ("TARGET_ENV", target.env),
+        ("TARGET_ABI", target.abi),
+    ]
+    .iter()
+    .filter(|(_, value)| !value.is_empty())
+    .map(|(cfg, value)| {
+        let value: String = value
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}{}_{}", prefix, cfg, value)
+    })
+    .collect()
+}
+
 // FIXME: Use parsed target.
 fn autodetect_android_compiler(raw_target: &str, gnu: &str, clang: &str) -> String {
     let new_clang_key = match raw_target {
@@ -4242,19 +8049,37 @@ fn map_darwin_target_from_rust_to_compiler_architecture<'a>(target: &TargetInfo<
     }
 }
 
+/// Whether the Apple deployment target is conveyed to the compiler in the
+/// versioned `--target` triple, instead of with a `-m*-version-min=` flag.
+///
+/// Exactly one of the two is used, so that the compiler never sees multiple,
+/// possibly conflicting, minimum versions.
+fn apple_version_in_llvm_target(cmd: &Tool, target: &TargetInfo<'_>) -> bool {
+    // Only Clang is passed `--target`.
+    cmd.is_like_clang() && target.apple_version_in_llvm_target()
+}
+
 #[derive(Clone, Copy, PartialEq)]
 enum AsmFileExt {
     /// `.asm` files. On MSVC targets, we assume these should be passed to MASM
     /// (`ml{,64}.exe`).
     DotAsm,
-    /// `.s` or `.S` files, which do not have the special handling on MSVC targets.
+    /// `.s` files, which are passed to the assembler as they are. These do not
+    /// have the special handling on MSVC targets.
     DotS,
+    /// `.S` or `.sx` files, which go through the C preprocessor first. These do
+    /// not have the special handling on MSVC targets either.
+    DotCapitalS,
 }
 
 impl AsmFileExt {
     fn from_path(file: &Path) -> Option<Self> {
         if let Some(ext) = file.extension() {
             if let Some(ext) = ext.to_str() {
+                // The case of `.s` matters, as with GCC and Clang.
+                if ext == "S" || ext.eq_ignore_ascii_case("sx") {
+                    return Some(AsmFileExt::DotCapitalS);
+                }
                 let ext = ext.to_lowercase();
                 match &*ext {
                     "asm" => return Some(AsmFileExt::DotAsm),
@@ -4265,6 +8090,17 @@ impl AsmFileExt {
         }
         None
     }
+
+    /// The language to pass with `-x` to GCC and Clang, so that the C
+    /// preprocessor runs exactly on the files which need it even on
+    /// case-insensitive file systems.
+    fn gnu_language(self) -> Option<&'static str> {
+        match self {
+            AsmFileExt::DotAsm => None,
+            AsmFileExt::DotS => Some("assembler"),
+            AsmFileExt::DotCapitalS => Some("assembler-with-cpp"),
+        }
+    }
 }
 
 /// Returns true if `cc` has been disabled by `CC_FORCE_DISABLE`.
@@ -4315,6 +8151,53 @@ fn check_disabled() -> Result<(), Error> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_cargo_cfg_definitions() {
+        let target = TargetInfo::from_rustc_target("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(
+            cargo_cfg_definitions("FOO_", &target),
+            [
+                "FOO_TARGET_ARCH_X86_64",
+                "FOO_TARGET_VENDOR_UNKNOWN",
+                "FOO_TARGET_OS_LINUX",
+                "FOO_TARGET_ENV_GNU",
+            ]
+        );
+
+        let target = TargetInfo::from_rustc_target("armv7-unknown-linux-musleabihf").unwrap();
+        assert_eq!(
+            cargo_cfg_definitions("", &target),
+            [
+                "TARGET_ARCH_ARM",
+                "TARGET_VENDOR_UNKNOWN",
+                "TARGET_OS_LINUX",
+                "TARGET_ENV_MUSL",
+                "TARGET_ABI_EABIHF",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_dumpbin_functions() {
+        let symbols = "\
+Dump of file foo.obj
+
+File Type: COFF OBJECT
+
+COFF SYMBOL TABLE
+000 01047B1D ABS    notype       Static       | @comp.id
+008 00000000 SECT3  notype ()    External     | foo
+009 00000000 UNDEF  notype ()    External     | printf
+00A 00000010 SECT3  notype ()    Static       | helper
+00B 00000000 SECT4  notype       External     | counter
+00C 00000020 SECT5  notype ()    External     | ?bar@@YAHH@Z (int __cdecl bar(int))
+";
+        assert_eq!(
+            parse_dumpbin_functions(symbols).collect::<Vec<_>>(),
+            ["foo", "?bar@@YAHH@Z"]
+        );
+    }
+
     #[test]
     fn test_android_clang_compiler_uses_target_arg_internally() {
         for version in 16..21 {
@@ -1,30 +1,37 @@
-use std::marker::PhantomData;
-
 use crate::{utilities::OnceLock, Error};
 
-pub(crate) struct JobToken(PhantomData<()>);
+pub(crate) struct JobToken<'a>(&'a JobTokenServer);
 
-impl JobToken {
-    fn new() -> Self {
-        Self(PhantomData)
+impl<'a> JobToken<'a> {
+    fn new(server: &'a JobTokenServer) -> Self {
+        Self(server)
     }
 }
 
-impl Drop for JobToken {
+impl Drop for JobToken<'_> {
     fn drop(&mut self) {
-        match JobTokenServer::new() {
+        match self.0 {
             JobTokenServer::Inherited(jobserver) => jobserver.release_token_raw(),
             JobTokenServer::InProcess(jobserver) => jobserver.release_token_raw(),
         }
     }
 }
 
-enum JobTokenServer {
+pub(crate) enum JobTokenServer {
     Inherited(inherited_jobserver::JobServer),
     InProcess(inprocess_jobserver::JobServer),
 }
 
 impl JobTokenServer {
+    /// Create a jobserver from a client explicitly passed to
+    /// [`Build::jobserver`](crate::Build::jobserver).
+    ///
+    /// Like a jobserver inherited from the environment, the calling process
+    /// is assumed to already hold one implicit token.
+    pub(crate) fn from_client(client: jobserver::Client) -> Self {
+        Self::Inherited(inherited_jobserver::JobServer::from_client(client))
+    }
+
     /// This function returns a static reference to the jobserver because
     ///  - creating a jobserver from env is a bit fd-unsafe (e.g. the fd might
     ///    be closed by other jobserver users in the process) and better do it
@@ -33,7 +40,7 @@ impl JobTokenServer {
     ///    present), we will create a global in-process only jobserver
     ///    that has to be static so that it will be shared by all cc
     ///    compilation.
-    fn new() -> &'static Self {
+    pub(crate) fn global() -> &'static Self {
         // TODO: Replace with a OnceLock once MSRV is 1.70
         static JOBSERVER: OnceLock<JobTokenServer> = OnceLock::new();
 
@@ -45,32 +52,39 @@ impl JobTokenServer {
     }
 }
 
-pub(crate) enum ActiveJobTokenServer {
-    Inherited(inherited_jobserver::ActiveJobServer<'static>),
-    InProcess(&'static inprocess_jobserver::JobServer),
+pub(crate) struct ActiveJobTokenServer<'a> {
+    server: &'a JobTokenServer,
+    active: ActiveJobServer<'a>,
 }
 
-impl ActiveJobTokenServer {
-    pub(crate) fn new() -> Self {
-        match JobTokenServer::new() {
+enum ActiveJobServer<'a> {
+    Inherited(inherited_jobserver::ActiveJobServer<'a>),
+    InProcess(&'a inprocess_jobserver::JobServer),
+}
+
+impl<'a> ActiveJobTokenServer<'a> {
+    pub(crate) fn new(server: &'a JobTokenServer) -> Self {
+        let active = match server {
             JobTokenServer::Inherited(inherited_jobserver) => {
-                Self::Inherited(inherited_jobserver.enter_active())
+                ActiveJobServer::Inherited(inherited_jobserver.enter_active())
             }
-            JobTokenServer::InProcess(inprocess_jobserver) => Self::InProcess(inprocess_jobserver),
-        }
+            JobTokenServer::InProcess(inprocess_jobserver) => {
+                ActiveJobServer::InProcess(inprocess_jobserver)
+            }
+        };
+        Self { server, active }
     }
 
-    pub(crate) async fn acquire(&mut self) -> Result<JobToken, Error> {
-        match self {
-            Self::Inherited(jobserver) => jobserver.acquire().await,
-            Self::InProcess(jobserver) => Ok(jobserver.acquire().await),
+    pub(crate) async fn acquire(&mut self) -> Result<JobToken<'a>, Error> {
+        match &mut self.active {
+            ActiveJobServer::Inherited(jobserver) => jobserver.acquire().await?,
+            ActiveJobServer::InProcess(jobserver) => jobserver.acquire().await,
         }
+        Ok(JobToken::new(self.server))
     }
 }
 
 mod inherited_jobserver {
-    use super::JobToken;
-
     use crate::{parallel::async_executor::YieldOnce, Error, ErrorKind};
 
     use std::{
@@ -78,7 +92,7 @@ mod inherited_jobserver {
         sync::{mpsc, Mutex, MutexGuard, PoisonError},
     };
 
-    pub(super) struct JobServer {
+    pub(crate) struct JobServer {
         /// Implicit token for this process which is obtained and will be
         /// released in parent. Since JobTokens only give back what they got,
         /// there should be at most one global implicit token in the wild.
@@ -103,10 +117,14 @@ mod inherited_jobserver {
 
     impl JobServer {
         pub(super) unsafe fn from_env() -> Option<Self> {
-            jobserver::Client::from_env().map(|inner| Self {
+            jobserver::Client::from_env().map(Self::from_client)
+        }
+
+        pub(super) fn from_client(inner: jobserver::Client) -> Self {
+            Self {
                 inner,
                 global_implicit_token: Mutex::new(true),
-            })
+            }
         }
 
         fn get_global_implicit_token(&self) -> MutexGuard<'_, bool> {
@@ -165,19 +183,21 @@ mod inherited_jobserver {
     }
 
     impl<'a> ActiveJobServer<'a> {
-        pub(super) async fn acquire(&mut self) -> Result<JobToken, Error> {
+        /// Acquire a token, which the caller is responsible for releasing
+        /// through [`JobServer::release_token_raw`].
+        pub(super) async fn acquire(&mut self) -> Result<(), Error> {
             let mut has_requested_token = false;
 
             loop {
                 // Fast path
                 if mem::replace(&mut *self.jobserver.get_global_implicit_token(), false) {
-                    break Ok(JobToken::new());
+                    break Ok(());
                 }
 
                 match self.jobserver.inner.try_acquire() {
                     Ok(Some(acquired)) => {
                         acquired.drop_without_releasing();
-                        break Ok(JobToken::new());
+                        break Ok(());
                     }
                     Ok(None) => YieldOnce::default().await,
                     Err(err) if err.kind() == io::ErrorKind::Unsupported => {
@@ -193,7 +213,7 @@ mod inherited_jobserver {
                             Ok(res) => {
                                 let acquired = res?;
                                 acquired.drop_without_releasing();
-                                break Ok(JobToken::new());
+                                break Ok(());
                             }
                             Err(mpsc::TryRecvError::Disconnected) => break Err(Error::new(
                                 ErrorKind::JobserverHelpThreadError,
@@ -216,8 +236,6 @@ mod inherited_jobserver {
 }
 
 mod inprocess_jobserver {
-    use super::JobToken;
-
     use crate::parallel::async_executor::YieldOnce;
 
     use std::{
@@ -250,14 +268,16 @@ mod inprocess_jobserver {
             Self(AtomicU32::new(parallelism))
         }
 
-        pub(super) async fn acquire(&self) -> JobToken {
+        /// Acquire a token, which the caller is responsible for releasing
+        /// through [`JobServer::release_token_raw`].
+        pub(super) async fn acquire(&self) {
             loop {
                 let res = self
                     .0
                     .fetch_update(AcqRel, Acquire, |tokens| tokens.checked_sub(1));
 
                 if res.is_ok() {
-                    break JobToken::new();
+                    break;
                 }
 
                 YieldOnce::default().await
@@ -269,3 +289,46 @@ mod inprocess_jobserver {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::{ActiveJobTokenServer, JobTokenServer};
+    use crate::parallel::async_executor::block_on;
+
+    #[test]
+    fn explicit_client_acquire_release() {
+        let client = jobserver::Client::new(2).unwrap();
+        let server = JobTokenServer::from_client(client.clone());
+
+        let acquire_release = async {
+            let mut tokens = ActiveJobTokenServer::new(&server);
+
+            // The implicit token, then both tokens from the client.
+            let mut acquired = Vec::new();
+            for _ in 0..3 {
+                acquired.push(tokens.acquire().await?);
+            }
+            assert_eq!(client.available().unwrap(), 0);
+
+            // The first released token takes the place of the implicit one.
+            acquired.pop();
+            assert_eq!(client.available().unwrap(), 0);
+
+            // The rest go back to the client.
+            acquired.clear();
+            assert_eq!(client.available().unwrap(), 2);
+
+            // The implicit token is reused before any other.
+            let token = tokens.acquire().await?;
+            assert_eq!(client.available().unwrap(), 2);
+            drop(token);
+            assert_eq!(client.available().unwrap(), 2);
+
+            Ok(())
+        };
+
+        block_on(acquire_release, async { Ok(()) }, &Cell::new(false)).unwrap();
+    }
+}
@@ -11,14 +11,12 @@ compile_error!("Only unix and windows support non-blocking pipes! For other OSes
 fn get_flags(fd: std::os::unix::io::RawFd) -> Result<i32, Error> {
     let flags = unsafe { libc::fcntl(fd, libc::F_GETFL, 0) };
     if flags == -1 {
+        let err = std::io::Error::last_os_error();
         Err(Error::new(
-            ErrorKind::IOError,
-            format!(
-                "Failed to get flags for pipe {}: {}",
-                fd,
-                std::io::Error::last_os_error()
-            ),
-        ))
+            ErrorKind::IOError(err.kind()),
+            format!("Failed to get flags for pipe {}: {}", fd, err),
+        )
+        .with_source(err))
     } else {
         Ok(flags)
     }
@@ -27,14 +25,12 @@ fn get_flags(fd: std::os::unix::io::RawFd) -> Result<i32, Error> {
 #[cfg(unix)]
 fn set_flags(fd: std::os::unix::io::RawFd, flags: std::os::raw::c_int) -> Result<(), Error> {
     if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } == -1 {
+        let err = std::io::Error::last_os_error();
         Err(Error::new(
-            ErrorKind::IOError,
-            format!(
-                "Failed to set flags for pipe {}: {}",
-                fd,
-                std::io::Error::last_os_error()
-            ),
-        ))
+            ErrorKind::IOError(err.kind()),
+            format!("Failed to set flags for pipe {}: {}", fd, err),
+        )
+        .with_source(err))
     } else {
         Ok(())
     }
@@ -68,23 +64,24 @@ pub fn bytes_available(stderr: &mut ChildStderr) -> Result<usize, Error> {
             )
         } == 0
         {
+            let err = std::io::Error::last_os_error();
             return Err(Error::new(
-                ErrorKind::IOError,
-                format!(
-                    "PeekNamedPipe failed with {}",
-                    std::io::Error::last_os_error()
-                ),
-            ));
+                ErrorKind::IOError(err.kind()),
+                format!("PeekNamedPipe failed with {}", err),
+            )
+            .with_source(err));
         }
     }
     #[cfg(unix)]
     {
         use std::os::unix::io::AsRawFd;
         if unsafe { libc::ioctl(stderr.as_raw_fd(), libc::FIONREAD, &mut bytes_available) } != 0 {
+            let err = std::io::Error::last_os_error();
             return Err(Error::new(
-                ErrorKind::IOError,
-                format!("ioctl failed with {}", std::io::Error::last_os_error()),
-            ));
+                ErrorKind::IOError(err.kind()),
+                format!("ioctl failed with {}", err),
+            )
+            .with_source(err));
         }
     }
     Ok(bytes_available.try_into().unwrap())
@@ -0,0 +1,139 @@
+//! Support for precompiled headers, see
+//! [`Build::precompiled_header`](crate::Build::precompiled_header).
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{tool::ToolFamily, Error, ErrorKind};
+
+/// The language a precompiled header is compiled as, which has to match the
+/// language of the sources using it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Language {
+    C,
+    Cpp,
+}
+
+impl Language {
+    /// The language of a header, which is C++ for the extensions only used by
+    /// C++ headers, and decided by `Build::cpp` for `.h`.
+    pub(crate) fn of_header(header: &Path, cpp: bool) -> Self {
+        match header.extension().and_then(|ext| ext.to_str()) {
+            Some("hpp" | "hh" | "hxx" | "h++" | "H") => Language::Cpp,
+            _ if cpp => Language::Cpp,
+            _ => Language::C,
+        }
+    }
+
+    /// The language of a source file, or `None` for other files like
+    /// assembly, which never use the precompiled header.
+    pub(crate) fn of_source(src: &Path) -> Option<Self> {
+        match src.extension().and_then(|ext| ext.to_str()) {
+            Some("c") => Some(Language::C),
+            Some("cc" | "cpp" | "cxx" | "c++" | "C") => Some(Language::Cpp),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Language::C => "C",
+            Language::Cpp => "C++",
+        }
+    }
+}
+
+/// Where the precompiled header for `header` is written in `dir`.
+pub(crate) fn pch_path(family: ToolFamily, dir: &Path, header: &Path) -> Result<PathBuf, Error> {
+    let name = header.file_name().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidArgument,
+            format!("precompiled header {} has no file name", header.display()),
+        )
+    })?;
+    let mut name = name.to_owned();
+    match family {
+        // GCC looks for `foo.h.gch` when including `foo.h`.
+        ToolFamily::Gnu => name.push(".gch"),
+        ToolFamily::Clang { .. } => name.push(".pch"),
+        ToolFamily::Msvc { .. } => {
+            return Err(Error::new(
+                ErrorKind::UnsupportedFeature,
+                "precompiled headers are only supported with GCC and Clang",
+            ))
+        }
+    }
+    Ok(dir.join(name))
+}
+
+/// Check that some of the sources use the precompiled header, as a
+/// precompiled header of one language can't be used by the other.
+pub(crate) fn check_sources(language: Language, sources: &[&Path]) -> Result<(), Error> {
+    if sources
+        .iter()
+        .any(|src| Language::of_source(src) == Some(language))
+    {
+        return Ok(());
+    }
+    Err(Error::new(
+        ErrorKind::InvalidArgument,
+        format!(
+            "the precompiled header is compiled as {}, but there are no {} sources to use it",
+            language.name(),
+            language.name()
+        ),
+    ))
+}
+
+/// The flags to compile `header` to the precompiled header `pch`.
+pub(crate) fn compile_flags(language: Language, header: &Path, pch: &Path) -> Vec<OsString> {
+    let language = match language {
+        Language::C => "c-header",
+        Language::Cpp => "c++-header",
+    };
+    vec![
+        "-x".into(),
+        language.into(),
+        header.into(),
+        "-o".into(),
+        pch.into(),
+    ]
+}
+
+/// The flags to use the precompiled header `pch` in a source compiled by the
+/// compiler family.
+pub(crate) fn use_flags(family: ToolFamily, pch: &Path) -> Vec<OsString> {
+    match family {
+        // GCC only uses the precompiled header if it's included as the header.
+        ToolFamily::Gnu => vec!["-include".into(), pch.with_extension("").into()],
+        ToolFamily::Clang { .. } | ToolFamily::Msvc { .. } => {
+            vec!["-include-pch".into(), pch.into()]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{check_sources, Language};
+
+    #[test]
+    fn languages() {
+        assert_eq!(
+            Language::of_header(Path::new("a.hpp"), false),
+            Language::Cpp
+        );
+        assert_eq!(Language::of_header(Path::new("a.h"), false), Language::C);
+        assert_eq!(Language::of_header(Path::new("a.h"), true), Language::Cpp);
+        assert_eq!(Language::of_source(Path::new("a.c")), Some(Language::C));
+        assert_eq!(Language::of_source(Path::new("a.cc")), Some(Language::Cpp));
+        assert_eq!(Language::of_source(Path::new("a.S")), None);
+
+        let sources = [Path::new("a.c"), Path::new("b.S")];
+        assert!(check_sources(Language::C, &sources).is_ok());
+        assert!(check_sources(Language::Cpp, &sources).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Writing of response files, which pass the arguments of a command in a
+//! file named with `@path` to get around command line length limits.
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    path::Path,
+    process::Command,
+};
+
+use crate::{tool::ToolFamily, Error, ErrorKind, RspEncoding};
+
+/// Command lines longer than this use a response file on Windows.
+///
+/// This is a bit less than the limit of 32767 UTF-16 code units there.
+const MAX_WINDOWS_COMMAND_LEN: usize = 32_000;
+
+/// Command lines and environments longer than this together use a response
+/// file on Unix.
+///
+/// Linux usually allows 2 MiB for both and macOS 1 MiB, so this leaves room
+/// for wrappers and the pointers to each string.
+const MAX_UNIX_COMMAND_LEN: usize = 512 * 1024;
+
+/// Arguments this long use a response file on Unix, as Linux rejects them
+/// whatever the total length.
+const MAX_UNIX_ARG_LEN: usize = 128 * 1024;
+
+/// The encoding understood by compilers of the family.
+///
+/// MSVC reads response files without a BOM in the ANSI code page, so only
+/// UTF-16LE round-trips all paths, while GCC doesn't understand BOMs at all.
+pub(crate) fn default_encoding(family: ToolFamily) -> RspEncoding {
+    match family {
+        ToolFamily::Msvc { clang_cl: false } => RspEncoding::Utf16Le,
+        ToolFamily::Msvc { clang_cl: true } | ToolFamily::Gnu | ToolFamily::Clang { .. } => {
+            RspEncoding::Utf8
+        }
+    }
+}
+
+/// Whether the command line is too long to pass directly, given the limit
+/// configured with `Build::command_length_limit`.
+pub(crate) fn is_too_long(cmd: &Command, limit: Option<usize>) -> bool {
+    is_too_long_with_env(cmd, limit, env::vars_os())
+}
+
+/// Like `is_too_long`, with the environment the command inherits.
+fn is_too_long_with_env(
+    cmd: &Command,
+    limit: Option<usize>,
+    inherited: impl Iterator<Item = (OsString, OsString)>,
+) -> bool {
+    if cfg!(windows) {
+        return command_length(cmd) > limit.unwrap_or(MAX_WINDOWS_COMMAND_LEN);
+    }
+    // The environment shares the space reserved for the arguments.
+    cmd.get_args().any(|arg| arg.len() >= MAX_UNIX_ARG_LEN)
+        || command_length(cmd) + environment_length(cmd, inherited)
+            > limit.unwrap_or(MAX_UNIX_COMMAND_LEN)
+}
+
+/// The length of the command line.
+fn command_length(cmd: &Command) -> usize {
+    let len = cmd.get_args().map(|arg| arg.len() + 3).sum::<usize>();
+    cmd.get_program().len() + len
+}
+
+/// The length of the environment of the command, given the one it inherits.
+fn environment_length(
+    cmd: &Command,
+    inherited: impl Iterator<Item = (OsString, OsString)>,
+) -> usize {
+    let mut vars = inherited.collect::<Vec<_>>();
+    for (key, value) in cmd.get_envs() {
+        vars.retain(|(k, _)| k != key);
+        if let Some(value) = value {
+            vars.push((key.to_owned(), value.to_owned()));
+        }
+    }
+    // Each variable is passed as `KEY=VALUE` with a terminating NUL.
+    vars.iter()
+        .map(|(key, value)| key.len() + value.len() + 2)
+        .sum::<usize>()
+}
+
+/// Move the arguments of `cmd` after the first `skip`, which belong to a
+/// compiler wrapper, to a response file at `path`.
+pub(crate) fn use_response_file(
+    cmd: Command,
+    skip: usize,
+    path: &Path,
+    family: ToolFamily,
+    encoding: RspEncoding,
+) -> Result<Command, Error> {
+    let args = cmd.get_args().skip(skip).collect::<Vec<_>>();
+    fs::write(path, encode(&args, family, encoding)?)?;
+
+    let mut new_cmd = Command::new(cmd.get_program());
+    new_cmd.args(cmd.get_args().take(skip));
+    let mut arg = OsString::from("@");
+    arg.push(path);
+    new_cmd.arg(arg);
+    for (key, value) in cmd.get_envs() {
+        match value {
+            Some(value) => new_cmd.env(key, value),
+            None => new_cmd.env_remove(key),
+        };
+    }
+    if let Some(dir) = cmd.get_current_dir() {
+        new_cmd.current_dir(dir);
+    }
+    Ok(new_cmd)
+}
+
+/// Encode the arguments as a response file, one per line and quoted the way
+/// the compiler family splits them.
+pub(crate) fn encode(
+    args: &[&OsStr],
+    family: ToolFamily,
+    encoding: RspEncoding,
+) -> Result<Vec<u8>, Error> {
+    let mut contents = String::new();
+    for arg in args {
+        let arg = arg.to_str().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidArgument,
+                format!(
+                    "argument {} can't be written to a response file as it isn't valid Unicode",
+                    arg.to_string_lossy()
+                ),
+            )
+        })?;
+        match family {
+            ToolFamily::Msvc { .. } => quote_windows(arg, &mut contents),
+            ToolFamily::Gnu | ToolFamily::Clang { .. } => quote_gnu(arg, &mut contents),
+        }
+        contents.push('\n');
+    }
+
+    Ok(match encoding {
+        // No BOM, since GCC would read it as part of the first argument.
+        RspEncoding::Utf8 => contents.into_bytes(),
+        RspEncoding::Utf16Le => [0xFF, 0xFE]
+            .iter()
+            .copied()
+            .chain(contents.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+    })
+}
+
+/// Quote an argument for GCC and Clang, which treat backslashes as escapes.
+fn quote_gnu(arg: &str, out: &mut String) {
+    out.push('"');
+    for c in arg.chars() {
+        if matches!(c, '"' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+/// Quote an argument for MSVC, which follows the `CommandLineToArgvW` rules
+/// where backslashes are only special before a quote.
+fn quote_windows(arg: &str, out: &mut String) {
+    out.push('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                out.extend(std::iter::repeat('\\').take(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                out.extend(std::iter::repeat('\\').take(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            out.push(c);
+        }
+    }
+    out.extend(std::iter::repeat('\\').take(backslashes * 2));
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::{OsStr, OsString},
+        iter,
+        process::Command,
+    };
+
+    use super::{encode, is_too_long_with_env};
+    use crate::{tool::ToolFamily, RspEncoding};
+
+    fn encode_str(args: &[&str], family: ToolFamily, encoding: RspEncoding) -> Vec<u8> {
+        let args = args.iter().map(OsStr::new).collect::<Vec<_>>();
+        encode(&args, family, encoding).unwrap()
+    }
+
+    #[test]
+    fn command_length() {
+        let is_too_long = |cmd: &Command, limit| is_too_long_with_env(cmd, limit, iter::empty());
+        let mut cmd = Command::new("cc");
+        cmd.arg("foo.c");
+        assert!(!is_too_long(&cmd, None));
+        assert!(is_too_long(&cmd, Some(5)));
+
+        // The environment only counts on Unix, where the default limit is
+        // much higher.
+        cmd.env("CC_TEST_LONG_VAR", "x".repeat(40_000));
+        assert!(!is_too_long(&cmd, None));
+        assert_eq!(is_too_long(&cmd, Some(32_000)), !cfg!(windows));
+        let inherited = (OsString::from("HUGE"), OsString::from("x".repeat(600_000)));
+        assert_eq!(
+            is_too_long_with_env(&cmd, None, iter::once(inherited)),
+            !cfg!(windows)
+        );
+
+        let mut cmd = Command::new("cc");
+        cmd.arg(format!("-DX={}", "x".repeat(200_000)));
+        assert!(is_too_long(&cmd, None));
+    }
+
+    #[test]
+    fn gnu_quoting() {
+        let contents = encode_str(
+            &["-DX=\"a b\"", "C:\\src\\ü.c"],
+            ToolFamily::Gnu,
+            RspEncoding::Utf8,
+        );
+        assert_eq!(
+            String::from_utf8(contents).unwrap(),
+            "\"-DX=\\\"a b\\\"\"\n\"C:\\\\src\\\\ü.c\"\n"
+        );
+    }
+
+    #[test]
+    fn windows_quoting() {
+        let msvc = ToolFamily::Msvc { clang_cl: false };
+        let contents = encode_str(
+            &["-DX=\"a b\"", "C:\\src\\ü.c", "-IC:\\dir\\"],
+            msvc,
+            RspEncoding::Utf8,
+        );
+        assert_eq!(
+            String::from_utf8(contents).unwrap(),
+            "\"-DX=\\\"a b\\\"\"\n\"C:\\src\\ü.c\"\n\"-IC:\\dir\\\\\"\n"
+        );
+    }
+
+    #[test]
+    fn utf16le_has_bom() {
+        let msvc = ToolFamily::Msvc { clang_cl: false };
+        let contents = encode_str(&["ü"], msvc, RspEncoding::Utf16Le);
+        assert_eq!(contents, [0xFF, 0xFE, b'"', 0, 0xFC, 0, b'"', 0, b'\n', 0]);
+    }
+}
@@ -0,0 +1,52 @@
+//! The check for sources needing RTTI when it's disabled, see
+//! [`Build::lint_rtti_usage`](crate::Build::lint_rtti_usage).
+
+/// The operators that need RTTI.
+const RTTI_OPERATORS: &[&str] = &["dynamic_cast", "typeid"];
+
+/// The first use of an operator needing RTTI in a C++ source, as its line
+/// number and the operator.
+///
+/// This only looks for the operators as words outside of line comments, so
+/// it may point at one in a string or a block comment, but the compiler
+/// error it comes before is only a guess too.
+pub(crate) fn find_rtti_usage(source: &str) -> Option<(usize, &'static str)> {
+    source.lines().enumerate().find_map(|(i, line)| {
+        let code = line.split("//").next().unwrap_or_default();
+        let operator = RTTI_OPERATORS
+            .iter()
+            .find(|operator| has_word(code, operator))?;
+        Some((i + 1, *operator))
+    })
+}
+
+/// Whether `word` appears in `code`, and not as a part of a longer
+/// identifier.
+fn has_word(code: &str, word: &str) -> bool {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    code.match_indices(word).any(|(start, _)| {
+        let before = code[..start].chars().next_back();
+        let after = code[start + word.len()..].chars().next();
+        !before.map_or(false, is_identifier) && !after.map_or(false, is_identifier)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_rtti_usage;
+
+    #[test]
+    fn rtti_usage() {
+        assert_eq!(
+            find_rtti_usage("struct A {};\nauto &t = typeid(A);\n"),
+            Some((2, "typeid"))
+        );
+        assert_eq!(
+            find_rtti_usage("B *b = dynamic_cast<B *>(a);"),
+            Some((1, "dynamic_cast"))
+        );
+        assert_eq!(find_rtti_usage("// dynamic_cast is slow\n"), None);
+        assert_eq!(find_rtti_usage("int my_typeid_count;\n"), None);
+        assert_eq!(find_rtti_usage("auto b = static_cast<B *>(a);\n"), None);
+    }
+}
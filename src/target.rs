@@ -1,18 +1,68 @@
-//! Parsing of `rustc` target names to match the values exposed to Cargo
+//! The `rustc` targets known to this crate.
+//!
+//! Target names are parsed internally to match the values exposed to Cargo
 //! build scripts (`CARGO_CFG_*`).
 
+mod abi;
 mod apple;
+mod arm;
+mod features;
 mod generated;
+mod gnu;
+mod hardening;
+mod libc;
 mod llvm;
 mod parser;
+mod spec;
 
+pub(crate) use libc::LibcFlavor;
 pub(crate) use parser::TargetInfoParser;
+pub(crate) use spec::OwnedTargetInfo;
+
+/// How code refers to addresses, which decides whether it can be loaded at
+/// any address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RelocationModel {
+    /// Code that has to be loaded at a fixed address.
+    Static,
+    /// Position-independent code for shared objects.
+    Pic,
+    /// Position-independent code for executables.
+    Pie,
+}
+
+impl RelocationModel {
+    /// Parse the name used by `rustc -Crelocation-model`.
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "static" => Some(Self::Static),
+            "pic" => Some(Self::Pic),
+            "pie" => Some(Self::Pie),
+            _ => None,
+        }
+    }
+}
+
+/// The format of the object files of a target, as `rustc` names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryFormat {
+    Elf,
+    MachO,
+    /// PE/COFF, used by Windows and UEFI.
+    Coff,
+    Wasm,
+    /// The format of AIX.
+    Xcoff,
+}
 
 /// Information specific to a `rustc` target.
 ///
 /// See <https://doc.rust-lang.org/cargo/appendix/glossary.html#target>.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub(crate) struct TargetInfo<'a> {
+    /// The target name the information was parsed from, e.g. the `TARGET`
+    /// set by Cargo, see [`TargetInfo::triple`].
+    triple: &'a str,
     /// The full architecture, including the subarchitecture.
     ///
     /// This differs from `cfg!(target_arch)`, which only specifies the
@@ -39,3 +89,438 @@ pub(crate) struct TargetInfo<'a> {
     /// This is the same as the value of `cfg!(target_abi)`.
     pub abi: &'a str,
 }
+
+/// Operating systems that are small enough to be treated like bare-metal for
+/// most purposes, e.g. by using `none` as their LLVM OS.
+const EMBEDDED_OSES: &[&str] = &["solid_asp3", "horizon", "teeos", "nuttx", "espidf"];
+
+/// Hosted operating systems whose binaries are ELF shared objects and
+/// executables, and thus support calling through the PLT.
+const ELF_OSES: &[&str] = &[
+    "linux",
+    "android",
+    "freebsd",
+    "netbsd",
+    "openbsd",
+    "dragonfly",
+    "solaris",
+    "illumos",
+    "fuchsia",
+];
+
+impl<'a> TargetInfo<'a> {
+    /// The target name as given to `rustc --target`, like
+    /// `x86_64-unknown-linux-gnu`, or the name of a custom target.
+    pub(crate) fn triple(&self) -> &'a str {
+        self.triple
+    }
+}
+
+impl TargetInfo<'_> {
+    /// Whether the target runs without any operating system.
+    pub(crate) fn is_bare_metal(&self) -> bool {
+        self.os == "none"
+    }
+
+    /// Whether the target is bare-metal or runs on an RTOS-like operating
+    /// system.
+    pub(crate) fn is_embedded(&self) -> bool {
+        self.is_bare_metal() || EMBEDDED_OSES.contains(&self.os)
+    }
+
+    /// Whether the target runs on Horizon, the operating system of the
+    /// Nintendo 3DS and Switch.
+    pub(crate) fn is_horizon(&self) -> bool {
+        self.os == "horizon"
+    }
+
+    /// Whether `rustc` defaults to the position-independent relocation model
+    /// for this target, as opposed to the static one.
+    pub(crate) fn is_pic_by_default(&self) -> bool {
+        // Disable generation of PIC on bare-metal for now: rust-lld doesn't support this yet
+        //
+        // `rustc` also defaults to disable PIC on WASM, apart from Emscripten, which supports
+        // dynamic linking:
+        // <https://github.com/rust-lang/rust/blob/1.82.0/compiler/rustc_target/src/spec/base/wasm.rs#L101-L108>
+        // <https://github.com/rust-lang/rust/blob/1.82.0/compiler/rustc_target/src/spec/targets/wasm32_unknown_emscripten.rs>
+        //
+        // ESP-IDF applications are statically linked firmware images, and `rustc` uses the
+        // static relocation model there as well. So do Unikraft's unikernels, and the 3DS,
+        // which has no dynamic loader, unlike the Switch whose executables are PIE.
+        self.os != "windows"
+            && !self.is_bare_metal()
+            && !self.is_uefi()
+            && self.os != "espidf"
+            && self.vendor != "unikraft"
+            && !(self.is_horizon() && self.arch == "arm")
+            && (!self.is_wasm_family() || self.os == "emscripten")
+    }
+
+    /// Whether `rustc` links the C runtime statically by default for this
+    /// target, i.e. enables the `crt-static` target feature.
+    ///
+    /// Only the musl targets with a prebuilt self-contained musl do, the
+    /// other musl targets link against the system's `libc.so`.
+    pub(crate) fn is_crt_static_by_default(&self) -> bool {
+        self.env == "musl"
+            && self.vendor == "unknown"
+            && matches!(
+                self.full_arch,
+                "aarch64"
+                    | "arm"
+                    | "armv5te"
+                    | "armv7"
+                    | "i586"
+                    | "i686"
+                    | "powerpc64le"
+                    | "x86_64"
+            )
+    }
+
+    /// The `_WIN32_WINNT` value of the oldest Windows version the target
+    /// supports, or `None` if it isn't a Windows target.
+    pub(crate) fn win32_winnt(&self) -> Option<&'static str> {
+        match (self.os, self.vendor) {
+            ("windows", "win7") => Some("0x0601"),
+            ("windows", _) => Some("0x0A00"),
+            _ => None,
+        }
+    }
+
+    /// The version of QNX Neutrino targeted, e.g. `7.1`, or `None` if it
+    /// isn't a QNX target.
+    ///
+    /// The version is part of the target name, e.g. `qnx710` in
+    /// `aarch64-unknown-nto-qnx710`, and of the `env`, e.g. `nto71`.
+    pub(crate) fn qnx_version(&self) -> Option<&'static str> {
+        if self.os != "nto" {
+            return None;
+        }
+        // `nto71_iosock` is QNX 7.1 with the io-sock network stack.
+        match self.env.split('_').next() {
+            Some("nto70") => Some("7.0"),
+            Some("nto71") => Some("7.1"),
+            Some("nto80") => Some("8.0"),
+            _ => None,
+        }
+    }
+
+    /// Whether the target runs on Redox, whose C library is relibc.
+    pub(crate) fn is_redox(&self) -> bool {
+        self.os == "redox"
+    }
+
+    /// Whether the target is WebAssembly, including Emscripten's `asmjs`.
+    pub(crate) fn is_wasm_family(&self) -> bool {
+        self.arch == "wasm32" || self.arch == "wasm64"
+    }
+
+    /// Whether the target is WebAssembly with threads, which needs the atomics
+    /// and bulk memory features, like `wasm32-wasip1-threads`.
+    pub(crate) fn wasm_threads(&self) -> bool {
+        self.is_wasm_family() && (self.triple.ends_with("-threads") || self.os == "linux")
+    }
+
+    /// Whether the target builds UEFI applications, which are freestanding
+    /// PE images using the Windows calling convention.
+    pub(crate) fn is_uefi(&self) -> bool {
+        self.os == "uefi"
+    }
+
+    /// The format of the target's object files, which is ELF unless the
+    /// platform has its own.
+    pub(crate) fn binary_format(&self) -> BinaryFormat {
+        if self.vendor == "apple" {
+            BinaryFormat::MachO
+        } else if matches!(self.os, "windows" | "uefi") {
+            BinaryFormat::Coff
+        } else if self.os == "aix" {
+            BinaryFormat::Xcoff
+        } else if self.is_wasm_family() {
+            BinaryFormat::Wasm
+        } else {
+            BinaryFormat::Elf
+        }
+    }
+
+    /// Whether the target's object files are ELF, as opposed to Mach-O, COFF,
+    /// XCOFF, WebAssembly or PTX ones.
+    pub(crate) fn is_elf(&self) -> bool {
+        // `rustc` counts PTX as ELF, but it can't be handled like ELF.
+        self.binary_format() == BinaryFormat::Elf && self.os != "cuda" && self.arch != "nvptx64"
+    }
+
+    /// Whether the target's C code may be called with a stack aligned to
+    /// fewer bytes than it assumes, as with the SSE code of `i686` targets
+    /// called by code following the original i386 ABI, which only aligns it
+    /// to 4 bytes. `i586` code doesn't use SSE, and Apple's ABI always
+    /// aligns it to 16 bytes.
+    pub(crate) fn needs_stack_realign(&self) -> bool {
+        self.full_arch == "i686" && self.vendor != "apple"
+    }
+
+    /// Whether the target links dynamically against ELF shared objects.
+    pub(crate) fn is_dynamic_elf(&self) -> bool {
+        ELF_OSES.contains(&self.os)
+    }
+}
+
+/// The names of all the `rustc` targets known to this crate, in sorted order.
+///
+/// This is useful to validate or complete a target name given by a user, but
+/// note that `rustc` may know targets added after this version of the crate.
+///
+/// # Example
+///
+/// ```
+/// assert!(cc::target::known_triples().any(|t| t == "x86_64-unknown-linux-gnu"));
+/// ```
+pub fn known_triples() -> impl Iterator<Item = &'static str> {
+    generated::LLVM_TARGETS.iter().map(|(target, _)| *target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{known_triples, BinaryFormat, RelocationModel, TargetInfo};
+
+    #[test]
+    fn known_triples_are_sorted() {
+        let triples = known_triples().collect::<Vec<_>>();
+        assert!(!triples.is_empty());
+        assert!(triples.windows(2).all(|pair| pair[0] < pair[1]));
+        for triple in ["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"] {
+            assert!(triples.contains(&triple), "{}", triple);
+        }
+    }
+
+    #[test]
+    fn bare_metal_and_embedded() {
+        let targets = [
+            ("thumbv7em-none-eabihf", true, true),
+            ("riscv32imac-unknown-none-elf", true, true),
+            ("thumbv7em-nuttx-eabihf", false, true),
+            ("riscv32imc-esp-espidf", false, true),
+            ("armv6k-nintendo-3ds", false, true),
+            ("aarch64-nintendo-switch-freestanding", false, true),
+            ("x86_64-unknown-linux-gnu", false, false),
+            ("aarch64-apple-darwin", false, false),
+        ];
+
+        for (target, bare_metal, embedded) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.is_bare_metal(), bare_metal, "{}", target);
+            assert_eq!(info.is_embedded(), embedded, "{}", target);
+        }
+    }
+
+    #[test]
+    fn qnx() {
+        let info = TargetInfo::from_rustc_target("aarch64-unknown-nto-qnx710").unwrap();
+        assert_eq!((info.os, info.env), ("nto", "nto71"));
+        assert_eq!(info.qnx_version(), Some("7.1"));
+
+        let info = TargetInfo::from_rustc_target("x86_64-pc-nto-qnx710_iosock").unwrap();
+        assert_eq!((info.os, info.env), ("nto", "nto71_iosock"));
+        assert_eq!(info.qnx_version(), Some("7.1"));
+
+        let info = TargetInfo::from_rustc_target("x86_64-pc-nto-qnx800").unwrap();
+        assert_eq!(info.qnx_version(), Some("8.0"));
+
+        let info = TargetInfo::from_rustc_target("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(info.qnx_version(), None);
+    }
+
+    #[test]
+    fn pic_and_dynamic_elf() {
+        let targets = [
+            ("x86_64-unknown-linux-gnu", true, true),
+            ("aarch64-linux-android", true, true),
+            ("x86_64-unknown-freebsd", true, true),
+            ("aarch64-apple-darwin", true, false),
+            ("x86_64-pc-windows-msvc", false, false),
+            ("wasm32-wasip1", false, false),
+            ("wasm32-unknown-unknown", false, false),
+            ("wasm32-unknown-emscripten", true, false),
+            // The kernel's BPF programs are bare-metal, while Solana's are
+            // loaded as shared objects.
+            ("bpfel-unknown-none", false, false),
+            ("sbf-solana-solana", true, false),
+            ("riscv32imc-esp-espidf", false, false),
+            ("thumbv7em-none-eabihf", false, false),
+            ("aarch64-unknown-nto-qnx710", true, false),
+            // musl targets are PIC like the other Linux ones, linked as
+            // static PIE when `crt-static` is enabled.
+            ("x86_64-unknown-linux-musl", true, true),
+            ("aarch64-unknown-linux-musl", true, true),
+            ("mips-unknown-linux-musl", true, true),
+            ("aarch64-unknown-linux-ohos", true, true),
+            ("x86_64-unikraft-linux-musl", false, true),
+            ("armv6k-nintendo-3ds", false, false),
+            ("aarch64-nintendo-switch-freestanding", true, false),
+        ];
+
+        for (target, pic, dynamic_elf) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.is_pic_by_default(), pic, "{}", target);
+            assert_eq!(info.is_dynamic_elf(), dynamic_elf, "{}", target);
+        }
+    }
+
+    #[test]
+    fn wasm_variants() {
+        for (target, os, llvm_target, threads) in [
+            (
+                "wasm32-unknown-unknown",
+                "unknown",
+                "wasm32-unknown-unknown",
+                false,
+            ),
+            ("wasm32v1-none", "none", "wasm32-unknown-unknown", false),
+            ("wasm32-wasip1", "wasi", "wasm32-wasip1", false),
+            ("wasm32-wasip1-threads", "wasi", "wasm32-wasi", true),
+            ("wasm32-wasip2", "wasi", "wasm32-wasip2", false),
+        ] {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.arch, "wasm32", "{}", target);
+            assert_eq!(info.os, os, "{}", target);
+            assert_eq!(info.llvm_target(target, None), llvm_target, "{}", target);
+            assert_eq!(info.wasm_threads(), threads, "{}", target);
+            assert!(!info.is_pic_by_default(), "{}", target);
+            assert_eq!(info.binary_format(), BinaryFormat::Wasm, "{}", target);
+        }
+        let info = TargetInfo::from_rustc_target("wasm32v1-none").unwrap();
+        assert_eq!(info.full_arch, "wasm32v1");
+        assert!(info.is_bare_metal());
+    }
+
+    #[test]
+    fn stack_realign() {
+        for (target, realign) in [
+            ("i686-unknown-linux-gnu", true),
+            ("i686-pc-windows-gnu", true),
+            ("i686-linux-android", true),
+            ("i586-unknown-linux-gnu", false),
+            ("i686-apple-darwin", false),
+            ("x86_64-unknown-linux-gnu", false),
+        ] {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.needs_stack_realign(), realign, "{}", target);
+        }
+    }
+
+    #[test]
+    fn binary_formats() {
+        for (target, format) in [
+            ("x86_64-unknown-linux-gnu", BinaryFormat::Elf),
+            ("thumbv7em-none-eabihf", BinaryFormat::Elf),
+            ("aarch64-apple-darwin", BinaryFormat::MachO),
+            ("aarch64-apple-ios-sim", BinaryFormat::MachO),
+            ("x86_64-pc-windows-msvc", BinaryFormat::Coff),
+            ("x86_64-pc-windows-gnu", BinaryFormat::Coff),
+            ("x86_64-unknown-uefi", BinaryFormat::Coff),
+            ("wasm32-unknown-unknown", BinaryFormat::Wasm),
+            ("wasm32-wasip1", BinaryFormat::Wasm),
+            ("powerpc64-ibm-aix", BinaryFormat::Xcoff),
+        ] {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.binary_format(), format, "{}", target);
+        }
+    }
+
+    #[test]
+    fn elf() {
+        for target in [
+            "x86_64-unknown-linux-gnu",
+            "aarch64-linux-android",
+            "thumbv7em-none-eabihf",
+            "x86_64-unknown-freebsd",
+        ] {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert!(info.is_elf(), "{}", target);
+        }
+        for target in [
+            "aarch64-apple-darwin",
+            "x86_64-pc-windows-gnu",
+            "x86_64-unknown-uefi",
+            "powerpc64-ibm-aix",
+            "wasm32-unknown-unknown",
+            "nvptx64-nvidia-cuda",
+        ] {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert!(!info.is_elf(), "{}", target);
+        }
+    }
+
+    #[test]
+    fn crt_static_by_default() {
+        let targets = [
+            ("x86_64-unknown-linux-musl", true),
+            ("i686-unknown-linux-musl", true),
+            ("aarch64-unknown-linux-musl", true),
+            ("armv7-unknown-linux-musleabihf", true),
+            ("powerpc64le-unknown-linux-musl", true),
+            ("mips-unknown-linux-musl", false),
+            ("riscv64gc-unknown-linux-musl", false),
+            ("aarch64-unknown-linux-ohos", false),
+            ("x86_64-unknown-linux-gnu", false),
+            ("x86_64-pc-windows-msvc", false),
+        ];
+
+        for (target, crt_static) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.is_crt_static_by_default(), crt_static, "{}", target);
+        }
+    }
+
+    #[test]
+    fn windows_versions() {
+        let targets = [
+            ("x86_64-win7-windows-msvc", Some("0x0601")),
+            ("i686-win7-windows-gnu", Some("0x0601")),
+            ("x86_64-pc-windows-msvc", Some("0x0A00")),
+            ("aarch64-pc-windows-gnullvm", Some("0x0A00")),
+            ("x86_64-uwp-windows-msvc", Some("0x0A00")),
+            ("x86_64-unknown-linux-gnu", None),
+            ("x86_64-unknown-uefi", None),
+        ];
+
+        for (target, version) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.win32_winnt(), version, "{}", target);
+            if version.is_some() {
+                assert!(!info.is_pic_by_default(), "{}", target);
+            }
+        }
+    }
+
+    #[test]
+    fn relocation_models() {
+        assert_eq!(
+            RelocationModel::parse("static"),
+            Some(RelocationModel::Static)
+        );
+        assert_eq!(RelocationModel::parse("pic"), Some(RelocationModel::Pic));
+        assert_eq!(RelocationModel::parse("pie"), Some(RelocationModel::Pie));
+        assert_eq!(RelocationModel::parse("PIC"), None);
+        assert_eq!(RelocationModel::parse("dynamic-no-pic"), None);
+    }
+
+    #[test]
+    fn equality_and_hashing() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |info: &TargetInfo<'_>| {
+            let mut hasher = DefaultHasher::new();
+            info.hash(&mut hasher);
+            hasher.finish()
+        };
+        let a = TargetInfo::from_rustc_target("x86_64-unknown-linux-gnu").unwrap();
+        let b = TargetInfo::from_rustc_target("x86_64-unknown-linux-gnu").unwrap();
+        let c = TargetInfo::from_rustc_target("x86_64-unknown-linux-musl").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(a, c);
+        assert_ne!(hash(&a), hash(&c));
+    }
+}
@@ -0,0 +1,91 @@
+//! The calling conventions of RISC-V and MIPS targets.
+
+use super::TargetInfo;
+
+impl TargetInfo<'_> {
+    /// The ABI to pass with `-mabi=` to GCC-style compilers, matching the one
+    /// `rustc` uses, if the target has a choice.
+    ///
+    /// On RISC-V, this follows the base ISA and the floating-point
+    /// extensions in the architecture, e.g. `lp64d` for `riscv64gc` but
+    /// `lp64` for `riscv64imac`. On MIPS, this follows the pointer width,
+    /// and the `abin32` ABI of custom targets.
+    pub(crate) fn mabi(&self) -> Option<&'static str> {
+        match self.arch {
+            "riscv32" | "riscv64" => {
+                // The extensions after `riscv32`/`riscv64`, e.g. `imac`, `gc`
+                // or the `a23` profile, which includes `d`.
+                let extensions = self.full_arch.get("riscv64".len()..).unwrap_or_default();
+                let base = extensions.split('_').next().unwrap_or_default();
+                let is_profile =
+                    base.starts_with('a') && base[1..].starts_with(|c: char| c.is_ascii_digit());
+                // The `e` base ISA has fewer registers, and no hard-float ABI.
+                let float = if base.starts_with('e') {
+                    "e"
+                } else if is_profile || base.contains(['g', 'd']) {
+                    "d"
+                } else if base.contains('f') {
+                    "f"
+                } else {
+                    ""
+                };
+                Some(match (self.arch, float) {
+                    ("riscv64", "d") => "lp64d",
+                    ("riscv64", "f") => "lp64f",
+                    ("riscv64", "e") => "lp64e",
+                    ("riscv64", _) => "lp64",
+                    (_, "d") => "ilp32d",
+                    (_, "f") => "ilp32f",
+                    (_, "e") => "ilp32e",
+                    _ => "ilp32",
+                })
+            }
+            "mips" | "mips32r6" => Some("32"),
+            "mips64" | "mips64r6" if self.abi == "abin32" => Some("n32"),
+            "mips64" | "mips64r6" => Some("64"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TargetInfo;
+
+    #[test]
+    fn mabi() {
+        let targets = [
+            ("riscv64gc-unknown-linux-gnu", Some("lp64d")),
+            ("riscv64gc-unknown-none-elf", Some("lp64d")),
+            ("riscv64imac-unknown-none-elf", Some("lp64")),
+            ("riscv32gc-unknown-linux-gnu", Some("ilp32d")),
+            ("riscv32imafc-unknown-none-elf", Some("ilp32f")),
+            ("riscv32imac-esp-espidf", Some("ilp32")),
+            ("riscv32i-unknown-none-elf", Some("ilp32")),
+            ("riscv32emc-unknown-none-elf", Some("ilp32e")),
+            ("mips-unknown-linux-gnu", Some("32")),
+            ("mipsel-sony-psp", Some("32")),
+            ("mipsisa32r6el-unknown-linux-gnu", Some("32")),
+            ("mips64-unknown-linux-gnuabi64", Some("64")),
+            ("mips64el-unknown-linux-muslabi64", Some("64")),
+            ("mipsisa64r6-unknown-linux-gnuabi64", Some("64")),
+            ("x86_64-unknown-linux-gnu", None),
+            ("aarch64-unknown-linux-gnu", None),
+        ];
+        for (target, mabi) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.mabi(), mabi, "{}", target);
+        }
+
+        let n32 = TargetInfo {
+            triple: "mips64-unknown-linux-gnuabin32",
+            full_arch: "mips64",
+            arch: "mips64",
+            vendor: "unknown",
+            os: "linux",
+            env: "gnu",
+            abi: "abin32",
+        };
+        assert_eq!(n32.mabi(), Some("n32"));
+    }
+}
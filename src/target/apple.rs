@@ -0,0 +1,77 @@
+use super::TargetInfo;
+
+impl TargetInfo<'_> {
+    pub(crate) fn apple_sdk_name(&self) -> &'static str {
+        match (self.os, self.abi) {
+            ("macos", "") => "macosx",
+            ("ios", "") => "iphoneos",
+            ("ios", "sim") => "iphonesimulator",
+            ("ios", "macabi") => "macosx",
+            ("tvos", "") => "appletvos",
+            ("tvos", "sim") => "appletvsimulator",
+            ("watchos", "") => "watchos",
+            ("watchos", "sim") => "watchsimulator",
+            ("visionos", "") => "xros",
+            ("visionos", "sim") => "xrsimulator",
+            (os, _) => panic!("invalid Apple target OS {}", os),
+        }
+    }
+
+    pub(crate) fn apple_version_flag(&self, min_version: &str) -> String {
+        // There are many aliases for these, and `-mtargetos=` is preferred on Clang nowadays, but
+        // for compatibility with older Clang, we use the earliest supported name here.
+        //
+        // NOTE: GCC does not support `-miphoneos-version-min=` etc. (because it does not support
+        // iOS in general), but we specify them anyhow in case we actually have a Clang-like
+        // compiler disguised as a GNU-like compiler, or in case GCC adds support for these in the
+        // future.
+        //
+        // See also:
+        // https://clang.llvm.org/docs/ClangCommandLineReference.html#cmdoption-clang-mmacos-version-min
+        // https://clang.llvm.org/docs/AttributeReference.html#availability
+        // https://gcc.gnu.org/onlinedocs/gcc/Darwin-Options.html#index-mmacosx-version-min
+        match (self.os, self.abi) {
+            ("macos", "") => format!("-mmacosx-version-min={min_version}"),
+            ("ios", "") => format!("-miphoneos-version-min={min_version}"),
+            ("ios", "sim") => format!("-mios-simulator-version-min={min_version}"),
+            ("ios", "macabi") => format!("-mtargetos=ios{min_version}-macabi"),
+            ("tvos", "") => format!("-mappletvos-version-min={min_version}"),
+            ("tvos", "sim") => format!("-mappletvsimulator-version-min={min_version}"),
+            ("watchos", "") => format!("-mwatchos-version-min={min_version}"),
+            ("watchos", "sim") => format!("-mwatchsimulator-version-min={min_version}"),
+            // `-mxros-version-min` does not exist
+            // https://github.com/llvm/llvm-project/issues/88271
+            ("visionos", "") => format!("-mtargetos=xros{min_version}"),
+            ("visionos", "sim") => format!("-mtargetos=xros{min_version}-simulator"),
+            (os, _) => panic!("invalid Apple target OS {}", os),
+        }
+    }
+
+    /// Whether the deployment target has to be passed in the versioned LLVM
+    /// target triple, as there is no usable `-m*-version-min=` flag.
+    ///
+    /// On visionOS and Mac Catalyst, there is no `-m*-version-min=` flag:
+    /// <https://github.com/llvm/llvm-project/issues/88271>
+    /// And the workaround to use `-mtargetos=` cannot be used with the `--target` flag.
+    pub(crate) fn apple_version_in_llvm_target(&self) -> bool {
+        self.os == "visionos" || self.abi == "macabi"
+    }
+
+    /// Whether the target uses Apple's `arm64e` ABI with pointer
+    /// authentication, rather than the plain `arm64` one.
+    ///
+    /// Both have `aarch64` as their `cfg!(target_arch)`, so this is only
+    /// visible in the full architecture. Clang enables the `-fptrauth-*`
+    /// features itself when given an `arm64e` triple or `-arch arm64e`.
+    pub(crate) fn is_arm64e(&self) -> bool {
+        self.vendor == "apple" && self.full_arch == "arm64e"
+    }
+
+    /// Whether the target is an Apple device, i.e. iOS, tvOS, watchOS or
+    /// visionOS but not their simulators or Mac Catalyst.
+    pub(crate) fn is_apple_device(&self) -> bool {
+        self.vendor == "apple"
+            && matches!(self.os, "ios" | "tvos" | "watchos" | "visionos")
+            && self.abi.is_empty()
+    }
+}
@@ -0,0 +1,108 @@
+//! Floating-point configuration of ARM targets.
+
+use super::TargetInfo;
+
+impl TargetInfo<'_> {
+    /// The FPU to pass with `-mfpu=` to GCC-style compilers, if any.
+    ///
+    /// This is derived from the `neon` hint in the architecture and the
+    /// profile of hard-float targets, picking the lowest common denominator
+    /// FPU of the respective cores.
+    pub(crate) fn arm_fpu(&self) -> Option<&'static str> {
+        if self.arch != "arm" {
+            return None;
+        }
+        let arch = self.full_arch;
+        if arch.contains("neon") {
+            return Some("neon-vfpv4");
+        }
+        if self.os == "android" && arch.contains("v7") {
+            // On android we can guarantee some extra float instructions
+            // (specified in the android spec online).
+            return Some("vfpv3-d16");
+        }
+        if self.abi != "eabihf" {
+            return None;
+        }
+        match arch {
+            arch if arch.starts_with("thumbv7em") => Some("fpv4-sp-d16"),
+            arch if arch.starts_with("thumbv8m.main") => Some("fpv5-sp-d16"),
+            // A, R and the M profile Linux targets, see e.g. the Cortex-R4
+            // technical reference manual.
+            arch if arch.contains("v7") => Some("vfpv3-d16"),
+            // For us arm == armv6 by default.
+            arch if arch == "arm" || arch.starts_with("armv6") => Some("vfp"),
+            _ => None,
+        }
+    }
+
+    /// The floating-point ABI to pass with `-mfloat-abi=` to GCC-style
+    /// compilers, if any.
+    pub(crate) fn arm_float_abi(&self) -> Option<&'static str> {
+        if self.arch != "arm" {
+            return None;
+        }
+        if self.os == "android" {
+            Some("softfp")
+        } else if self.abi == "eabihf" {
+            Some("hard")
+        } else if self.abi == "eabi" && self.os == "linux" {
+            Some("soft")
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TargetInfo;
+
+    #[test]
+    fn arm_float_flags() {
+        let targets = [
+            (
+                "thumbv7neon-unknown-linux-gnueabihf",
+                Some("neon-vfpv4"),
+                Some("hard"),
+            ),
+            (
+                "armv7neon-unknown-linux-musleabihf",
+                Some("neon-vfpv4"),
+                Some("hard"),
+            ),
+            (
+                "armv7-unknown-linux-gnueabihf",
+                Some("vfpv3-d16"),
+                Some("hard"),
+            ),
+            ("armv7-unknown-linux-gnueabi", None, Some("soft")),
+            ("armv7-linux-androideabi", Some("vfpv3-d16"), Some("softfp")),
+            (
+                "thumbv7neon-linux-androideabi",
+                Some("neon-vfpv4"),
+                Some("softfp"),
+            ),
+            ("arm-unknown-linux-gnueabihf", Some("vfp"), Some("hard")),
+            ("arm-unknown-linux-gnueabi", None, Some("soft")),
+            ("armv6-unknown-freebsd", Some("vfp"), Some("hard")),
+            ("armv5te-unknown-linux-gnueabi", None, Some("soft")),
+            ("thumbv7em-none-eabihf", Some("fpv4-sp-d16"), Some("hard")),
+            ("thumbv7em-none-eabi", None, None),
+            (
+                "thumbv8m.main-none-eabihf",
+                Some("fpv5-sp-d16"),
+                Some("hard"),
+            ),
+            ("armv7r-none-eabihf", Some("vfpv3-d16"), Some("hard")),
+            ("thumbv6m-none-eabi", None, None),
+            ("aarch64-unknown-linux-gnu", None, None),
+        ];
+
+        for (target, fpu, float_abi) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.arm_fpu(), fpu, "{}", target);
+            assert_eq!(info.arm_float_abi(), float_abi, "{}", target);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Translation of the Rust target features enabled for the target into the
+//! compiler flags enabling the same ISA extensions for C code.
+
+use super::TargetInfo;
+use crate::tool::ToolFamily;
+
+/// The x86 features known to `rustc`, and the GCC/Clang `-m` flag enabling
+/// each of them.
+const X86_FEATURES: &[(&str, &str)] = &[
+    ("fxsr", "-mfxsr"),
+    ("sse", "-msse"),
+    ("sse2", "-msse2"),
+    ("sse3", "-msse3"),
+    ("ssse3", "-mssse3"),
+    ("sse4.1", "-msse4.1"),
+    ("sse4.2", "-msse4.2"),
+    ("sse4a", "-msse4a"),
+    ("popcnt", "-mpopcnt"),
+    ("cmpxchg16b", "-mcx16"),
+    ("lzcnt", "-mlzcnt"),
+    ("movbe", "-mmovbe"),
+    ("bmi1", "-mbmi"),
+    ("bmi2", "-mbmi2"),
+    ("adx", "-madx"),
+    ("aes", "-maes"),
+    ("pclmulqdq", "-mpclmul"),
+    ("sha", "-msha"),
+    ("rdrand", "-mrdrnd"),
+    ("rdseed", "-mrdseed"),
+    ("xsave", "-mxsave"),
+    ("xsaveopt", "-mxsaveopt"),
+    ("xsavec", "-mxsavec"),
+    ("xsaves", "-mxsaves"),
+    ("f16c", "-mf16c"),
+    ("fma", "-mfma"),
+    ("avx", "-mavx"),
+    ("avx2", "-mavx2"),
+    ("avx512f", "-mavx512f"),
+    ("avx512bw", "-mavx512bw"),
+    ("avx512cd", "-mavx512cd"),
+    ("avx512dq", "-mavx512dq"),
+    ("avx512vl", "-mavx512vl"),
+    ("avx512ifma", "-mavx512ifma"),
+    ("avx512vbmi", "-mavx512vbmi"),
+    ("avx512vbmi2", "-mavx512vbmi2"),
+    ("avx512vnni", "-mavx512vnni"),
+    ("avx512bitalg", "-mavx512bitalg"),
+    ("avx512vpopcntdq", "-mavx512vpopcntdq"),
+    ("gfni", "-mgfni"),
+    ("vaes", "-mvaes"),
+    ("vpclmulqdq", "-mvpclmulqdq"),
+];
+
+/// The `aarch64` features known to `rustc`, and the `-march` extension
+/// enabling each of them with GCC/Clang.
+const AARCH64_FEATURES: &[(&str, &str)] = &[
+    ("crc", "crc"),
+    ("lse", "lse"),
+    ("rdm", "rdma"),
+    ("fp16", "fp16"),
+    ("fhm", "fp16fml"),
+    ("dotprod", "dotprod"),
+    ("rcpc", "rcpc"),
+    ("aes", "aes"),
+    ("sha2", "sha2"),
+    ("sha3", "sha3"),
+    ("sm4", "sm4"),
+    ("rand", "rng"),
+    ("mte", "memtag"),
+    ("ssbs", "ssbs"),
+    ("sb", "sb"),
+    ("flagm", "flagm"),
+    ("bf16", "bf16"),
+    ("i8mm", "i8mm"),
+    ("f32mm", "f32mm"),
+    ("f64mm", "f64mm"),
+    ("sve", "sve"),
+    ("sve2", "sve2"),
+    ("sve2-aes", "sve2-aes"),
+    ("sve2-sm4", "sve2-sm4"),
+    ("sve2-sha3", "sve2-sha3"),
+    ("sve2-bitperm", "sve2-bitperm"),
+];
+
+impl TargetInfo<'_> {
+    /// The flags to pass to a compiler of the given family to enable the ISA
+    /// extensions of the Rust target features in `features`, as listed in
+    /// `CARGO_CFG_TARGET_FEATURE`.
+    ///
+    /// Only `x86`, `x86_64` and `aarch64` are supported, and features without a matching
+    /// flag are left out.
+    pub(crate) fn target_feature_flags(&self, family: ToolFamily, features: &str) -> Vec<String> {
+        let features = features
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .collect::<Vec<_>>();
+        let has = |feature: &str| features.contains(&feature);
+
+        match (self.arch, family) {
+            ("x86" | "x86_64", ToolFamily::Msvc { .. }) => {
+                // MSVC only has `/arch` levels.
+                let avx512 = ["avx512f", "avx512bw", "avx512cd", "avx512dq", "avx512vl"];
+                let arch = if avx512.iter().all(|f| has(f)) {
+                    "AVX512"
+                } else if has("avx2") {
+                    "AVX2"
+                } else if has("avx") {
+                    "AVX"
+                } else {
+                    return Vec::new();
+                };
+                vec![format!("-arch:{}", arch)]
+            }
+            ("x86" | "x86_64", ToolFamily::Gnu | ToolFamily::Clang { .. }) => X86_FEATURES
+                .iter()
+                .filter(|(feature, _)| has(feature))
+                .map(|(_, flag)| flag.to_string())
+                .collect(),
+            ("aarch64", family) => {
+                // The highest architecture version, e.g. `v8.2a` or `v9a`.
+                let feature_version = features
+                    .iter()
+                    .filter_map(|f| f.strip_prefix('v')?.strip_suffix('a'))
+                    .filter_map(|v| {
+                        let (major, minor) = v.split_once('.').unwrap_or((v, "0"));
+                        Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?))
+                    })
+                    .max();
+                // The version can't be lower than the one of the target's
+                // architecture, even if the features don't say so.
+                let version = feature_version.max(self.aarch64_base_version());
+                match family {
+                    ToolFamily::Msvc { .. } => match version {
+                        Some((major, minor)) if (major, minor) > (8, 0) => {
+                            vec![format!("-arch:armv{}.{}", major, minor)]
+                        }
+                        _ => Vec::new(),
+                    },
+                    ToolFamily::Gnu | ToolFamily::Clang { .. } => {
+                        let extensions = AARCH64_FEATURES
+                            .iter()
+                            .filter(|(feature, _)| has(feature))
+                            .map(|(_, extension)| *extension)
+                            .collect::<Vec<_>>();
+                        if feature_version.is_none() && extensions.is_empty() {
+                            return Vec::new();
+                        }
+                        let mut march = match version {
+                            Some((major, 0)) => format!("-march=armv{}-a", major),
+                            Some((major, minor)) => format!("-march=armv{}.{}-a", major, minor),
+                            None => "-march=armv8-a".to_string(),
+                        };
+                        for extension in extensions {
+                            march.push('+');
+                            march.push_str(extension);
+                        }
+                        vec![march]
+                    }
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The `aarch64` architecture version the target's `full_arch` implies,
+    /// if it's above `v8.0a`, e.g. `v8.3a` for the pointer authentication of
+    /// `arm64e`.
+    fn aarch64_base_version(&self) -> Option<(u32, u32)> {
+        match self.full_arch {
+            "arm64e" => Some((8, 3)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tool::ToolFamily, TargetInfo};
+
+    #[test]
+    fn x86_64() {
+        let info = TargetInfo::from_rustc_target("x86_64-unknown-linux-gnu").unwrap();
+        let features = "avx,avx2,bmi1,bmi2,fma,fxsr,lzcnt,pclmulqdq,popcnt,sse,sse2,sse3,\
+                        sse4.1,sse4.2,ssse3,xsave";
+        assert_eq!(
+            info.target_feature_flags(ToolFamily::Gnu, features),
+            [
+                "-mfxsr", "-msse", "-msse2", "-msse3", "-mssse3", "-msse4.1", "-msse4.2",
+                "-mpopcnt", "-mlzcnt", "-mbmi", "-mbmi2", "-mpclmul", "-mxsave", "-mfma", "-mavx",
+                "-mavx2",
+            ]
+        );
+        assert_eq!(
+            info.target_feature_flags(ToolFamily::Gnu, "fxsr,sse,sse2"),
+            ["-mfxsr", "-msse", "-msse2"]
+        );
+
+        let msvc = ToolFamily::Msvc { clang_cl: false };
+        assert_eq!(info.target_feature_flags(msvc, features), ["-arch:AVX2"]);
+        assert!(info.target_feature_flags(msvc, "fxsr,sse,sse2").is_empty());
+        assert_eq!(
+            info.target_feature_flags(msvc, "avx,avx2,avx512f,avx512bw,avx512cd,avx512dq,avx512vl"),
+            ["-arch:AVX512"]
+        );
+    }
+
+    #[test]
+    fn aarch64() {
+        let info = TargetInfo::from_rustc_target("aarch64-unknown-linux-gnu").unwrap();
+        let features = "aes,crc,dotprod,fp16,lse,neon,rcpc,rdm,sha2,sve,v8.1a,v8.2a";
+        assert_eq!(
+            info.target_feature_flags(ToolFamily::Gnu, features),
+            ["-march=armv8.2-a+crc+lse+rdma+fp16+dotprod+rcpc+aes+sha2+sve"]
+        );
+        assert_eq!(
+            info.target_feature_flags(ToolFamily::Gnu, "crc,neon"),
+            ["-march=armv8-a+crc"]
+        );
+        assert_eq!(
+            info.target_feature_flags(ToolFamily::Gnu, "v8.1a,v9a,sve2"),
+            ["-march=armv9-a+sve2"]
+        );
+        assert!(info
+            .target_feature_flags(ToolFamily::Gnu, "fp,neon")
+            .is_empty());
+
+        let msvc = ToolFamily::Msvc { clang_cl: false };
+        assert_eq!(info.target_feature_flags(msvc, features), ["-arch:armv8.2"]);
+        assert!(info.target_feature_flags(msvc, "crc,neon").is_empty());
+
+        // The base version comes from the architecture for `arm64e`.
+        let info = TargetInfo::from_rustc_target("arm64e-apple-darwin").unwrap();
+        assert_eq!(
+            info.target_feature_flags(ToolFamily::Clang { zig_cc: false }, "aes,crc,sha2"),
+            ["-march=armv8.3-a+crc+aes+sha2"]
+        );
+        assert_eq!(
+            info.target_feature_flags(ToolFamily::Gnu, "crc,v8.1a,v8.4a"),
+            ["-march=armv8.4-a+crc"]
+        );
+        assert!(info
+            .target_feature_flags(ToolFamily::Gnu, "neon")
+            .is_empty());
+    }
+
+    #[test]
+    fn other_arches() {
+        let info = TargetInfo::from_rustc_target("riscv64gc-unknown-linux-gnu").unwrap();
+        assert!(info
+            .target_feature_flags(ToolFamily::Gnu, "a,c,d,f,m")
+            .is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use super::TargetInfo;
+
+impl TargetInfo<'_> {
+    /// The prefix of GNU binutils and GCC for this target, following the
+    /// naming used by common cross toolchain packages, e.g. `aarch64-linux-gnu`
+    /// for the `aarch64-linux-gnu-ar` archiver.
+    ///
+    /// This is derived from the target's components, and only used as a last
+    /// resort for targets without a well-known prefix.
+    pub(crate) fn gnu_tool_prefix(&self) -> Option<String> {
+        let arch = match self.arch {
+            "arm" => "arm",
+            // The RISC-V extensions are not part of the prefix.
+            "riscv32" | "riscv64" => self.arch,
+            _ => self.full_arch,
+        };
+        match (self.os, self.env, self.abi) {
+            ("linux", "gnu" | "musl" | "uclibc", abi) => {
+                Some(format!("{}-linux-{}{}", arch, self.env, abi))
+            }
+            ("none", _, _) if arch == "arm" => Some("arm-none-eabi".to_string()),
+            ("none", _, _) if arch.starts_with("riscv") => Some(format!("{}-unknown-elf", arch)),
+            // Redox's toolchains are named after the target.
+            _ if self.is_redox() => Some(format!("{}-unknown-redox", self.full_arch)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TargetInfo;
+
+    #[test]
+    fn gnu_tool_prefix() {
+        let targets = [
+            ("x86_64-unknown-linux-gnu", Some("x86_64-linux-gnu")),
+            ("aarch64_be-unknown-linux-gnu", Some("aarch64_be-linux-gnu")),
+            ("armv7-unknown-linux-gnueabihf", Some("arm-linux-gnueabihf")),
+            ("armv5te-unknown-linux-musleabi", Some("arm-linux-musleabi")),
+            ("riscv64gc-unknown-linux-gnu", Some("riscv64-linux-gnu")),
+            (
+                "mips64el-unknown-linux-gnuabi64",
+                Some("mips64el-linux-gnuabi64"),
+            ),
+            ("m68k-unknown-linux-gnu", Some("m68k-linux-gnu")),
+            ("thumbv7em-none-eabihf", Some("arm-none-eabi")),
+            ("riscv32imac-unknown-none-elf", Some("riscv32-unknown-elf")),
+            ("x86_64-unknown-redox", Some("x86_64-unknown-redox")),
+            ("aarch64-unknown-redox", Some("aarch64-unknown-redox")),
+            ("x86_64-pc-windows-msvc", None),
+            ("aarch64-apple-darwin", None),
+        ];
+
+        for (target, expected) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.gnu_tool_prefix().as_deref(), expected, "{}", target);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Hardening flags supported by each target.
+
+use super::TargetInfo;
+use crate::{tool::ToolFamily, HardeningLevel};
+
+impl TargetInfo<'_> {
+    /// The flags to pass to a compiler of the given family to harden code at
+    /// the given level.
+    ///
+    /// Flags that the target doesn't support, or whose runtime support isn't
+    /// available on it, are left out rather than making compilation fail.
+    pub(crate) fn hardening_flags(
+        &self,
+        family: ToolFamily,
+        level: HardeningLevel,
+        optimized: bool,
+    ) -> Vec<&'static str> {
+        let mut flags = Vec::new();
+        if level == HardeningLevel::None {
+            return flags;
+        }
+        let full = level == HardeningLevel::Full;
+
+        match family {
+            ToolFamily::Msvc { .. } => {
+                flags.push("-GS");
+                if full && matches!(self.arch, "x86" | "x86_64" | "aarch64" | "arm") {
+                    flags.push("-guard:cf");
+                }
+            }
+            ToolFamily::Gnu | ToolFamily::Clang { .. } => {
+                // The stack protector needs a guard value and failure handler
+                // from the C library.
+                if !self.is_embedded() && !self.is_uefi() && !self.arch.starts_with("wasm") {
+                    flags.push("-fstack-protector-strong");
+                }
+                // Fortified functions are provided by hosted C libraries, and
+                // only take effect when optimizing.
+                if optimized && self.is_dynamic_elf() {
+                    flags.push("-D_FORTIFY_SOURCE=2");
+                }
+                if full
+                    && self.is_dynamic_elf()
+                    && matches!(
+                        self.arch,
+                        "x86" | "x86_64" | "aarch64" | "powerpc64" | "s390x"
+                    )
+                {
+                    flags.push("-fstack-clash-protection");
+                }
+                if full && !self.is_embedded() && matches!(self.arch, "x86" | "x86_64") {
+                    flags.push("-fcf-protection");
+                }
+            }
+        }
+        flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tool::ToolFamily, HardeningLevel, TargetInfo};
+
+    #[test]
+    fn linux_gnu_x86_64() {
+        let info = TargetInfo::from_rustc_target("x86_64-unknown-linux-gnu").unwrap();
+        let flags = |level, optimized| info.hardening_flags(ToolFamily::Gnu, level, optimized);
+
+        assert!(flags(HardeningLevel::None, true).is_empty());
+        assert_eq!(
+            flags(HardeningLevel::Basic, true),
+            ["-fstack-protector-strong", "-D_FORTIFY_SOURCE=2"]
+        );
+        assert_eq!(
+            flags(HardeningLevel::Basic, false),
+            ["-fstack-protector-strong"]
+        );
+        assert_eq!(
+            flags(HardeningLevel::Full, true),
+            [
+                "-fstack-protector-strong",
+                "-D_FORTIFY_SOURCE=2",
+                "-fstack-clash-protection",
+                "-fcf-protection"
+            ]
+        );
+    }
+
+    #[test]
+    fn msvc() {
+        let info = TargetInfo::from_rustc_target("x86_64-pc-windows-msvc").unwrap();
+        let family = ToolFamily::Msvc { clang_cl: false };
+        let flags = |level| info.hardening_flags(family, level, true);
+
+        assert!(flags(HardeningLevel::None).is_empty());
+        assert_eq!(flags(HardeningLevel::Basic), ["-GS"]);
+        assert_eq!(flags(HardeningLevel::Full), ["-GS", "-guard:cf"]);
+    }
+
+    #[test]
+    fn unsupported_targets() {
+        let flags = |target| {
+            TargetInfo::from_rustc_target(target)
+                .unwrap()
+                .hardening_flags(ToolFamily::Gnu, HardeningLevel::Full, true)
+        };
+
+        assert!(flags("thumbv7em-none-eabihf").is_empty());
+        assert!(flags("wasm32-unknown-unknown").is_empty());
+        assert_eq!(flags("aarch64-apple-darwin"), ["-fstack-protector-strong"]);
+        assert_eq!(
+            flags("riscv64gc-unknown-linux-gnu"),
+            ["-fstack-protector-strong", "-D_FORTIFY_SOURCE=2"]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use super::TargetInfo;
+
+/// The C standard library implementation used by a target.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum LibcFlavor {
+    /// The GNU C library.
+    Glibc,
+    /// musl libc.
+    Musl,
+    /// uClibc / uClibc-ng, mostly found on embedded Linux.
+    Uclibc,
+    /// Newlib, mostly found on bare-metal and RTOS targets.
+    Newlib,
+    /// Android's C library.
+    Bionic,
+    /// The Microsoft C runtime, used by both the MSVC and MinGW toolchains.
+    Msvcrt,
+    /// Any other (or no) C library.
+    None,
+}
+
+impl TargetInfo<'_> {
+    /// The C standard library implementation used by this target.
+    ///
+    /// This is derived from the target environment, and falls back to the
+    /// operating system for targets that don't specify one.
+    pub(crate) fn libc_flavor(&self) -> LibcFlavor {
+        match (self.os, self.env) {
+            (_, "musl") => LibcFlavor::Musl,
+            // OpenHarmony uses musl, but has its own `target_env`.
+            ("linux", "ohos") => LibcFlavor::Musl,
+            (_, "uclibc") => LibcFlavor::Uclibc,
+            (_, "newlib") => LibcFlavor::Newlib,
+            (_, "msvc") | ("windows", "gnu") => LibcFlavor::Msvcrt,
+            (_, "gnu") => LibcFlavor::Glibc,
+            ("android", _) => LibcFlavor::Bionic,
+            _ => LibcFlavor::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LibcFlavor;
+    use crate::TargetInfo;
+
+    #[test]
+    fn libc_flavor() {
+        let targets = [
+            ("x86_64-unknown-linux-gnu", LibcFlavor::Glibc),
+            ("aarch64-unknown-linux-gnu", LibcFlavor::Glibc),
+            ("i686-unknown-hurd-gnu", LibcFlavor::Glibc),
+            ("x86_64-unknown-linux-musl", LibcFlavor::Musl),
+            ("armv7-unknown-linux-musleabihf", LibcFlavor::Musl),
+            ("aarch64-unknown-linux-ohos", LibcFlavor::Musl),
+            ("armv7-unknown-linux-uclibceabihf", LibcFlavor::Uclibc),
+            ("armv5te-unknown-linux-uclibceabi", LibcFlavor::Uclibc),
+            ("mipsel-unknown-linux-uclibc", LibcFlavor::Uclibc),
+            ("armv7-sony-vita-newlibeabihf", LibcFlavor::Newlib),
+            ("aarch64-nintendo-switch-freestanding", LibcFlavor::None),
+            ("aarch64-linux-android", LibcFlavor::Bionic),
+            ("armv7-linux-androideabi", LibcFlavor::Bionic),
+            ("x86_64-pc-windows-msvc", LibcFlavor::Msvcrt),
+            ("x86_64-pc-windows-gnu", LibcFlavor::Msvcrt),
+            ("x86_64-pc-windows-gnullvm", LibcFlavor::Msvcrt),
+            ("x86_64-apple-darwin", LibcFlavor::None),
+            ("x86_64-unknown-freebsd", LibcFlavor::None),
+            ("thumbv7em-none-eabihf", LibcFlavor::None),
+            ("wasm32-unknown-unknown", LibcFlavor::None),
+        ];
+
+        for (target, expected) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.libc_flavor(), expected, "{target}");
+        }
+    }
+}
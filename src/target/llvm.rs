@@ -0,0 +1,794 @@
+use std::borrow::Cow;
+
+use super::{generated, TargetInfo};
+
+impl<'a> TargetInfo<'a> {
+    /// The architecture as spelled in LLVM/Clang target triples.
+    ///
+    /// This mostly matches [`full_arch`](Self::full_arch), except that LLVM
+    /// doesn't know the extensions in the RISC-V arch names, and that Apple
+    /// targets use `arm64` for `aarch64`. The rest, such as `powerpc` and
+    /// `powerpc64`, are already LLVM's own spelling (`ppc` and `ppc64` are
+    /// only the Darwin `-arch` names).
+    pub(crate) fn llvm_arch(&self) -> &'a str {
+        match self.full_arch {
+            riscv32 if riscv32.starts_with("riscv32") => "riscv32",
+            riscv64 if riscv64.starts_with("riscv64") => "riscv64",
+            "aarch64" if self.vendor == "apple" => "arm64",
+            _ if self.is_arm64e() => "arm64e",
+            "armv7" if self.vendor == "sony" => "thumbv7a", // FIXME
+            // `rustc` uses `i586-pc-unknown` for `i686-pc-nto-qnx700`.
+            "i686" if self.os == "nto" => "i586",
+            arch => arch,
+        }
+    }
+
+    /// The LLVM/Clang target triple.
+    ///
+    /// See <https://clang.llvm.org/docs/CrossCompilation.html#target-triple>.
+    ///
+    /// Rust and Clang don't really agree on target naming, so we first try to
+    /// find the matching trible based on `rustc`'s output, but if no such
+    /// triple exists, we attempt to construct the triple from scratch.
+    ///
+    /// NOTE: You should never need to match on this explicitly, use the
+    /// fields on [`TargetInfo`] instead.
+    pub(crate) fn llvm_target(
+        &self,
+        rustc_target: &str,
+        version: Option<&str>,
+    ) -> Cow<'static, str> {
+        if rustc_target == "armv7-apple-ios" {
+            // FIXME(madsmtm): Unnecessary once we bump MSRV to Rust 1.74
+            return Cow::Borrowed("armv7-apple-ios");
+        } else if self.is_uefi() {
+            // Override the UEFI LLVM targets.
+            //
+            // The rustc mappings (as of 1.82) for the UEFI targets are:
+            // * i686-unknown-uefi -> i686-unknown-windows-gnu
+            // * x86_64-unknown-uefi -> x86_64-unknown-windows
+            // * aarch64-unknown-uefi -> aarch64-unknown-windows
+            //
+            // However, in cc-rs all the UEFI targets use
+            // -windows-gnu. This has been the case since 2021 [1].
+            // * i686-unknown-uefi -> i686-unknown-windows-gnu
+            // * x86_64-unknown-uefi -> x86_64-unknown-windows-gnu
+            // * aarch64-unknown-uefi -> aarch64-unknown-windows-gnu
+            //
+            // For now, override the UEFI mapping to keep the behavior
+            // of cc-rs unchanged.
+            //
+            // TODO: as discussed in [2], it may be possible to switch
+            // to new UEFI targets added to clang, and regardless it
+            // would be good to have consistency between rustc and
+            // cc-rs.
+            //
+            // [1]: https://github.com/rust-lang/cc-rs/pull/623
+            // [2]: https://github.com/rust-lang/cc-rs/pull/1264
+            return Cow::Owned(format!("{}-unknown-windows-gnu", self.full_arch));
+        } else if self.arch == "bpf" || self.arch == "sbf" {
+            // BPF triples consist of only the architecture, which includes
+            // the endianness, e.g. `bpfel-unknown-none` -> `bpfel` and
+            // `sbf-solana-solana` -> `sbf`.
+            return Cow::Owned(self.full_arch.to_string());
+        } else if self.arch == "avr" {
+            // The AVR chip is selected with `-mmcu=` rather than the triple.
+            return Cow::Borrowed("avr-unknown-unknown");
+        } else if self.arch == "m68k" && self.is_bare_metal() {
+            // Like BPF, bare-metal m68k uses only the architecture.
+            return Cow::Borrowed("m68k");
+        } else if self.vendor == "espressif" {
+            // The Espressif targets don't follow the usual scheme: Xtensa is
+            // not an upstream LLVM architecture, and Espressif's LLVM fork
+            // only knows it as `xtensa-none-elf`, while the RISC-V chips use a
+            // bare `riscv32` triple.
+            match self.arch {
+                "xtensa" => return Cow::Borrowed("xtensa-none-elf"),
+                "riscv32" => return Cow::Borrowed("riscv32"),
+                _ => {}
+            }
+        }
+
+        // If no version is requested, let's take the triple directly from
+        // `rustc` (the logic below is not yet good enough for most targets).
+        //
+        // FIXME(madsmtm): This should ideally be removed.
+        if version.is_none() {
+            if let Ok(index) = generated::LLVM_TARGETS
+                .binary_search_by_key(&rustc_target, |(rustc_target, _)| rustc_target)
+            {
+                let (_, llvm_target) = &generated::LLVM_TARGETS[index];
+                return Cow::Borrowed(llvm_target);
+            }
+        }
+
+        // Otherwise, attempt to construct the triple from the target info.
+
+        let arch = self.llvm_arch();
+        let vendor = match self.vendor {
+            // The 3DS uses the vendor-less triple of bare-metal ARM.
+            "nintendo" if self.arch == "arm" => "",
+            "kmc" | "nintendo" => "unknown",
+            "unknown" if self.os == "android" => "linux",
+            "uwp" | "win7" => "pc",
+            _ if self.arch == "msp430" => "",
+            vendor => vendor,
+        };
+        let os = match self.os {
+            "macos" => "macosx",
+            "visionos" => "xros",
+            "uefi" => "windows",
+            _ if self.is_embedded() => "none",
+            "nto" => "unknown",    // FIXME
+            "trusty" => "unknown", // FIXME
+            os => os,
+        };
+        let version = version.unwrap_or("");
+        let env = match self.env {
+            "newlib" | "nto70" | "nto71" | "nto71_iosock" | "nto80" | "p1" | "p2" | "relibc"
+            | "sgx" | "uclibc" => "",
+            // These bare-metal triples keep the `elf` of the Rust target.
+            "" if self.is_bare_metal() && matches!(self.arch, "hexagon" | "msp430" | "sparc") => {
+                "elf"
+            }
+            env => env,
+        };
+        let abi = match self.abi {
+            "sim" => "simulator",
+            "llvm" | "softfloat" | "uwp" | "vec-extabi" => "",
+            "ilp32" => "_ilp32",
+            "abi64" => "",
+            // LLVM has no hard-float spelling of the C-SKY ABI.
+            "abiv2hf" => "abiv2",
+            abi => abi,
+        };
+        Cow::Owned(match (vendor, env, abi) {
+            ("", "", "") => format!("{arch}-{os}{version}"),
+            ("", env, abi) => format!("{arch}-{os}{version}-{env}{abi}"),
+            (vendor, "", "") => format!("{arch}-{vendor}-{os}{version}"),
+            (vendor, env, abi) => format!("{arch}-{vendor}-{os}{version}-{env}{abi}"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::process::Command;
+
+    use crate::target::generated;
+    use crate::TargetInfo;
+
+    #[test]
+    fn test_old_ios_target() {
+        assert_eq!(
+            TargetInfo {
+                triple: "armv7-apple-ios",
+                full_arch: "armv7",
+                arch: "armv7",
+                vendor: "apple",
+                os: "ios",
+                env: "",
+                abi: "",
+            }
+            .llvm_target("armv7-apple-ios", None),
+            "armv7-apple-ios"
+        );
+    }
+
+    #[test]
+    fn llvm_arch() {
+        for (rustc_target, llvm_arch) in [
+            ("x86_64-unknown-linux-gnu", "x86_64"),
+            ("i686-pc-windows-msvc", "i686"),
+            ("aarch64-unknown-linux-gnu", "aarch64"),
+            ("aarch64-apple-darwin", "arm64"),
+            ("aarch64-apple-ios-sim", "arm64"),
+            ("arm64e-apple-ios", "arm64e"),
+            ("arm64_32-apple-watchos", "arm64_32"),
+            ("powerpc-unknown-linux-gnu", "powerpc"),
+            ("powerpc64-unknown-linux-gnu", "powerpc64"),
+            ("powerpc64le-unknown-linux-gnu", "powerpc64le"),
+            ("riscv32imac-unknown-none-elf", "riscv32"),
+            ("riscv64gc-unknown-linux-gnu", "riscv64"),
+            ("armv7-sony-vita-newlibeabihf", "thumbv7a"),
+        ] {
+            let info = TargetInfo::from_rustc_target(rustc_target).unwrap();
+            assert_eq!(info.llvm_arch(), llvm_arch, "{}", rustc_target);
+            // The guessed triple is built from the same arch.
+            let guessed = info.llvm_target(rustc_target, Some("1.0"));
+            assert!(
+                guessed.starts_with(&format!("{}-", llvm_arch)),
+                "{}: {}",
+                rustc_target,
+                guessed
+            );
+        }
+    }
+
+    #[test]
+    fn basic_llvm_triple_guessing() {
+        assert_eq!(
+            TargetInfo {
+                triple: "invalid",
+                full_arch: "aarch64",
+                arch: "aarch64",
+                vendor: "unknown",
+                os: "linux",
+                env: "",
+                abi: "",
+            }
+            .llvm_target("invalid", None),
+            "aarch64-unknown-linux"
+        );
+        assert_eq!(
+            TargetInfo {
+                triple: "invalid",
+                full_arch: "x86_64",
+                arch: "x86_64",
+                vendor: "unknown",
+                os: "linux",
+                env: "gnu",
+                abi: "",
+            }
+            .llvm_target("invalid", None),
+            "x86_64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            TargetInfo {
+                triple: "invalid",
+                full_arch: "x86_64",
+                arch: "x86_64",
+                vendor: "unknown",
+                os: "linux",
+                env: "gnu",
+                abi: "eabi",
+            }
+            .llvm_target("invalid", None),
+            "x86_64-unknown-linux-gnueabi"
+        );
+        assert_eq!(
+            TargetInfo {
+                triple: "invalid",
+                full_arch: "x86_64",
+                arch: "x86_64",
+                vendor: "apple",
+                os: "macos",
+                env: "",
+                abi: "",
+            }
+            .llvm_target("invalid", None),
+            "x86_64-apple-macosx"
+        );
+    }
+
+    #[test]
+    fn llvm_version() {
+        assert_eq!(
+            TargetInfo {
+                triple: "aarch64-apple-ios-sim",
+                full_arch: "aarch64",
+                arch: "aarch64",
+                vendor: "apple",
+                os: "ios",
+                env: "",
+                abi: "sim",
+            }
+            .llvm_target("aarch64-apple-ios-sim", Some("14.0")),
+            "arm64-apple-ios14.0-simulator"
+        );
+        assert_eq!(
+            TargetInfo {
+                triple: "aarch64-apple-visionos",
+                full_arch: "aarch64",
+                arch: "aarch64",
+                vendor: "apple",
+                os: "visionos",
+                env: "",
+                abi: "",
+            }
+            .llvm_target("aarch64-apple-visionos", Some("2.0")),
+            "arm64-apple-xros2.0"
+        );
+        assert_eq!(
+            TargetInfo {
+                triple: "aarch64-apple-ios-macabi",
+                full_arch: "aarch64",
+                arch: "aarch64",
+                vendor: "apple",
+                os: "ios",
+                env: "",
+                abi: "macabi",
+            }
+            .llvm_target("aarch64-apple-ios-macabi", Some("13.1")),
+            "arm64-apple-ios13.1-macabi"
+        );
+    }
+
+    #[test]
+    fn win7() {
+        for target in ["i686-win7-windows-msvc", "x86_64-win7-windows-gnu"] {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            let expected = target.replace("win7", "pc");
+            assert_eq!(info.llvm_target(target, None), expected);
+            // The fallback agrees with `rustc`, as LLVM has no `win7` vendor.
+            assert_eq!(info.llvm_target("invalid", None), expected);
+        }
+    }
+
+    #[test]
+    fn uefi() {
+        assert_eq!(
+            TargetInfo {
+                triple: "i686-unknown-uefi",
+                full_arch: "i686",
+                arch: "x86",
+                vendor: "unknown",
+                os: "uefi",
+                env: "",
+                abi: "",
+            }
+            .llvm_target("i686-unknown-uefi", None),
+            "i686-unknown-windows-gnu"
+        );
+        assert_eq!(
+            TargetInfo {
+                triple: "x86_64-unknown-uefi",
+                full_arch: "x86_64",
+                arch: "x86_64",
+                vendor: "unknown",
+                os: "uefi",
+                env: "",
+                abi: "",
+            }
+            .llvm_target("x86_64-unknown-uefi", None),
+            "x86_64-unknown-windows-gnu"
+        );
+        assert_eq!(
+            TargetInfo {
+                triple: "aarch64-unknown-uefi",
+                full_arch: "aarch64",
+                arch: "aarch64",
+                vendor: "unknown",
+                os: "uefi",
+                env: "",
+                abi: "",
+            }
+            .llvm_target("aarch64-unknown-uefi", None),
+            "aarch64-unknown-windows-gnu"
+        );
+    }
+
+    /// A target whose guessed LLVM triple differs from the one `rustc` uses.
+    #[derive(Debug)]
+    struct Mismatch {
+        target: String,
+        /// The `(os, vendor, env)` of the target, or `None` if it failed to parse.
+        group: Option<(String, String, String)>,
+        expected: String,
+        actual: Result<String, String>,
+    }
+
+    /// Compare the guessed LLVM triple of every target known to `rustc`
+    /// against the one in its target spec, and return all mismatches.
+    fn llvm_target_mismatches(rustc: &str) -> Vec<Mismatch> {
+        let target_list = Command::new(rustc)
+            .arg("--print=target-list")
+            .output()
+            .unwrap()
+            .stdout;
+        let target_list = String::from_utf8(target_list).unwrap();
+
+        let mut mismatches = Vec::new();
+        for target in target_list.lines() {
+            let spec_json = Command::new(rustc)
+                .arg("--target")
+                .arg(target)
+                .arg("-Zunstable-options")
+                .arg("--print=target-spec-json")
+                .env("RUSTC_BOOTSTRAP", "1") // Crimes
+                .output()
+                .unwrap()
+                .stdout;
+            let spec_json = String::from_utf8(spec_json).unwrap();
+
+            // JSON crimes
+            let expected = spec_json
+                .split_once("llvm-target\": \"")
+                .unwrap()
+                .1
+                .split_once("\"")
+                .unwrap()
+                .0;
+            let info = TargetInfo::from_rustc_target(target);
+            let actual = info
+                .as_ref()
+                .map(|info| info.llvm_target("invalid", None).into_owned())
+                .map_err(|err| err.to_string());
+
+            if actual.as_deref() != Ok(expected) {
+                mismatches.push(Mismatch {
+                    target: target.to_string(),
+                    group: info.ok().map(|info| {
+                        (
+                            info.os.to_string(),
+                            info.vendor.to_string(),
+                            info.env.to_string(),
+                        )
+                    }),
+                    expected: expected.to_string(),
+                    actual,
+                });
+            }
+        }
+        mismatches
+    }
+
+    #[test]
+    fn apple_arm64_spelling() {
+        let mut apple_targets = 0;
+        for &(target, expected) in generated::LLVM_TARGETS {
+            let info = match TargetInfo::from_rustc_target(target) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            if info.full_arch != "aarch64" {
+                continue;
+            }
+
+            // Check both the generated mapping and the fallback construction.
+            let fallback = info.llvm_target("invalid", None);
+            if info.vendor == "apple" {
+                apple_targets += 1;
+                assert!(
+                    expected.starts_with("arm64-apple-"),
+                    "{}: {}",
+                    target,
+                    expected
+                );
+                assert!(
+                    fallback.starts_with("arm64-apple-"),
+                    "{}: {}",
+                    target,
+                    fallback
+                );
+            } else {
+                assert!(expected.starts_with("aarch64-"), "{}: {}", target, expected);
+                assert!(fallback.starts_with("aarch64-"), "{}: {}", target, fallback);
+            }
+        }
+        assert_ne!(apple_targets, 0);
+
+        let info = TargetInfo::from_rustc_target("aarch64-apple-darwin").unwrap();
+        assert_eq!(info.llvm_target("invalid", None), "arm64-apple-macosx");
+        let info = TargetInfo::from_rustc_target("aarch64-unknown-linux-gnu").unwrap();
+        assert_eq!(
+            info.llvm_target("invalid", None),
+            "aarch64-unknown-linux-gnu"
+        );
+    }
+
+    #[test]
+    fn arm64e() {
+        let mut arm64e_targets = 0;
+        for &(target, expected) in generated::LLVM_TARGETS {
+            let info = match TargetInfo::from_rustc_target(target) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            assert_eq!(
+                info.is_arm64e(),
+                target.starts_with("arm64e-"),
+                "{}",
+                target
+            );
+            if info.is_arm64e() {
+                arm64e_targets += 1;
+                assert_eq!(info.arch, "aarch64", "{}", target);
+                assert!(expected.starts_with("arm64e-apple-"), "{}", expected);
+                assert_eq!(info.llvm_target("invalid", None), expected, "{}", target);
+            }
+        }
+        assert_ne!(arm64e_targets, 0);
+
+        let info = TargetInfo::from_rustc_target("arm64e-apple-ios").unwrap();
+        assert_eq!(
+            info.llvm_target("arm64e-apple-ios", Some("17.0")),
+            "arm64e-apple-ios17.0"
+        );
+        let info = TargetInfo::from_rustc_target("aarch64-apple-ios").unwrap();
+        assert!(!info.is_arm64e());
+    }
+
+    #[test]
+    fn bpf_and_sbf() {
+        let mut bpf_targets = 0;
+        for &(target, expected) in generated::LLVM_TARGETS {
+            let info = match TargetInfo::from_rustc_target(target) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            if info.arch == "bpf" || info.arch == "sbf" {
+                bpf_targets += 1;
+                assert_eq!(info.llvm_target("invalid", None), expected, "{}", target);
+            }
+        }
+        assert_ne!(bpf_targets, 0);
+
+        let info = TargetInfo::from_rustc_target("sbf-solana-solana").unwrap();
+        assert_eq!(info.arch, "sbf");
+        assert_eq!(info.llvm_target("sbf-solana-solana", None), "sbf");
+        let info = TargetInfo::from_rustc_target("bpfeb-unknown-none").unwrap();
+        assert_eq!(info.arch, "bpf");
+        assert_eq!(info.llvm_target("invalid", None), "bpfeb");
+    }
+
+    /// The arches other than the common x86, ARM, RISC-V, WebAssembly,
+    /// PowerPC and MIPS ones are only used by few, often tier 3, targets.
+    #[test]
+    fn niche_arches() {
+        let common = [
+            "x86",
+            "x86_64",
+            "arm",
+            "aarch64",
+            "arm64ec",
+            "riscv32",
+            "riscv64",
+            "wasm32",
+            "wasm64",
+            "powerpc",
+            "powerpc64",
+            "mips",
+            "mips64",
+            "mips32r6",
+            "mips64r6",
+        ];
+        let mut niche_targets = 0;
+        let mut mismatches = Vec::new();
+        for &(target, expected) in generated::LLVM_TARGETS {
+            let info = match TargetInfo::from_rustc_target(target) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            if common.contains(&info.arch) {
+                continue;
+            }
+            niche_targets += 1;
+            let actual = info.llvm_target("invalid", None);
+            if actual != expected {
+                mismatches.push((target, expected, actual));
+            }
+        }
+        assert_ne!(niche_targets, 0);
+        assert!(mismatches.is_empty(), "{:#?}", mismatches);
+    }
+
+    #[test]
+    fn nintendo() {
+        for target in [
+            "armv6k-nintendo-3ds",
+            "aarch64-nintendo-switch-freestanding",
+        ] {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert!(info.is_horizon(), "{}", target);
+            assert!(info.is_embedded(), "{}", target);
+            assert!(!info.is_bare_metal(), "{}", target);
+            // Check both the generated mapping and the fallback construction.
+            let expected = generated::LLVM_TARGETS
+                .iter()
+                .find(|(rustc_target, _)| *rustc_target == target)
+                .unwrap()
+                .1;
+            assert_eq!(info.llvm_target(target, None), expected, "{target}");
+            assert_eq!(info.llvm_target("invalid", None), expected, "{target}");
+        }
+    }
+
+    #[test]
+    fn uefi_round_trip() {
+        let mut uefi_targets = 0;
+        for &(target, generated) in generated::LLVM_TARGETS {
+            let info = match TargetInfo::from_rustc_target(target) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            assert_eq!(info.is_uefi(), target.ends_with("-uefi"), "{}", target);
+            if !info.is_uefi() {
+                continue;
+            }
+            uefi_targets += 1;
+            assert!(!info.is_pic_by_default(), "{}", target);
+            assert!(!info.is_elf(), "{}", target);
+            // Both the overridden and the generated triples are for Windows on
+            // the same architecture, only the environment differs.
+            let expected = format!("{}-unknown-windows-gnu", info.full_arch);
+            assert_eq!(info.llvm_target(target, None), expected, "{}", target);
+            assert_eq!(info.llvm_target("invalid", None), expected, "{}", target);
+            assert!(
+                generated.starts_with(&format!("{}-unknown-windows", info.full_arch)),
+                "{}",
+                generated
+            );
+        }
+        assert_ne!(uefi_targets, 0);
+    }
+
+    #[test]
+    fn redox() {
+        let mut redox_targets = 0;
+        for &(target, expected) in generated::LLVM_TARGETS {
+            let info = match TargetInfo::from_rustc_target(target) {
+                Ok(info) => info,
+                Err(_) => continue,
+            };
+            assert_eq!(info.is_redox(), target.ends_with("-redox"), "{}", target);
+            if info.is_redox() {
+                redox_targets += 1;
+                assert_eq!(info.env, "relibc", "{}", target);
+                assert_eq!(info.llvm_target("invalid", None), expected, "{}", target);
+            }
+        }
+        assert_ne!(redox_targets, 0);
+    }
+
+    /// Check that triples constructed for arbitrary combinations of the
+    /// components of known targets are well-formed, to catch special cases
+    /// that only break for targets `rustc` doesn't have (yet).
+    #[test]
+    fn llvm_target_for_component_combinations() {
+        let infos = generated::LLVM_TARGETS
+            .iter()
+            .filter_map(|(target, _)| TargetInfo::from_rustc_target(target).ok())
+            .collect::<Vec<_>>();
+        let mut archs = infos
+            .iter()
+            .map(|info| (info.full_arch, info.arch))
+            .collect::<Vec<_>>();
+        let mut vendors = infos.iter().map(|info| info.vendor).collect::<Vec<_>>();
+        let mut oses = infos.iter().map(|info| info.os).collect::<Vec<_>>();
+        let mut envabis = infos
+            .iter()
+            .map(|info| (info.env, info.abi))
+            .collect::<Vec<_>>();
+        for components in [&mut vendors, &mut oses] {
+            components.sort_unstable();
+            components.dedup();
+        }
+        archs.sort_unstable();
+        archs.dedup();
+        envabis.sort_unstable();
+        envabis.dedup();
+
+        // A fixed xorshift generator keeps this deterministic and fast,
+        // while still covering far more than the known targets.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut pick = |len: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % len as u64) as usize
+        };
+        for _ in 0..10_000 {
+            let (full_arch, arch) = archs[pick(archs.len())];
+            let (env, abi) = envabis[pick(envabis.len())];
+            let info = TargetInfo {
+                triple: "invalid",
+                full_arch,
+                arch,
+                vendor: vendors[pick(vendors.len())],
+                os: oses[pick(oses.len())],
+                env,
+                abi,
+            };
+
+            for version in [None, Some("10.0")] {
+                let triple = info.llvm_target("invalid", version);
+                assert!(!triple.is_empty(), "{:?}", info);
+                assert!(
+                    !triple.starts_with('-') && !triple.ends_with('-'),
+                    "{:?}: {}",
+                    info,
+                    triple
+                );
+                assert!(!triple.contains("--"), "{:?}: {}", info, triple);
+            }
+
+            // These shouldn't panic either.
+            let _ = info.libc_flavor();
+            let _ = info.is_embedded();
+            let _ = info.gnu_tool_prefix();
+        }
+    }
+
+    #[test]
+    fn espressif() {
+        let targets = [
+            ("riscv32imc-esp-espidf", "riscv32"),
+            ("riscv32imac-esp-espidf", "riscv32"),
+            ("riscv32imafc-esp-espidf", "riscv32"),
+            ("xtensa-esp32-espidf", "xtensa-none-elf"),
+            ("xtensa-esp32s2-espidf", "xtensa-none-elf"),
+            ("xtensa-esp32s3-espidf", "xtensa-none-elf"),
+            ("xtensa-esp32-none-elf", "xtensa-none-elf"),
+            ("xtensa-esp32s2-none-elf", "xtensa-none-elf"),
+            ("xtensa-esp32s3-none-elf", "xtensa-none-elf"),
+        ];
+
+        for (target, expected) in targets {
+            let info = TargetInfo::from_rustc_target(target).unwrap();
+            assert_eq!(info.vendor, "espressif", "{target}");
+            // The triples are special-cased before the generated mapping is
+            // consulted, so they don't depend on the name of the target.
+            assert_eq!(info.llvm_target(target, None), expected, "{target}");
+            assert_eq!(info.llvm_target("invalid", None), expected, "{target}");
+        }
+    }
+
+    #[test]
+    fn qnx() {
+        let mut qnx_targets = 0;
+        for &(target, expected) in generated::LLVM_TARGETS {
+            let info = match TargetInfo::from_rustc_target(target) {
+                Ok(info) if info.os == "nto" => info,
+                _ => continue,
+            };
+            qnx_targets += 1;
+            assert!(info.qnx_version().is_some(), "{}", target);
+            // Check both the generated mapping and the fallback construction.
+            assert_eq!(info.llvm_target(target, None), expected, "{target}");
+            assert_eq!(info.llvm_target("invalid", None), expected, "{target}");
+        }
+        assert_ne!(qnx_targets, 0);
+    }
+
+    #[test]
+    #[ignore = "not yet done"]
+    #[allow(clippy::disallowed_methods)]
+    fn llvm_for_all_rustc_targets() {
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+
+        let mismatches = llvm_target_mismatches(&rustc);
+        if mismatches.is_empty() {
+            return;
+        }
+
+        // Group by os/vendor/env, so that systematic failures (e.g. all
+        // targets of one OS breaking together) stand out.
+        let mut groups = BTreeMap::<_, Vec<&Mismatch>>::new();
+        for mismatch in &mismatches {
+            groups.entry(&mismatch.group).or_default().push(mismatch);
+        }
+
+        eprintln!("{:<16} {:<16} {:<16} {:>5}", "os", "vendor", "env", "count");
+        for (group, group_mismatches) in &groups {
+            let (os, vendor, env) = match group {
+                Some((os, vendor, env)) => (&**os, &**vendor, &**env),
+                None => ("<unparsable>", "", ""),
+            };
+            eprintln!(
+                "{:<16} {:<16} {:<16} {:>5}",
+                os,
+                vendor,
+                env,
+                group_mismatches.len()
+            );
+        }
+        eprintln!();
+
+        for (group, group_mismatches) in &groups {
+            eprintln!("{:?}:", group);
+            for mismatch in group_mismatches {
+                eprintln!("  {}:", mismatch.target);
+                eprintln!("    expected: Ok({:?})", mismatch.expected);
+                eprintln!("      actual: {:?}", mismatch.actual);
+            }
+        }
+
+        panic!(
+            "{} targets in {} groups failed comparison",
+            mismatches.len(),
+            groups.len()
+        );
+    }
+}
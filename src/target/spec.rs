@@ -0,0 +1,204 @@
+//! Reading of custom `rustc` target specifications.
+//!
+//! See <https://doc.rust-lang.org/rustc/targets/custom.html>.
+
+use std::{fs, path::Path};
+
+use crate::{json, target::TargetInfo, Error, ErrorKind};
+
+/// Information about a target read from a target specification JSON file.
+///
+/// This can be used as a key to cache computations per target, which
+/// compares all its fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct OwnedTargetInfo {
+    /// The name of the target, which is the file stem of the specification.
+    triple: Box<str>,
+    full_arch: Box<str>,
+    arch: Box<str>,
+    vendor: Box<str>,
+    os: Box<str>,
+    env: Box<str>,
+    abi: Box<str>,
+    /// The `llvm-target` of the specification.
+    pub(crate) llvm_target: Box<str>,
+    /// The `relocation-model` of the specification, if set.
+    pub(crate) relocation_model: Option<Box<str>>,
+}
+
+impl OwnedTargetInfo {
+    pub(crate) fn target_info(&self) -> TargetInfo<'_> {
+        TargetInfo {
+            triple: &self.triple,
+            full_arch: &self.full_arch,
+            arch: &self.arch,
+            vendor: &self.vendor,
+            os: &self.os,
+            env: &self.env,
+            abi: &self.abi,
+        }
+    }
+
+    /// Whether the specification uses a position-independent relocation
+    /// model, or `None` if it uses `rustc`'s default.
+    pub(crate) fn is_pic(&self) -> Option<bool> {
+        self.relocation_model
+            .as_deref()
+            .map(|model| matches!(model, "pic" | "pie"))
+    }
+
+    fn from_json(triple: &str, json: &str) -> Result<Self, String> {
+        let spec = json::parse(json)?;
+        if !matches!(spec, json::JsonValue::Object(_)) {
+            return Err("expected an object".to_string());
+        }
+        let field = |name: &str| spec.get(name)?.as_str().map(Box::<str>::from);
+
+        let llvm_target = field("llvm-target").ok_or("missing `llvm-target`")?;
+        let arch = field("arch").ok_or("missing `arch`")?;
+        // The architecture in the LLVM triple includes the subarchitecture.
+        let full_arch = llvm_target.split('-').next().unwrap_or_default().into();
+
+        // Use the same defaults as `rustc` for omitted fields.
+        Ok(Self {
+            triple: triple.into(),
+            full_arch,
+            arch,
+            vendor: field("vendor").unwrap_or_else(|| "unknown".into()),
+            os: field("os").unwrap_or_else(|| "none".into()),
+            env: field("env").unwrap_or_default(),
+            abi: field("abi").unwrap_or_default(),
+            llvm_target,
+            relocation_model: field("relocation-model"),
+        })
+    }
+}
+
+impl TargetInfo<'_> {
+    /// Read the target information from a `rustc` target specification JSON
+    /// file, as passed to `rustc --target path/to/spec.json`.
+    pub(crate) fn from_json_spec(path: &Path) -> Result<OwnedTargetInfo, Error> {
+        let json = fs::read_to_string(path).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidTarget,
+                format!(
+                    "failed to read target specification {}: {e}",
+                    path.display()
+                ),
+            )
+        })?;
+        // `rustc` names custom targets after the file.
+        let triple = path
+            .file_stem()
+            .map_or_else(Default::default, |stem| stem.to_string_lossy());
+        OwnedTargetInfo::from_json(&triple, &json).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidTarget,
+                format!("invalid target specification {}: {e}", path.display()),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+
+    use super::OwnedTargetInfo;
+
+    fn hash(info: &OwnedTargetInfo) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        info.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn minimal_spec() {
+        let spec = r#"{
+            "arch": "arm",
+            "data-layout": "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64",
+            "llvm-target": "thumbv7em-none-eabihf",
+            "target-endian": "little",
+            "target-pointer-width": "32",
+            "target-c-int-width": 32,
+            "relocation-model": "static",
+            "linker-flavor": "gnu-lld",
+            "pre-link-args": { "gnu-lld": ["--nmagic", "-Tlink.x"] },
+            "features": "+vfp4d16sp,\"escaped\",",
+            "panic-strategy": "abort",
+            "disable-redzone": true,
+            "max-atomic-width": 32
+        }"#;
+        let info = OwnedTargetInfo::from_json("custom", spec).unwrap();
+        assert_eq!(&*info.llvm_target, "thumbv7em-none-eabihf");
+        assert_eq!(info.is_pic(), Some(false));
+
+        let info = info.target_info();
+        assert_eq!(info.triple(), "custom");
+        assert_eq!(info.full_arch, "thumbv7em");
+        assert_eq!(info.arch, "arm");
+        assert_eq!(info.vendor, "unknown");
+        assert_eq!(info.os, "none");
+        assert_eq!(info.env, "");
+        assert_eq!(info.abi, "");
+    }
+
+    #[test]
+    fn hosted_spec() {
+        let spec = r#"{"arch":"x86_64","llvm-target":"x86_64-unknown-linux-gnu","os":"linux",
+            "env":"gnu","vendor":"custom","relocation-model":"pic","is-builtin":false}"#;
+        let info = OwnedTargetInfo::from_json("custom", spec).unwrap();
+        assert_eq!(info.is_pic(), Some(true));
+        let info = info.target_info();
+        assert_eq!(info.full_arch, "x86_64");
+        assert_eq!(info.vendor, "custom");
+        assert_eq!(info.os, "linux");
+        assert_eq!(info.env, "gnu");
+    }
+
+    #[test]
+    fn invalid_spec() {
+        for spec in [
+            "",
+            "[]",
+            r#"{"arch": "arm"}"#,
+            r#"{"arch": "arm", "llvm-target": "arm-none-eabi""#,
+            r#"{"arch": "arm", "llvm-target": "arm-none-eabi"} x"#,
+            r#"{"arch": "arm", "llvm-target": "arm-none-eabi", "x": }"#,
+        ] {
+            assert!(
+                OwnedTargetInfo::from_json("custom", spec).is_err(),
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn equality_and_hashing() {
+        let spec = |triple: &str| {
+            OwnedTargetInfo::from_json(
+                "custom",
+                &format!(
+                    r#"{{"arch": "arm", "llvm-target": "{triple}", "relocation-model": "static"}}"#
+                ),
+            )
+            .unwrap()
+        };
+        let a = spec("thumbv7em-none-eabihf");
+        let b = spec("thumbv7em-none-eabihf");
+        let c = spec("thumbv6m-none-eabi");
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(a, c);
+        assert_ne!(hash(&a), hash(&c));
+
+        let mut cache = std::collections::HashMap::new();
+        cache.insert(a, 1);
+        assert_eq!(cache.get(&b), Some(&1));
+        assert_eq!(cache.get(&c), None);
+    }
+}
@@ -1,6 +1,6 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     env,
     ffi::{OsStr, OsString},
     io::Write,
@@ -13,7 +13,7 @@ use crate::{
     command_helpers::{run_output, CargoOutput},
     run,
     tempfile::NamedTempfile,
-    Error, ErrorKind, OutputKind,
+    CompilerFamily, Error, ErrorKind, Lto, OutputKind, WarningLevel,
 };
 
 pub(crate) type CompilerFamilyLookupCache = HashMap<Box<[Box<OsStr>]>, ToolFamily>;
@@ -167,21 +167,25 @@ impl Tool {
 
             // Ensure all the parent directories exist otherwise temp file creation
             // will fail
-            std::fs::create_dir_all(&out_dir).map_err(|err| Error {
-                kind: ErrorKind::IOError,
-                message: format!("failed to create OUT_DIR '{}': {}", out_dir.display(), err)
-                    .into(),
+            std::fs::create_dir_all(&out_dir).map_err(|err| {
+                Error::new(
+                    ErrorKind::IOError(err.kind()),
+                    format!("failed to create OUT_DIR '{}': {}", out_dir.display(), err),
+                )
+                .with_source(err)
             })?;
 
             let mut tmp =
-                NamedTempfile::new(&out_dir, "detect_compiler_family.c").map_err(|err| Error {
-                    kind: ErrorKind::IOError,
-                    message: format!(
-                        "failed to create detect_compiler_family.c temp file in '{}': {}",
-                        out_dir.display(),
-                        err
+                NamedTempfile::new(&out_dir, "detect_compiler_family.c").map_err(|err| {
+                    Error::new(
+                        ErrorKind::IOError(err.kind()),
+                        format!(
+                            "failed to create detect_compiler_family.c temp file in '{}': {}",
+                            out_dir.display(),
+                            err
+                        ),
                     )
-                    .into(),
+                    .with_source(err)
                 })?;
             let mut tmp_file = tmp.take_file().unwrap();
             tmp_file.write_all(include_bytes!("detect_compiler_family.c"))?;
@@ -227,6 +231,12 @@ impl Tool {
             }
 
             let family = detect_family_inner(path, args, cargo_output, out_dir)?;
+            log_record!(
+                debug,
+                "detected the {:?} compiler family for {}",
+                family,
+                path.display()
+            );
             cached_compiler_family
                 .write()
                 .unwrap()
@@ -257,6 +267,10 @@ impl Tool {
                     }
                 }
                 Some(fname) if fname.contains("zig") => ToolFamily::Clang { zig_cc: true },
+                // Emscripten's drivers run Clang.
+                Some(fname) if fname.starts_with("emcc") || fname.starts_with("em++") => {
+                    ToolFamily::Clang { zig_cc: false }
+                }
                 _ => ToolFamily::Gnu,
             }
         });
@@ -294,6 +308,49 @@ impl Tool {
         self.args.push(flag);
     }
 
+    /// Remove the earlier occurrences of warning flags passed more than once,
+    /// e.g. `-Wall` from both `CFLAGS` and [`Build::warning_level`], so that
+    /// the last one, which the compiler uses, stays in place.
+    ///
+    /// Flags passed on by a preceding `-Xclang`, `-Xcompiler`, etc. are kept,
+    /// as are the `-Wl,`, `-Wa,` and `-Wp,` flags passing arguments to other
+    /// tools, where repetition is meaningful.
+    ///
+    /// [`Build::warning_level`]: crate::Build::warning_level
+    pub(crate) fn dedup_warning_args(&mut self) {
+        let msvc = self.is_like_msvc();
+        let is_warning_arg = |arg: &str| {
+            let flag = match arg.strip_prefix('-') {
+                Some(flag) => flag,
+                None if msvc => match arg.strip_prefix('/') {
+                    Some(flag) => flag,
+                    None => return false,
+                },
+                None => return false,
+            };
+            flag.starts_with(['W', 'w'])
+                && !["Wl,", "Wa,", "Wp,"].iter().any(|p| flag.starts_with(p))
+        };
+
+        let mut seen = HashSet::new();
+        let mut keep = vec![true; self.args.len()];
+        for i in (0..self.args.len()).rev() {
+            let passed_on = i > 0
+                && self.args[i - 1]
+                    .to_str()
+                    .map_or(false, |prev| prev.starts_with("-X"));
+            let arg = match self.args[i].to_str() {
+                Some(arg) if !passed_on && is_warning_arg(arg) => arg,
+                _ => continue,
+            };
+            if !seen.insert(arg) {
+                keep[i] = false;
+            }
+        }
+        let mut keep = keep.into_iter();
+        self.args.retain(|_| keep.next().unwrap());
+    }
+
     /// Checks if an argument or flag has already been specified or conflicts.
     ///
     /// Currently only checks optimization flags.
@@ -470,6 +527,29 @@ pub enum ToolFamily {
     Msvc { clang_cl: bool },
 }
 
+impl ToolFamily {
+    /// The public family, which doesn't distinguish `zig cc` from Clang.
+    pub(crate) fn compiler_family(self) -> CompilerFamily {
+        match self {
+            ToolFamily::Gnu => CompilerFamily::Gnu,
+            ToolFamily::Clang { .. } => CompilerFamily::Clang,
+            ToolFamily::Msvc { clang_cl: false } => CompilerFamily::Msvc,
+            ToolFamily::Msvc { clang_cl: true } => CompilerFamily::ClangCl,
+        }
+    }
+}
+
+impl From<CompilerFamily> for ToolFamily {
+    fn from(family: CompilerFamily) -> Self {
+        match family {
+            CompilerFamily::Gnu => ToolFamily::Gnu,
+            CompilerFamily::Clang => ToolFamily::Clang { zig_cc: false },
+            CompilerFamily::Msvc => ToolFamily::Msvc { clang_cl: false },
+            CompilerFamily::ClangCl => ToolFamily::Msvc { clang_cl: true },
+        }
+    }
+}
+
 impl ToolFamily {
     /// What the flag to request debug info for this family of tools look like
     pub(crate) fn add_debug_flags(&self, cmd: &mut Tool, dwarf_version: Option<u32>) {
@@ -513,6 +593,49 @@ impl ToolFamily {
         }
     }
 
+    /// What the flags for a preset warning level are
+    pub(crate) fn warning_level_flags(&self, level: WarningLevel) -> &'static [&'static str] {
+        match (*self, level) {
+            (_, WarningLevel::Default) => &[],
+            (ToolFamily::Msvc { .. }, WarningLevel::Off) => &["-W0"],
+            (ToolFamily::Msvc { .. }, WarningLevel::All) => &["-W3"],
+            (ToolFamily::Msvc { .. }, WarningLevel::Extra) => &["-W4"],
+            (ToolFamily::Msvc { .. }, WarningLevel::Pedantic) => &["-Wall"],
+            (ToolFamily::Gnu | ToolFamily::Clang { .. }, WarningLevel::Off) => &["-w"],
+            (ToolFamily::Gnu | ToolFamily::Clang { .. }, WarningLevel::All) => &["-Wall"],
+            (ToolFamily::Gnu | ToolFamily::Clang { .. }, WarningLevel::Extra) => {
+                &["-Wall", "-Wextra"]
+            }
+            (ToolFamily::Gnu | ToolFamily::Clang { .. }, WarningLevel::Pedantic) => {
+                &["-Wall", "-Wextra", "-Wpedantic"]
+            }
+        }
+    }
+
+    /// What the flag to silence a warning given by name or MSVC number is, if
+    /// this compiler understands that form
+    pub(crate) fn suppress_warning_flag(&self, warning: &str) -> Option<String> {
+        let is_number = warning.bytes().all(|b| b.is_ascii_digit());
+        match *self {
+            ToolFamily::Msvc { .. } if is_number => Some(format!("-wd{}", warning)),
+            ToolFamily::Msvc { clang_cl: false } => None,
+            _ if is_number => None,
+            _ => Some(format!("-Wno-{}", warning)),
+        }
+    }
+
+    /// What the flag to turn a warning given by name or MSVC number into an
+    /// error is, if this compiler understands that form
+    pub(crate) fn warning_as_error_flag(&self, warning: &str) -> Option<String> {
+        let is_number = warning.bytes().all(|b| b.is_ascii_digit());
+        match *self {
+            ToolFamily::Msvc { .. } if is_number => Some(format!("-we{}", warning)),
+            ToolFamily::Msvc { clang_cl: false } => None,
+            _ if is_number => None,
+            _ => Some(format!("-Werror={}", warning)),
+        }
+    }
+
     /// What the flag to turn warning into errors
     pub(crate) fn warnings_to_errors_flag(&self) -> &'static str {
         match *self {
@@ -521,7 +644,216 @@ impl ToolFamily {
         }
     }
 
+    /// What the flags to enable some kind of LTO are, if it is supported
+    pub(crate) fn lto_flags(&self, lto: Lto) -> Option<&'static [&'static str]> {
+        match (*self, lto) {
+            (ToolFamily::Clang { .. } | ToolFamily::Msvc { clang_cl: true }, Lto::Fat) => {
+                Some(&["-flto=full"])
+            }
+            (ToolFamily::Clang { .. } | ToolFamily::Msvc { clang_cl: true }, Lto::Thin) => {
+                Some(&["-flto=thin"])
+            }
+            // Fat objects keep them usable when not linking with `-flto`.
+            (ToolFamily::Gnu, Lto::Fat) => Some(&["-flto", "-ffat-lto-objects"]),
+            (ToolFamily::Msvc { clang_cl: false }, Lto::Fat) => Some(&["-GL"]),
+            (ToolFamily::Gnu | ToolFamily::Msvc { clang_cl: false }, Lto::Thin) => None,
+        }
+    }
+
+    /// What the flag to limit the number of reported errors is, if any
+    pub(crate) fn max_errors_flag(&self) -> Option<&'static str> {
+        match *self {
+            ToolFamily::Msvc { clang_cl: false } => None,
+            ToolFamily::Gnu => Some("-fmax-errors="),
+            ToolFamily::Clang { .. } | ToolFamily::Msvc { clang_cl: true } => {
+                Some("-ferror-limit=")
+            }
+        }
+    }
+
+    /// What the flag to enable or disable colored diagnostics is, if any
+    pub(crate) fn diagnostics_color_flag(&self, color: bool) -> Option<&'static str> {
+        match (*self, color) {
+            (ToolFamily::Msvc { clang_cl: false }, _) => None,
+            (ToolFamily::Gnu, true) => Some("-fdiagnostics-color=always"),
+            (ToolFamily::Gnu, false) => Some("-fdiagnostics-color=never"),
+            (ToolFamily::Clang { .. } | ToolFamily::Msvc { clang_cl: true }, true) => {
+                Some("-fcolor-diagnostics")
+            }
+            (ToolFamily::Clang { .. } | ToolFamily::Msvc { clang_cl: true }, false) => {
+                Some("-fno-color-diagnostics")
+            }
+        }
+    }
+
     pub(crate) fn verbose_stderr(&self) -> bool {
         matches!(*self, ToolFamily::Clang { .. })
     }
 }
+
+/// Parse the major and minor version of a compiler from the banner it prints
+/// with `--version`, or without arguments for `cl.exe`.
+pub(crate) fn parse_version_banner(family: ToolFamily, banner: &str) -> Option<(u32, u32)> {
+    let version = match family {
+        // `gcc (Ubuntu 11.4.0-1ubuntu1~22.04) 11.4.0`, where the version
+        // follows the package in parentheses.
+        ToolFamily::Gnu => {
+            let line = banner.lines().next()?;
+            let rest = match line.find(')') {
+                Some(end) => &line[end + 1..],
+                None => line.split_once(' ')?.1,
+            };
+            rest.split_whitespace().next()?
+        }
+        // `Apple clang version 15.0.0 (clang-1500.3.9.4)`
+        ToolFamily::Clang { .. } | ToolFamily::Msvc { clang_cl: true } => banner
+            .lines()
+            .find_map(|line| line.split_once("version "))?
+            .1
+            .split_whitespace()
+            .next()?,
+        // `Microsoft (R) C/C++ Optimizing Compiler Version 19.38.33133 for x64`
+        ToolFamily::Msvc { clang_cl: false } => banner
+            .lines()
+            .find_map(|line| line.split_once("Version "))?
+            .1
+            .split_whitespace()
+            .next()?,
+    };
+
+    // Versions may have suffixes, as in `10-win32` or `14.0.0-1ubuntu1`.
+    let number = |part: &str| {
+        part.split(|c: char| !c.is_ascii_digit())
+            .next()?
+            .parse()
+            .ok()
+    };
+    let mut parts = version.split('.');
+    let major = number(parts.next()?)?;
+    let minor = parts.next().and_then(number).unwrap_or(0);
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use super::{parse_version_banner, Tool, ToolFamily};
+
+    #[test]
+    fn dedup_warning_args() {
+        let dedup = |family: ToolFamily, args: &[&str]| {
+            let mut tool = Tool::with_family(PathBuf::from("cc"), family);
+            tool.args = args.iter().map(OsString::from).collect();
+            tool.dedup_warning_args();
+            tool.args
+        };
+
+        assert_eq!(
+            dedup(
+                ToolFamily::Gnu,
+                &[
+                    "-Wall",
+                    "-Wextra",
+                    "-Wno-unused",
+                    "-Wl,-z",
+                    "-Wall",
+                    "-Xclang",
+                    "-Wfoo",
+                    "-Wl,-z",
+                    "-Xclang",
+                    "-Wfoo",
+                    "-Wextra",
+                    "-O2",
+                    "-O2",
+                ]
+            ),
+            [
+                "-Wno-unused",
+                "-Wl,-z",
+                "-Wall",
+                "-Xclang",
+                "-Wfoo",
+                "-Wl,-z",
+                "-Xclang",
+                "-Wfoo",
+                "-Wextra",
+                "-O2",
+                "-O2",
+            ]
+        );
+        assert_eq!(
+            dedup(
+                ToolFamily::Msvc { clang_cl: false },
+                &["-W4", "/wd4100", "-wd4100", "/W4", "-W4"]
+            ),
+            ["/wd4100", "-wd4100", "/W4", "-W4"]
+        );
+    }
+
+    #[test]
+    fn warning_as_error_flags() {
+        let clang = ToolFamily::Clang { zig_cc: false };
+        let msvc = ToolFamily::Msvc { clang_cl: false };
+        let clang_cl = ToolFamily::Msvc { clang_cl: true };
+        for (family, name, number) in [
+            (ToolFamily::Gnu, Some("-Werror=unused"), None),
+            (clang, Some("-Werror=unused"), None),
+            (msvc, None, Some("-we4013")),
+            (clang_cl, Some("-Werror=unused"), Some("-we4013")),
+        ] {
+            assert_eq!(family.warning_as_error_flag("unused").as_deref(), name);
+            assert_eq!(family.warning_as_error_flag("4013").as_deref(), number);
+        }
+    }
+
+    #[test]
+    fn version_banners() {
+        let clang = ToolFamily::Clang { zig_cc: false };
+        let msvc = ToolFamily::Msvc { clang_cl: false };
+        let banners = [
+            (
+                ToolFamily::Gnu,
+                "gcc (Ubuntu 9.4.0-1ubuntu1~20.04.2) 9.4.0\nCopyright (C) 2019 Free Software Foundation, Inc.\n",
+                (9, 4),
+            ),
+            (ToolFamily::Gnu, "gcc (GCC) 12.2.1 20221121 (Red Hat 12.2.1-4)\n", (12, 2)),
+            (ToolFamily::Gnu, "x86_64-w64-mingw32-gcc (GCC) 10-win32 20220113\n", (10, 0)),
+            (
+                clang,
+                "Ubuntu clang version 13.0.1-2ubuntu2.2\nTarget: x86_64-pc-linux-gnu\n",
+                (13, 0),
+            ),
+            (clang, "clang version 15.0.7\nTarget: x86_64-unknown-linux-gnu\n", (15, 0)),
+            (clang, "Apple clang version 15.0.0 (clang-1500.3.9.4)\n", (15, 0)),
+            (
+                ToolFamily::Msvc { clang_cl: true },
+                "clang version 17.0.6\nTarget: x86_64-pc-windows-msvc\n",
+                (17, 0),
+            ),
+            (
+                msvc,
+                "Microsoft (R) C/C++ Optimizing Compiler Version 19.38.33133 for x64\n",
+                (19, 38),
+            ),
+        ];
+        for (family, banner, version) in banners {
+            assert_eq!(
+                parse_version_banner(family, banner),
+                Some(version),
+                "{}",
+                banner
+            );
+        }
+
+        let gcc_9 = parse_version_banner(ToolFamily::Gnu, banners[0].1).unwrap();
+        let gcc_12 = parse_version_banner(ToolFamily::Gnu, banners[1].1).unwrap();
+        assert!(gcc_12 >= (9, 0) && gcc_9 < (12, 0));
+        let clang_13 = parse_version_banner(clang, banners[3].1).unwrap();
+        let clang_15 = parse_version_banner(clang, banners[4].1).unwrap();
+        assert!(clang_15 >= (13, 0) && clang_13 < (15, 0));
+
+        assert_eq!(parse_version_banner(clang, "emcc (Emscripten)\n"), None);
+        assert_eq!(parse_version_banner(msvc, ""), None);
+    }
+}
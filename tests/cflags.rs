@@ -9,6 +9,9 @@ use std::env;
 fn cflags() {
     gnu_no_warnings_if_cflags();
     cflags_order();
+    define_overrides_cflags();
+    warning_flags_dedup();
+    ignore_environment();
 }
 
 fn gnu_no_warnings_if_cflags() {
@@ -23,8 +26,8 @@ fn gnu_no_warnings_if_cflags() {
 ///
 /// 1. Default flags
 /// 2. Rustflags.
-/// 3. Builder flags.
-/// 4. Environment flags.
+/// 3. Environment flags.
+/// 4. Builder flags.
 fn cflags_order() {
     // FIXME(madsmtm): Re-enable once `is_flag_supported` works in CI regardless of `target`.
     // unsafe { std::env::set_var("CARGO_ENCODED_RUSTFLAGS", "-Cdwarf-version=5") };
@@ -47,11 +50,76 @@ fn cflags_order() {
     test.cmd(0)
         // .must_have_in_order("-static", "-gdwarf-5")
         // .must_have_in_order("-gdwarf-5", "-Lbuilder-flag1")
-        .must_have_in_order("-static", "-Lbuilder-flag1")
-        .must_have_in_order("-Lbuilder-flag1", "-Lbuilder-flag2")
-        .must_have_in_order("-Lbuilder-flag2", "-Larbitrary1")
+        .must_have_in_order("-static", "-Larbitrary1")
         .must_have_in_order("-Larbitrary1", "-Larbitrary2")
         .must_have_in_order("-Larbitrary1", "-Larbitrary2")
         .must_have_in_order("-Larbitrary2", "-Larbitrary3")
-        .must_have_in_order("-Larbitrary3", "-Larbitrary4");
+        .must_have_in_order("-Larbitrary3", "-Larbitrary4")
+        .must_have_in_order("-Larbitrary4", "-Lbuilder-flag1")
+        .must_have_in_order("-Lbuilder-flag1", "-Lbuilder-flag2");
+}
+
+/// Explicit defines are passed last, so the compiler uses them over the
+/// conflicting ones from the environment.
+fn define_overrides_cflags() {
+    unsafe { env::set_var("CFLAGS", "-DFOO=env -DBAR=env") };
+
+    let test = Test::gnu();
+    test.gcc()
+        .define("FOO", "explicit")
+        .flag("-DBAR=flag")
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have_in_order("-DFOO=env", "-DFOO=explicit")
+        .must_have_in_order("-DBAR=env", "-DBAR=flag");
+}
+
+/// Warning flags passed more than once are only kept in their last place, so
+/// that they don't mask a `-Wno-` between them.
+fn warning_flags_dedup() {
+    unsafe { env::set_var("CFLAGS", "-Wall -Wno-unused") };
+
+    let test = Test::gnu();
+    test.gcc()
+        .warning_level(cc::WarningLevel::All)
+        .flag("-Wl,--as-needed")
+        .flag("-Wl,--as-needed")
+        .file("foo.c")
+        .compile("foo");
+
+    let cmd = test.cmd(0);
+    let count = |arg: &str| cmd.args.iter().filter(|a| *a == arg).count();
+    assert_eq!(count("-Wall"), 1, "{:?}", cmd.args);
+    assert_eq!(count("-Wl,--as-needed"), 2, "{:?}", cmd.args);
+    cmd.must_have_in_order("-Wall", "-Wno-unused");
+}
+
+/// With `ignore_environment`, neither the flags nor the compiler come from
+/// the environment.
+fn ignore_environment() {
+    unsafe { env::set_var("CFLAGS", "-bogus-cflag") };
+    unsafe { env::set_var("TARGET_CFLAGS", "-bogus-target-cflag") };
+    unsafe { env::set_var("CC", "bogus-cc") };
+    unsafe { env::set_var("CRATE_CC_NO_DEFAULTS", "1") };
+
+    let test = Test::gnu();
+    let mut build = test.gcc();
+    build.ignore_environment(true).file("foo.c");
+    assert_eq!(build.get_compiler().path(), std::path::Path::new("cc"));
+    build.compile("foo");
+
+    test.cmd(0)
+        .must_not_have("-bogus-cflag")
+        .must_not_have("-bogus-target-cflag")
+        // The defaults are still there, including the warnings that `CFLAGS`
+        // would otherwise replace.
+        .must_have("-O2")
+        .must_have("-Wall");
+
+    unsafe { env::remove_var("CFLAGS") };
+    unsafe { env::remove_var("TARGET_CFLAGS") };
+    unsafe { env::remove_var("CC") };
+    unsafe { env::remove_var("CRATE_CC_NO_DEFAULTS") };
 }
@@ -0,0 +1,84 @@
+//! This test is in its own module because it re-runs itself in a child process to capture the
+//! metadata printed to stdout.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::{child_case, lines_starting_with, run_child, Test};
+use std::env;
+use std::fs;
+
+/// Re-run `cpp_link_stdlib` for `case`, and return the `rustc-link-lib`
+/// metadata it printed.
+fn link_libs(case: &str) -> Vec<String> {
+    lines_starting_with(&run_child("cpp_link_stdlib", case), "cargo:rustc-link-lib")
+}
+
+/// Re-run `cpp_link_stdlib` for `case`, and return the `rustc-link-search`
+/// metadata it printed.
+fn link_search(case: &str) -> Vec<String> {
+    lines_starting_with(
+        &run_child("cpp_link_stdlib", case),
+        "cargo:rustc-link-search",
+    )
+}
+
+#[test]
+fn cpp_link_stdlib() {
+    if let Some(case) = child_case() {
+        // The explicit setting must win over the environment.
+        env::set_var("CXXSTDLIB", "c++");
+        let test = Test::gnu();
+        let mut build = test.gcc();
+        build.cpp(true).file("foo.c");
+        match case.as_str() {
+            "none" => build.cpp_link_stdlib(None),
+            "explicit" => build.cpp_link_stdlib("c++_static"),
+            "static" => {
+                // Pretend that only libstdc++ is installed with the compiler.
+                fs::write(test.td.path().join("libstdc++.a"), "").unwrap();
+                build.cpp_link_stdlib("stdc++").cpp_stdlib_static(true)
+            }
+            "static-libcxx" => build.cpp_set_stdlib("c++").cpp_stdlib_static(true),
+            _ => &mut build,
+        };
+        build.compile("foo");
+        return;
+    }
+
+    assert_eq!(
+        link_libs("default"),
+        [
+            "cargo:rustc-link-lib=static=foo",
+            "cargo:rustc-link-lib=c++"
+        ]
+    );
+    assert_eq!(link_libs("none"), ["cargo:rustc-link-lib=static=foo"]);
+    assert_eq!(
+        link_libs("explicit"),
+        [
+            "cargo:rustc-link-lib=static=foo",
+            "cargo:rustc-link-lib=c++_static"
+        ]
+    );
+
+    assert_eq!(
+        link_libs("static"),
+        [
+            "cargo:rustc-link-lib=static=foo",
+            "cargo:rustc-link-lib=static:-bundle=stdc++"
+        ]
+    );
+    // The search path of the out directory, and that of libstdc++.
+    let search = link_search("static");
+    assert_eq!(search.len(), 2, "{:?}", search);
+    assert_eq!(search[0], search[1]);
+    assert_eq!(link_search("static-libcxx").len(), 1);
+    assert_eq!(
+        link_libs("static-libcxx")[1..],
+        [
+            "cargo:rustc-link-lib=static:-bundle=c++",
+            "cargo:rustc-link-lib=static:-bundle=c++abi"
+        ]
+    );
+}
@@ -0,0 +1,42 @@
+//! This test is in its own module because it modifies the environment of the whole process, which
+//! the archiver lookup uses directly.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::Test;
+use std::env;
+
+#[test]
+fn cross_archiver_prefix() {
+    let test = Test::gnu();
+    let mut path = vec![test.td.path().to_owned()];
+    path.extend(env::split_paths(&env::var_os("PATH").unwrap()));
+    env::set_var("PATH", env::join_paths(path).unwrap());
+    // The archiver is probed without the environment configured for the build.
+    env::set_var("CC_SHIM_OUT_DIR", test.td.path());
+
+    let archiver = |test: &Test| {
+        test.gcc()
+            .target("m68k-unknown-linux-gnu")
+            .compiler(test.td.path().join("cc"))
+            .try_get_archiver()
+            .unwrap()
+            .get_program()
+            .to_owned()
+    };
+
+    // Without a prefixed archiver, fall back to the host one.
+    assert_eq!(archiver(&test), "ar");
+
+    // `m68k-unknown-linux-gnu` has no well-known prefix, so it is derived
+    // from the target.
+    test.shim("m68k-linux-gnu-gcc-ar");
+    assert_eq!(archiver(&test), "m68k-linux-gnu-gcc-ar");
+    test.shim("m68k-linux-gnu-ar");
+    assert_eq!(archiver(&test), "m68k-linux-gnu-ar");
+
+    // The environment still takes precedence.
+    env::set_var("AR", "custom-ar");
+    assert_eq!(archiver(&test), "custom-ar");
+}
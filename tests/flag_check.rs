@@ -0,0 +1,46 @@
+//! This test is in its own module because it modifies the environment of the whole process, which
+//! the flag checks use directly.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::{Test, Warnings};
+use std::env;
+
+#[test]
+fn flag_check_without_warnings() {
+    let test = Test::gnu();
+    // The flags are checked without the environment configured for the build.
+    env::set_var("CC_SHIM_OUT_DIR", test.td.path());
+
+    // The shim warns about an unused function in the source of the flag
+    // checks when they are compiled with `-Wall`.
+    let warnings = Warnings::default();
+    test.gcc()
+        .compiler(test.td.path().join("cc"))
+        .warnings(true)
+        .warning_writer(Box::new(warnings.clone()))
+        .flag_if_supported("-fshim-flag")
+        .file("foo.c")
+        .compile("foo");
+
+    let lines = warnings.lines();
+    assert!(
+        !lines.iter().any(|line| line.contains("flag_check")),
+        "{:?}",
+        lines
+    );
+
+    let check = (0..)
+        .map(|i| test.cmd(i))
+        .find(|cmd| cmd.has("-fshim-flag".as_ref()))
+        .unwrap();
+    check.must_not_have("-Wall").must_not_have("-Wextra");
+    // The flag is supported, and the source is compiled with the warnings.
+    (0..)
+        .map(|i| test.cmd(i))
+        .find(|cmd| cmd.has("foo.c".as_ref()))
+        .unwrap()
+        .must_have("-fshim-flag")
+        .must_have("-Wall");
+}
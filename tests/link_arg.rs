@@ -0,0 +1,90 @@
+//! This test is in its own module because it re-runs itself in a child process to capture the
+//! metadata printed to stdout.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::{child_case, lines_starting_with, run_child, Test};
+
+/// Re-run the test `name` for `case`, and return the `rustc-link-arg*`
+/// metadata it printed.
+fn link_args(name: &str, case: &str) -> Vec<String> {
+    lines_starting_with(&run_child(name, case), "cargo:rustc-link-arg")
+}
+
+#[test]
+fn link_arg() {
+    if child_case().is_some() {
+        let test = Test::gnu();
+        test.gcc()
+            .link_arg("-Wl,--no-as-needed")
+            .link_arg_bins("-Wl,-z,now")
+            .link_arg_tests("-Wl,--gc-sections")
+            .file("foo.c")
+            .compile("foo");
+        return;
+    }
+
+    assert_eq!(
+        link_args("link_arg", "1"),
+        [
+            "cargo:rustc-link-arg=-Wl,--no-as-needed",
+            "cargo:rustc-link-arg-bins=-Wl,-z,now",
+            "cargo:rustc-link-arg-tests=-Wl,--gc-sections",
+        ]
+    );
+}
+
+#[test]
+fn lto_object_path() {
+    if let Some(case) = child_case() {
+        let test = Test::clang();
+        test.shim("xcrun");
+        let mut build = test.gcc();
+        build.compiler("clang").lto(cc::Lto::Fat).file("foo.c");
+        if case == "apple" {
+            build.target("aarch64-apple-darwin");
+        }
+        build.compile("foo");
+        return;
+    }
+
+    let args = link_args("lto_object_path", "apple");
+    assert_eq!(args.len(), 1, "{:?}", args);
+    let path = args[0]
+        .strip_prefix("cargo:rustc-link-arg=-Wl,-object_path_lto,")
+        .unwrap();
+    assert!(path.ends_with("lto.o"), "{}", path);
+    assert_eq!(link_args("lto_object_path", "linux"), [] as [&str; 0]);
+}
+
+#[test]
+fn apple_framework() {
+    if child_case().is_some() {
+        let test = Test::clang();
+        test.shim("xcrun");
+        test.gcc()
+            .compiler("clang")
+            .target("aarch64-apple-darwin")
+            .framework_path("vendor/Frameworks")
+            .apple_framework("Foundation")
+            .apple_framework("Security")
+            .file("foo.c")
+            .compile("foo");
+        return;
+    }
+
+    let metadata = lines_starting_with(&run_child("apple_framework", "1"), "cargo:rustc-link-");
+    let frameworks = metadata
+        .iter()
+        .filter(|line| line.contains("framework="))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        frameworks,
+        [
+            "cargo:rustc-link-search=framework=vendor/Frameworks",
+            "cargo:rustc-link-lib=framework=Foundation",
+            "cargo:rustc-link-lib=framework=Security",
+        ]
+    );
+}
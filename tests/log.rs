@@ -0,0 +1,46 @@
+//! This test is in its own module because it installs a logger for the whole process.
+#![cfg(feature = "log")]
+
+mod support;
+
+use crate::support::Test;
+use std::sync::Mutex;
+
+static RECORDS: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+
+struct CapturingLogger;
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        let message = record.args().to_string();
+        RECORDS.lock().unwrap().push((record.level(), message));
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn compile_logs_compiler_invocations() {
+    log::set_logger(&CapturingLogger).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let test = Test::gnu();
+    let compiler = test.td.path().join("cc");
+    test.gcc().compiler(&compiler).file("foo.c").compile("foo");
+
+    let records = RECORDS.lock().unwrap();
+    let compiler = compiler.display().to_string();
+    assert!(
+        records
+            .iter()
+            .any(|(level, message)| *level == log::Level::Info
+                && message.starts_with("running: ")
+                && message.contains(&compiler)),
+        "{:#?}",
+        records
+    );
+}
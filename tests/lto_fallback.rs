@@ -0,0 +1,99 @@
+//! This test is in its own module because it re-runs itself in a child process to capture the
+//! warnings printed to stdout.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::{child_case, run_child, Test};
+use std::fs;
+
+/// A test whose `foo.c` compiles, and whose `lto.c` only fails with LTO.
+fn lto_test() -> Test {
+    let test = Test::gnu();
+    fs::write(test.td.path().join("foo.c"), "int foo;\n").unwrap();
+    fs::write(
+        test.td.path().join("lto.c"),
+        "// shim-lto-fails\nint lto;\n",
+    )
+    .unwrap();
+    test
+}
+
+/// The args of all the commands run in the test.
+fn commands(test: &Test) -> Vec<Vec<String>> {
+    (0..)
+        .take_while(|i| test.td.path().join(format!("out{}", i)).exists())
+        .map(|i| test.cmd(i).args)
+        .collect()
+}
+
+#[test]
+fn lto_fallback() {
+    if child_case().is_some() {
+        let test = lto_test();
+        test.gcc()
+            .lto(cc::Lto::Fat)
+            .lto_fallback(true)
+            .file(test.td.path().join("foo.c"))
+            .file(test.td.path().join("lto.c"))
+            .compile("foo");
+
+        let commands = commands(&test);
+        let lto = |args: &&Vec<String>| args.last().map_or(false, |src| src.ends_with("lto.c"));
+        let retry = commands.iter().filter(lto).nth(1).unwrap();
+        assert!(retry.iter().any(|a| a == "-fno-lto"), "{:?}", retry);
+        assert!(!retry.iter().any(|a| a.starts_with("-flto")), "{:?}", retry);
+        assert!(
+            !retry.iter().any(|a| a == "-ffat-lto-objects"),
+            "{:?}",
+            retry
+        );
+        // Only the failing file is compiled again.
+        let foo = |args: &&Vec<String>| args.last().map_or(false, |src| src.ends_with("foo.c"));
+        assert_eq!(commands.iter().filter(foo).count(), 1);
+        return;
+    }
+
+    let stdout = run_child("lto_fallback", "1");
+    assert!(
+        stdout.contains("cargo:warning=lto1: internal compiler error"),
+        "{}",
+        stdout
+    );
+    let warning = stdout
+        .lines()
+        .find(|line| line.contains("failed with LTO, compiling it again without LTO"))
+        .unwrap_or_else(|| panic!("no warning about the retry:\n{}", stdout));
+    assert!(
+        warning.starts_with("cargo:warning=compiling "),
+        "{}",
+        warning
+    );
+    assert!(warning.contains("lto.c"), "{}", warning);
+}
+
+#[test]
+fn lto_fallback_disabled() {
+    let test = lto_test();
+    test.gcc()
+        .lto(cc::Lto::Fat)
+        .cargo_warnings(false)
+        .file(test.td.path().join("lto.c"))
+        .try_compile("foo")
+        .unwrap_err();
+    assert_eq!(commands(&test).len(), 1);
+}
+
+#[test]
+fn lto_fallback_other_failure() {
+    let test = lto_test();
+    fs::write(test.td.path().join("error.c"), "#error broken\n").unwrap();
+    test.gcc()
+        .lto(cc::Lto::Fat)
+        .lto_fallback(true)
+        .cargo_warnings(false)
+        .file(test.td.path().join("error.c"))
+        .try_compile("foo")
+        .unwrap_err();
+    assert_eq!(commands(&test).len(), 1);
+}
@@ -0,0 +1,30 @@
+//! This test is in its own module because it re-runs itself in a child process to capture the
+//! metadata printed to stdout.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::{child_case, lines_starting_with, run_child, Test};
+use std::fs;
+
+#[test]
+fn module_definition_link_arg() {
+    if let Some(def) = child_case() {
+        let test = Test::msvc();
+        test.gcc()
+            .module_definition(def)
+            .file("foo.c")
+            .compile("foo");
+        return;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let def = dir.path().join("foo.def");
+    fs::write(&def, "EXPORTS\n    foo\n").unwrap();
+    let stdout = run_child("module_definition_link_arg", def.to_str().unwrap());
+    let metadata = lines_starting_with(&stdout, "cargo:rustc-link-arg");
+    assert_eq!(
+        metadata,
+        [format!("cargo:rustc-link-arg=/DEF:{}", def.display())]
+    );
+}
@@ -0,0 +1,36 @@
+//! This test is in its own module because it modifies the environment of the whole process, which
+//! the flag checks and the system's temporary directory use directly.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::Test;
+use std::env;
+
+#[test]
+fn writes_confined_to_out_dir() {
+    let test = Test::gnu();
+    // The flags are checked without the environment configured for the build.
+    env::set_var("CC_SHIM_OUT_DIR", test.td.path());
+    env::remove_var("OUT_DIR");
+    // Nothing may be written to the system's temporary directory, which
+    // doesn't exist until something is.
+    let tmp = test.td.path().join("system-tmp");
+    for var in ["TMPDIR", "TMP", "TEMP"] {
+        env::set_var(var, &tmp);
+    }
+
+    let out_dir = test.td.path().join("out");
+    std::fs::create_dir(&out_dir).unwrap();
+    test.gcc()
+        .compiler(test.td.path().join("cc"))
+        .out_dir(&out_dir)
+        .flag_if_supported("-fshim-flag")
+        .file("foo.c")
+        .file("bar.c")
+        .unity_build(2)
+        .compile("foo");
+
+    assert!(!tmp.exists(), "{} was written to", tmp.display());
+    assert!(out_dir.join("libfoo.a").exists());
+}
@@ -0,0 +1,44 @@
+//! This test is in its own module because it modifies the environment of the whole process, which
+//! the compiler lookup uses directly.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::Test;
+use cc::CompilerFamily;
+use std::env;
+use std::path::Path;
+
+#[test]
+fn prefer_compiler() {
+    let test = Test::new();
+    // Only the shims are found, not any compilers installed on the host.
+    env::set_var("PATH", test.td.path());
+    // The compiler family is detected without the environment configured for the build.
+    env::set_var("CC_SHIM_OUT_DIR", test.td.path());
+
+    let compiler = |test: &Test, families: &[CompilerFamily]| {
+        let compiler = test
+            .gcc()
+            .target("x86_64-unknown-linux-gnu")
+            .host("x86_64-unknown-linux-gnu")
+            .prefer_compiler(families)
+            .try_get_compiler()
+            .unwrap();
+        compiler.path().to_owned()
+    };
+    let preferred = [CompilerFamily::Clang, CompilerFamily::Gnu];
+
+    // Skip compilers that aren't available.
+    test.shim("gcc");
+    assert_eq!(compiler(&test, &preferred), Path::new("gcc"));
+    test.shim("clang");
+    assert_eq!(compiler(&test, &preferred), Path::new("clang"));
+
+    // Without a preference, the platform's default is used.
+    assert_eq!(compiler(&test, &[]), Path::new("cc"));
+
+    // The environment still takes precedence.
+    env::set_var("CC", "gcc");
+    assert_eq!(compiler(&test, &[CompilerFamily::Clang]), Path::new("gcc"));
+}
@@ -0,0 +1,44 @@
+//! This test is in its own module because it modifies the environment of the whole process, which
+//! is where the relocation model override is read from.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::Test;
+use std::env;
+
+#[test]
+fn relocation_model_override() {
+    let var = "CC_RELOCATION_MODEL_x86_64-unknown-linux-gnu";
+
+    // The guess for the target is PIC.
+    let test = Test::gnu();
+    test.gcc().file("foo.c").compile("foo");
+    test.cmd(0).must_have("-fPIC");
+
+    env::set_var(var, "static");
+    let test = Test::gnu();
+    test.gcc().file("foo.c").compile("foo");
+    test.cmd(0).must_not_have("-fPIC").must_not_have("-fPIE");
+
+    env::set_var(var, "pie");
+    let test = Test::gnu();
+    test.gcc().file("foo.c").compile("foo");
+    test.cmd(0).must_have("-fPIE").must_not_have("-fPIC");
+
+    // An explicit setting in the build script still wins.
+    let test = Test::gnu();
+    test.gcc().pic(true).file("foo.c").compile("foo");
+    test.cmd(0).must_have("-fPIC").must_not_have("-fPIE");
+
+    env::set_var(var, "ropi");
+    let test = Test::gnu();
+    let err = test.gcc().file("foo.c").try_compile("foo").unwrap_err();
+    assert!(
+        err.to_string().contains("invalid relocation model"),
+        "{}",
+        err
+    );
+
+    env::remove_var(var);
+}
@@ -0,0 +1,59 @@
+//! This test is in its own module because it re-runs itself in a child process to capture the
+//! warnings printed to stdout.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::{child_case, lines_starting_with, run_child, Test};
+use std::fs;
+
+/// Re-run `rtti_usage` for `case`, and return the warnings it printed.
+fn warnings(case: &str) -> Vec<String> {
+    lines_starting_with(&run_child("rtti_usage", case), "cargo:warning=")
+}
+
+#[test]
+fn rtti_usage() {
+    if let Some(case) = child_case() {
+        let test = Test::gnu();
+        fs::write(
+            test.td.path().join("typeid.cpp"),
+            "#include <typeinfo>\n\nconst char *name(int x) { return typeid(x).name(); }\n",
+        )
+        .unwrap();
+        let mut build = test.gcc();
+        build
+            .cpp(true)
+            .file(test.td.path().join("foo.c"))
+            .file(test.td.path().join("typeid.cpp"));
+        match case.as_str() {
+            "lint" => build.cpp_rtti(false).lint_rtti_usage(true),
+            "rtti" => build.cpp_rtti(true).lint_rtti_usage(true),
+            _ => build.cpp_rtti(false),
+        };
+        build.compile("foo");
+        return;
+    }
+
+    let lint = warnings("lint");
+    let culprit = lint
+        .iter()
+        .find(|line| line.contains("needs RTTI"))
+        .unwrap_or_else(|| panic!("no warning about RTTI: {:?}", lint));
+    assert!(
+        culprit.contains("typeid.cpp:3: `typeid` needs RTTI"),
+        "{}",
+        culprit
+    );
+    assert_eq!(lint.iter().filter(|line| line.contains("RTTI")).count(), 1);
+
+    for case in ["rtti", "no-lint"] {
+        let warnings = warnings(case);
+        assert!(
+            !warnings.iter().any(|line| line.contains("RTTI")),
+            "{}: {:?}",
+            case,
+            warnings
+        );
+    }
+}
@@ -7,6 +7,8 @@ use std::fs::{self, File};
 use std::io;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
 
 use tempfile::{Builder, TempDir};
 
@@ -20,6 +22,31 @@ pub struct Execution {
     pub args: Vec<String>,
 }
 
+/// A writer collecting the compiler messages passed to `Build::warning_writer`.
+#[derive(Clone, Default)]
+pub struct Warnings(Arc<Mutex<Vec<u8>>>);
+
+impl Warnings {
+    /// Return the lines written so far.
+    pub fn lines(&self) -> Vec<String> {
+        let output = self.0.lock().unwrap();
+        String::from_utf8_lossy(&output)
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+}
+
+impl Write for Warnings {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 impl Test {
     pub fn new() -> Test {
         // This is ugly: `sccache` needs to introspect the compiler it is
@@ -168,6 +195,40 @@ impl Execution {
     }
 }
 
+/// The environment variable telling a test re-run by [`run_child`] which case
+/// to run.
+const CHILD_ENV: &str = "CC_TEST_CHILD_CASE";
+
+/// The case a test re-run by [`run_child`] runs, or `None` in the parent test.
+pub fn child_case() -> Option<String> {
+    env::var(CHILD_ENV).ok()
+}
+
+/// Re-run the test `test_name` of this test binary in a child process for
+/// `case`, and return what it printed to stdout, like `cargo:` metadata.
+///
+/// Tests using this are in their own binaries, as they capture everything
+/// `cc` prints while building.
+pub fn run_child(test_name: &str, case: &str) -> String {
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", test_name, "--nocapture"])
+        .env(CHILD_ENV, case)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "child test failed:\n{}", stdout);
+    stdout
+}
+
+/// The lines of the output of [`run_child`] starting with `prefix`.
+pub fn lines_starting_with(stdout: &str, prefix: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter(|line| line.starts_with(prefix))
+        .map(str::to_string)
+        .collect()
+}
+
 /// Hard link an executable or copy it if that fails.
 ///
 /// We first try to hard link an executable to save space. If that fails (as on Windows with
@@ -0,0 +1,33 @@
+//! This test is in its own module because it modifies the environment of the whole process, which
+//! the flag checks use directly.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::Test;
+use std::env;
+
+#[test]
+fn supported_flags() {
+    let test = Test::gnu();
+    // The flags are checked without the environment configured for the build.
+    env::set_var("CC_SHIM_OUT_DIR", test.td.path());
+
+    // The shim prints `--shim-warning=` flags to stderr, which makes them
+    // unsupported.
+    let mut build = test.gcc();
+    build
+        .compiler(test.td.path().join("cc"))
+        .flag_if_supported("-fshim-flag")
+        .flag_if_supported("--shim-warning=unknown flag")
+        .file("foo.c")
+        .compile("foo");
+
+    assert_eq!(
+        build.supported_flags(),
+        [
+            ("--shim-warning=unknown flag".to_string(), false),
+            ("-fshim-flag".to_string(), true),
+        ]
+    );
+}
@@ -0,0 +1,47 @@
+//! This test is in its own module because it modifies the environment of the whole process, which
+//! is where the target features are read from.
+#![allow(clippy::disallowed_methods)]
+
+mod support;
+
+use crate::support::Test;
+use std::env;
+
+#[test]
+fn match_rust_target_features() {
+    env::set_var(
+        "CARGO_CFG_TARGET_FEATURE",
+        "fxsr,sse,sse2,sse4.1,sse4.2,avx2",
+    );
+
+    let test = Test::gnu();
+    test.gcc()
+        .match_rust_target_features(true)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-msse4.2").must_have("-mavx2");
+
+    // The features are only translated when asked to.
+    let test = Test::gnu();
+    test.gcc().file("foo.c").compile("foo");
+    test.cmd(0)
+        .must_not_have("-msse4.2")
+        .must_not_have("-mavx2");
+
+    // The `aarch64` features are combined into a single `-march`.
+    env::set_var(
+        "CARGO_CFG_TARGET_FEATURE",
+        "aes,crc,lse,neon,rdm,sha2,v8.1a",
+    );
+    let test = Test::gnu();
+    test.gcc()
+        .target("aarch64-unknown-linux-gnu")
+        .host("aarch64-unknown-linux-gnu")
+        .match_rust_target_features(true)
+        .file("foo.c")
+        .compile("foo");
+    let cmd = test.cmd(0);
+    cmd.must_have("-march=armv8.1-a+crc+lse+rdma+aes+sha2");
+    let marches = cmd.args.iter().filter(|a| a.starts_with("-march=")).count();
+    assert_eq!(marches, 1, "{:?}", cmd.args);
+}
@@ -1,6 +1,9 @@
 #![allow(clippy::disallowed_methods)]
 
-use crate::support::Test;
+use crate::support::{child_case, lines_starting_with, run_child, Execution, Test, Warnings};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
 
 mod support;
 
@@ -55,6 +58,53 @@ fn gnu_opt_level_s() {
         .must_not_have("-Oz");
 }
 
+#[test]
+fn gnu_opt_level_z() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc().opt_level_str("z").file("foo.c").compile("foo");
+
+    test.cmd(0)
+        .must_have("-Os")
+        .must_not_have("-Oz")
+        .must_not_have("-O2");
+}
+
+#[test]
+fn clang_opt_level_s() {
+    reset_env();
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .opt_level_str("s")
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("-Os")
+        .must_not_have("-Oz")
+        .must_not_have("-O2");
+}
+
+#[test]
+fn clang_opt_level_z() {
+    reset_env();
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .opt_level_str("z")
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("-Oz")
+        .must_not_have("-Os")
+        .must_not_have("-O2");
+}
+
 #[test]
 fn gnu_debug() {
     let test = Test::gnu();
@@ -256,629 +306,3218 @@ fn gnu_i686_pic() {
 }
 
 #[test]
-fn gnu_x86_64_no_plt() {
-    let target = "x86_64-unknown-linux-gnu";
+fn gnu_compile_with_deps_info() {
     let test = Test::gnu();
-    test.gcc()
-        .pic(true)
-        .use_plt(false)
-        .target(target)
-        .host(target)
-        .file("foo.c")
-        .compile("foo");
-    test.cmd(0).must_have("-fno-plt");
-}
+    let src = test.td.path().join("foo.c");
+    let header = test.td.path().join("foo.h");
+    fs::write(&src, "#include \"foo.h\"\n").unwrap();
+    fs::write(&header, "").unwrap();
+    let artifacts = test.gcc().file(&src).compile_with_deps_info("foo");
 
-#[test]
-fn gnu_aarch64_none_no_pic() {
-    for target in &["aarch64-unknown-none-softfloat", "aarch64-unknown-none"] {
-        let test = Test::gnu();
-        test.gcc()
-            .target(target)
-            .host(target)
-            .file("foo.c")
-            .compile("foo");
+    test.cmd(0).must_have("-MMD").must_have("-MF");
+    assert_eq!(artifacts.dependencies[&src], [header]);
 
-        test.cmd(0).must_not_have("-fPIC");
-    }
+    // Dependency files are only requested when needed.
+    let test = Test::gnu();
+    test.gcc().file("foo.c").compile("foo");
+    test.cmd(0).must_not_have("-MMD");
 }
 
 #[test]
-fn gnu_uefi_no_pic() {
-    reset_env();
-
-    for arch in &["aarch64", "i686", "x86_64"] {
-        let target = format!("{}-unknown-uefi", arch);
-        let test = Test::gnu();
-        test.gcc()
-            .target(&target)
-            .host(&target)
-            .file("foo.c")
-            .compile("foo");
-
-        test.cmd(0).must_not_have("-fPIC");
+fn gnu_depfile_dir() {
+    let test = Test::gnu();
+    let deps = test.td.path().join("deps");
+    // Two sources with the same name in different directories.
+    for dir in ["a", "b"].iter() {
+        fs::create_dir_all(test.td.path().join(dir)).unwrap();
+        fs::write(test.td.path().join(dir).join("foo.c"), "").unwrap();
+    }
+    let artifacts = test
+        .gcc()
+        .file(test.td.path().join("a/foo.c"))
+        .file(test.td.path().join("b/foo.c"))
+        .depfile_dir(&deps)
+        .compile_with_deps_info("foo");
+
+    let mut dep_files = fs::read_dir(&deps)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    dep_files.sort();
+    let mut expected = artifacts
+        .objects
+        .iter()
+        .map(|obj| deps.join(obj.with_extension("d").file_name().unwrap()))
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(dep_files, expected);
+    for (i, obj) in (0..).zip(&artifacts.objects) {
+        let dep_file = deps.join(obj.with_extension("d").file_name().unwrap());
+        test.cmd(i)
+            .must_have_in_order("-MF", dep_file.to_str().unwrap());
     }
+    assert_eq!(artifacts.dependencies.len(), 2);
 }
 
 #[test]
-fn gnu_set_stdlib() {
-    reset_env();
-
+fn gnu_precompiled_header() {
     let test = Test::gnu();
     test.gcc()
-        .cpp_set_stdlib(Some("foo"))
+        .cpp(true)
+        .precompiled_header("common.hpp")
         .file("foo.c")
+        .file("bar.cpp")
         .compile("foo");
 
-    test.cmd(0).must_not_have("-stdlib=foo");
-}
-
-#[test]
-fn gnu_include() {
-    let test = Test::gnu();
-    test.gcc().include("foo/bar").file("foo.c").compile("foo");
-
-    test.cmd(0).must_have("-I").must_have("foo/bar");
+    let pch_dir = test.td.path().join("pch");
+    test.cmd(0)
+        .must_have_in_order("c++-header", "common.hpp")
+        .must_have_in_order("-o", pch_dir.join("common.hpp.gch").to_str().unwrap());
+    test.cmd(1).must_have("foo.c").must_not_have("-include");
+    test.cmd(2)
+        .must_have_in_order("-include", pch_dir.join("common.hpp").to_str().unwrap())
+        .must_have("bar.cpp");
 }
 
 #[test]
-fn gnu_define() {
-    let test = Test::gnu();
+fn clang_precompiled_header() {
+    let test = Test::clang();
     test.gcc()
-        .define("FOO", "bar")
-        .define("BAR", None)
+        .compiler("clang")
+        .precompiled_header("common.h")
         .file("foo.c")
         .compile("foo");
 
-    test.cmd(0).must_have("-DFOO=bar").must_have("-DBAR");
-}
-
-#[test]
-fn gnu_compile_assembly() {
-    let test = Test::gnu();
-    test.gcc().file("foo.S").compile("foo");
-    test.cmd(0).must_have("foo.S");
+    let pch = test.td.path().join("pch").join("common.h.pch");
+    test.cmd(0).must_have_in_order("c-header", "common.h");
+    test.cmd(1)
+        .must_have_in_order("-include-pch", pch.to_str().unwrap());
 }
 
 #[test]
-fn gnu_shared() {
-    reset_env();
-
+fn precompiled_header_language_mismatch() {
     let test = Test::gnu();
-    test.gcc()
+    let err = test
+        .gcc()
+        .precompiled_header("common.hpp")
         .file("foo.c")
-        .shared_flag(true)
-        .static_flag(false)
-        .compile("foo");
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(err.to_string().contains("no C++ sources"), "{}", err);
+    assert!(!test.td.path().join("out0").exists());
+}
 
-    test.cmd(0).must_have("-shared").must_not_have("-static");
+fn cpp_module_sources(test: &Test) -> (std::path::PathBuf, std::path::PathBuf) {
+    let interface = test.td.path().join("math.cppm");
+    let implementation = test.td.path().join("math.cpp");
+    fs::write(
+        &interface,
+        "export module math;\nexport int add(int, int);\n",
+    )
+    .unwrap();
+    fs::write(
+        &implementation,
+        "module math;\nint add(int a, int b) { return a + b; }\n",
+    )
+    .unwrap();
+    (interface, implementation)
 }
 
 #[test]
-#[cfg(target_os = "linux")]
-fn gnu_link_shared() {
-    use std::process::Command;
-
-    let output = Command::new("rustc").arg("-vV").output().unwrap();
-    let stdout_buf = String::from_utf8(output.stdout).unwrap();
-    let target = stdout_buf
-        .lines()
-        .find_map(|l| l.strip_prefix("host: "))
-        .unwrap();
-
-    reset_env();
-    let test = Test::gnu();
-    let root_dir = env!("CARGO_MANIFEST_DIR");
-    let src = format!("{root_dir}/dev-tools/cc-test/src/foo.c");
-
-    cc::Build::new()
-        .host(target)
-        .target(target)
-        .opt_level(2)
-        .out_dir(test.td.path())
-        .file(&src)
-        .shared_flag(true)
-        .static_flag(false)
-        .link_shared_flag(true)
+fn clang_cpp_modules() {
+    let test = Test::clang();
+    let (interface, implementation) = cpp_module_sources(&test);
+    test.gcc()
+        .compiler("clang++")
+        .cpp(true)
+        .cpp_module_impl(&implementation)
+        .cpp_module_interface(&interface)
         .compile("foo");
 
-    assert!(test.td.path().join("libfoo.so").exists());
+    let bmi_dir = test.td.path().join("cpp-modules");
+    let search_path = format!("-fprebuilt-module-path={}", bmi_dir.display());
+    test.cmd(0)
+        .must_have(&interface)
+        .must_have("-std=c++20")
+        .must_have_in_order("-x", "c++-module")
+        .must_have(format!(
+            "-fmodule-output={}",
+            bmi_dir.join("math.pcm").display()
+        ))
+        .must_have(&search_path);
+    test.cmd(1)
+        .must_have(&implementation)
+        .must_have(&search_path)
+        .must_not_have("c++-module");
 }
 
 #[test]
-fn gnu_flag_if_supported() {
-    reset_env();
-
-    if cfg!(windows) {
-        return;
-    }
+fn gnu_cpp_modules() {
     let test = Test::gnu();
+    let (interface, implementation) = cpp_module_sources(&test);
     test.gcc()
-        .file("foo.c")
-        .flag("-v")
-        .flag_if_supported("-Wall")
-        .flag_if_supported("-Wflag-does-not-exist")
-        .flag_if_supported("-std=c++11")
+        .cpp(true)
+        .std("c++23")
+        .cpp_module_interface(&interface)
+        .cpp_module_impl(&implementation)
         .compile("foo");
 
+    let bmi_dir = test.td.path().join("cpp-modules");
+    let mapper = bmi_dir.join("module-mapper.txt");
+    let mapper_flag = format!("-fmodule-mapper={}", mapper.display());
     test.cmd(0)
-        .must_have("-v")
-        .must_have("-Wall")
-        .must_not_have("-Wflag-does-not-exist")
-        .must_not_have("-std=c++11");
+        .must_have(&interface)
+        .must_have("-fmodules-ts")
+        .must_have(&mapper_flag)
+        .must_have_in_order("-x", "c++")
+        .must_have("-std=c++23")
+        .must_not_have("-std=c++20");
+    test.cmd(1)
+        .must_have(&implementation)
+        .must_have(&mapper_flag)
+        .must_not_have("-x");
+    assert_eq!(
+        fs::read_to_string(mapper).unwrap(),
+        format!("math {}\n", bmi_dir.join("math.gcm").display())
+    );
 }
 
-#[cfg(not(windows))]
 #[test]
-fn gnu_flag_if_supported_cpp() {
-    let test = Test::gnu();
+fn msvc_cpp_modules() {
+    let test = Test::msvc();
+    let (interface, implementation) = cpp_module_sources(&test);
     test.gcc()
         .cpp(true)
-        .file("foo.cpp")
-        .flag_if_supported("-std=c++11")
+        .cpp_module_interface(&interface)
+        .cpp_module_impl(&implementation)
         .compile("foo");
 
-    test.cmd(0).must_have("-std=c++11");
+    let bmi_dir = test.td.path().join("cpp-modules");
+    test.cmd(0)
+        .must_have(&interface)
+        .must_have("-std:c++20")
+        .must_have("-interface")
+        .must_have_in_order(
+            "-ifcOutput",
+            &bmi_dir.join("math.ifc").display().to_string(),
+        )
+        .must_have_in_order("-ifcSearchDir", &bmi_dir.display().to_string());
+    test.cmd(1)
+        .must_have(&implementation)
+        .must_have("-ifcSearchDir")
+        .must_not_have("-interface");
 }
 
 #[test]
-fn gnu_static() {
-    reset_env();
-
-    let test = Test::gnu();
+fn clang_cpp_std_module() {
+    let test = Test::clang();
+    let (interface, _) = cpp_module_sources(&test);
+    let libcxx = test.td.path().join("share").join("libc++").join("v1");
+    fs::create_dir_all(&libcxx).unwrap();
+    fs::write(libcxx.join("std.cppm"), "module;\nexport module std;\n").unwrap();
+    fs::write(
+        test.td.path().join("libc++.modules.json"),
+        r#"{"version": 1, "revision": 1, "modules": [{
+            "logical-name": "std",
+            "source-path": "share/libc++/v1/std.cppm",
+            "is-std-library": true,
+            "local-arguments": {"system-include-directories": ["share/libc++/v1"]}
+        }]}"#,
+    )
+    .unwrap();
     test.gcc()
-        .file("foo.c")
-        .shared_flag(false)
-        .static_flag(true)
+        .compiler("clang++")
+        .cpp(true)
+        .cpp_std_modules_std(true)
+        .cpp_module_interface(&interface)
         .compile("foo");
 
-    test.cmd(0).must_have("-static").must_not_have("-shared");
-}
-
-#[test]
-fn gnu_no_dash_dash() {
+    let bmi_dir = test.td.path().join("cpp-modules");
+    let search_path = format!("-fprebuilt-module-path={}", bmi_dir.display());
+    test.cmd(0)
+        .must_have("-print-file-name=libc++.modules.json");
+    test.cmd(1)
+        .must_have(libcxx.join("std.cppm"))
+        .must_have_in_order("-isystem", &libcxx.display().to_string())
+        .must_have("-Wno-reserved-module-identifier")
+        .must_have(format!(
+            "-fmodule-output={}",
+            bmi_dir.join("std.pcm").display()
+        ));
+    test.cmd(2)
+        .must_have(&interface)
+        .must_have(&search_path)
+        .must_not_have("-Wno-reserved-module-identifier");
+
+    // GCC has no `libc++.modules.json`.
     let test = Test::gnu();
-    test.gcc().file("foo.c").compile("foo");
-
-    test.cmd(0).must_not_have("--");
+    let err = test
+        .gcc()
+        .cpp(true)
+        .cpp_std_modules_std(true)
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(err.to_string().contains("Clang and libc++"), "{}", err);
 }
 
 #[test]
-fn gnu_std_c() {
+fn cpp_modules_require_cpp() {
     let test = Test::gnu();
-    test.gcc().file("foo.c").std("c11").compile("foo");
-
-    test.cmd(0).must_have("-std=c11");
+    let (interface, _) = cpp_module_sources(&test);
+    let err = test
+        .gcc()
+        .cpp_module_interface(&interface)
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(err.to_string().contains("Build::cpp(true)"), "{}", err);
 }
 
 #[test]
-fn msvc_smoke() {
-    reset_env();
-
-    let test = Test::msvc();
-    test.gcc().file("foo.c").compile("foo");
+fn gnu_prebuilt_objects() {
+    let test = Test::gnu();
+    let prebuilt = test.td.path().join("vendor.o");
+    fs::write(&prebuilt, "").unwrap();
+    let artifacts = test
+        .gcc()
+        .file("foo.c")
+        .object(&prebuilt)
+        .compile_with_deps_info("foo");
 
-    test.cmd(0)
-        .must_have("-O2")
-        .must_have("foo.c")
-        .must_not_have("-Z7")
-        .must_have("-c")
-        .must_have("-MD");
+    // Only the source is compiled, and both objects are archived.
+    let compiled = test.td.path().join("db3b6bfb95261072-foo.o");
+    test.cmd(0).must_have("foo.c").must_not_have(&prebuilt);
     test.cmd(1)
-        .must_have(test.td.path().join("db3b6bfb95261072-foo.o"));
+        .must_have(&compiled)
+        .must_have(&prebuilt)
+        .must_have_in_order(
+            &compiled.display().to_string(),
+            &prebuilt.display().to_string(),
+        );
+    assert_eq!(artifacts.objects, [compiled, prebuilt]);
 }
 
 #[test]
-fn msvc_opt_level_0() {
-    reset_env();
-
-    let test = Test::msvc();
-    test.gcc().opt_level(0).file("foo.c").compile("foo");
-
-    test.cmd(0).must_not_have("-O2");
+fn clang_time_trace() {
+    let test = Test::clang();
+    let artifacts = test
+        .gcc()
+        .compiler("clang")
+        .file("foo.c")
+        .file("bar.c")
+        .time_trace(true)
+        .compile_with_deps_info("foo");
+    test.cmd(0).must_have("-ftime-trace");
+
+    let mut traces = artifacts.time_traces;
+    traces.sort();
+    let mut expected = artifacts
+        .objects
+        .iter()
+        .map(|obj| obj.with_extension("json"))
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(traces, expected);
+    for trace in &traces {
+        assert!(trace.starts_with(test.td.path()), "{}", trace.display());
+    }
 }
 
 #[test]
-fn msvc_debug() {
-    let test = Test::msvc();
-    test.gcc().debug(true).file("foo.c").compile("foo");
-    test.cmd(0).must_have("-Z7");
+fn gnu_time_trace_ignored() {
+    let test = Test::gnu();
+    let artifacts = test
+        .gcc()
+        .file("foo.c")
+        .time_trace(true)
+        .compile_with_deps_info("foo");
+    test.cmd(0).must_not_have("-ftime-trace");
+    assert!(artifacts.time_traces.is_empty());
 }
 
 #[test]
-fn msvc_include() {
+fn msvc_win7_platform_defines() {
     let test = Test::msvc();
-    test.gcc().include("foo/bar").file("foo.c").compile("foo");
-
-    test.cmd(0).must_have("-I").must_have("foo/bar");
-}
+    test.gcc()
+        .target("x86_64-win7-windows-msvc")
+        .host("x86_64-win7-windows-msvc")
+        .platform_defines(true)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-D_WIN32_WINNT=0x0601")
+        .must_have("-DWINVER=0x0601");
 
-#[test]
-fn msvc_define() {
     let test = Test::msvc();
     test.gcc()
-        .define("FOO", "bar")
-        .define("BAR", None)
+        .platform_defines(true)
+        .define("WINVER", "0x0603")
         .file("foo.c")
         .compile("foo");
+    test.cmd(0)
+        .must_have("-D_WIN32_WINNT=0x0A00")
+        .must_have("-DWINVER=0x0603")
+        .must_not_have("-DWINVER=0x0A00");
 
-    test.cmd(0).must_have("-DFOO=bar").must_have("-DBAR");
+    // Nothing is defined unless asked to.
+    let test = Test::msvc();
+    test.gcc()
+        .target("x86_64-win7-windows-msvc")
+        .host("x86_64-win7-windows-msvc")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_not_have("-D_WIN32_WINNT=0x0601");
 }
 
 #[test]
-fn msvc_static_crt() {
-    let test = Test::msvc();
-    test.gcc().static_crt(true).file("foo.c").compile("foo");
+fn musl_static_by_default() {
+    let compile = |test: &Test, compiler: &str, target: &str, static_flag: Option<bool>| {
+        let mut build = test.gcc();
+        build
+            .target(target)
+            .host(target)
+            .compiler(test.td.path().join(compiler))
+            .file("foo.c");
+        if let Some(static_flag) = static_flag {
+            build.static_flag(static_flag);
+        }
+        build.compile("foo");
+    };
 
-    test.cmd(0).must_have("-MT");
-}
+    let test = Test::gnu();
+    compile(&test, "cc", "x86_64-unknown-linux-musl", None);
+    test.cmd(0).must_have("-static");
+
+    let test = Test::clang();
+    compile(&test, "clang", "aarch64-unknown-linux-musl", None);
+    test.cmd(0).must_have("-static");
+
+    // The musl targets without a self-contained musl link dynamically.
+    let test = Test::gnu();
+    compile(&test, "cc", "mips-unknown-linux-musl", None);
+    test.cmd(0).must_not_have("-static");
+
+    let test = Test::gnu();
+    compile(&test, "cc", "x86_64-unknown-linux-gnu", None);
+    test.cmd(0).must_not_have("-static");
+
+    let test = Test::gnu();
+    compile(&test, "cc", "x86_64-unknown-linux-musl", Some(false));
+    test.cmd(0).must_not_have("-static");
+}
 
 #[test]
-fn msvc_no_static_crt() {
-    let test = Test::msvc();
-    test.gcc().static_crt(false).file("foo.c").compile("foo");
+fn gnu_no_files() {
+    let test = Test::gnu();
+    test.gcc().compile("empty");
+    assert_eq!(
+        fs::read(test.td.path().join("libempty.a")).unwrap(),
+        b"!<arch>\n"
+    );
+}
 
-    test.cmd(0).must_have("-MD");
+#[test]
+fn gnu_allow_empty() {
+    if let Some(case) = child_case() {
+        let test = Test::gnu();
+        let mut build = test.gcc();
+        if case == "allow" {
+            build.allow_empty(true);
+        }
+        build.compile("empty");
+        assert!(test.td.path().join("libempty.a").exists());
+        return;
+    }
+
+    // Unlike by default, an explicitly allowed empty library isn't linked.
+    let link_metadata =
+        |case| lines_starting_with(&run_child("gnu_allow_empty", case), "cargo:rustc-link-");
+    assert_eq!(link_metadata("allow"), [] as [&str; 0]);
+    let metadata = link_metadata("default");
+    assert!(
+        metadata.contains(&"cargo:rustc-link-lib=static=empty".to_string()),
+        "{:?}",
+        metadata
+    );
+
+    let test = Test::gnu();
+    let err = test
+        .gcc()
+        .allow_empty(false)
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(err.to_string().contains("no source files"), "{}", err);
+    assert!(!test.td.path().join("libfoo.a").exists());
 }
 
 #[test]
-fn msvc_no_dash_dash() {
-    let test = Test::msvc();
-    test.gcc().file("foo.c").compile("foo");
+fn gnu_strip() {
+    let test = Test::gnu();
+    test.shim("strip");
+    test.gcc()
+        .file("foo.c")
+        .strip(cc::StripLevel::Debug)
+        .compile("foo");
+    test.cmd(3)
+        .must_have("--strip-debug")
+        .must_have(test.td.path().join("libfoo.a"));
 
-    test.cmd(0).must_not_have("--");
+    let test = Test::gnu();
+    test.shim("strip");
+    test.gcc()
+        .file("foo.c")
+        .strip(cc::StripLevel::Symbols)
+        .compile("foo");
+    test.cmd(3).must_have("--strip-unneeded");
+
+    let test = Test::gnu();
+    test.shim("strip");
+    test.gcc()
+        .file("foo.c")
+        .strip(cc::StripLevel::None)
+        .compile("foo");
+    assert!(!test.td.path().join("out3").exists());
 }
 
 #[test]
-fn msvc_std_c() {
-    let test = Test::msvc();
-    test.gcc().file("foo.c").std("c11").compile("foo");
+fn gnu_archive_update() {
+    let test = Test::gnu();
+    // The shim truncates `libfoo.a` whenever it compiles.
+    let archive = test.td.path().join("libbar.a");
+    let members = || {
+        let contents = fs::read_to_string(&archive).unwrap();
+        contents.lines().map(str::to_string).collect::<Vec<_>>()
+    };
+    let names = |artifacts: &cc::CompileArtifacts| {
+        let mut names = artifacts
+            .objects
+            .iter()
+            .map(|obj| obj.file_name().unwrap().to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
 
-    test.cmd(0).must_have("-std:c11");
+    let artifacts = test
+        .gcc()
+        .archive_update(true)
+        .file("foo.c")
+        .file("bar.c")
+        .compile_with_deps_info("bar");
+    // The archive is created from scratch, with its members sorted.
+    test.cmd(2).must_have("cq");
+    assert_eq!(members(), names(&artifacts));
+
+    // A removed source is pruned, and the remaining object is replaced.
+    let artifacts = test
+        .gcc()
+        .archive_update(true)
+        .file("foo.c")
+        .compile_with_deps_info("bar");
+    test.cmd(5).must_have("t");
+    test.cmd(6).must_have("d");
+    test.cmd(7).must_have("r").must_have(&artifacts.objects[0]);
+    test.cmd(8).must_have("s");
+    assert_eq!(members(), names(&artifacts));
 }
 
-// Disable this test with the parallel feature because the execution
-// order is not deterministic.
-#[cfg(not(feature = "parallel"))]
 #[test]
-fn asm_flags() {
+fn gnu_response_file_encoding() {
+    let long_define = "x".repeat(200_000);
+    for encoding in [None, Some(cc::RspEncoding::Utf16Le)] {
+        let test = Test::gnu();
+        let src = test.td.path().join("ünïcödé.c");
+        fs::write(&src, "").unwrap();
+        let mut build = test.gcc();
+        if let Some(encoding) = encoding {
+            build.response_file_encoding(encoding);
+        }
+        build
+            .define("LONG", &*long_define)
+            .file(&src)
+            .compile("foo");
+
+        let args = test.cmd(0).args;
+        assert_eq!(args.len(), 1, "{:?}", args);
+        let rsp = fs::read(args[0].strip_prefix('@').unwrap()).unwrap();
+        let contents = match encoding {
+            None => String::from_utf8(rsp).unwrap(),
+            Some(_) => {
+                assert_eq!(rsp[..2], [0xFF, 0xFE]);
+                let units = rsp[2..]
+                    .chunks(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                String::from_utf16(&units).unwrap()
+            }
+        };
+        let quoted_src = format!("\"{}\"\n", src.display()).replace('\\', "\\\\");
+        assert!(contents.ends_with(&quoted_src), "{}", contents);
+        assert!(contents.contains(&format!("\"-DLONG={}\"\n", long_define)));
+    }
+
+    // Short command lines are passed directly.
     let test = Test::gnu();
     test.gcc()
+        .response_file_encoding(cc::RspEncoding::Utf8)
         .file("foo.c")
-        .file("x86_64.asm")
-        .file("x86_64.S")
-        .asm_flag("--abc")
         .compile("foo");
-    test.cmd(0).must_not_have("--abc");
-    test.cmd(1).must_have("--abc");
-    test.cmd(2).must_have("--abc");
+    test.cmd(0).must_have("foo.c");
 }
 
 #[test]
-fn gnu_apple_sysroot() {
-    let targets = ["aarch64-apple-darwin", "x86_64-apple-darwin"];
+fn gnu_command_length_limit() {
+    let test = Test::gnu();
+    test.gcc()
+        .command_length_limit(10)
+        .file("foo.c")
+        .compile("foo");
 
-    for target in targets {
+    let args = test.cmd(0).args;
+    assert_eq!(args.len(), 1, "{:?}", args);
+    let rsp = fs::read_to_string(args[0].strip_prefix('@').unwrap()).unwrap();
+    assert!(rsp.ends_with("\"foo.c\"\n"), "{}", rsp);
+}
+
+#[test]
+fn gnu_report_timings() {
+    let test = Test::gnu();
+    let fast = test.td.path().join("fast.c");
+    let slow = test.td.path().join("slow.c");
+    fs::write(&fast, "").unwrap();
+    fs::write(&slow, "// shim-sleep-ms=200\n").unwrap();
+    let artifacts = test
+        .gcc()
+        .report_timings(true)
+        .file(&fast)
+        .file(&slow)
+        .compile_with_deps_info("foo");
+
+    let timings = &artifacts.timings;
+    assert_eq!(timings.len(), 2, "{:?}", timings);
+    assert_eq!(timings[0].0, slow);
+    assert_eq!(timings[1].0, fast);
+    assert!(timings[0].1 >= Duration::from_millis(200), "{:?}", timings);
+    assert!(timings[0].1 >= timings[1].1, "{:?}", timings);
+
+    // Nothing is recorded by default.
+    let test = Test::gnu();
+    let artifacts = test.gcc().file("foo.c").compile_with_deps_info("foo");
+    assert!(artifacts.timings.is_empty());
+}
+
+#[test]
+fn gnu_x86_64_no_plt() {
+    let target = "x86_64-unknown-linux-gnu";
+    let test = Test::gnu();
+    test.gcc()
+        .pic(true)
+        .use_plt(false)
+        .target(target)
+        .host(target)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-fno-plt");
+}
+
+#[test]
+fn gnu_no_plt() {
+    let target = "x86_64-unknown-linux-gnu";
+    let test = Test::gnu();
+    test.gcc()
+        .no_plt(true)
+        .target(target)
+        .host(target)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-fPIC").must_have("-fno-plt");
+
+    // Static relocation model.
+    let test = Test::gnu();
+    test.gcc()
+        .no_plt(true)
+        .pic(false)
+        .target(target)
+        .host(target)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_not_have("-fno-plt");
+
+    let target = "thumbv7em-none-eabihf";
+    let test = Test::gnu();
+    test.gcc()
+        .no_plt(true)
+        .target(target)
+        .host(target)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_not_have("-fPIC").must_not_have("-fno-plt");
+}
+
+#[test]
+fn gnu_arm_float_flags() {
+    let targets = [
+        (
+            "thumbv7neon-unknown-linux-gnueabihf",
+            Some("-mfpu=neon-vfpv4"),
+            "-mfloat-abi=hard",
+        ),
+        (
+            "armv7-unknown-linux-gnueabihf",
+            Some("-mfpu=vfpv3-d16"),
+            "-mfloat-abi=hard",
+        ),
+        ("arm-unknown-linux-gnueabi", None, "-mfloat-abi=soft"),
+    ];
+    for (target, fpu, float_abi) in targets {
         let test = Test::gnu();
-        test.shim("fake-gcc")
-            .gcc()
-            .compiler("fake-gcc")
+        test.gcc()
             .target(target)
             .host(target)
             .file("foo.c")
             .compile("foo");
-
         let cmd = test.cmd(0);
-        cmd.must_not_have("-isysroot");
+        cmd.must_have(float_abi);
+        match fpu {
+            Some(fpu) => cmd.must_have(fpu),
+            None => cmd.must_not_have("-mfpu=vfp"),
+        };
     }
+
+    // Explicit flags override the derived ones.
+    let target = "armv7-unknown-linux-gnueabihf";
+    let test = Test::gnu();
+    test.gcc()
+        .target(target)
+        .host(target)
+        .flag("-mfpu=neon")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have_in_order("-mfpu=vfpv3-d16", "-mfpu=neon");
 }
 
 #[test]
-#[cfg(target_os = "macos")] // Invokes xcrun
-fn gnu_apple_arch() {
-    let cases = [
-        ("x86_64-apple-darwin", "x86_64"),
-        ("x86_64h-apple-darwin", "x86_64h"),
-        ("aarch64-apple-darwin", "arm64"),
-        ("arm64e-apple-darwin", "arm64e"),
-        ("i686-apple-darwin", "i386"),
-        ("aarch64-apple-ios", "arm64"),
-        ("armv7s-apple-ios", "armv7s"),
-        ("arm64_32-apple-watchos", "arm64_32"),
-        ("armv7k-apple-watchos", "armv7k"),
-    ];
+fn gnu_aarch64_none_no_pic() {
+    for target in &["aarch64-unknown-none-softfloat", "aarch64-unknown-none"] {
+        let test = Test::gnu();
+        test.gcc()
+            .target(target)
+            .host(target)
+            .file("foo.c")
+            .compile("foo");
 
-    for (target, arch) in cases {
+        test.cmd(0).must_not_have("-fPIC");
+    }
+}
+
+#[test]
+fn gnu_espidf_no_pic() {
+    reset_env();
+
+    for target in &["riscv32imc-esp-espidf", "xtensa-esp32-espidf"] {
         let test = Test::gnu();
-        test.shim("fake-gcc")
-            .gcc()
-            .compiler("fake-gcc")
-            .target(&target)
-            .host(&"aarch64-apple-darwin")
+        test.gcc()
+            .target(target)
+            .host(target)
             .file("foo.c")
             .compile("foo");
 
-        let cmd = test.cmd(0);
-        cmd.must_have_in_order("-arch", arch);
+        test.cmd(0).must_not_have("-fPIC");
     }
 }
 
 #[test]
-#[cfg(target_os = "macos")] // Invokes xcrun
-fn gnu_apple_deployment_target() {
-    let cases = [
-        ("x86_64-apple-darwin", "-mmacosx-version-min=10.12"),
-        ("aarch64-apple-darwin", "-mmacosx-version-min=10.12"),
-        ("aarch64-apple-ios", "-miphoneos-version-min=10.0"),
-        ("aarch64-apple-ios-sim", "-mios-simulator-version-min=10.0"),
-        ("x86_64-apple-ios", "-mios-simulator-version-min=10.0"),
-        ("aarch64-apple-ios-macabi", "-mtargetos=ios10.0-macabi"),
-        ("aarch64-apple-tvos", "-mappletvos-version-min=10.0"),
-        (
-            "aarch64-apple-tvos-sim",
-            "-mappletvsimulator-version-min=10.0",
-        ),
-        ("aarch64-apple-watchos", "-mwatchos-version-min=5.0"),
-        (
-            "aarch64-apple-watchos-sim",
-            "-mwatchsimulator-version-min=5.0",
-        ),
-        ("aarch64-apple-visionos", "-mtargetos=xros1.0"),
-        ("aarch64-apple-visionos-sim", "-mtargetos=xros1.0-simulator"),
-    ];
+fn gnu_riscv_and_mips_mabi() {
+    reset_env();
 
-    for (target, os_version_flag) in cases {
+    for (target, mabi) in [
+        ("riscv64gc-unknown-none-elf", "-mabi=lp64d"),
+        ("riscv64imac-unknown-none-elf", "-mabi=lp64"),
+        ("mips64-unknown-linux-gnuabi64", "-mabi=64"),
+    ] {
         let test = Test::gnu();
-        test.shim("fake-gcc")
-            .gcc()
-            .compiler("fake-gcc")
+        test.gcc()
+            .target(target)
+            .host(target)
+            .file("foo.c")
+            .compile("foo");
+        test.cmd(0).must_have(mabi);
+
+        let test = Test::clang();
+        test.gcc()
+            .compiler("clang")
+            .target(target)
+            .host(target)
+            .file("foo.c")
+            .compile("foo");
+        test.cmd(0).must_have(mabi);
+    }
+}
+
+#[test]
+fn gnu_uefi_no_pic() {
+    reset_env();
+
+    for arch in &["aarch64", "i686", "x86_64"] {
+        let target = format!("{}-unknown-uefi", arch);
+        let test = Test::gnu();
+        test.gcc()
+            .target(&target)
+            .host(&target)
+            .file("foo.c")
+            .compile("foo");
+
+        test.cmd(0).must_not_have("-fPIC");
+    }
+}
+
+#[test]
+fn clang_uefi() {
+    for arch in ["aarch64", "i686", "x86_64"] {
+        let target = format!("{}-unknown-uefi", arch);
+        let test = Test::clang();
+        test.gcc()
+            .compiler("clang")
             .target(&target)
-            .host(&"aarch64-apple-darwin")
-            // Avoid dependency on environment in test.
-            .__set_env("MACOSX_DEPLOYMENT_TARGET", "10.12")
-            .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "10.0")
-            .__set_env("TVOS_DEPLOYMENT_TARGET", "10.0")
-            .__set_env("WATCHOS_DEPLOYMENT_TARGET", "5.0")
-            .__set_env("XROS_DEPLOYMENT_TARGET", "1.0")
             .file("foo.c")
             .compile("foo");
 
-        let cmd = test.cmd(0);
-        cmd.must_have(os_version_flag);
-    }
+        test.cmd(0)
+            .must_have(format!("--target={}-unknown-windows-gnu", arch))
+            .must_have("-ffreestanding")
+            .must_not_have("-fPIC");
+    }
+}
+
+#[test]
+fn gnu_set_stdlib() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .cpp_set_stdlib(Some("foo"))
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0).must_not_have("-stdlib=foo");
+}
+
+#[test]
+fn gnu_include() {
+    let test = Test::gnu();
+    test.gcc().include("foo/bar").file("foo.c").compile("foo");
+
+    test.cmd(0).must_have("-I").must_have("foo/bar");
+}
+
+#[test]
+fn gnu_include_first() {
+    let test = Test::gnu();
+    test.gcc()
+        .include("system")
+        .include_first("vendored")
+        .include("extra")
+        .include_first("overrides")
+        .file("foo.c")
+        .compile("foo");
+
+    let cmd = test.cmd(0);
+    let includes = cmd
+        .args
+        .windows(2)
+        .filter(|pair| pair[0] == "-I")
+        .map(|pair| pair[1].as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(includes, ["overrides", "vendored", "system", "extra"]);
+}
+
+#[test]
+fn gnu_include_relative() {
+    let test = Test::gnu();
+    test.gcc()
+        .include_relative("vendor/include")
+        .file("foo.c")
+        .compile("foo");
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("vendor/include");
+    test.cmd(0).must_have("-I").must_have(dir);
+}
+
+#[test]
+fn gnu_define() {
+    let test = Test::gnu();
+    test.gcc()
+        .define("FOO", "bar")
+        .define("BAR", None)
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0).must_have("-DFOO=bar").must_have("-DBAR");
+}
+
+#[test]
+fn gnu_define_cargo_cfgs() {
+    let target = "x86_64-unknown-linux-gnu";
+    let test = Test::gnu();
+    test.gcc()
+        .target(target)
+        .host(target)
+        .define_cargo_cfgs("FOO_")
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("-DFOO_TARGET_ARCH_X86_64")
+        .must_have("-DFOO_TARGET_VENDOR_UNKNOWN")
+        .must_have("-DFOO_TARGET_OS_LINUX")
+        .must_have("-DFOO_TARGET_ENV_GNU");
+}
+
+#[test]
+fn gnu_compile_assembly() {
+    let test = Test::gnu();
+    test.gcc().file("foo.S").compile("foo");
+    test.cmd(0).must_have("foo.S");
+}
+
+#[test]
+fn gnu_preprocessed_assembly() {
+    let test = Test::gnu();
+    let src = test.td.path().join("foo.S");
+    fs::write(&src, "#ifdef FOO\n    nop\n#endif\n").unwrap();
+    test.gcc()
+        .define("FOO", None)
+        .include("include")
+        .file(&src)
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("-DFOO")
+        .must_have_in_order("-I", "include")
+        .must_have_in_order("assembler-with-cpp", src.to_str().unwrap())
+        .must_have_in_order("-x", "assembler-with-cpp");
+}
+
+#[test]
+fn gnu_raw_assembly() {
+    let test = Test::gnu();
+    test.gcc().file("foo.s").compile("foo");
+
+    test.cmd(0)
+        .must_have_in_order("-x", "assembler")
+        .must_have_in_order("assembler", "foo.s")
+        .must_not_have("assembler-with-cpp");
+}
+
+#[test]
+fn gnu_shared() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .file("foo.c")
+        .shared_flag(true)
+        .static_flag(false)
+        .compile("foo");
+
+    test.cmd(0).must_have("-shared").must_not_have("-static");
+}
+
+#[test]
+fn gnu_linker_flavor() {
+    let flavors = [
+        (cc::LinkerFlavor::Lld, "ld.lld", "-fuse-ld=lld"),
+        (cc::LinkerFlavor::Gold, "ld.gold", "-fuse-ld=gold"),
+        (cc::LinkerFlavor::Bfd, "ld.bfd", "-fuse-ld=bfd"),
+        (cc::LinkerFlavor::Mold, "ld.mold", "-fuse-ld=mold"),
+    ];
+    for (flavor, linker, flag) in flavors {
+        let test = Test::gnu();
+        test.shim(linker);
+        test.gcc()
+            .file("foo.c")
+            .link_shared_flag(true)
+            .linker_flavor(flavor)
+            .compile("foo");
+        test.cmd(0).must_not_have(flag);
+        test.cmd(1).must_have("-shared").must_have(flag);
+    }
+
+    // Without the linker, the default one is used. Only the test directory is
+    // searched, as the host may have `ld.mold` installed.
+    let test = Test::gnu();
+    test.gcc()
+        .__set_env("PATH", test.td.path())
+        .file("foo.c")
+        .link_shared_flag(true)
+        .linker_flavor(cc::LinkerFlavor::Mold)
+        .compile("foo");
+    test.cmd(1)
+        .must_have("-shared")
+        .must_not_have("-fuse-ld=mold");
+}
+
+#[test]
+fn clang_addrsig_old_binutils() {
+    reset_env();
+
+    let compile = |ar_version: &str, addrsig: Option<bool>| {
+        let test = Test::clang();
+        // The `ar` shim reports the version in this file.
+        fs::write(test.td.path().join("ar-version"), ar_version).unwrap();
+        test.gcc()
+            .target("x86_64-unknown-linux-gnu")
+            .compiler("clang")
+            .addrsig(addrsig)
+            .file("foo.c")
+            .compile("foo");
+        test
+    };
+
+    let old = "GNU ar (GNU Binutils for Ubuntu) 2.34\n";
+    let new = "GNU ar (GNU Binutils) 2.40\n";
+    compile(old, None)
+        .cmd(0)
+        .must_have("-fno-addrsig")
+        .must_not_have("-faddrsig");
+    compile(new, None)
+        .cmd(0)
+        .must_not_have("-fno-addrsig")
+        .must_not_have("-faddrsig");
+    compile("LLVM (http://llvm.org/):\n  LLVM version 17.0.6\n", None)
+        .cmd(0)
+        .must_not_have("-fno-addrsig");
+    compile(old, Some(true))
+        .cmd(0)
+        .must_have("-faddrsig")
+        .must_not_have("-fno-addrsig");
+    compile(new, Some(false)).cmd(0).must_have("-fno-addrsig");
+
+    // GCC has no address-significance tables.
+    let test = Test::gnu();
+    fs::write(test.td.path().join("ar-version"), old).unwrap();
+    test.gcc().file("foo.c").compile("foo");
+    test.cmd(0).must_not_have("-fno-addrsig");
+}
+
+#[test]
+fn msvc_module_definition() {
+    let test = Test::msvc();
+    let def = test.td.path().join("foo.def");
+    fs::write(&def, "EXPORTS\n    foo\n").unwrap();
+    test.gcc()
+        .file("foo.c")
+        .link_shared_flag(true)
+        .module_definition(&def)
+        .compile("foo");
+
+    test.cmd(1)
+        .must_have("-shared")
+        .must_have_in_order("/link", &format!("/DEF:{}", def.display()));
+}
+
+#[test]
+fn gnu_mingw_module_definition() {
+    let test = Test::gnu();
+    let def = test.td.path().join("foo.def");
+    fs::write(&def, "EXPORTS\n    foo\n").unwrap();
+    test.gcc()
+        .target("x86_64-pc-windows-gnu")
+        .host("x86_64-pc-windows-gnu")
+        .compiler(test.td.path().join("cc"))
+        .file("foo.c")
+        .link_shared_flag(true)
+        .module_definition(&def)
+        .compile("foo");
+
+    test.cmd(1).must_have("-shared").must_have(&def);
+}
+
+#[test]
+fn msvc_export_all_symbols() {
+    let test = Test::msvc();
+    test.shim("dumpbin.exe");
+    test.gcc()
+        .file("foo.c")
+        .file("bar.c")
+        .link_shared_flag(true)
+        .export_all_symbols(true)
+        .compile("foo");
+
+    // The shim reports that each object defines a function named after its
+    // source.
+    let def = test.td.path().join("foo.def");
+    let exports = fs::read_to_string(&def).unwrap();
+    assert_eq!(exports, "EXPORTS\n    foo\n    bar\n");
+    test.cmd(2).must_have("/SYMBOLS");
+    test.cmd(3)
+        .must_have("-shared")
+        .must_have_in_order("/link", &format!("/DEF:{}", def.display()));
+}
+
+#[test]
+fn gnu_mingw_export_all_symbols() {
+    let test = Test::gnu();
+    test.gcc()
+        .target("x86_64-pc-windows-gnu")
+        .host("x86_64-pc-windows-gnu")
+        .compiler(test.td.path().join("cc"))
+        .file("foo.c")
+        .link_shared_flag(true)
+        .export_all_symbols(true)
+        .compile("foo");
+
+    test.cmd(1)
+        .must_have("-shared")
+        .must_have("-Wl,--export-all-symbols");
+}
+
+#[test]
+fn export_all_symbols_with_module_definition() {
+    let test = Test::msvc();
+    let def = test.td.path().join("foo.def");
+    fs::write(&def, "EXPORTS\n    foo\n").unwrap();
+    let err = test
+        .gcc()
+        .file("foo.c")
+        .link_shared_flag(true)
+        .module_definition(&def)
+        .export_all_symbols(true)
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(err.to_string().contains("export_all_symbols"), "{}", err);
+}
+
+#[test]
+fn module_definition_must_exist() {
+    let test = Test::msvc();
+    let err = test
+        .gcc()
+        .file("foo.c")
+        .module_definition(test.td.path().join("missing.def"))
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(err.to_string().contains("missing.def"), "{}", err);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn gnu_link_shared() {
+    use std::process::Command;
+
+    let output = Command::new("rustc").arg("-vV").output().unwrap();
+    let stdout_buf = String::from_utf8(output.stdout).unwrap();
+    let target = stdout_buf
+        .lines()
+        .find_map(|l| l.strip_prefix("host: "))
+        .unwrap();
+
+    reset_env();
+    let test = Test::gnu();
+    let root_dir = env!("CARGO_MANIFEST_DIR");
+    let src = format!("{root_dir}/dev-tools/cc-test/src/foo.c");
+
+    cc::Build::new()
+        .host(target)
+        .target(target)
+        .opt_level(2)
+        .out_dir(test.td.path())
+        .file(&src)
+        .shared_flag(true)
+        .static_flag(false)
+        .link_shared_flag(true)
+        .compile("foo");
+
+    assert!(test.td.path().join("libfoo.so").exists());
+}
+
+#[test]
+fn gnu_flag_if_supported() {
+    reset_env();
+
+    if cfg!(windows) {
+        return;
+    }
+    let test = Test::gnu();
+    test.gcc()
+        .file("foo.c")
+        .flag("-v")
+        .flag_if_supported("-Wall")
+        .flag_if_supported("-Wflag-does-not-exist")
+        .flag_if_supported("-std=c++11")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("-v")
+        .must_have("-Wall")
+        .must_not_have("-Wflag-does-not-exist")
+        .must_not_have("-std=c++11");
+}
+
+#[test]
+fn gnu_probe_cpp_snippet() {
+    let test = Test::gnu();
+    let build = test.gcc();
+    assert!(build.probe_cpp_snippet("int f();").unwrap());
+    assert!(!build.probe_cpp_snippet("#error unsupported").unwrap());
+    // The results are cached.
+    assert!(build.probe_cpp_snippet("int f();").unwrap());
+    assert!(!test.td.path().join("out2").exists());
+
+    test.cmd(0).must_have("-c").must_have("-O2");
+    let src = test.cmd(1).args.last().unwrap().clone();
+    assert!(src.ends_with(".cpp"), "{}", src);
+    assert_eq!(fs::read_to_string(src).unwrap(), "#error unsupported\n");
+}
+
+#[test]
+fn clang_apple_framework_shared() {
+    let test = Test::clang();
+    test.shim("xcrun");
+    test.gcc()
+        .compiler("clang")
+        .target("aarch64-apple-darwin")
+        .framework_path("vendor/Frameworks")
+        .apple_framework("Foundation")
+        .link_shared_flag(true)
+        .file("foo.c")
+        .compile("foo");
+
+    // Leave out the queries of `xcrun`, which run first.
+    let cmds = (0..)
+        .take_while(|i| test.td.path().join(format!("out{}", i)).exists())
+        .map(|i| test.cmd(i))
+        .filter(|cmd| !cmd.has("--sdk".as_ref()))
+        .collect::<Vec<_>>();
+    cmds[0]
+        .must_have("-Fvendor/Frameworks")
+        .must_not_have("-framework");
+    cmds[1]
+        .must_have("-shared")
+        .must_have("-Fvendor/Frameworks")
+        .must_have_in_order("-framework", "Foundation");
+}
+
+#[test]
+fn gnu_reproducible() {
+    reset_env();
+
+    let test = Test::gnu();
+    let mut build = test.gcc();
+    build.reproducible(true).file("foo.c");
+    let compiler = build.get_compiler();
+    let env = |var: &str| {
+        compiler
+            .env()
+            .iter()
+            .find(|(name, _)| name == var)
+            .map(|(_, value)| value.to_str().unwrap().to_string())
+    };
+    let epoch = std::env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| "0".to_string());
+    assert_eq!(env("SOURCE_DATE_EPOCH"), Some(epoch));
+    assert_eq!(env("TZ").as_deref(), Some("UTC"));
+    assert_eq!(env("LC_ALL").as_deref(), Some("C"));
+
+    build.compile("foo");
+    let out_dir_map = format!("-ffile-prefix-map={}=out", test.td.path().display());
+    let manifest_dir_map = format!("-ffile-prefix-map={}=.", env!("CARGO_MANIFEST_DIR"));
+    test.cmd(0)
+        .must_have("-Wdate-time")
+        .must_have_in_order(&manifest_dir_map, &out_dir_map);
+
+    let test = Test::gnu();
+    test.gcc().file("foo.c").compile("foo");
+    test.cmd(0).must_not_have("-Wdate-time");
+}
+
+#[test]
+fn msvc_reproducible() {
+    let test = Test::msvc();
+    test.gcc().reproducible(true).file("foo.c").compile("foo");
+    test.cmd(0)
+        .must_have("-Brepro")
+        .must_not_have("-Wdate-time");
+}
+
+#[test]
+fn gnu_x86_stack_realign() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .target("i686-unknown-linux-gnu")
+        .host("i686-unknown-linux-gnu")
+        .x86_stack_realign(true)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-mstackrealign");
+
+    for target in ["x86_64-unknown-linux-gnu", "i586-unknown-linux-gnu"] {
+        let test = Test::gnu();
+        test.gcc()
+            .target(target)
+            .host(target)
+            .x86_stack_realign(true)
+            .file("foo.c")
+            .compile("foo");
+        test.cmd(0).must_not_have("-mstackrealign");
+    }
+
+    let test = Test::gnu();
+    test.gcc()
+        .target("i686-unknown-linux-gnu")
+        .host("i686-unknown-linux-gnu")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_not_have("-mstackrealign");
+}
+
+#[test]
+fn msvc_x86_stack_realign() {
+    if child_case().is_some() {
+        let test = Test::msvc();
+        test.gcc()
+            .target("i686-pc-windows-msvc")
+            .host("i686-pc-windows-msvc")
+            .x86_stack_realign(true)
+            .file("foo.c")
+            .file("bar.c")
+            .compile("foo");
+        test.cmd(0).must_not_have("-mstackrealign");
+        return;
+    }
+
+    // The compiler is looked up for each file, but the warning is only
+    // printed once.
+    let stdout = run_child("msvc_x86_stack_realign", "1");
+    let warnings = lines_starting_with(&stdout, "cargo:warning=x86_stack_realign");
+    assert_eq!(warnings.len(), 1, "{}", stdout);
+}
+
+#[test]
+fn gnu_cpp_rtti() {
+    let test = Test::gnu();
+    test.gcc()
+        .cpp(true)
+        .cpp_rtti(false)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-fno-rtti").must_not_have("-frtti");
+
+    let test = Test::gnu();
+    test.gcc().cpp(true).file("foo.c").compile("foo");
+    test.cmd(0)
+        .must_not_have("-fno-rtti")
+        .must_not_have("-frtti");
+}
+
+#[test]
+fn msvc_cpp_rtti() {
+    let test = Test::msvc();
+    test.gcc()
+        .cpp(true)
+        .cpp_rtti(false)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-GR-").must_not_have("-fno-rtti");
+}
+
+#[test]
+fn gnu_cpp_stdlib_static_shared() {
+    let test = Test::gnu();
+    test.gcc()
+        .cpp(true)
+        .cpp_stdlib_static(true)
+        .link_shared_flag(true)
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0).must_not_have("-static-libstdc++");
+    test.cmd(1)
+        .must_have("-shared")
+        .must_have("-static-libstdc++");
+    test.cmd(2).must_have("-print-file-name=libstdc++.a");
+}
+
+#[test]
+fn clang_cpp_stdlib_static_shared() {
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .cpp(true)
+        .cpp_set_stdlib("c++")
+        .cpp_stdlib_static(true)
+        .link_shared_flag(true)
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0).must_have("-stdlib=libc++");
+    test.cmd(1)
+        .must_have("-shared")
+        .must_have("-static-libstdc++");
+    test.cmd(2).must_have("-print-file-name=libc++.a");
+    test.cmd(3).must_have("-print-file-name=libc++abi.a");
+}
+
+#[test]
+fn msvc_cpp_stdlib_static() {
+    let test = Test::msvc();
+    test.gcc()
+        .cpp(true)
+        .cpp_link_stdlib("stdc++")
+        .cpp_stdlib_static(true)
+        .link_shared_flag(true)
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(1).must_not_have("-static-libstdc++");
+    assert!(!test.td.path().join("out2").exists());
+}
+
+#[test]
+fn gnu_apple_framework_unsupported() {
+    let test = Test::gnu();
+    let err = test
+        .gcc()
+        .apple_framework("Foundation")
+        .file("foo.c")
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("frameworks can only be linked on Apple targets"),
+        "{}",
+        err
+    );
+    assert!(!test.td.path().join("out0").exists());
+}
+
+#[test]
+fn gnu_binary_blob() {
+    let test = Test::gnu();
+    test.shim("objcopy");
+    let blob = test.td.path().join("font.ttf");
+    fs::write(&blob, "abc").unwrap();
+    test.gcc()
+        .file("foo.c")
+        .add_binary_blob("my_blob", &blob, ".rodata.blob")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have_in_order("-I", "binary")
+        .must_have_in_order("-O", "elf64-x86-64")
+        .must_have(".data=.rodata.blob,alloc,load,readonly,data,contents")
+        .must_have("_binary_font_ttf_start=my_blob")
+        .must_have("font.ttf");
+    let obj = test.cmd(0).args.last().unwrap().clone();
+    assert!(obj.ends_with("blob-my_blob-data.o"), "{}", obj);
+    let source = fs::read_to_string(test.td.path().join("blob-my_blob.c")).unwrap();
+    assert!(
+        source.contains("const size_t my_blob_size = 3;"),
+        "{}",
+        source
+    );
+    assert!(!source.contains("unsigned char"), "{}", source);
+
+    let members = fs::read_to_string(test.td.path().join("libfoo.a")).unwrap();
+    let members = members.lines().collect::<Vec<_>>();
+    assert!(members.contains(&"blob-my_blob-data.o"), "{:?}", members);
+    assert!(
+        members.iter().any(|m| m.ends_with("blob-my_blob.o")),
+        "{:?}",
+        members
+    );
+
+    // The symbols of C have a leading underscore on 32-bit MinGW.
+    let test = Test::gnu();
+    test.shim("objcopy");
+    let blob = test.td.path().join("font.ttf");
+    fs::write(&blob, "abc").unwrap();
+    test.gcc()
+        .target("i686-pc-windows-gnu")
+        .host("i686-pc-windows-gnu")
+        .file("foo.c")
+        .add_binary_blob("my_blob", &blob, ".rodata.blob")
+        .compile("foo");
+    test.cmd(0)
+        .must_have_in_order("-O", "pe-i386")
+        .must_have("_binary_font_ttf_start=_my_blob");
+}
+
+#[test]
+fn msvc_binary_blob() {
+    let test = Test::msvc();
+    let blob = test.td.path().join("font.ttf");
+    fs::write(&blob, "ab").unwrap();
+    test.gcc()
+        .file("foo.c")
+        .add_binary_blob("my_blob", &blob, ".rdata$blob")
+        .compile("foo");
+
+    let source = fs::read_to_string(test.td.path().join("blob-my_blob.c")).unwrap();
+    assert!(
+        source.contains("__declspec(allocate(\".rdata$blob\")) const unsigned char my_blob[2]"),
+        "{}",
+        source
+    );
+    assert!(test
+        .cmd(1)
+        .args
+        .iter()
+        .any(|a| a.ends_with("blob-my_blob.c")));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn gnu_flag_if_supported_cpp() {
+    let test = Test::gnu();
+    test.gcc()
+        .cpp(true)
+        .file("foo.cpp")
+        .flag_if_supported("-std=c++11")
+        .compile("foo");
+
+    test.cmd(0).must_have("-std=c++11");
+}
+
+#[test]
+fn gnu_static() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .file("foo.c")
+        .shared_flag(false)
+        .static_flag(true)
+        .compile("foo");
+
+    test.cmd(0).must_have("-static").must_not_have("-shared");
+}
+
+#[test]
+fn gnu_no_dash_dash() {
+    let test = Test::gnu();
+    test.gcc().file("foo.c").compile("foo");
+
+    test.cmd(0).must_not_have("--");
+}
+
+#[test]
+fn gnu_std_c() {
+    let test = Test::gnu();
+    test.gcc().file("foo.c").std("c11").compile("foo");
+
+    test.cmd(0).must_have("-std=c11");
+}
+
+#[test]
+fn gnu_charsets() {
+    let test = Test::gnu();
+    test.gcc()
+        .file("foo.c")
+        .source_charset("latin1")
+        .execution_charset("UTF-8")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("-finput-charset=latin1")
+        .must_have("-fexec-charset=UTF-8");
+}
+
+#[test]
+fn msvc_smoke() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc().file("foo.c").compile("foo");
+
+    test.cmd(0)
+        .must_have("-O2")
+        .must_have("foo.c")
+        .must_not_have("-Z7")
+        .must_have("-c")
+        .must_have("-MD");
+    test.cmd(1)
+        .must_have(test.td.path().join("db3b6bfb95261072-foo.o"));
+}
+
+#[test]
+fn msvc_opt_level_0() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc().opt_level(0).file("foo.c").compile("foo");
+
+    test.cmd(0).must_not_have("-O2");
+}
+
+#[test]
+fn msvc_opt_level_s() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc().opt_level_str("s").file("foo.c").compile("foo");
+
+    test.cmd(0).must_have("-O1").must_not_have("-O2");
+}
+
+#[test]
+fn msvc_opt_level_z() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc().opt_level_str("z").file("foo.c").compile("foo");
+
+    test.cmd(0).must_have("-O1").must_not_have("-O2");
+}
+
+#[test]
+fn msvc_debug() {
+    let test = Test::msvc();
+    test.gcc().debug(true).file("foo.c").compile("foo");
+    test.cmd(0).must_have("-Z7");
+}
+
+#[test]
+fn msvc_include() {
+    let test = Test::msvc();
+    test.gcc().include("foo/bar").file("foo.c").compile("foo");
+
+    test.cmd(0).must_have("-I").must_have("foo/bar");
+}
+
+#[test]
+fn msvc_define() {
+    let test = Test::msvc();
+    test.gcc()
+        .define("FOO", "bar")
+        .define("BAR", None)
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0).must_have("-DFOO=bar").must_have("-DBAR");
+}
+
+#[test]
+fn msvc_static_crt() {
+    let test = Test::msvc();
+    test.gcc().static_crt(true).file("foo.c").compile("foo");
+
+    test.cmd(0).must_have("-MT");
+}
+
+#[test]
+fn msvc_no_static_crt() {
+    let test = Test::msvc();
+    test.gcc().static_crt(false).file("foo.c").compile("foo");
+
+    test.cmd(0).must_have("-MD");
+}
+
+#[test]
+fn msvc_no_dash_dash() {
+    let test = Test::msvc();
+    test.gcc().file("foo.c").compile("foo");
+
+    test.cmd(0).must_not_have("--");
+}
+
+#[test]
+fn msvc_std_c() {
+    let test = Test::msvc();
+    test.gcc().file("foo.c").std("c11").compile("foo");
+
+    test.cmd(0).must_have("-std:c11");
+}
+
+#[test]
+fn msvc_charsets() {
+    let test = Test::msvc();
+    test.gcc()
+        .file("foo.c")
+        .source_charset("latin1")
+        .execution_charset("UTF-8")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("-source-charset:latin1")
+        .must_have("-execution-charset:UTF-8");
+}
+
+// Disable this test with the parallel feature because the execution
+// order is not deterministic.
+#[cfg(not(feature = "parallel"))]
+#[test]
+fn asm_flags() {
+    let test = Test::gnu();
+    test.gcc()
+        .file("foo.c")
+        .file("x86_64.asm")
+        .file("x86_64.S")
+        .asm_flag("--abc")
+        .compile("foo");
+    test.cmd(0).must_not_have("--abc");
+    test.cmd(1).must_have("--abc");
+    test.cmd(2).must_have("--abc");
+}
+
+#[test]
+fn gnu_apple_sysroot() {
+    let targets = ["aarch64-apple-darwin", "x86_64-apple-darwin"];
+
+    for target in targets {
+        let test = Test::gnu();
+        test.shim("fake-gcc")
+            .gcc()
+            .compiler("fake-gcc")
+            .target(target)
+            .host(target)
+            .file("foo.c")
+            .compile("foo");
+
+        let cmd = test.cmd(0);
+        cmd.must_not_have("-isysroot");
+    }
+}
+
+#[test]
+#[cfg(target_os = "macos")] // Invokes xcrun
+fn gnu_apple_arch() {
+    let cases = [
+        ("x86_64-apple-darwin", "x86_64"),
+        ("x86_64h-apple-darwin", "x86_64h"),
+        ("aarch64-apple-darwin", "arm64"),
+        ("arm64e-apple-darwin", "arm64e"),
+        ("i686-apple-darwin", "i386"),
+        ("aarch64-apple-ios", "arm64"),
+        ("armv7s-apple-ios", "armv7s"),
+        ("arm64_32-apple-watchos", "arm64_32"),
+        ("armv7k-apple-watchos", "armv7k"),
+    ];
+
+    for (target, arch) in cases {
+        let test = Test::gnu();
+        test.shim("fake-gcc")
+            .gcc()
+            .compiler("fake-gcc")
+            .target(&target)
+            .host(&"aarch64-apple-darwin")
+            .file("foo.c")
+            .compile("foo");
+
+        let cmd = test.cmd(0);
+        cmd.must_have_in_order("-arch", arch);
+    }
+}
+
+#[test]
+#[cfg(target_os = "macos")] // Invokes xcrun
+fn gnu_apple_deployment_target() {
+    let cases = [
+        ("x86_64-apple-darwin", "-mmacosx-version-min=10.12"),
+        ("aarch64-apple-darwin", "-mmacosx-version-min=10.12"),
+        ("aarch64-apple-ios", "-miphoneos-version-min=10.0"),
+        ("aarch64-apple-ios-sim", "-mios-simulator-version-min=10.0"),
+        ("x86_64-apple-ios", "-mios-simulator-version-min=10.0"),
+        ("aarch64-apple-ios-macabi", "-mtargetos=ios10.0-macabi"),
+        ("aarch64-apple-tvos", "-mappletvos-version-min=10.0"),
+        (
+            "aarch64-apple-tvos-sim",
+            "-mappletvsimulator-version-min=10.0",
+        ),
+        ("aarch64-apple-watchos", "-mwatchos-version-min=5.0"),
+        (
+            "aarch64-apple-watchos-sim",
+            "-mwatchsimulator-version-min=5.0",
+        ),
+        ("aarch64-apple-visionos", "-mtargetos=xros1.0"),
+        ("aarch64-apple-visionos-sim", "-mtargetos=xros1.0-simulator"),
+    ];
+
+    for (target, os_version_flag) in cases {
+        let test = Test::gnu();
+        test.shim("fake-gcc")
+            .gcc()
+            .compiler("fake-gcc")
+            .target(&target)
+            .host(&"aarch64-apple-darwin")
+            // Avoid dependency on environment in test.
+            .__set_env("MACOSX_DEPLOYMENT_TARGET", "10.12")
+            .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "10.0")
+            .__set_env("TVOS_DEPLOYMENT_TARGET", "10.0")
+            .__set_env("WATCHOS_DEPLOYMENT_TARGET", "5.0")
+            .__set_env("XROS_DEPLOYMENT_TARGET", "1.0")
+            .file("foo.c")
+            .compile("foo");
+
+        let cmd = test.cmd(0);
+        cmd.must_have(os_version_flag);
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn macos_cpp_minimums() {
+    let versions = &[
+        // Too low
+        ("10.7", (10, 9)),
+        // Minimum
+        ("10.9", (10, 9)),
+        // Higher
+        ("11.0", (11, 0)),
+    ];
+
+    let target = "x86_64-apple-darwin";
+    for (deployment_target, set_version) in versions {
+        let test = Test::gnu();
+        test.gcc()
+            .target(target)
+            .host(target)
+            .cpp(true)
+            .__set_env("MACOSX_DEPLOYMENT_TARGET", deployment_target)
+            .file("foo.c")
+            .compile("foo");
+
+        let exec = test.cmd(0);
+        let deployment_arg = exec
+            .args
+            .iter()
+            .find_map(|arg| arg.strip_prefix("-mmacosx-version-min="))
+            .expect("no deployment target argument was set");
+
+        let mut deployment_parts = deployment_arg.split('.').map(|v| v.parse::<u32>().unwrap());
+
+        let major = deployment_parts.next().unwrap();
+        let minor = deployment_parts.next().unwrap();
+
+        // Check that we are on at least our minimums since this test reads from system
+        // SDK state, and that can vary per-system. It should never go lower then the deployment
+        // target we pass.
+        assert!(major >= set_version.0);
+
+        // If still on 10.x make sure `x` didn't go lower.
+        if major == set_version.0 {
+            assert!(minor >= set_version.1);
+        }
+    }
+
+    let test = Test::gnu();
+    test.gcc()
+        .target(target)
+        .host(target)
+        .__set_env("MACOSX_DEPLOYMENT_TARGET", "10.7")
+        .file("foo.c")
+        .compile("foo");
+
+    // No C++ leaves it untouched
+    test.cmd(0).must_have("-mmacosx-version-min=10.7");
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn clang_apple_tvos() {
+    let target = "aarch64-apple-tvos";
+    let test = Test::clang();
+    test.gcc()
+        .__set_env("TVOS_DEPLOYMENT_TARGET", "9.0")
+        .target(target)
+        .host(target)
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0).must_have("--target=arm64-apple-tvos");
+    test.cmd(0).must_have("-mappletvos-version-min=9.0");
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn clang_apple_mac_catalyst() {
+    let output = std::process::Command::new("xcrun")
+        .args(["--show-sdk-path", "--sdk", "macosx"])
+        .output()
+        .unwrap();
+    if !output.status.success() {
+        return;
+    }
+    let sdkroot = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+    let test = Test::clang();
+    test.gcc()
+        .target("aarch64-apple-ios-macabi")
+        .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "15.0")
+        .file("foo.c")
+        .compile("foo");
+    let execution = test.cmd(0);
+
+    execution.must_have("--target=arm64-apple-ios15.0-macabi");
+    // --target and -mtargetos= don't mix
+    execution.must_not_have("-mtargetos=");
+    execution.must_have_in_order("-isysroot", sdkroot);
+    execution.must_have_in_order(
+        "-isystem",
+        &format!("{sdkroot}/System/iOSSupport/usr/include"),
+    );
+    execution.must_have_in_order(
+        "-iframework",
+        &format!("{sdkroot}/System/iOSSupport/System/Library/Frameworks"),
+    );
+    execution.must_have(format!("-L{sdkroot}/System/iOSSupport/usr/lib"));
+    execution.must_have(format!(
+        "-F{sdkroot}/System/iOSSupport/System/Library/Frameworks"
+    ));
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn clang_apple_tvsimulator() {
+    let target = "x86_64-apple-tvos";
+
+    let test = Test::clang();
+    test.gcc()
+        .__set_env("TVOS_DEPLOYMENT_TARGET", "9.0")
+        .target(target)
+        .host(target)
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("--target=x86_64-apple-tvos-simulator");
+    test.cmd(0).must_have("-mappletvsimulator-version-min=9.0");
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn clang_apple_visionos() {
+    // Only run this test if visionOS is available on the host machine
+    let output = std::process::Command::new("xcrun")
+        .args(["--show-sdk-version", "--sdk", "xros"])
+        .output()
+        .unwrap();
+    if !output.status.success() {
+        return;
+    }
+
+    let test = Test::clang();
+    test.gcc()
+        .__set_env("XROS_DEPLOYMENT_TARGET", "1.0")
+        .target("aarch64-apple-visionos")
+        .file("foo.c")
+        .compile("foo");
+
+    dbg!(test.cmd(0).args);
+
+    test.cmd(0).must_have("--target=arm64-apple-xros1.0");
+    // --target and -mtargetos= don't mix.
+    test.cmd(0).must_not_have("-mtargetos=");
+
+    // Flags that don't exist.
+    test.cmd(0).must_not_have("-mxros-version-min=1.0");
+    test.cmd(0).must_not_have("-mxrsimulator-version-min=1.0");
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn apple_sdkroot_wrong() {
+    let output = std::process::Command::new("xcrun")
+        .args(["--show-sdk-path", "--sdk", "iphoneos"])
+        .output()
+        .unwrap();
+    if !output.status.success() {
+        return;
+    }
+
+    let wrong_sdkroot = "/Library/Developer/CommandLineTools/SDKs/MacOSX.sdk";
+    let test = Test::clang();
+    test.gcc()
+        .__set_env("SDKROOT", wrong_sdkroot)
+        .target("aarch64-apple-ios")
+        .file("foo.c")
+        .compile("foo");
+
+    dbg!(test.cmd(0).args);
+
+    test.cmd(0)
+        .must_have(std::str::from_utf8(&output.stdout).unwrap().trim());
+    test.cmd(0).must_not_have(wrong_sdkroot);
+}
+
+#[test]
+fn compile_intermediates() {
+    let test = Test::gnu();
+    let intermediates = test
+        .gcc()
+        .file("foo.c")
+        .file("x86_64.asm")
+        .file("x86_64.S")
+        .asm_flag("--abc")
+        .compile_intermediates();
+
+    assert_eq!(intermediates.len(), 3);
+
+    assert!(intermediates[0].display().to_string().contains("foo"));
+    assert!(intermediates[1].display().to_string().contains("x86_64"));
+    assert!(intermediates[2].display().to_string().contains("x86_64"));
+
+    // Only the compiler should have run, not the archiver.
+    for i in 0..3 {
+        test.cmd(i).must_have("-c").must_not_have("cq");
+    }
+    assert!(!test.td.path().join("out3").exists());
+}
+
+#[test]
+fn object_naming_same_basename() {
+    let test = Test::gnu();
+    let objects = test
+        .gcc()
+        .file("a/utils.c")
+        .file("b/utils.c")
+        .compile_intermediates();
+    assert_eq!(objects.len(), 2);
+    assert_ne!(objects[0], objects[1]);
+
+    let test = Test::gnu();
+    let objects = test
+        .gcc()
+        .object_naming(cc::ObjectNaming::Mirrored)
+        .file("a/utils.c")
+        .file("b/utils.c")
+        .compile_intermediates();
+    assert_eq!(
+        objects,
+        [
+            test.td.path().join("a").join("utils.o"),
+            test.td.path().join("b").join("utils.o"),
+        ]
+    );
+
+    let test = Test::gnu();
+    let err = test
+        .gcc()
+        .object_naming(cc::ObjectNaming::Flat)
+        .file("a/utils.c")
+        .file("b/utils.c")
+        .try_compile_intermediates()
+        .unwrap_err();
+    assert!(err.to_string().contains("would both be compiled to"));
+}
+
+#[test]
+fn object_naming_flat() {
+    let test = Test::gnu();
+    let objects = test
+        .gcc()
+        .object_naming(cc::ObjectNaming::Flat)
+        .file("src/foo.c")
+        .compile_intermediates();
+    assert_eq!(objects, [test.td.path().join("foo.o")]);
+}
+
+#[test]
+fn llvm_arch() {
+    let test = Test::clang();
+    let arch = |target| test.gcc().target(target).try_get_llvm_arch().unwrap();
+    assert_eq!(arch("aarch64-apple-darwin"), "arm64");
+    assert_eq!(arch("aarch64-unknown-linux-gnu"), "aarch64");
+    assert_eq!(arch("riscv64gc-unknown-linux-gnu"), "riscv64");
+}
+
+#[test]
+fn gnu_qnx() {
+    let test = Test::gnu();
+    test.shim("qcc").shim("q++");
+    test.gcc()
+        .target("aarch64-unknown-nto-qnx710")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-Vgcc_ntoaarch64le_cxx");
+
+    let test = Test::gnu();
+    test.shim("q++");
+    test.gcc()
+        .target("x86_64-pc-nto-qnx800")
+        .cpp(true)
+        .file("foo.cpp")
+        .compile("foo");
+    test.cmd(0).must_have("-Vgcc_ntox86_64_cxx");
+
+    let test = Test::gnu();
+    test.shim("qcc");
+    test.gcc()
+        .target("i686-pc-nto-qnx700")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-Vgcc_ntox86_cxx");
+}
+
+#[test]
+fn cuda_relocatable() {
+    let compile = |relocatable: Option<bool>, files: &[&str]| {
+        let test = Test::gnu();
+        test.shim("nvcc");
+        let mut build = test.gcc();
+        build.cuda(true);
+        if let Some(relocatable) = relocatable {
+            build.cuda_relocatable(relocatable);
+        }
+        for file in files {
+            build.file(file);
+        }
+        build.compile("foo");
+        (0..)
+            .take_while(|i| test.td.path().join(format!("out{}", i)).exists())
+            .map(|i| test.cmd(i))
+            .collect::<Vec<_>>()
+    };
+    let device_link =
+        |cmds: &[support::Execution]| cmds.iter().any(|cmd| cmd.has("--device-link".as_ref()));
+
+    let cmds = compile(Some(true), &["foo.cu"]);
+    cmds[0].must_have("-rdc=true").must_not_have("--device-c");
+    assert!(device_link(&cmds));
+    let link = cmds
+        .iter()
+        .find(|cmd| cmd.has("--device-link".as_ref()))
+        .unwrap();
+    assert!(
+        link.args.iter().any(|arg| arg.ends_with("foo_dlink.o")),
+        "{:?}",
+        link.args
+    );
+
+    // By default, only several files are relocatable.
+    let cmds = compile(None, &["foo.cu"]);
+    cmds[0]
+        .must_not_have("-rdc=true")
+        .must_not_have("--device-c");
+    assert!(device_link(&cmds));
+    let cmds = compile(None, &["foo.cu", "bar.cu"]);
+    cmds[0].must_have("--device-c");
+
+    let cmds = compile(Some(false), &["foo.cu", "bar.cu"]);
+    cmds[0]
+        .must_not_have("-rdc=true")
+        .must_not_have("--device-c");
+    assert!(!device_link(&cmds));
+}
+
+#[test]
+fn gnu_redox_cross_prefix() {
+    let test = Test::gnu();
+    test.shim("x86_64-unknown-redox-gcc");
+    let mut build = test.gcc();
+    build.target("x86_64-unknown-redox");
+
+    assert_eq!(
+        build.get_compiler().path(),
+        Path::new("x86_64-unknown-redox-gcc")
+    );
+    build.file("foo.c").compile("foo");
+    test.cmd(0).must_have("foo.c");
+}
+
+#[test]
+fn gnu_for_host() {
+    let test = Test::gnu();
+    let mut build = test.gcc();
+    build
+        .target("aarch64-unknown-linux-gnu")
+        .host("x86_64-unknown-linux-gnu")
+        .define_cargo_cfgs("CFG_");
+    assert_eq!(
+        build.get_compiler().path(),
+        Path::new("aarch64-linux-gnu-gcc")
+    );
+
+    // The host's compiler and flags are used instead.
+    build.for_host(true);
+    assert_eq!(build.get_compiler().path(), Path::new("cc"));
+    build.file("foo.c").compile("foo");
+    test.cmd(0)
+        .must_have("-m64")
+        .must_have("-DCFG_TARGET_ARCH_X86_64")
+        .must_not_have("-DCFG_TARGET_ARCH_AARCH64");
+}
+
+#[test]
+fn compiler_at_least_other_family() {
+    let test = Test::gnu();
+    let at_least = test
+        .gcc()
+        .compiler_family(cc::CompilerFamily::Gnu)
+        .compiler_at_least(cc::CompilerFamily::Clang, 3, 0)
+        .unwrap();
+    assert!(!at_least);
+}
+
+#[test]
+fn clang_android() {
+    let target = "arm-linux-androideabi";
+
+    // On Windows, we don't use the Android NDK shims for Clang, so verify that
+    // we use "clang" and set the target correctly.
+    #[cfg(windows)]
+    {
+        let test = Test::new();
+        test.shim("clang").shim("llvm-ar");
+        test.gcc()
+            .target(target)
+            .host("x86_64-pc-windows-msvc")
+            .file("foo.c")
+            .compile("foo");
+        test.cmd(0).must_have("--target=arm-linux-androideabi");
+    }
+
+    // On non-Windows, we do use the shims, so make sure that we use the shim
+    // and don't set the target.
+    #[cfg(not(windows))]
+    {
+        let test = Test::new();
+        test.shim("arm-linux-androideabi-clang")
+            .shim("arm-linux-androideabi-ar")
+            .shim("llvm-ar");
+        test.gcc().target(target).file("foo.c").compile("foo");
+        test.cmd(0).must_not_have("--target=arm-linux-androideabi");
+    }
+}
+
+#[test]
+fn apple_sdk_from_xcrun() {
+    reset_env();
+
+    let test = Test::clang();
+    test.shim("xcrun");
+    test.gcc()
+        .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "15.0")
+        .target("aarch64-apple-ios-sim")
+        .compiler("clang")
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0)
+        .must_have("--show-sdk-path")
+        .must_have_in_order("--sdk", "iphonesimulator");
+    test.cmd(1).must_have_in_order(
+        "-isysroot",
+        test.td.path().join("iphonesimulator").to_str().unwrap(),
+    );
+}
+
+#[test]
+fn apple_embed_bitcode() {
+    reset_env();
+
+    let compile = |target: &str, debug: bool| {
+        let test = Test::clang();
+        test.shim("xcrun");
+        test.gcc()
+            .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "15.0")
+            .__set_env("MACOSX_DEPLOYMENT_TARGET", "11.0")
+            .target(target)
+            .compiler("clang")
+            .debug(debug)
+            .apple_embed_bitcode(true)
+            .file("foo.c")
+            .compile("foo");
+        // The compile command is the only one passed the source.
+        (0..)
+            .map(|i| test.cmd(i))
+            .find(|cmd| cmd.has("foo.c".as_ref()))
+            .unwrap()
+    };
+
+    compile("aarch64-apple-ios", false)
+        .must_have("-fembed-bitcode")
+        .must_not_have("-fembed-bitcode-marker");
+    compile("aarch64-apple-ios", true)
+        .must_have("-fembed-bitcode-marker")
+        .must_not_have("-fembed-bitcode");
+    for target in [
+        "aarch64-apple-ios-sim",
+        "x86_64-apple-ios",
+        "aarch64-apple-darwin",
+    ] {
+        compile(target, false)
+            .must_not_have("-fembed-bitcode")
+            .must_not_have("-fembed-bitcode-marker");
+    }
+}
+
+#[test]
+fn apple_sdk_explicit() {
+    reset_env();
+
+    let test = Test::clang();
+    test.shim("xcrun");
+    test.gcc()
+        .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "15.0")
+        .target("aarch64-apple-ios")
+        .compiler("clang")
+        .apple_sdk(Some("iphoneos17.5"))
+        .file("foo.c")
+        .compile("foo");
+
+    test.cmd(0).must_have_in_order("--sdk", "iphoneos17.5");
+    test.cmd(1).must_have_in_order(
+        "-isysroot",
+        test.td.path().join("iphoneos17.5").to_str().unwrap(),
+    );
+}
+
+#[test]
+fn spawn_failure_io_error_source() {
+    use std::error::Error as _;
+
+    reset_env();
+
+    let test = Test::gnu();
+    let err = test
+        .gcc()
+        .compiler("cc-rs-nonexistent-compiler")
+        .file("foo.c")
+        .try_compile("foo")
+        .unwrap_err();
+
+    let source = err.source().expect("spawn failure should have a source");
+    let io_err = source
+        .downcast_ref::<std::io::Error>()
+        .expect("source should be an io::Error");
+    assert_eq!(io_err.kind(), std::io::ErrorKind::NotFound);
+}
+
+/// The first compile command that was run, skipping `xcrun` invocations.
+fn compile_cmd(test: &Test) -> Execution {
+    (0..)
+        .map(|i| test.cmd(i))
+        .find(|execution| execution.args.iter().any(|arg| arg == "-c"))
+        .unwrap()
+}
+
+/// The number of flags conveying an Apple deployment target, apart from `--target`.
+fn apple_version_args(execution: &Execution) -> usize {
+    execution
+        .args
+        .iter()
+        .filter(|arg| arg.contains("version-min=") || arg.starts_with("-mtargetos="))
+        .count()
+}
+
+#[test]
+fn apple_deployment_target_macos() {
+    reset_env();
+
+    let test = Test::clang();
+    test.shim("xcrun");
+    test.gcc()
+        .__set_env("MACOSX_DEPLOYMENT_TARGET", "10.15")
+        .target("x86_64-apple-darwin")
+        .compiler("clang")
+        .apple_deployment_target("12.0")
+        .file("foo.c")
+        .compile("foo");
+
+    let execution = compile_cmd(&test);
+    execution
+        .must_have("--target=x86_64-apple-macosx")
+        .must_have("-mmacosx-version-min=12.0")
+        .must_not_have("-mmacosx-version-min=10.15");
+    assert_eq!(apple_version_args(&execution), 1);
+}
+
+#[test]
+fn apple_deployment_target_ios() {
+    reset_env();
+
+    let test = Test::clang();
+    test.shim("xcrun");
+    test.gcc()
+        .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "15.0")
+        .target("aarch64-apple-ios")
+        .compiler("clang")
+        .file("foo.c")
+        .compile("foo");
+
+    let execution = compile_cmd(&test);
+    execution
+        .must_have("--target=arm64-apple-ios")
+        .must_have("-miphoneos-version-min=15.0");
+    assert_eq!(apple_version_args(&execution), 1);
+}
+
+#[test]
+fn apple_deployment_target_mac_catalyst() {
+    reset_env();
+
+    let test = Test::clang();
+    test.shim("xcrun");
+    test.gcc()
+        .target("aarch64-apple-ios-macabi")
+        .compiler("clang")
+        .apple_deployment_target("15.0")
+        .file("foo.c")
+        .compile("foo");
+
+    let execution = compile_cmd(&test);
+    // The version is only passed in the `--target` triple.
+    execution.must_have("--target=arm64-apple-ios15.0-macabi");
+    assert_eq!(apple_version_args(&execution), 0);
+}
+
+#[test]
+fn gnu_diagnostics_color() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .diagnostics_color(cc::ColorChoice::Always)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-fdiagnostics-color=always");
+
+    let test = Test::gnu();
+    test.gcc()
+        .diagnostics_color(cc::ColorChoice::Never)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-fdiagnostics-color=never")
+        .must_not_have("-fdiagnostics-color=always");
+}
+
+#[test]
+fn clang_diagnostics_color() {
+    reset_env();
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .diagnostics_color(cc::ColorChoice::Always)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-fcolor-diagnostics")
+        .must_not_have("-fno-color-diagnostics");
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .diagnostics_color(cc::ColorChoice::Never)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-fno-color-diagnostics")
+        .must_not_have("-fcolor-diagnostics");
+}
+
+#[test]
+fn msvc_diagnostics_color() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc()
+        .diagnostics_color(cc::ColorChoice::Always)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_not_have("-fcolor-diagnostics")
+        .must_not_have("-fdiagnostics-color=always");
+}
+
+#[test]
+fn gnu_default_visibility_hidden() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .default_visibility(cc::Visibility::Hidden)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-fvisibility=hidden")
+        .must_not_have("-fvisibility-inlines-hidden");
+
+    let test = Test::gnu();
+    test.gcc()
+        .cpp(true)
+        .default_visibility(cc::Visibility::Hidden)
+        .file("foo.cpp")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-fvisibility=hidden")
+        .must_have("-fvisibility-inlines-hidden");
+}
+
+#[test]
+fn gnu_cpp_inlines_hidden() {
+    reset_env();
+
+    // Not enabled without hidden visibility.
+    let test = Test::gnu();
+    test.gcc().cpp(true).file("foo.cpp").compile("foo");
+    test.cmd(0).must_not_have("-fvisibility-inlines-hidden");
+
+    // Can be explicitly disabled.
+    let test = Test::gnu();
+    test.gcc()
+        .cpp(true)
+        .default_visibility(cc::Visibility::Hidden)
+        .cpp_inlines_hidden(false)
+        .file("foo.cpp")
+        .compile("foo");
+    test.cmd(0).must_not_have("-fvisibility-inlines-hidden");
+
+    // And explicitly enabled.
+    let test = Test::gnu();
+    test.gcc()
+        .cpp(true)
+        .cpp_inlines_hidden(true)
+        .file("foo.cpp")
+        .compile("foo");
+    test.cmd(0).must_have("-fvisibility-inlines-hidden");
+}
+
+#[test]
+fn msvc_cpp_inlines_hidden() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc()
+        .cpp(true)
+        .default_visibility(cc::Visibility::Hidden)
+        .file("foo.cpp")
+        .compile("foo");
+    test.cmd(0)
+        .must_not_have("-fvisibility=hidden")
+        .must_not_have("-fvisibility-inlines-hidden");
+}
+
+#[test]
+fn max_errors() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc().max_errors(20).file("foo.c").compile("foo");
+    test.cmd(0).must_have("-fmax-errors=20");
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .max_errors(20)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-ferror-limit=20")
+        .must_not_have("-fmax-errors=20");
+
+    let test = Test::msvc();
+    test.gcc().max_errors(20).file("foo.c").compile("foo");
+    test.cmd(0)
+        .must_not_have("-ferror-limit=20")
+        .must_not_have("-fmax-errors=20");
+}
+
+#[test]
+fn gnu_warning_level() {
+    reset_env();
+
+    let all = ["-w", "-Wall", "-Wextra", "-Wpedantic"];
+    let levels: [(cc::WarningLevel, &[&str]); 5] = [
+        (cc::WarningLevel::Off, &["-w"]),
+        (cc::WarningLevel::Default, &[]),
+        (cc::WarningLevel::All, &["-Wall"]),
+        (cc::WarningLevel::Extra, &["-Wall", "-Wextra"]),
+        (
+            cc::WarningLevel::Pedantic,
+            &["-Wall", "-Wextra", "-Wpedantic"],
+        ),
+    ];
+    for (level, expected) in levels {
+        let test = Test::gnu();
+        test.gcc().warning_level(level).file("foo.c").compile("foo");
+        for flag in all {
+            if expected.contains(&flag) {
+                test.cmd(0).must_have(flag);
+            } else {
+                test.cmd(0).must_not_have(flag);
+            }
+        }
+    }
+}
+
+#[test]
+fn msvc_warning_level() {
+    reset_env();
+
+    let all = ["-W0", "-W3", "-W4", "-Wall"];
+    let levels = [
+        (cc::WarningLevel::Off, Some("-W0")),
+        (cc::WarningLevel::Default, None),
+        (cc::WarningLevel::All, Some("-W3")),
+        (cc::WarningLevel::Extra, Some("-W4")),
+        (cc::WarningLevel::Pedantic, Some("-Wall")),
+    ];
+    for (level, expected) in levels {
+        let test = Test::msvc();
+        test.gcc().warning_level(level).file("foo.c").compile("foo");
+        for flag in all {
+            if expected == Some(flag) {
+                test.cmd(0).must_have(flag);
+            } else {
+                test.cmd(0).must_not_have(flag);
+            }
+        }
+    }
+}
+
+#[test]
+fn gnu_warning_level_overrides_warnings() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .warnings(true)
+        .warning_level(cc::WarningLevel::Off)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-w").must_not_have("-Wall");
+
+    let test = Test::gnu();
+    test.gcc()
+        .warning_level(cc::WarningLevel::Off)
+        .warnings(true)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-Wall").must_not_have("-w");
+}
+
+#[test]
+fn gnu_warning_writer() {
+    reset_env();
+
+    let test = Test::gnu();
+    let warnings = Warnings::default();
+    test.gcc()
+        .warning_writer(Box::new(warnings.clone()))
+        .cargo_warnings(false)
+        .flag("--shim-warning=foo.c:1: warning: unused")
+        .file("foo.c")
+        .file("bar.c")
+        .compile("foo");
+
+    // Compiler family detection may also write to the buffer, depending on
+    // the host's `cc`.
+    let lines = warnings.lines();
+    let count = lines
+        .iter()
+        .filter(|line| *line == "foo.c:1: warning: unused")
+        .count();
+    assert_eq!(count, 2, "{:?}", lines);
+}
+
+#[test]
+fn gnu_suppress_warning() {
+    reset_env();
+
+    let test = Test::gnu();
+    test.gcc()
+        .warning_level(cc::WarningLevel::Extra)
+        .suppress_warning("unused-parameter")
+        .suppress_warning("4100")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have_in_order("-Wextra", "-Wno-unused-parameter")
+        .must_not_have("-wd4100");
+}
+
+#[test]
+fn msvc_suppress_warning() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc()
+        .warning_level(cc::WarningLevel::Extra)
+        .suppress_warning("unused-parameter")
+        .suppress_warning("4100")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have_in_order("-W4", "-wd4100")
+        .must_not_have("-Wno-unused-parameter");
+}
+
+#[test]
+fn suppress_warning_invalid() {
+    reset_env();
+
+    for warning in ["-Wunused-parameter", "no-unused-parameter", "/wd4100", ""] {
+        let test = Test::gnu();
+        let err = test
+            .gcc()
+            .suppress_warning(warning)
+            .file("foo.c")
+            .try_compile("foo")
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid warning"), "{}", err);
+    }
+}
+
+#[test]
+fn gnu_warning_as_error() {
+    reset_env();
+
+    let test = Test::gnu();
+    let mut build = test.gcc();
+    build
+        .warning_as_error("implicit-function-declaration")
+        .warning_as_error("4013")
+        .file("foo.c");
+    // The snippets aren't compiled with the flags.
+    assert!(build.probe_cpp_snippet("int f();").unwrap());
+    build.compile("foo");
+    test.cmd(0)
+        .must_not_have("-Werror=implicit-function-declaration")
+        .must_not_have("-Werror");
+    test.cmd(1)
+        .must_have("-Werror=implicit-function-declaration")
+        .must_not_have("-Werror")
+        .must_not_have("-we4013");
+}
+
+#[test]
+fn msvc_warning_as_error() {
+    reset_env();
+
+    let test = Test::msvc();
+    test.gcc()
+        .warning_as_error("implicit-function-declaration")
+        .warning_as_error("4013")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-we4013")
+        .must_not_have("-WX")
+        .must_not_have("-Werror=implicit-function-declaration");
+}
+
+#[test]
+fn warning_as_error_invalid() {
+    reset_env();
+
+    for warning in ["-Werror=unused", "error=unused", "/we4013", "", "a b"] {
+        let test = Test::gnu();
+        let err = test
+            .gcc()
+            .warning_as_error(warning)
+            .file("foo.c")
+            .try_compile("foo")
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid warning"), "{}", err);
+    }
+}
+
+#[test]
+fn clang_llvm_target() {
+    let target = "riscv64gc-unknown-none-elf";
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target(target)
+        .host(target)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("--target=riscv64");
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target(target)
+        .host(target)
+        .llvm_target("riscv64-unknown-elf")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("--target=riscv64-unknown-elf")
+        .must_not_have("--target=riscv64");
+}
+
+#[test]
+fn clang_wasm_default_flags() {
+    reset_env();
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target("wasm32-unknown-unknown")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-fno-exceptions")
+        .must_not_have("-fPIC")
+        .must_not_have("-ffunction-sections")
+        .must_not_have("-fdata-sections");
+
+    // Emscripten supports dynamic linking and unwinding.
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target("wasm32-unknown-emscripten")
+        .archiver(test.td.path().join("ar"))
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-fPIC")
+        .must_not_have("-fno-exceptions")
+        .must_not_have("-ffunction-sections");
+}
+
+#[test]
+fn clang_wasm_variants() {
+    reset_env();
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target("wasm32-wasip1-threads")
+        .archiver(test.td.path().join("ar"))
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("--target=wasm32-wasi")
+        .must_have("-matomics")
+        .must_have("-mbulk-memory")
+        .must_have("-pthread")
+        .must_not_have("-fPIC");
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target("wasm32-wasip1")
+        .archiver(test.td.path().join("ar"))
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_not_have("-matomics")
+        .must_not_have("-pthread");
+
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target("wasm32v1-none")
+        .archiver(test.td.path().join("ar"))
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("--target=wasm32-unknown-unknown")
+        .must_have("-mcpu=mvp")
+        .must_have("-mmutable-globals")
+        .must_not_have("-matomics")
+        .must_not_have("-fPIC");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn emscripten_emcc() {
+    for (cpp, compiler, src) in [(false, "emcc", "foo.c"), (true, "em++", "foo.cpp")] {
+        let test = Test::new();
+        test.shim(compiler).shim("emar");
+        let mut build = test.gcc();
+        build.target("wasm32-unknown-emscripten").cpp(cpp);
+
+        let tool = build.get_compiler();
+        assert_eq!(tool.path(), Path::new(compiler));
+        assert!(tool.is_like_clang());
+        build.file(src).compile("foo");
+        let cmd = test.cmd(0);
+        assert!(
+            !cmd.args.iter().any(|arg| arg.starts_with("--target")),
+            "{:?}",
+            cmd.args
+        );
+        cmd.must_have("-fPIC");
+    }
+}
+
+#[test]
+fn clang_target_spec_json() {
+    let test = Test::clang();
+    let spec = test.td.path().join("thumbv7em-custom-none.json");
+    let mut build = test.gcc();
+    build
+        .compiler("clang")
+        .target(spec.to_str().unwrap())
+        .host("x86_64-unknown-linux-gnu")
+        .file("foo.c");
+    // The specification is only read when compiling.
+    fs::write(
+        &spec,
+        r#"{
+            "arch": "arm",
+            "llvm-target": "thumbv7em-none-eabihf",
+            "relocation-model": "static",
+            "target-endian": "little",
+            "target-pointer-width": "32",
+            "max-atomic-width": 32
+        }"#,
+    )
+    .unwrap();
+    build.compile("foo");
+    test.cmd(0)
+        .must_have("--target=thumbv7em-none-eabihf")
+        .must_not_have("-fPIC");
+
+    let missing = test.td.path().join("missing.json");
+    let err = test
+        .gcc()
+        .compiler("clang")
+        .target(missing.to_str().unwrap())
+        .file("foo.c")
+        .try_compile("foo")
+        .unwrap_err();
+    assert!(err.to_string().contains("target specification"), "{}", err);
+}
+
+#[test]
+fn clang_dwarf_version() {
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target("x86_64-unknown-linux-gnu")
+        .debug(true)
+        .dwarf_version(5)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-gdwarf-5")
+        .must_not_have("-gdwarf-4");
+
+    // Without debug information, there is nothing to configure.
+    let test = Test::clang();
+    test.gcc()
+        .compiler("clang")
+        .target("x86_64-unknown-linux-gnu")
+        .debug(false)
+        .dwarf_version(5)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_not_have("-gdwarf-5");
 }
 
-#[cfg(target_os = "macos")]
 #[test]
-fn macos_cpp_minimums() {
-    let versions = &[
-        // Too low
-        ("10.7", (10, 9)),
-        // Minimum
-        ("10.9", (10, 9)),
-        // Higher
-        ("11.0", (11, 0)),
-    ];
+fn msvc_dwarf_version() {
+    let test = Test::msvc();
+    test.gcc()
+        .debug(true)
+        .dwarf_version(5)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-Z7").must_not_have("-gdwarf-5");
+}
 
-    let target = "x86_64-apple-darwin";
-    for (deployment_target, set_version) in versions {
+#[test]
+fn dwarf_version_out_of_range() {
+    for version in [0, 1, 6] {
         let test = Test::gnu();
-        test.gcc()
-            .target(target)
-            .host(target)
-            .cpp(true)
-            .__set_env("MACOSX_DEPLOYMENT_TARGET", deployment_target)
+        let err = test
+            .gcc()
+            .debug(true)
+            .dwarf_version(version)
             .file("foo.c")
-            .compile("foo");
+            .try_compile("foo")
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported DWARF version"),
+            "{}",
+            err
+        );
+    }
+}
 
-        let exec = test.cmd(0);
-        let deployment_arg = exec
-            .args
-            .iter()
-            .find_map(|arg| arg.strip_prefix("-mmacosx-version-min="))
-            .expect("no deployment target argument was set");
+/// The arguments of all commands run by `test` that compiled an object.
+fn compile_cmds(test: &Test) -> Vec<Execution> {
+    (0..)
+        .map(|i| test.td.path().join(format!("out{}", i)))
+        .take_while(|out| out.exists())
+        .enumerate()
+        .map(|(i, _)| test.cmd(i as u32))
+        .filter(|cmd| cmd.args.iter().any(|arg| arg == "-c"))
+        .collect()
+}
 
-        let mut deployment_parts = deployment_arg.split('.').map(|v| v.parse::<u32>().unwrap());
+#[test]
+fn gnu_unity_build() {
+    reset_env();
 
-        let major = deployment_parts.next().unwrap();
-        let minor = deployment_parts.next().unwrap();
+    let test = Test::gnu();
+    test.gcc()
+        .files(["a.c", "b.c", "c.c", "d.c", "e.c", "f.S"])
+        .unity_build(2)
+        .compile("foo");
 
-        // Check that we are on at least our minimums since this test reads from system
-        // SDK state, and that can vary per-system. It should never go lower then the deployment
-        // target we pass.
-        assert!(major >= set_version.0);
+    // ceil(5 / 2) unity-built translation units, plus the assembly file.
+    let cmds = compile_cmds(&test);
+    assert_eq!(cmds.len(), 4);
+    assert_eq!(cmds.iter().filter(|cmd| cmd.has("f.S".as_ref())).count(), 1);
+    assert!(cmds.iter().all(|cmd| !cmd.has("a.c".as_ref())));
+    // The last chunk has a single file, which is compiled directly.
+    assert_eq!(cmds.iter().filter(|cmd| cmd.has("e.c".as_ref())).count(), 1);
+
+    let unity_files = std::fs::read_dir(test.td.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("c".as_ref()))
+        .map(|path| std::fs::read_to_string(path).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(unity_files.len(), 2);
+    let includes = unity_files
+        .iter()
+        .flat_map(|contents| contents.lines())
+        .filter(|line| line.starts_with("#include"))
+        .count();
+    assert_eq!(includes, 4);
+}
 
-        // If still on 10.x make sure `x` didn't go lower.
-        if major == set_version.0 {
-            assert!(minor >= set_version.1);
-        }
-    }
+#[test]
+fn gnu_unity_build_exclude() {
+    reset_env();
 
     let test = Test::gnu();
     test.gcc()
-        .target(target)
-        .host(target)
-        .__set_env("MACOSX_DEPLOYMENT_TARGET", "10.7")
-        .file("foo.c")
+        .files(["a.c", "b.c", "c.c"])
+        .unity_build(8)
+        .unity_exclude("b.c")
         .compile("foo");
 
-    // No C++ leaves it untouched
-    test.cmd(0).must_have("-mmacosx-version-min=10.7");
+    // Both files left are unity-built together, and the excluded one is
+    // compiled on its own.
+    let cmds = compile_cmds(&test);
+    assert_eq!(cmds.len(), 2);
+    assert_eq!(cmds.iter().filter(|cmd| cmd.has("b.c".as_ref())).count(), 1);
+    assert!(cmds.iter().all(|cmd| !cmd.has("a.c".as_ref())));
 }
 
-#[cfg(target_os = "macos")]
 #[test]
-fn clang_apple_tvos() {
-    let target = "aarch64-apple-tvos";
-    let test = Test::clang();
+fn unity_build_zero_chunk_size() {
+    let test = Test::gnu();
     test.gcc()
-        .__set_env("TVOS_DEPLOYMENT_TARGET", "9.0")
-        .target(target)
-        .host(target)
         .file("foo.c")
-        .compile("foo");
+        .unity_build(0)
+        .try_compile("foo")
+        .unwrap_err();
+}
 
-    test.cmd(0).must_have("--target=arm64-apple-tvos");
-    test.cmd(0).must_have("-mappletvos-version-min=9.0");
+#[test]
+fn gnu_add_system_include() {
+    let test = Test::gnu();
+    test.gcc()
+        .include("src")
+        .add_system_include("vendor/include")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-I")
+        .must_have("src")
+        .must_have_in_order("-isystem", "vendor/include");
 }
 
-#[cfg(target_os = "macos")]
 #[test]
-fn clang_apple_mac_catalyst() {
-    let output = std::process::Command::new("xcrun")
-        .args(["--show-sdk-path", "--sdk", "macosx"])
-        .output()
-        .unwrap();
-    if !output.status.success() {
-        return;
-    }
-    let sdkroot = std::str::from_utf8(&output.stdout).unwrap().trim();
+fn gnu_add_system_includes() {
+    reset_env();
 
-    let test = Test::clang();
+    // The shim warns about `shim-warning.h` in include directories, like a
+    // compiler warning about the headers found there.
+    let test = Test::gnu();
+    let local = test.td.path().join("local");
+    let vendor = test.td.path().join("vendor");
+    for dir in [&local, &vendor] {
+        fs::create_dir(dir).unwrap();
+        fs::write(dir.join("shim-warning.h"), "").unwrap();
+    }
+    let warnings = Warnings::default();
     test.gcc()
-        .target("aarch64-apple-ios-macabi")
-        .__set_env("IPHONEOS_DEPLOYMENT_TARGET", "15.0")
+        .warning_writer(Box::new(warnings.clone()))
+        .include(&local)
+        .add_system_includes([&vendor])
         .file("foo.c")
         .compile("foo");
-    let execution = test.cmd(0);
 
-    execution.must_have("--target=arm64-apple-ios15.0-macabi");
-    // --target and -mtargetos= don't mix
-    execution.must_not_have("-mtargetos=");
-    execution.must_have_in_order("-isysroot", sdkroot);
-    execution.must_have_in_order(
-        "-isystem",
-        &format!("{sdkroot}/System/iOSSupport/usr/include"),
-    );
-    execution.must_have_in_order(
-        "-iframework",
-        &format!("{sdkroot}/System/iOSSupport/System/Library/Frameworks"),
-    );
-    execution.must_have(format!("-L{sdkroot}/System/iOSSupport/usr/lib"));
-    execution.must_have(format!(
-        "-F{sdkroot}/System/iOSSupport/System/Library/Frameworks"
-    ));
+    test.cmd(0)
+        .must_have_in_order("-I", &local.display().to_string())
+        .must_have_in_order("-isystem", &vendor.display().to_string());
+    let lines = warnings.lines();
+    let warned = |dir: &Path| {
+        lines
+            .iter()
+            .any(|line| line.starts_with(&*dir.to_string_lossy()))
+    };
+    assert!(warned(&local), "{:?}", lines);
+    assert!(!warned(&vendor), "{:?}", lines);
 }
 
-#[cfg(target_os = "macos")]
 #[test]
-fn clang_apple_tvsimulator() {
-    let target = "x86_64-apple-tvos";
-
+fn clang_add_system_include() {
     let test = Test::clang();
     test.gcc()
-        .__set_env("TVOS_DEPLOYMENT_TARGET", "9.0")
-        .target(target)
-        .host(target)
+        .compiler("clang")
+        .add_system_include("vendor/include")
         .file("foo.c")
         .compile("foo");
-
     test.cmd(0)
-        .must_have("--target=x86_64-apple-tvos-simulator");
-    test.cmd(0).must_have("-mappletvsimulator-version-min=9.0");
+        .must_have_in_order("-isystem", "vendor/include")
+        .must_not_have("-I");
 }
 
-#[cfg(target_os = "macos")]
 #[test]
-fn clang_apple_visionos() {
-    // Only run this test if visionOS is available on the host machine
-    let output = std::process::Command::new("xcrun")
-        .args(["--show-sdk-version", "--sdk", "xros"])
-        .output()
-        .unwrap();
-    if !output.status.success() {
-        return;
-    }
+fn msvc_add_system_include() {
+    let test = Test::msvc();
+    test.gcc()
+        .add_system_include("vendor/include")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0)
+        .must_have("-external:W0")
+        .must_have_in_order("-external:I", "vendor/include")
+        .must_not_have("-isystem");
+}
 
+#[test]
+fn lto() {
     let test = Test::clang();
     test.gcc()
-        .__set_env("XROS_DEPLOYMENT_TARGET", "1.0")
-        .target("aarch64-apple-visionos")
+        .compiler("clang")
+        .lto(cc::Lto::Thin)
         .file("foo.c")
         .compile("foo");
+    test.cmd(0).must_have("-flto=thin");
 
-    dbg!(test.cmd(0).args);
+    let test = Test::gnu();
+    test.gcc().lto(cc::Lto::Fat).file("foo.c").compile("foo");
+    test.cmd(0)
+        .must_have("-flto")
+        .must_have("-ffat-lto-objects");
 
-    test.cmd(0).must_have("--target=arm64-apple-xros1.0");
-    // --target and -mtargetos= don't mix.
-    test.cmd(0).must_not_have("-mtargetos=");
+    let test = Test::msvc();
+    test.gcc().lto(cc::Lto::Fat).file("foo.c").compile("foo");
+    test.cmd(0).must_have("-GL");
+}
 
-    // Flags that don't exist.
-    test.cmd(0).must_not_have("-mxros-version-min=1.0");
-    test.cmd(0).must_not_have("-mxrsimulator-version-min=1.0");
+#[test]
+fn gnu_thin_lto_unsupported() {
+    let test = Test::gnu();
+    let err = test
+        .gcc()
+        .lto(cc::Lto::Thin)
+        .file("foo.c")
+        .try_compile("foo")
+        .unwrap_err();
+    let message = err.to_string();
+    assert!(message.starts_with("UnsupportedFeature: "), "{}", message);
+    assert!(message.contains("Thin LTO"), "{}", message);
+    assert!(
+        message.contains("when compiling for x86_64-"),
+        "{}",
+        message
+    );
 }
 
-#[cfg(target_os = "macos")]
 #[test]
-fn apple_sdkroot_wrong() {
-    let output = std::process::Command::new("xcrun")
-        .args(["--show-sdk-path", "--sdk", "iphoneos"])
-        .output()
-        .unwrap();
-    if !output.status.success() {
-        return;
-    }
+fn hardening() {
+    reset_env();
 
-    let wrong_sdkroot = "/Library/Developer/CommandLineTools/SDKs/MacOSX.sdk";
-    let test = Test::clang();
+    let test = Test::gnu();
     test.gcc()
-        .__set_env("SDKROOT", wrong_sdkroot)
-        .target("aarch64-apple-ios")
+        .target("x86_64-unknown-linux-gnu")
+        .opt_level(2)
+        .hardening(cc::HardeningLevel::Full)
         .file("foo.c")
         .compile("foo");
+    test.cmd(0)
+        .must_have("-fstack-protector-strong")
+        .must_have("-D_FORTIFY_SOURCE=2")
+        .must_have("-fstack-clash-protection")
+        .must_have("-fcf-protection");
 
-    dbg!(test.cmd(0).args);
-
+    let test = Test::gnu();
+    test.gcc()
+        .target("x86_64-unknown-linux-gnu")
+        .opt_level(0)
+        .hardening(cc::HardeningLevel::Basic)
+        .file("foo.c")
+        .compile("foo");
     test.cmd(0)
-        .must_have(std::str::from_utf8(&output.stdout).unwrap().trim());
-    test.cmd(0).must_not_have(wrong_sdkroot);
+        .must_have("-fstack-protector-strong")
+        .must_not_have("-D_FORTIFY_SOURCE=2")
+        .must_not_have("-fstack-clash-protection");
+
+    let test = Test::msvc();
+    test.gcc()
+        .hardening(cc::HardeningLevel::Full)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-GS").must_have("-guard:cf");
+
+    let test = Test::msvc();
+    test.gcc()
+        .hardening(cc::HardeningLevel::None)
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_not_have("-GS").must_not_have("-guard:cf");
 }
 
 #[test]
-fn compile_intermediates() {
+fn gnu_get_compiler_command() {
+    reset_env();
+
     let test = Test::gnu();
-    let intermediates = test
+    let argv = test
         .gcc()
-        .file("foo.c")
-        .file("x86_64.asm")
-        .file("x86_64.S")
-        .asm_flag("--abc")
-        .compile_intermediates();
-
-    assert_eq!(intermediates.len(), 3);
+        .std("c11")
+        .include("include")
+        .define("FOO", "1")
+        .get_compiler_command(std::path::Path::new("src/foo.c"))
+        .unwrap();
 
-    assert!(intermediates[0].display().to_string().contains("foo"));
-    assert!(intermediates[1].display().to_string().contains("x86_64"));
-    assert!(intermediates[2].display().to_string().contains("x86_64"));
+    // Nothing was compiled.
+    assert!(!test.td.path().join("out0").exists());
+
+    assert_eq!(argv[0], "cc");
+    assert_eq!(argv.last().unwrap(), "src/foo.c");
+    let position = |arg: &str| {
+        argv.iter()
+            .position(|a| a == arg)
+            .unwrap_or_else(|| panic!("didn't find {:?} in {:?}", arg, argv))
+    };
+    assert!(position("-std=c11") < position("-I"));
+    assert_eq!(argv[position("-I") + 1], "include");
+    assert!(position("-I") < position("-DFOO=1"));
+    assert!(position("-DFOO=1") < position("-c"));
 }
 
 #[test]
-fn clang_android() {
-    let target = "arm-linux-androideabi";
+fn gnu_x32() {
+    let test = Test::gnu();
+    test.gcc()
+        .target("x86_64-unknown-linux-gnux32")
+        .file("foo.c")
+        .compile("foo");
+    test.cmd(0).must_have("-mx32").must_not_have("-m64");
+}
 
-    // On Windows, we don't use the Android NDK shims for Clang, so verify that
-    // we use "clang" and set the target correctly.
-    #[cfg(windows)]
-    {
-        let test = Test::new();
-        test.shim("clang").shim("llvm-ar");
-        test.gcc()
-            .target(target)
-            .host("x86_64-pc-windows-msvc")
-            .file("foo.c")
-            .compile("foo");
-        test.cmd(0).must_have("--target=arm-linux-androideabi");
-    }
+#[test]
+fn compiler_family_skips_detection() {
+    let test = Test::gnu();
+    test.shim("xcc");
+    test.gcc()
+        .target("x86_64-unknown-linux-gnu")
+        .compiler(test.td.path().join("xcc"))
+        .compiler_family(cc::CompilerFamily::Clang)
+        .file("foo.c")
+        .compile("foo");
 
-    // On non-Windows, we do use the shims, so make sure that we use the shim
-    // and don't set the target.
-    #[cfg(not(windows))]
-    {
-        let test = Test::new();
-        test.shim("arm-linux-androideabi-clang")
-            .shim("arm-linux-androideabi-ar")
-            .shim("llvm-ar");
-        test.gcc().target(target).file("foo.c").compile("foo");
-        test.cmd(0).must_not_have("--target=arm-linux-androideabi");
-    }
+    // The first command run is the compilation itself, rather than the
+    // detection of the compiler family with `-E`.
+    test.cmd(0)
+        .must_have("-c")
+        .must_not_have("-E")
+        .must_have("--target=x86_64-unknown-linux-gnu");
 }